@@ -0,0 +1,910 @@
+// ------------------------------------------------------------------------- //
+// Rusph: configuration of a run of the rusph driver.                        //
+// A run is described by one configuration file. Its unnamed section selects //
+// the initial conditions and the physics (kernel, EoS, viscosity, time      //
+// integrator, boundary and external force), and the setups and forces that //
+// need parameters read them from their own section:                         //
+//      setup = sod                                                          //
+//      kernel = quintic                                                     //
+//      ...                                                                  //
+//      [sod]                                                                //
+//      rhol = 1.0                                                           //
+// The presets in rusph/presets reproduce the test systems.                  //
+// ------------------------------------------------------------------------- //
+
+use std::{
+    error::Error,
+    f64::consts::PI,
+    ops::Range,
+};
+
+use csv::Writer;
+
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+use structures::{
+    Particle,
+    Star,
+};
+
+use sphfunctions::{
+    Integrator,
+    PrescribedMotion,
+};
+use datafunctions::{
+    OutputFormat,
+    Value,
+};
+
+use spfunc::gamma::gamma;
+
+type ViscosityFn = fn(f64, f64, f64, f64, f64, f64, f64) -> (f64, f64);
+type BoundaryFn = fn(&mut Vec<Particle>, f64, f64, f64, f64, f64, f64, f64, f64);
+
+// Sections allowed in a configuration file besides the unnamed one.
+pub const SECTIONS: &[&str] = &["sod", "sedov", "kelvin_helmholtz", "rayleigh_taylor", "toy_star", "shear_flow", "turbulence", "accretion_disc",
+                                "accretion_disc_uniform", "accretion_disc_phantom", "lid_driven_cavity", "piston", "gravity", "shearing_box", "sinks"];
+
+// Seed of the random placement of the disc's particles
+const SEED: u64 = 1234;
+
+// Choices given by name in a configuration file.
+macro_rules! named_value {
+    ($ty:ident, $what:literal, $($name:literal => $variant:ident),*) => {
+        impl Value for $ty {
+            fn parse(text: &str) -> Result<$ty, String> {
+                match text {
+                    $($name => Ok($ty::$variant),)*
+                    _ => Err(format!("'{}' is not {} ({})", text, $what, [$($name),*].join(", "))),
+                }
+            }
+        }
+    };
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Setup {
+    Sod,
+    Sedov,
+    KelvinHelmholtz,
+    RayleighTaylor,
+    ToyStar,
+    ShearFlow,
+    Turbulence,
+    AccretionDisc,
+    AccretionDiscUniform,
+    AccretionDiscPhantom,
+    LidDrivenCavity,
+    Piston,
+}
+named_value!(Setup, "a setup", "sod" => Sod, "sedov" => Sedov, "kelvin_helmholtz" => KelvinHelmholtz, "rayleigh_taylor" => RayleighTaylor, "toy_star" => ToyStar,
+             "shear_flow" => ShearFlow, "turbulence" => Turbulence, "accretion_disc" => AccretionDisc, "accretion_disc_uniform" => AccretionDiscUniform,
+             "accretion_disc_phantom" => AccretionDiscPhantom, "lid_driven_cavity" => LidDrivenCavity, "piston" => Piston);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Kernel {
+    Cubic,
+    Quintic,
+    Gaussian,
+    WendlandC2,
+}
+named_value!(Kernel, "a kernel", "cubic" => Cubic, "quintic" => Quintic, "gaussian" => Gaussian, "wendland_c2" => WendlandC2);
+
+impl Kernel {
+    pub fn f(&self) -> fn(f64) -> f64 {
+        match self {
+            Kernel::Cubic      => sphfunctions::f_cubic_kernel,
+            Kernel::Quintic    => sphfunctions::f_quintic_kernel,
+            Kernel::Gaussian   => sphfunctions::f_gaussian_kernel,
+            Kernel::WendlandC2 => sphfunctions::f_c2wendland_kernel,
+        }
+    }
+
+    pub fn dfdq(&self) -> fn(f64) -> f64 {
+        match self {
+            Kernel::Cubic      => sphfunctions::dfdq_cubic_kernel,
+            Kernel::Quintic    => sphfunctions::dfdq_quintic_kernel,
+            Kernel::Gaussian   => sphfunctions::dfdq_gaussian_kernel,
+            Kernel::WendlandC2 => sphfunctions::dfdq_c2wendland_kernel,
+        }
+    }
+
+    // Normalization constant of the kernel
+    pub fn sigma(&self) -> f64 {
+        match self {
+            Kernel::Cubic      => 1./PI,
+            Kernel::Quintic    => 1./(120.*PI),
+            Kernel::Gaussian   => 1./PI.powf(1.5),
+            Kernel::WendlandC2 => 21./(16.*PI),
+        }
+    }
+
+    // Kernel radius
+    pub fn rkern(&self) -> f64 {
+        match self {
+            Kernel::Cubic | Kernel::WendlandC2  => 2.,
+            Kernel::Quintic | Kernel::Gaussian  => 3.,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Eos {
+    IdealGas,
+    Polytropic,
+    IsothermalDisc,
+}
+named_value!(Eos, "an equation of state", "ideal_gas" => IdealGas, "polytropic" => Polytropic, "isothermal_disc" => IsothermalDisc);
+
+impl Eos {
+    pub fn pressure(&self) -> fn(f64, f64, f64, f64, f64, f64, f64) -> f64 {
+        match self {
+            Eos::IdealGas       => sphfunctions::eos_ideal_gas,
+            Eos::Polytropic     => sphfunctions::eos_polytropic,
+            Eos::IsothermalDisc => sphfunctions::eos_isothermal_disc,
+        }
+    }
+
+    pub fn sound_speed(&self) -> fn(f64, f64, f64, f64, f64, f64, f64) -> f64 {
+        match self {
+            Eos::IdealGas       => sphfunctions::sound_speed_ideal_gas,
+            Eos::Polytropic     => sphfunctions::sound_speed_polytropic,
+            Eos::IsothermalDisc => sphfunctions::sound_speed_isothermal_disc,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Viscosity {
+    Mon92,
+    Mon97,
+    LodatoPrice10,
+}
+named_value!(Viscosity, "an artificial viscosity", "mon92" => Mon92, "mon97" => Mon97, "lodato_price10" => LodatoPrice10);
+
+impl Viscosity {
+    pub fn function(&self) -> ViscosityFn {
+        match self {
+            Viscosity::Mon92         => sphfunctions::mon92_art_vis,
+            Viscosity::Mon97         => sphfunctions::mon97_art_vis,
+            Viscosity::LodatoPrice10 => sphfunctions::lodatoprice10_art_vis,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Scheme {
+    VelocityVerlet,
+    Leapfrog,
+    RungeKutta2,
+    RungeKutta4,
+    Yoshida4,
+    Block,
+}
+named_value!(Scheme, "a time integrator", "velocity_verlet" => VelocityVerlet, "leapfrog" => Leapfrog, "rk2" => RungeKutta2, "rk4" => RungeKutta4, "yoshida4" => Yoshida4,
+             "block" => Block);
+
+impl Scheme {
+    // The block steps (sphfunctions::block_kdk_integrator) give every
+    // particle its own step, so they are no Integrator: None.
+    pub fn integrator(&self) -> Option<Box<dyn Integrator>> {
+        match self {
+            Scheme::VelocityVerlet => Some(Box::new(sphfunctions::VelocityVerlet)),
+            Scheme::Leapfrog       => Some(Box::new(sphfunctions::Leapfrog)),
+            Scheme::RungeKutta2    => Some(Box::new(sphfunctions::RungeKutta2)),
+            Scheme::RungeKutta4    => Some(Box::new(sphfunctions::RungeKutta4)),
+            Scheme::Yoshida4       => Some(Box::new(sphfunctions::Yoshida4)),
+            Scheme::Block          => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Boundary {
+    Periodic,
+    Box,
+    None,
+    ShearingBox,
+}
+named_value!(Boundary, "a boundary", "periodic" => Periodic, "box" => Box, "none" => None, "shearing_box" => ShearingBox);
+
+impl Boundary {
+    pub fn function(&self) -> BoundaryFn {
+        match self {
+            Boundary::Periodic    => sphfunctions::periodic_boundary,
+            Boundary::Box         => sphfunctions::box_boundary,
+            Boundary::None        => sphfunctions::none_boundary,
+            Boundary::ShearingBox => sphfunctions::periodic_boundary,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Force {
+    None,
+    ToyStar,
+    Gravity,
+    Sinks,
+}
+named_value!(Force, "an external force", "none" => None, "toy_star" => ToyStar, "gravity" => Gravity, "sinks" => Sinks);
+
+// -------- Configuration --------
+
+datafunctions::config! {
+    pub struct Run {
+        setup: Setup,                    "",        "Initial conditions (sod, sedov, kelvin_helmholtz, rayleigh_taylor, toy_star, shear_flow, turbulence, accretion_disc, accretion_disc_uniform, accretion_disc_phantom, lid_driven_cavity or piston)";
+        output_dir: String,              "",        "Directory of the run's files (created if needed)";
+        kernel: Kernel = "quintic",      "",        "Kernel (cubic, quintic, gaussian or wendland_c2)";
+        eos: Eos = "ideal_gas",          "",        "Equation of state (ideal_gas, polytropic or isothermal_disc)";
+        adiabatic: bool = true,          "",        "Evolve the internal energy (false=isothermal)";
+        gamma: f64,                      "",        "Heat capacity ratio", min 1.0;
+        coeff: f64 = 0.0,                "",        "Constant of the EoS (K of the polytrope, cs0^2 of the disc)";
+        q_index: f64 = 0.0,              "",        "Index of the sound speed of the disc, cs^2 = cs0^2 R^(-2q)";
+        viscosity: Viscosity = "mon97",  "",        "Artificial viscosity (mon92, mon97 or lodato_price10)";
+        integrator: Scheme = "leapfrog", "",        "Time integrator (velocity_verlet, leapfrog, rk2, rk4, yoshida4 or block)";
+        nbins: u32 = 5,                  "",        "Number of timestep bins of the block integrator (smallest step is dt/2^nbins)";
+        limiter: bool = true,            "",        "Saitoh-Makino timestep limiter of the block integrator";
+        force: Force = "none",           "",        "External force (none, toy_star, gravity or sinks)";
+        boundary: Boundary = "periodic", "",        "Boundary of the domain (periodic, box, none or shearing_box)";
+        xper: bool = true,               "",        "Periodic in the x direction";
+        yper: bool = true,               "",        "Periodic in the y direction";
+        zper: bool = true,               "",        "Periodic in the z direction";
+        xwall: bool = false,             "",        "Reflective walls with mirror particles at the x ends";
+        ywall: bool = false,             "",        "Reflective walls with mirror particles at the y ends";
+        zwall: bool = false,             "",        "Reflective walls with mirror particles at the z ends";
+        boundary_particles: bool = false, "",       "Boundary particles take the state of the gas next to them (Adami, Hu & Adams 2012)";
+        x0: f64,                         "L",       "Bottom left corner  (x-coordinate)";
+        y0: f64,                         "L",       "Bottom left corner  (y-coordinate)";
+        z0: f64,                         "L",       "Bottom left corner  (z-coordinate)";
+        wd: f64,                         "L",       "Width of the system's domain", min 0.0;
+        lg: f64,                         "L",       "Length of the system's domain", min 0.0;
+        hg: f64,                         "L",       "Height of the system's domain", min 0.0;
+        eta: f64 = 1.2,                  "",        "Dimensionless constant specifying the smoothing length", min 0.0;
+        t0: f64 = 0.0,                   "T",       "Initial time";
+        tf: f64,                         "T",       "Final time";
+        dt_sav: f64,                     "T",       "Recording time step", min 0.0;
+        s: i32 = 10,                     "",        "Maximum number of particles in a leaf (Bucket size)", min 1;
+        alpha: f64 = 0.5,                "",        "Fraction of the bucket size s", min 0.0, max 1.0;
+        beta: f64 = 0.5,                 "",        "Maximum ratio of cells with less than alpha*s particles", min 0.0, max 1.0;
+        chk_steps: u32 = 0,              "",        "Steps between checkpoints (0=off)";
+        chk_min: f64 = 60.0,             "min",     "Wall-clock minutes between checkpoints (0=off)", min 0.0;
+        max_walltime: f64 = 0.0,         "h",       "Hours after which the run checkpoints and stops (0=off)", min 0.0;
+        output: OutputFormat = "binary", "",        "Snapshot format (binary, csv or vtu)";
+        prefix: String = "Ev_",          "",        "Prefix of the snapshots' names";
+        digits: u32 = 0,                 "",        "Digits of the snapshots' numbers, zero-padded (0=no padding)";
+        diag_steps: u32 = 0,             "",        "Steps between lines of Diagnostics.csv (0=off)";
+        diag_dt: f64 = 0.0,              "T",       "Time between lines of Diagnostics.csv (0=off)", min 0.0;
+    }
+}
+
+datafunctions::config! {
+    pub struct Sod {
+        rhol: f64,                       "M/L^3",   "Initial left density", min 0.0;
+        rhor: f64,                       "M/L^3",   "Initial right density", min 0.0;
+        pl: f64,                         "P",       "Left initial pressure", min 0.0;
+        pr: f64,                         "P",       "Right initial pressure", min 0.0;
+        xm: f64,                         "L",       "Discontinuity position";
+        nxl: u32,                        "",        "Particle resolution in the x direction (left)", min 1;
+        nxr: u32,                        "",        "Particle resolution in the x direction (right)", min 1;
+    }
+}
+
+datafunctions::config! {
+    pub struct Sedov {
+        rho0: f64,                       "M/L^3",   "Initial density", min 0.0;
+        e0: f64,                         "E",       "Initial energy", min 0.0;
+        nx: u32,                         "",        "Particle resolution", min 1;
+    }
+}
+
+datafunctions::config! {
+    pub struct KelvinHelmholtz {
+        y1: f64,                         "L",       "Y-lower edge of fluid 2";
+        y2: f64,                         "L",       "Y-upper edge of fluid 2";
+        rho1: f64,                       "M/L^3",   "Initial density fluid 1", min 0.0;
+        rho2: f64,                       "M/L^3",   "Initial density fluid 2", min 0.0;
+        vx1: f64,                        "L/T",     "Initial x velocity fluid 1";
+        vx2: f64,                        "L/T",     "Initial x velocity fluid 2";
+        p0: f64,                         "P",       "Initial pressure", min 0.0;
+        nx: u32,                         "",        "Particle resolution", min 1;
+    }
+}
+
+// Heavy fluid above the light one, in hydrostatic equilibrium with the
+// gravity of the [gravity] section along y.
+datafunctions::config! {
+    pub struct RayleighTaylor {
+        ym: f64,                         "L",       "Position of the interface";
+        rho1: f64,                       "M/L^3",   "Initial density of the lower fluid", min 0.0;
+        rho2: f64,                       "M/L^3",   "Initial density of the upper fluid", min 0.0;
+        p0: f64,                         "P",       "Pressure at the interface", min 0.0;
+        v0: f64 = 0.01,                  "L/T",     "Amplitude of the perturbation of vy";
+        nx: u32,                         "",        "Particle resolution", min 1;
+    }
+}
+
+// The star is centred at the origin, the centre of its potential.
+datafunctions::config! {
+    pub struct ToyStar {
+        nu: f64,                         "1/T",     "Viscocity parameter", min 0.0;
+        m: f64,                          "M",       "Star's mass", min 0.0;
+        r: f64,                          "L",       "Star's radius", min 0.0;
+        vx0: f64 = 0.0,                  "L/T",     "Initial star's velocity (x-coordinate)";
+        vy0: f64 = 0.0,                  "L/T",     "Initial star's velocity (y-coordinate)";
+        vz0: f64 = 0.0,                  "L/T",     "Initial star's velocity (z-coordinate)";
+        u0: f64 = 0.0,                   "E/M",     "Initial particles' energy", min 0.0;
+        n: u32,                          "",        "Particle resolution across the star's diameter", min 1;
+    }
+}
+
+// Uniform gas in the background flow of the shearing box, centred in x.
+datafunctions::config! {
+    pub struct ShearFlow {
+        rho0: f64,                       "M/L^3",   "Initial density", min 0.0;
+        u0: f64,                         "E/M",     "Initial particles' energy", min 0.0;
+        nx: u32,                         "",        "Particle resolution", min 1;
+    }
+}
+
+datafunctions::config! {
+    pub struct Gravity {
+        gx: f64 = 0.0,                   "L/T^2",   "Gravitational acceleration (x-coordinate)";
+        gy: f64 = 0.0,                   "L/T^2",   "Gravitational acceleration (y-coordinate)";
+        gz: f64 = 0.0,                   "L/T^2",   "Gravitational acceleration (z-coordinate)";
+    }
+}
+
+// Shearing box boundary: periodic in x and y, with the images across x
+// shifted by the shear flow (see sphfunctions::shear_offsets).
+datafunctions::config! {
+    pub struct ShearingBox {
+        q: f64 = 1.5,                    "",        "Shear parameter, -dln(omega)/dln(r) (1.5 for a Keplerian disc)";
+        omega: f64,                      "1/T",     "Angular velocity of the box";
+    }
+}
+
+// Gas read from a CSV file of positions, velocities and smoothing lengths,
+// every particle with the mass dm.
+datafunctions::config! {
+    pub struct Turbulence {
+        source: String,                  "",        "CSV file of the gas (x, y, z, vx, vy, vz, h)";
+        dm: f64,                         "M",       "Particles' mass", min 0.0;
+    }
+}
+
+// Disc around the star of the [sinks] section, with the surface density
+// sigma0 (R/r_ref)^(-p) and the sound speed of the isothermal_disc EoS,
+// sqrt(coeff) R^(-q).
+datafunctions::config! {
+    pub struct AccretionDisc {
+        r_in: f64,                       "L",       "Inner radius of the disc", min 0.0;
+        r_ref: f64,                      "L",       "Reference radius of the surface density", min 0.0;
+        r_out: f64,                      "L",       "Outer radius of the disc", min 0.0;
+        m_disc: f64,                     "",        "Disc's mass over the star's", min 0.0;
+        p_index: f64,                    "",        "Index of the surface density";
+        n: u32,                          "",        "Number of particles", min 1;
+    }
+}
+
+// Uniform annulus of height h around the star of the [sinks] section, in
+// Keplerian rotation.
+datafunctions::config! {
+    pub struct AccretionDiscUniform {
+        r_in: f64,                       "L",       "Inner radius of the disc", min 0.0;
+        r_out: f64,                      "L",       "Outer radius of the disc", min 0.0;
+        h: f64,                          "L",       "Height of the disc", min 0.0;
+        m_disc: f64,                     "M",       "Disc's mass", min 0.0;
+        nx: u32,                         "",        "Particle resolution across the disc", min 1;
+    }
+}
+
+// Disc exported from Phantom, as a CSV file of the gas (the particles get
+// the mass m_disc*m/n, m the mass of the star) or as a dump, whose sinks
+// replace the star of the [sinks] section.
+datafunctions::config! {
+    pub struct AccretionDiscPhantom {
+        source: String,                  "",        "Initial particles: a CSV export or a Phantom dump";
+        m_disc: f64,                     "",        "Disc's mass over the star's (CSV export)", min 0.0;
+        n: u32,                          "",        "Number of particles (CSV export)", min 1;
+    }
+}
+
+// Cavity filling the domain but for nb layers of boundary particles on its
+// sides in x and y. The top layers are the lid, sliding along x.
+datafunctions::config! {
+    pub struct LidDrivenCavity {
+        rho0: f64,                       "M/L^3",   "Initial density", min 0.0;
+        cs0: f64,                        "L/T",     "Initial sound speed", min 0.0;
+        v_lid: f64,                      "L/T",     "Velocity of the lid (x-direction)";
+        nb: u32,                         "",        "Layers of boundary particles in the walls", min 1;
+        nx: u32,                         "",        "Particle resolution of the cavity in the x direction", min 1;
+    }
+}
+
+// Tube along x closed by nb layers of boundary particles at each end: the
+// piston, at the lower end, moves into the gas.
+datafunctions::config! {
+    pub struct Piston {
+        rho0: f64,                       "M/L^3",   "Initial density", min 0.0;
+        p0: f64,                         "P",       "Initial pressure", min 0.0;
+        v_piston: f64,                   "L/T",     "Velocity of the piston (x-direction)";
+        nb: u32,                         "",        "Layers of boundary particles in the piston and the wall", min 1;
+        nx: u32,                         "",        "Particle resolution of the tube in the x direction", min 1;
+    }
+}
+
+// Star accreting the gas within hacc. The sinks are integrated with the
+// gas and among themselves, on substeps of eta_orb times their orbital time.
+datafunctions::config! {
+    pub struct Sinks {
+        m: f64,                          "M",       "Star's mass", min 0.0;
+        x: f64 = 0.0,                    "L",       "Star's position (x-coordinate)";
+        y: f64 = 0.0,                    "L",       "Star's position (y-coordinate)";
+        z: f64 = 0.0,                    "L",       "Star's position (z-coordinate)";
+        vx: f64 = 0.0,                   "L/T",     "Star's velocity (x-coordinate)";
+        vy: f64 = 0.0,                   "L/T",     "Star's velocity (y-coordinate)";
+        vz: f64 = 0.0,                   "L/T",     "Star's velocity (z-coordinate)";
+        hacc: f64,                       "L",       "Accretion radius", min 0.0;
+        facc: f64 = 0.8,                 "",        "Fraction of hacc within which every particle is accreted", min 0.0, max 1.0;
+        c: f64 = 0.0,                    "L/T",     "Speed of light in code units (0=Newtonian gravity)", min 0.0;
+        spin_x: f64 = 0.0,               "",        "Star's dimensionless spin, S = a G M^2/c (x-coordinate)";
+        spin_y: f64 = 0.0,               "",        "Star's dimensionless spin, S = a G M^2/c (y-coordinate)";
+        spin_z: f64 = 0.0,               "",        "Star's dimensionless spin, S = a G M^2/c (z-coordinate)";
+        eta_orb: f64 = 0.1,              "",        "Sink substep over the orbital time of the closest sink pair", min 0.0;
+    }
+}
+
+impl Sinks {
+    // The star of the section. G = 1.
+    pub fn star(&self) -> Star {
+        let s_star: f64 = if self.c > 0.0 {self.m*self.m/self.c} else {0.0};
+        Star{m: self.m, x: self.x, y: self.y, z: self.z, vx: self.vx, vy: self.vy, vz: self.vz, hacc: self.hacc, facc: self.facc,
+             sx: self.spin_x*s_star, sy: self.spin_y*s_star, sz: self.spin_z*s_star, c: self.c, ..Default::default()}
+    }
+}
+
+impl Run {
+    // Reads the unnamed section of a configuration file.
+    pub fn from_file(path: &str) -> Result<Run, Box<dyn Error>> {
+        Run::read_section(path, "", SECTIONS)
+    }
+
+    // The gamma argument of the EoS functions: the heat capacity ratio, or
+    // the index q of the isothermal disc.
+    pub fn eos_gamma(&self) -> f64 {
+        if self.eos == Eos::IsothermalDisc {self.q_index} else {self.gamma}
+    }
+
+    // Files of the run
+    pub fn file(&self, name: &str) -> String {
+        datafunctions::output_file(&self.output_dir, name)
+    }
+}
+
+// -------- Initial conditions --------
+
+// Builds the particles and the sinks of the setup of a run. The section of
+// the setup is read from the same configuration file. Runs with sinks get
+// the star of the [sinks] section, unless the setup brings its own.
+pub fn setup(path: &str, run: &Run) -> Result<(Vec<Particle>, Vec<Star>), Box<dyn Error>> {
+    let mut particles: Vec<Particle> = Vec::new();
+    let mut stars: Vec<Star> = Vec::new();
+    let sinks: Option<Sinks> = sinks(path, run)?;
+    match run.setup {
+        Setup::Sod => {
+            let sod: Sod = Sod::read_section(path, "sod", SECTIONS)?;
+            setup_sod(&mut particles, run, &sod);
+        },
+        Setup::Sedov => {
+            let sedov: Sedov = Sedov::read_section(path, "sedov", SECTIONS)?;
+            setup_sedov(&mut particles, run, &sedov);
+        },
+        Setup::KelvinHelmholtz => {
+            let kh: KelvinHelmholtz = KelvinHelmholtz::read_section(path, "kelvin_helmholtz", SECTIONS)?;
+            setup_kelvin_helmholtz(&mut particles, run, &kh);
+        },
+        Setup::RayleighTaylor => {
+            let rt: RayleighTaylor = RayleighTaylor::read_section(path, "rayleigh_taylor", SECTIONS)?;
+            if run.force != Force::Gravity {
+                return Err("The Rayleigh-Taylor setup needs force = gravity".into());
+            }
+            let gravity: Gravity = Gravity::read_section(path, "gravity", SECTIONS)?;
+            setup_rayleigh_taylor(&mut particles, run, &rt, &gravity)?;
+        },
+        Setup::ToyStar => {
+            let star: ToyStar = ToyStar::read_section(path, "toy_star", SECTIONS)?;
+            setup_toy_star(&mut particles, run, &star);
+        },
+        Setup::ShearFlow => {
+            let flow: ShearFlow = ShearFlow::read_section(path, "shear_flow", SECTIONS)?;
+            let shear: ShearingBox = ShearingBox::read_section(path, "shearing_box", SECTIONS)?;
+            setup_shear_flow(&mut particles, run, &flow, &shear);
+        },
+        Setup::Turbulence => {
+            let turbulence: Turbulence = Turbulence::read_section(path, "turbulence", SECTIONS)?;
+            datafunctions::read_data_iso(&turbulence.source, turbulence.dm, &mut particles)?;
+        },
+        Setup::AccretionDisc => {
+            let disc: AccretionDisc = AccretionDisc::read_section(path, "accretion_disc", SECTIONS)?;
+            let star: Star = sinks.as_ref().ok_or("The accretion disc needs force = sinks")?.star();
+            setup_accretion_disc(&mut particles, run, &disc, &star);
+        },
+        Setup::AccretionDiscUniform => {
+            let disc: AccretionDiscUniform = AccretionDiscUniform::read_section(path, "accretion_disc_uniform", SECTIONS)?;
+            let star: Star = sinks.as_ref().ok_or("The accretion disc needs force = sinks")?.star();
+            setup_accretion_disc_uniform(&mut particles, run, &disc, &star);
+        },
+        Setup::AccretionDiscPhantom => {
+            let disc: AccretionDiscPhantom = AccretionDiscPhantom::read_section(path, "accretion_disc_phantom", SECTIONS)?;
+            let star: Star = sinks.as_ref().ok_or("The accretion disc needs force = sinks")?.star();
+            if disc.source.ends_with(".csv") {
+                datafunctions::read_data_iso(&disc.source, disc.m_disc*star.m/disc.n as f64, &mut particles)?;
+            } else {
+                datafunctions::read_phantom(&disc.source, &mut particles, &mut stars)?;
+                // The dump has no accretion fraction nor speed of light
+                for sink in stars.iter_mut() {
+                    sink.facc = star.facc;
+                    sink.c    = star.c;
+                }
+            }
+        },
+        Setup::LidDrivenCavity => {
+            let cavity: LidDrivenCavity = LidDrivenCavity::read_section(path, "lid_driven_cavity", SECTIONS)?;
+            setup_lid_driven_cavity(&mut particles, run, &cavity);
+        },
+        Setup::Piston => {
+            let piston: Piston = Piston::read_section(path, "piston", SECTIONS)?;
+            setup_piston(&mut particles, run, &piston);
+        },
+    }
+    if let Some(sinks) = &sinks {
+        if stars.is_empty() {
+            stars.push(sinks.star());
+        }
+    }
+    Ok((particles, stars))
+}
+
+// Two states at rest separated at xm. The sixth of each state next to the
+// ends of the tube is made of boundary particles.
+fn setup_sod(particles: &mut Vec<Particle>, run: &Run, sod: &Sod) {
+    let ul: f64     = sod.pl/((run.gamma - 1.)*sod.rhol);   // Left initial energy
+    let ur: f64     = sod.pr/((run.gamma - 1.)*sod.rhor);   // Right initial energy
+    let bxl: f64    = (sod.xm-run.x0)/6.;                   // Left boundary region
+    let bxr: f64    = (run.x0+run.wd-sod.xm)/6.;            // Right boundary region
+
+    partdistribution::init_dist_hcp(particles, sod.nxl, sod.rhol, run.eta, sod.xm-run.x0, run.lg, run.hg, run.x0, run.y0, run.z0);
+    partdistribution::init_dist_hcp(particles, sod.nxr, sod.rhor, run.eta, run.x0+run.wd-sod.xm, run.lg, run.hg, sod.xm, run.y0, run.z0);
+
+    for particle in particles.iter_mut() {
+        if particle.x <= sod.xm {
+            particle.u = ul;
+            if particle.x <= run.x0 + bxl {
+                particle.ptype = 1;
+            }
+        } else {
+            particle.u = ur;
+            if particle.x >= run.x0 + run.wd - bxr {
+                particle.ptype = 1;
+            }
+        }
+    }
+}
+
+// Uniform gas with the energy e0 spread with the cubic kernel over the
+// particles around the centre of the domain.
+fn setup_sedov(particles: &mut Vec<Particle>, run: &Run, sedov: &Sedov) {
+    let rkern: f64  = 2.;                               // Cubic Kernel radius
+    let h0: f64     = 2.*run.eta*(run.wd/sedov.nx as f64);
+    let xc: f64     = run.x0 + 0.5*run.wd;
+    let yc: f64     = run.y0 + 0.5*run.lg;
+    let zc: f64     = run.z0 + 0.5*run.hg;
+
+    partdistribution::init_dist_hcp(particles, sedov.nx, sedov.rho0, run.eta, run.wd, run.lg, run.hg, run.x0, run.y0, run.z0);
+    let dm: f64     = sedov.rho0*run.wd*run.lg*run.hg/particles.len() as f64;
+
+    let mut u_norm: f64 = 0.0;
+    for particle in particles.iter_mut() {
+        let q: f64 = ((particle.x-xc)*(particle.x-xc) + (particle.y-yc)*(particle.y-yc) + (particle.z-zc)*(particle.z-zc)).sqrt()/h0;
+        particle.u = if q < rkern {sphfunctions::f_cubic_kernel(q)} else {0.0};
+        u_norm += particle.u;
+    }
+    for particle in particles.iter_mut() {
+        particle.u *= sedov.e0/(dm*u_norm);
+    }
+}
+
+// Fluid 2 between y1 and y2 moving against fluid 1, with a perturbation
+// of vy at the interfaces.
+fn setup_kelvin_helmholtz(particles: &mut Vec<Particle>, run: &Run, kh: &KelvinHelmholtz) {
+    let lg2: f64    = 0.25*run.lg;
+    let u1: f64     = kh.p0/((run.gamma - 1.)*kh.rho1);
+    let u2: f64     = kh.p0/((run.gamma - 1.)*kh.rho2);
+    let ym: f64     = 0.5*(kh.y1 + kh.y2);
+
+    // Same lattice spacing in both fluids: the density contrast is set by the particles' mass.
+    partdistribution::init_dist_hcp(particles, kh.nx, kh.rho1, run.eta, run.wd, lg2, run.hg, run.x0, run.y0, run.z0);
+    partdistribution::init_dist_hcp(particles, kh.nx, kh.rho2, run.eta, run.wd, 0.5*run.lg, run.hg, run.x0, run.y0+lg2, run.z0);
+    partdistribution::init_dist_hcp(particles, kh.nx, kh.rho1, run.eta, run.wd, lg2, run.hg, run.x0, run.y0+0.75*run.lg, run.z0);
+
+    for particle in particles.iter_mut() {
+        if (particle.y - ym).abs() <= lg2 {
+            particle.vx = kh.vx2;
+            particle.u  = u2;
+        } else {
+            particle.vx = kh.vx1;
+            particle.u  = u1;
+        }
+        let exp1: f64   = (particle.y - kh.y1)/0.05;
+        let exp2: f64   = (particle.y - kh.y2)/0.05;
+        particle.vy = 0.1*(2.*PI*particle.x/0.5).sin()*((-exp1*exp1).exp() + (-exp2*exp2).exp());
+    }
+}
+
+// Fluid 2 above ym and fluid 1 below it, with the pressure p0 at the
+// interface and the hydrostatic profile dp/dy = rho*gy on each side. The
+// interface is perturbed by a single mode of vy that vanishes at the walls.
+fn setup_rayleigh_taylor(particles: &mut Vec<Particle>, run: &Run, rt: &RayleighTaylor, gravity: &Gravity) -> Result<(), Box<dyn Error>> {
+    let lg1: f64    = rt.ym - run.y0;
+    let lg2: f64    = run.y0 + run.lg - rt.ym;
+    let pressure = |rho: f64, y: f64| -> f64 {rt.p0 + rho*gravity.gy*(y - rt.ym)};
+    if pressure(rt.rho1, run.y0) <= 0.0 || pressure(rt.rho2, run.y0 + run.lg) <= 0.0 {
+        return Err("The Rayleigh-Taylor pressure must stay positive up to the walls: raise p0".into());
+    }
+
+    // Same lattice spacing in both fluids: the density contrast is set by the particles' mass.
+    partdistribution::init_dist_hcp(particles, rt.nx, rt.rho1, run.eta, run.wd, lg1, run.hg, run.x0, run.y0, run.z0);
+    partdistribution::init_dist_hcp(particles, rt.nx, rt.rho2, run.eta, run.wd, lg2, run.hg, run.x0, rt.ym, run.z0);
+
+    for particle in particles.iter_mut() {
+        let rho: f64    = if particle.y < rt.ym {rt.rho1} else {rt.rho2};
+        particle.u  = pressure(rho, particle.y)/((run.gamma - 1.)*rho);
+        particle.vy = 0.25*rt.v0*(1. + (2.*PI*(particle.x - run.x0)/run.wd).cos())*(1. + (2.*PI*(particle.y - rt.ym)/run.lg).cos());
+    }
+    Ok(())
+}
+
+// Uniform sphere of radius r and mass m at the origin.
+fn setup_toy_star(particles: &mut Vec<Particle>, run: &Run, star: &ToyStar) {
+    let rho: f64    = 3.*star.m/(4.*PI*star.r*star.r*star.r);
+    partdistribution::init_dist_hcp(particles, star.n, rho, run.eta, 2.*star.r, 2.*star.r, 2.*star.r, -star.r, -star.r, -star.r);
+    particles.retain(|particle| particle.x*particle.x + particle.y*particle.y + particle.z*particle.z < star.r*star.r);
+
+    let dm: f64     = star.m/particles.len() as f64;
+    let h: f64      = sphfunctions::h_from_density(dm, rho, run.eta);
+    for particle in particles.iter_mut() {
+        particle.m  = dm;
+        particle.h  = h;
+        particle.vx = star.vx0;
+        particle.vy = star.vy0;
+        particle.vz = star.vz0;
+        particle.u  = star.u0;
+    }
+}
+
+// Uniform gas moving with the background flow of the shearing box,
+// vy = -q*omega*(x - xc).
+fn setup_shear_flow(particles: &mut Vec<Particle>, run: &Run, flow: &ShearFlow, shear: &ShearingBox) {
+    let xc: f64     = run.x0 + 0.5*run.wd;
+    partdistribution::init_dist_hcp(particles, flow.nx, flow.rho0, run.eta, run.wd, run.lg, run.hg, run.x0, run.y0, run.z0);
+    for particle in particles.iter_mut() {
+        particle.vy = -shear.q*shear.omega*(particle.x - xc);
+        particle.u  = flow.u0;
+    }
+}
+
+// Disc of n particles placed at random (with a fixed seed) following the
+// surface density and the vertical hydrostatic profile, in the centre of
+// mass frame of the star.
+fn setup_accretion_disc(particles: &mut Vec<Particle>, run: &Run, disc: &AccretionDisc, star: &Star) {
+    let nbins: usize = 10000;                       // Number of bins for integration
+    let m_disc: f64 = disc.m_disc*star.m;
+    let sigma0: f64 = m_disc/partdistribution::disc_mass(disc.r_in, disc.r_out, disc.r_ref, disc.p_index, 1.0, nbins);
+    let cs0: f64    = run.coeff.sqrt();
+    let mut rng     = StdRng::seed_from_u64(SEED);
+
+    partdistribution::init_dist_disc1(particles, disc.n, star.m, disc.r_in, disc.r_out, m_disc, disc.p_index, run.q_index, disc.r_ref, sigma0, cs0, run.eta, nbins, &mut rng);
+    partdistribution::init_dist_disc_velocities(particles, disc.n, star.m, disc.r_in, disc.p_index, run.q_index, cs0, run.gamma);
+    partdistribution::com_frame(particles, disc.n, star.x, star.y, star.z, star.vx, star.vy, star.vz);
+}
+
+// Lattice in the box around the annulus, keeping the particles between r_in
+// and r_out from the star, with the Keplerian velocity of the star and the
+// particle.
+fn setup_accretion_disc_uniform(particles: &mut Vec<Particle>, run: &Run, disc: &AccretionDiscUniform, star: &Star) {
+    let rho: f64    = disc.m_disc/(PI*disc.h*(disc.r_out-disc.r_in)*(disc.r_out+disc.r_in));
+    partdistribution::init_dist_hcp(particles, disc.nx, rho, run.eta, 2.*disc.r_out, 2.*disc.r_out, disc.h, star.x-disc.r_out, star.y-disc.r_out, star.z-0.5*disc.h);
+    particles.retain(|particle| {
+        let r2: f64 = (particle.x-star.x)*(particle.x-star.x) + (particle.y-star.y)*(particle.y-star.y);
+        disc.r_in*disc.r_in < r2 && r2 < disc.r_out*disc.r_out
+    });
+
+    let dm: f64     = disc.m_disc/particles.len() as f64;
+    let h: f64      = sphfunctions::h_from_density(dm, rho, run.eta);
+    let m_t: f64    = star.m + dm;
+    for particle in particles.iter_mut() {
+        let (x, y): (f64, f64) = (particle.x - star.x, particle.y - star.y);
+        let omega: f64 = (m_t/(x*x + y*y).powf(1.5)).sqrt();
+        particle.m  = dm;
+        particle.h  = h;
+        particle.vx = star.vx - y*omega;
+        particle.vy = star.vy + x*omega;
+        particle.vz = star.vz;
+        particle.u  = 0.0;
+    }
+}
+
+// Gas at rest in the cavity, with the walls and the lid made of boundary
+// particles on the same cubic lattice. The lid is built last.
+fn setup_lid_driven_cavity(particles: &mut Vec<Particle>, run: &Run, cavity: &LidDrivenCavity) {
+    let dx: f64     = run.wd/(cavity.nx + 2*cavity.nb) as f64;  // Particle spacing
+    let bw: f64     = cavity.nb as f64*dx;                      // Width of the walls
+    let u0: f64     = cavity.cs0*cavity.cs0/(run.gamma*(run.gamma - 1.));
+
+    // Fluid
+    partdistribution::init_dist_cubic(particles, cavity.nx, cavity.rho0, run.eta, run.wd-2.*bw, run.lg-2.*bw, run.hg, run.x0+bw, run.y0+bw, run.z0);
+    let n: usize    = particles.len();
+
+    // Walls: bottom, left and right
+    partdistribution::init_dist_cubic(particles, cavity.nx+2*cavity.nb, cavity.rho0, run.eta, run.wd, bw, run.hg, run.x0, run.y0, run.z0);
+    partdistribution::init_dist_cubic(particles, cavity.nb, cavity.rho0, run.eta, bw, run.lg-2.*bw, run.hg, run.x0, run.y0+bw, run.z0);
+    partdistribution::init_dist_cubic(particles, cavity.nb, cavity.rho0, run.eta, bw, run.lg-2.*bw, run.hg, run.x0+run.wd-bw, run.y0+bw, run.z0);
+    let n_lid: usize = particles.len();
+
+    // Lid
+    partdistribution::init_dist_cubic(particles, cavity.nx+2*cavity.nb, cavity.rho0, run.eta, run.wd, bw, run.hg, run.x0, run.y0+run.lg-bw, run.z0);
+
+    for (ii, particle) in particles.iter_mut().enumerate() {
+        particle.u = u0;
+        if ii >= n {
+            particle.ptype = 1;
+        }
+        if ii >= n_lid {
+            particle.vx = cavity.v_lid;
+        }
+    }
+}
+
+// Gas at rest in the tube, between the piston and the wall.
+fn setup_piston(particles: &mut Vec<Particle>, run: &Run, piston: &Piston) {
+    let dx: f64     = run.wd/(piston.nx + 2*piston.nb) as f64;  // Particle spacing
+    let bw: f64     = piston.nb as f64*dx;                      // Width of the piston and the wall
+    let u0: f64     = piston.p0/((run.gamma - 1.)*piston.rho0);
+
+    // Gas
+    partdistribution::init_dist_cubic(particles, piston.nx, piston.rho0, run.eta, run.wd-2.*bw, run.lg, run.hg, run.x0+bw, run.y0, run.z0);
+    let n: usize    = particles.len();
+
+    // Piston
+    partdistribution::init_dist_cubic(particles, piston.nb, piston.rho0, run.eta, bw, run.lg, run.hg, run.x0, run.y0, run.z0);
+    let n_wall: usize = particles.len();
+
+    // Wall
+    partdistribution::init_dist_cubic(particles, piston.nb, piston.rho0, run.eta, bw, run.lg, run.hg, run.x0+run.wd-bw, run.y0, run.z0);
+
+    for (ii, particle) in particles.iter_mut().enumerate() {
+        particle.u = u0;
+        if ii >= n {
+            particle.ptype = 1;
+        }
+        if ii >= n && ii < n_wall {
+            particle.vx = piston.v_piston;
+        }
+    }
+}
+
+// -------- Boundary bodies --------
+
+// Boundary particles with the ids in ids follow the motion.
+pub struct Body {
+    pub ids: Range<u64>,
+    pub motion: Box<dyn PrescribedMotion>,
+}
+
+// Builds the moving boundaries of the setup of a run: the lid of the cavity
+// and the piston. Their particles are picked in Ini_00.csv, where the bodies
+// are at the start of the run.
+pub fn bodies(path: &str, run: &Run) -> Result<Vec<Body>, Box<dyn Error>> {
+    let mut initial: Vec<Particle> = Vec::new();
+    match run.setup {
+        Setup::LidDrivenCavity => {
+            let cavity: LidDrivenCavity = LidDrivenCavity::read_section(path, "lid_driven_cavity", SECTIONS)?;
+            let bw: f64 = run.lg*cavity.nb as f64/(cavity.nx + 2*cavity.nb) as f64;
+            datafunctions::read_data(&run.file("Ini_00.csv"), &mut initial)?;
+            let lid = initial.iter().filter(|p| p.ptype == 1 && p.y > run.y0 + run.lg - bw).map(|p| p.id);
+            Ok(vec![Body{ids: id_range(lid), motion: Box::new(sphfunctions::SlidingWall{vx: cavity.v_lid, vy: 0.0, vz: 0.0})}])
+        },
+        Setup::Piston => {
+            let piston: Piston = Piston::read_section(path, "piston", SECTIONS)?;
+            let bw: f64 = run.wd*piston.nb as f64/(piston.nx + 2*piston.nb) as f64;
+            datafunctions::read_data(&run.file("Ini_00.csv"), &mut initial)?;
+            let ids = initial.iter().filter(|p| p.ptype == 1 && p.x < run.x0 + bw).map(|p| p.id);
+            Ok(vec![Body{ids: id_range(ids), motion: Box::new(sphfunctions::Piston{vx: piston.v_piston, vy: 0.0, vz: 0.0})}])
+        },
+        _ => Ok(Vec::new()),
+    }
+}
+
+// Smallest range holding the ids
+fn id_range(ids: impl Iterator<Item = u64> + Clone) -> Range<u64> {
+    let id_min: u64 = ids.clone().min().unwrap_or(0);
+    let id_max: u64 = ids.max().unwrap_or(0);
+    return id_min..id_max+1;
+}
+
+// -------- External forces --------
+
+// Builds the external force of a run, reading its section if it has one.
+pub fn forces(path: &str, run: &Run) -> Result<Vec<Box<dyn sphfunctions::ExternalForce>>, Box<dyn Error>> {
+    let mut forces: Vec<Box<dyn sphfunctions::ExternalForce>> = Vec::new();
+    match run.force {
+        Force::None => {},
+        Force::ToyStar => {
+            let star: ToyStar = ToyStar::read_section(path, "toy_star", SECTIONS)?;
+            let lambda: f64 = coeff_static_grav_potential(run.coeff, run.gamma, star.m, star.r);
+            forces.push(Box::new(sphfunctions::ToyStar{nu: star.nu, lambda}));
+        },
+        Force::Gravity => {
+            let gravity: Gravity = Gravity::read_section(path, "gravity", SECTIONS)?;
+            forces.push(Box::new(sphfunctions::ConstantGravity{gx: gravity.gx, gy: gravity.gy, gz: gravity.gz}));
+        },
+        // The sinks move: they are added to the forces on every step
+        Force::Sinks => {},
+    }
+    // The frame of the shearing box
+    if let Some(shear) = shearing_box(path, run)? {
+        forces.push(Box::new(sphfunctions::ShearingBox{q: shear.q, omega: shear.omega, xc: run.x0 + 0.5*run.wd}));
+    }
+    Ok(forces)
+}
+
+// Reads the shearing box section when the boundary is a shearing box.
+pub fn shearing_box(path: &str, run: &Run) -> Result<Option<ShearingBox>, Box<dyn Error>> {
+    if run.boundary != Boundary::ShearingBox {
+        return Ok(None);
+    }
+    if !run.xper || !run.yper || run.xwall || run.ywall {
+        return Err("A shearing box must be periodic in x and y, without walls".into());
+    }
+    Ok(Some(ShearingBox::read_section(path, "shearing_box", SECTIONS)?))
+}
+
+// Reads the sinks section when the force is the sinks.
+pub fn sinks(path: &str, run: &Run) -> Result<Option<Sinks>, Box<dyn Error>> {
+    if run.force != Force::Sinks {
+        return Ok(None);
+    }
+    Ok(Some(Sinks::read_section(path, "sinks", SECTIONS)?))
+}
+
+// Coefficient of the static potential of a polytropic toy star
+fn coeff_static_grav_potential(k: f64, gamm: f64, m: f64, r: f64) -> f64 {
+    let gamma_func: f64 = gamma(1.5 + gamm/(gamm-1.))/gamma(gamm/(gamm-1.));
+    2.*k*(gamm/(gamm-1.))/PI.powf(1.5*(gamm-1.)) * (gamma_func*m/(r*r*r)).powf(gamm-1.)/(r*r)
+}
+
+// -------- Analysis --------
+
+// Writes Analysis.csv in the directory of a run: the number of gas
+// particles, their mass, momentum and kinetic and thermal energy at every
+// snapshot and the final state. Returns the number of snapshots read.
+pub fn analyze(run: &Run) -> Result<usize, Box<dyn Error>> {
+    let entries: Vec<datafunctions::ManifestEntry> = datafunctions::read_manifest(&run.output_dir)?;
+
+    let mut wtr = Writer::from_path(run.file("Analysis.csv"))?;
+    wtr.write_record(["t", "n", "mass", "px", "py", "pz", "ekin", "eth"])?;
+
+    let mut particles: Vec<Particle> = Vec::new();
+    let mut count: usize = 0;
+    for entry in entries.iter().filter(|entry| entry.stream == "snapshot" || entry.stream == "final") {
+        particles.clear();
+        datafunctions::read_output(&run.file(&entry.file), &mut particles)?;
+        let mut record: Vec<String> = vec![entry.t.to_string()];
+        record.extend(datafunctions::diagnostics(&particles).values());
+        wtr.write_record(&record)?;
+        count += 1;
+    }
+    wtr.flush()?;
+    Ok(count)
+}
@@ -0,0 +1,299 @@
+// ------------------------------------------------------------------------- //
+// Rusph: SPH runs described by a configuration file.                        //
+// setup writes the initial particles of the run, run evolves them, resume   //
+// continues a run from its last checkpoint and analyze writes the           //
+// conserved quantities of every snapshot.                                   //
+// Usage:                                                                    //
+//      cargo run --release -p rusph -- <command> <config>                   //
+// ------------------------------------------------------------------------- //
+
+use std::{
+    env,
+    error::Error,
+    process,
+    time::Instant,
+};
+
+use structures::{
+    Particle,
+    Node,
+    Pointer,
+    Star,
+};
+
+use tree_algorithm::BuildTree;
+
+use rusph::Run;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        println!("Usage: rusph <setup|run|resume|analyze> <config>");
+        process::exit(1);
+    }
+    let command: &str   = &args[1];
+    let path: &str      = &args[2];
+
+    let run: Run = match Run::from_file(path) {
+        Ok(run) => run,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
+
+    let result: Result<(), Box<dyn Error>> = match command {
+        "setup"   => setup(path, &run),
+        "run"     => evolve(path, &run, false),
+        "resume"  => evolve(path, &run, true),
+        "analyze" => rusph::analyze(&run).map(|n| println!("{} snapshots analyzed.", n)),
+        _ => Err(format!("Unknown command '{}' (setup, run, resume or analyze)", command).into()),
+    };
+    if let Err(err) = result {
+        println!("{}", err);
+        process::exit(1);
+    }
+    Ok(())
+}
+
+// Writes the initial particles to Ini_00.csv in the directory of the run,
+// and its sinks to Sinks_00 (a snapshot without particles).
+fn setup(path: &str, run: &Run) -> Result<(), Box<dyn Error>> {
+    let (particles, stars): (Vec<Particle>, Vec<Star>) = rusph::setup(path, run)?;
+    datafunctions::save_data(&run.file("Ini_00.csv"), &particles)?;
+    println!("{} particles written to {}.", particles.len(), run.file("Ini_00.csv"));
+    if !stars.is_empty() {
+        let stars: Vec<&Star> = stars.iter().collect();
+        datafunctions::save_snapshot(&run.file("Sinks_00"), &datafunctions::Header::default(), &Vec::new(), &stars)?;
+        println!("{} sinks written to {}.", stars.len(), run.file("Sinks_00"));
+    }
+    Ok(())
+}
+
+// Evolves the particles of a run from Ini_00.csv, or from its checkpoint.
+fn evolve(path: &str, run: &Run, resume: bool) -> Result<(), Box<dyn Error>> {
+    let path_checkpoint: String = run.file("Checkpoint");
+
+    //---------------------------------------------------------------------------------------------
+    // Physics
+    let f: fn(f64) -> f64       = run.kernel.f();
+    let dfdq: fn(f64) -> f64    = run.kernel.dfdq();
+    let sigma: f64              = run.kernel.sigma();   // Normalization constant of kernel
+    let rkern: f64              = run.kernel.rkern();   // Kernel radius
+    let eos                     = run.eos.pressure();
+    let cs                      = run.eos.sound_speed();
+    let viscosity               = run.viscosity.function();
+    let boundary                = run.boundary.function();
+    // None for the block steps
+    let integrator: Option<Box<dyn sphfunctions::Integrator>> = run.integrator.integrator();
+    let forces: Vec<Box<dyn sphfunctions::ExternalForce>> = rusph::forces(path, run)?;
+    let forces: Vec<&dyn sphfunctions::ExternalForce> = forces.iter().map(|force| force.as_ref()).collect();
+    let shear: Option<rusph::ShearingBox> = rusph::shearing_box(path, run)?;
+    let sinks: Option<rusph::Sinks> = rusph::sinks(path, run)?;
+    let eta_orb: f64            = sinks.as_ref().map_or(0.1, |sinks| sinks.eta_orb);
+    let bodies: Vec<rusph::Body> = rusph::bodies(path, run)?;
+    let bodies: Vec<sphfunctions::BoundaryBody> = bodies.iter().map(|body| sphfunctions::BoundaryBody{ids: body.ids.clone(), motion: body.motion.as_ref()}).collect();
+
+    let (x0, y0, z0): (f64, f64, f64)   = (run.x0, run.y0, run.z0);
+    let (wd, lg, hg): (f64, f64, f64)   = (run.wd, run.lg, run.hg);
+    let (xper, yper, zper): (bool, bool, bool) = (run.xper, run.yper, run.zper);
+    let (eta, gamma, coeff, eos_t): (f64, f64, f64, bool) = (run.eta, run.eos_gamma(), run.coeff, run.adiabatic);
+    let (s_, alpha_, beta_): (i32, f64, f64) = (run.s, run.alpha, run.beta);
+    let (xwall, ywall, zwall): (bool, bool, bool) = (run.xwall, run.ywall, run.zwall);
+    let walls: bool             = xwall || ywall || zwall;
+    // The block steps apply the boundary of the domain alone
+    if integrator.is_none() && (walls || shear.is_some() || !bodies.is_empty() || run.boundary_particles) {
+        return Err("The block integrator takes no walls, shearing box, moving boundaries nor boundary_particles".into());
+    }
+    // The state of the boundary particles is set through the ideal gas EoS
+    if run.boundary_particles && run.eos != rusph::Eos::IdealGas {
+        return Err("boundary_particles needs eos = ideal_gas".into());
+    }
+    // Offsets of the images across x at time t, (y_shear, vy_shear)
+    let offsets = |t: f64| -> (f64, f64) {
+        match &shear {
+            Some(shear) => sphfunctions::shear_offsets(shear.q, shear.omega, wd, lg, t),
+            None => (0.0, 0.0),
+        }
+    };
+
+    let mut dt: f64     = 0.01*run.dt_sav;  // Initial time step
+    let mut sav: bool   = false;            // Save data
+    let mut it_sav: u32 = 1;                // Save data iteration
+    //---------------------------------------------------------------------------------------------
+
+    // Create particles
+    let mut particles: Vec<Particle> = Vec::new();
+    let mut stars: Vec<Star> = Vec::new();
+    let state: Option<datafunctions::RunState> = if resume {
+        let state: datafunctions::RunState = datafunctions::read_checkpoint(&path_checkpoint, &mut particles, &mut stars)?;
+        println!("Resuming from {} at t = {} (iteration {}).", path_checkpoint, state.t, state.it);
+        Some(state)
+    } else {
+        datafunctions::read_data(&run.file("Ini_00.csv"), &mut particles)?;
+        if sinks.is_some() {
+            datafunctions::read_snapshot(&run.file("Sinks_00"), &mut Vec::new(), &mut stars)?;
+        }
+        None
+    };
+    let mut particles_ptr = Pointer(particles.as_mut_ptr());
+
+    let mut t: f64      = run.t0;           // Time
+    let mut n: usize    = particles.len();  // Number of particles
+    let mut it: u32     = 0;                // Time iterations
+    if let Some(state) = &state {
+        t       = state.t;
+        dt      = state.dt;
+        it      = state.it;
+        it_sav  = state.it_sav;
+    } else {
+        for particle in particles.iter_mut() {
+            particle.rho = sphfunctions::density_from_h(particle.m, particle.h, eta);
+        }
+    }
+
+    // Save time evolution
+    let mut output: datafunctions::Output = datafunctions::Output::new(&run.output_dir, &run.prefix, run.digits, run.output, run.diag_steps, run.diag_dt, state.as_ref())?;
+
+    // The tree also covers the ghosts beyond the walls
+    let h_max: f64      = particles.iter().fold(0.0, |a, particle| a.max(particle.h));
+    let margin = |wall: bool| -> f64 {if wall {2.0*rkern*h_max} else {0.0}};
+    let (xt0, wdt): (f64, f64) = (x0 - margin(xwall), wd + 2.0*margin(xwall));
+    let (yt0, lgt): (f64, f64) = (y0 - margin(ywall), lg + 2.0*margin(ywall));
+    let (zt0, hgt): (f64, f64) = (z0 - margin(zwall), hg + 2.0*margin(zwall));
+
+    let mut tree: Node  = <Node as BuildTree>::new(n as i32, xt0, yt0, zt0, wdt, lgt, hgt);
+    if run.boundary == rusph::Boundary::None {
+        tree.auto_root(rkern, xper, yper, zper);
+    }
+
+    let header: datafunctions::Header = datafunctions::Header{x0, y0, z0, wd, lg, hg, xper, yper, zper, eos_t, gamma, coeff, ..Default::default()};
+    output.header = header;
+    let mut checkpoint: datafunctions::Checkpoint = datafunctions::Checkpoint::new(&path_checkpoint, run.chk_steps, run.chk_min);
+    let stop: datafunctions::Stop = datafunctions::Stop::new(run.max_walltime)?;
+
+    // The block steps start from the forces of the initial state, and a
+    // resumed run keeps those of the checkpoint. Their tree is built
+    // between steps.
+    if integrator.is_none() {
+        tree.build_tree(s_, alpha_, beta_, &particles, 1.0e-02);
+    }
+    if integrator.is_none() && !resume {
+        let mut all_forces: Vec<&dyn sphfunctions::ExternalForce> = forces.clone();
+        all_forces.extend(stars.iter().map(|star| star as &dyn sphfunctions::ExternalForce));
+        sphfunctions::smoothing_length(&mut particles, eta, f, dfdq, sigma, rkern, 1e-03, 30, 0.0, &tree, s_, n, particles_ptr,
+                                       wdt, lgt, hgt, xt0, yt0, zt0, xper, yper, zper, 0.0);
+        sphfunctions::accelerations(&mut particles, eos_t, eos, cs, gamma, coeff,
+                                    sphfunctions::dwdh, f, dfdq, sigma, rkern,
+                                    &tree, s_, n, particles_ptr, wdt, lgt, hgt, xt0, yt0, zt0,
+                                    viscosity,
+                                    &all_forces, xper, yper, zper, 0.0, 0.0);
+    }
+
+    //------------------------------------ Main Loop ----------------------------------------------
+    let start  = Instant::now();   // Runing time
+    let mut stopping: bool = false; // Stopped before the final time
+    while t < run.tf  {
+        if let Some(integrator) = &integrator {
+            // The gas feels the sinks where they are at the start of the step
+            let mut all_forces: Vec<&dyn sphfunctions::ExternalForce> = forces.clone();
+            all_forces.extend(stars.iter().map(|star| star as &dyn sphfunctions::ExternalForce));
+            // The derivatives are evaluated at t_eval, which every call advances
+            // by the time elapsed since the previous one
+            let mut t_eval: f64 = t;
+            datafunctions::time_step(&mut t, &mut dt, run.dt_sav, &mut sav, &mut it_sav);
+            let mut derivatives = |particles: &mut Vec<Particle>, dt: f64| {
+                sphfunctions::move_boundaries(particles, &bodies, t_eval, t_eval + dt);
+                t_eval += dt;
+                let (y_shear, vy_shear): (f64, f64) = offsets(t_eval);
+                let mut n: usize    = particles.len();
+                let mut ptr: Pointer = Pointer(particles.as_mut_ptr());
+                // The ghosts of the previous evaluation are stale
+                sphfunctions::remove_ghosts(particles, &mut n, &mut ptr);
+                if walls {
+                    sphfunctions::reflective_boundary(particles, wd, lg, hg, x0, y0, z0, xwall, ywall, zwall);
+                }
+                boundary(particles, wd, lg, hg, x0, y0, z0, y_shear, vy_shear);
+                let sources: Vec<usize> = if walls {
+                    sphfunctions::mirror_ghosts(particles, &mut n, &mut ptr, rkern, wd, lg, hg, x0, y0, z0, xwall, ywall, zwall)
+                } else {
+                    Vec::new()
+                };
+                tree.restart(n);
+                tree.build_tree(s_, alpha_, beta_, particles, 1.0e-02);
+                sphfunctions::smoothing_length(particles, eta, f, dfdq, sigma, rkern, 1e-03, 30, dt, &tree, s_, n, ptr,
+                                               wdt, lgt, hgt, xt0, yt0, zt0, xper, yper, zper, y_shear);
+                sphfunctions::update_ghosts(particles, &sources);
+                if run.boundary_particles {
+                    sphfunctions::boundary_particles(particles, &bodies, t_eval, eos, gamma, coeff, eta, f, rkern,
+                                                     &all_forces, &tree, s_, n, ptr, wdt, lgt, hgt, xt0, yt0, zt0, xper, yper, zper, y_shear);
+                }
+                sphfunctions::accelerations(particles, eos_t, eos, cs, gamma, coeff,
+                                            sphfunctions::dwdh, f, dfdq, sigma, rkern,
+                                            &tree, s_, n, ptr, wdt, lgt, hgt, xt0, yt0, zt0,
+                                            viscosity,
+                                            &all_forces, xper, yper, zper, y_shear, vy_shear);
+            };
+            integrator.step(&mut particles, dt, &mut derivatives);
+            // The time step only sees the particles that are integrated
+            sphfunctions::remove_ghosts(&mut particles, &mut n, &mut particles_ptr);
+            tree.restart(n);
+            tree.build_tree(s_, alpha_, beta_, &particles, 1.0e-02);
+            if !stars.is_empty() {
+                for star in stars.iter_mut() {
+                    sphfunctions::accretion_boundary(star, &mut particles, &mut n, &mut particles_ptr, &mut tree, s_, wdt, lgt, hgt, xt0, yt0, zt0, xper, yper, zper, offsets(t).0);
+                }
+                sphfunctions::sinks_integrator(&mut stars, dt, eta_orb);
+                tree.restart(n);
+                tree.build_tree(s_, alpha_, beta_, &particles, 1.0e-02);
+            }
+            let (y_shear, vy_shear): (f64, f64) = offsets(t);
+            dt = sphfunctions::time_step_mon(&particles, n, gamma, coeff, rkern, wdt, lgt, hgt, xt0, yt0, zt0, &mut tree, s_, cs, xper, yper, zper, y_shear, vy_shear);
+            tree.restart(n);
+        } else {
+            // Block time step and particles' bins
+            let dts: Vec<f64> = sphfunctions::time_step_mon_particles(&particles, n, gamma, coeff, rkern, wdt, lgt, hgt, xt0, yt0, zt0, &mut tree, s_, cs, xper, yper, zper, 0.0, 0.0);
+            dt = sphfunctions::block_time_step(&dts, run.dt_sav, run.nbins);
+            datafunctions::time_step(&mut t, &mut dt, run.dt_sav, &mut sav, &mut it_sav);
+            sphfunctions::assign_bins(&mut particles, &dts, dt, run.nbins);
+            tree.restart(n);
+            sphfunctions::block_kdk_integrator(&mut particles, dt, run.nbins, run.limiter, eos_t, eos, cs, gamma, coeff,
+                                               sphfunctions::dwdh, f, dfdq, sigma, rkern,
+                                               eta, &mut tree, s_, alpha_, beta_, n, particles_ptr,
+                                               viscosity,
+                                               &forces, &mut stars, eta_orb,
+                                               boundary, xper, yper, zper, 0.0, 0.0, wdt, lgt, hgt, xt0, yt0, zt0);
+            for star in stars.iter_mut() {
+                sphfunctions::accretion_boundary(star, &mut particles, &mut n, &mut particles_ptr, &mut tree, s_, wdt, lgt, hgt, xt0, yt0, zt0, xper, yper, zper, 0.0);
+            }
+            tree.restart(n);
+            tree.build_tree(s_, alpha_, beta_, &particles, 1.0e-02);
+        }
+        println!("dt: {:.4}\tt: {:.4}", dt, t);
+        if sav {
+            output.snapshot(it_sav-2, t, &particles, &stars.iter().collect::<Vec<&Star>>())?;
+        }
+        it += 1;
+        output.step(it, t, dt, &particles)?;
+        stopping = stop.requested();
+        if checkpoint.due(it) || stopping {
+            let stars: Vec<&Star> = stars.iter().collect();
+            checkpoint.save(&datafunctions::RunState{t, t_boundary: t, dt, it, it_sav}, &header, &particles, &stars)?;
+            output.record("checkpoint", it, t, "Checkpoint")?;
+        }
+        if stopping {
+            println!("Stopping at t = {} (iteration {}). Continue with `rusph resume`.", t, it);
+            break;
+        }
+    }
+    if stopping {
+        println!("Simulation stopped at t = {} of {}, resumable from the checkpoint.\n Time {} s.\n Iterations: {}.", t, run.tf, start.elapsed().as_secs(), it);
+    } else {
+        println!("Simulation run successfully.\n Time {} s.\n Iterations: {}.", start.elapsed().as_secs(), it);
+    }
+    //---------------------------------------------------------------------------------------------
+
+    // Save final information
+    output.finish(t, &particles, &stars.iter().collect::<Vec<&Star>>())?;
+    Ok(())
+}
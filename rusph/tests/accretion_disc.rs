@@ -0,0 +1,35 @@
+// ------------------------------------------------------------------------- //
+// Accretion disc: the sink takes the gas inside its accretion radius and    //
+// the mass of the gas and the sink is kept.                                 //
+// ------------------------------------------------------------------------- //
+
+use std::fs;
+
+use structures::{Particle, Star};
+
+mod common;
+
+#[test]
+fn sink_keeps_the_mass() {
+    // The inner edge of the disc lies within hacc, so the sink accretes
+    // from the first step. The checkpoint of every step holds the sink.
+    let (dir, path) = common::config("accretion_disc_uniform", "accretion-disc", &[("nx", "48"), ("r_in", "0.1"), ("hacc", "0.15"), ("tf", "0.02"), ("dt_sav", "0.02"), ("chk_steps", "1"), ("output", "csv")]);
+    common::rusph("setup", &path);
+    let mut initial: Vec<Particle> = Vec::new();
+    datafunctions::read_data(&datafunctions::output_file(&dir, "Ini_00.csv"), &mut initial).unwrap();
+    common::rusph("run", &path);
+    let mut particles: Vec<Particle> = Vec::new();
+    let mut stars: Vec<Star> = Vec::new();
+    datafunctions::read_checkpoint(&datafunctions::output_file(&dir, "Checkpoint"), &mut particles, &mut stars).unwrap();
+    let last: Vec<Particle> = common::final_particles(&dir);
+    fs::remove_dir_all(&dir).ok();
+
+    assert_eq!(stars.len(), 1);
+    let gas: Vec<&Particle> = particles.iter().filter(|p| p.ptype == 0).collect();
+    assert!(gas.len() < initial.len(), "no particle accreted");
+    // The last checkpoint is that of the final snapshot
+    assert_eq!(last.len(), particles.len());
+    // m = 1, m_disc = 0.05
+    let m: f64 = gas.iter().map(|p| p.m).sum::<f64>() + stars[0].m;
+    assert!((m - 1.05).abs() < 1e-10, "total mass {}", m);
+}
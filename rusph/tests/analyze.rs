@@ -0,0 +1,42 @@
+// ------------------------------------------------------------------------- //
+// Analysis: analyze reads the VTU snapshots of a run back, and the          //
+// diagnostics follow the time cadence diag_dt.                              //
+// ------------------------------------------------------------------------- //
+
+use std::fs;
+
+use structures::Particle;
+
+mod common;
+
+#[test]
+fn vtu_snapshots_are_analyzed() {
+    let (dir, path) = common::config("shearing_box", "analyze", &[("nx", "8"), ("hg", "1.0"), ("tf", "0.3"), ("dt_sav", "0.1"), ("diag_dt", "0.1"), ("output", "vtu")]);
+    common::rusph("setup", &path);
+    let mut initial: Vec<Particle> = Vec::new();
+    datafunctions::read_data(&datafunctions::output_file(&dir, "Ini_00.csv"), &mut initial).unwrap();
+    common::rusph("run", &path);
+    common::rusph("analyze", &path);
+    let particles: Vec<Particle> = common::final_particles(&dir);
+    let analysis: String = fs::read_to_string(datafunctions::output_file(&dir, "Analysis.csv")).unwrap();
+    let diagnostics: String = fs::read_to_string(datafunctions::output_file(&dir, "Diagnostics.csv")).unwrap();
+    fs::remove_dir_all(&dir).ok();
+
+    // Every snapshot and the final state, all of them with the whole gas
+    let mass: f64 = initial.iter().map(|p| p.m).sum();
+    let rows: Vec<Vec<f64>> = analysis.lines().skip(1).map(|line| line.split(',').map(|value| value.parse::<f64>().unwrap()).collect()).collect();
+    assert!(rows.len() >= 4, "{} rows", rows.len());
+    for row in &rows {
+        assert_eq!(row[1] as usize, initial.len());
+        assert!((row[2] - mass).abs() < 1e-12*mass, "mass {} instead of {}", row[2], mass);
+    }
+    // The last row is the final state
+    let ekin: f64 = particles.iter().map(|p| 0.5*p.m*(p.vx*p.vx + p.vy*p.vy + p.vz*p.vz)).sum();
+    assert!((rows[rows.len() - 1][6] - ekin).abs() < 1e-12*ekin);
+    // One line once t passes each of 0.1, 0.2 and 0.3
+    let times: Vec<f64> = diagnostics.lines().skip(1).map(|line| line.split(',').next().unwrap().parse::<f64>().unwrap()).collect();
+    assert_eq!(times.len(), 3, "{:?}", times);
+    for (ii, t) in times.iter().enumerate() {
+        assert!(*t >= 0.1*(ii + 1) as f64 - 1e-12 && *t < 0.1*(ii + 2) as f64, "{:?}", times);
+    }
+}
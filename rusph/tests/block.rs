@@ -0,0 +1,32 @@
+// ------------------------------------------------------------------------- //
+// Block steps: the Sedov blast with the timestep bins and the limiter       //
+// keeps the mass and the total energy.                                      //
+// ------------------------------------------------------------------------- //
+
+use std::fs;
+
+use structures::Particle;
+
+mod common;
+
+fn energy(particles: &[Particle]) -> f64 {
+    return particles.iter().map(|p| p.m*(p.u + 0.5*(p.vx*p.vx + p.vy*p.vy + p.vz*p.vz))).sum();
+}
+
+#[test]
+fn sedov_keeps_the_energy() {
+    let (dir, path) = common::config("sedov_blast_wave", "block", &[("nx", "12"), ("tf", "0.01"), ("dt_sav", "0.01"), ("output", "csv")]);
+    common::rusph("setup", &path);
+    let mut initial: Vec<Particle> = Vec::new();
+    datafunctions::read_data(&datafunctions::output_file(&dir, "Ini_00.csv"), &mut initial).unwrap();
+    common::rusph("run", &path);
+    let particles: Vec<Particle> = common::final_particles(&dir);
+    fs::remove_dir_all(&dir).ok();
+
+    assert_eq!(particles.len(), initial.len());
+    // The blast is under way, and the energy moves from u to the kinetic
+    let v_max: f64 = particles.iter().map(|p| (p.vx*p.vx + p.vy*p.vy + p.vz*p.vz).sqrt()).fold(0.0, f64::max);
+    assert!(v_max > 0.0);
+    let (e0, e): (f64, f64) = (energy(&initial), energy(&particles));
+    assert!((e - e0).abs() < 1e-2*e0, "total energy {} instead of {}", e, e0);
+}
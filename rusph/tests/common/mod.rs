@@ -0,0 +1,45 @@
+// ------------------------------------------------------------------------- //
+// Helpers of the rusph tests: presets run from a scratch directory.         //
+// ------------------------------------------------------------------------- //
+
+use std::{
+    env,
+    fs,
+    process::{self, Command},
+};
+
+use structures::Particle;
+
+// A preset of rusph/presets with some keys replaced, written to a scratch
+// directory. Returns the directory and the configuration file.
+pub fn config(preset: &str, name: &str, keys: &[(&str, &str)]) -> (String, String) {
+    let dir: String = env::temp_dir().join(format!("rusph-{}-{}", name, process::id())).to_string_lossy().into_owned();
+    fs::create_dir_all(&dir).unwrap();
+    let mut keys: Vec<(&str, &str)> = keys.to_vec();
+    keys.push(("output_dir", &dir));
+    let preset: String = format!("{}/presets/{}.conf", env!("CARGO_MANIFEST_DIR"), preset);
+    let text: String = fs::read_to_string(preset).unwrap().lines().map(|line| {
+        let key: &str = line.split('=').next().unwrap().trim();
+        match keys.iter().find(|(k, _)| *k == key) {
+            Some((k, v)) => format!("{} = {}", k, v),
+            None => line.to_string(),
+        }
+    }).collect::<Vec<String>>().join("\n");
+    let path: String = datafunctions::output_file(&dir, "run.conf");
+    fs::write(&path, text).unwrap();
+    return (dir, path);
+}
+
+pub fn rusph(command: &str, path: &str) {
+    let out = Command::new(env!("CARGO_BIN_EXE_rusph")).args([command, path]).output().unwrap();
+    assert!(out.status.success(), "rusph {} failed:\n{}", command, String::from_utf8_lossy(&out.stdout));
+}
+
+// Particles of the last snapshot of the "final" stream of a run, in any
+// output format
+pub fn final_particles(dir: &str) -> Vec<Particle> {
+    let mut particles: Vec<Particle> = Vec::new();
+    let last: String = datafunctions::read_manifest(dir).unwrap().into_iter().filter(|entry| entry.stream == "final").last().unwrap().file;
+    datafunctions::read_output(&datafunctions::output_file(dir, &last), &mut particles).unwrap();
+    return particles;
+}
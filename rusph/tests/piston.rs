@@ -0,0 +1,36 @@
+// ------------------------------------------------------------------------- //
+// Piston: the boundary particles of the moving piston drive a shock with    //
+// the post-shock state of the analytic solution.                            //
+// ------------------------------------------------------------------------- //
+
+use std::fs;
+
+use structures::Particle;
+
+mod common;
+
+#[test]
+fn post_shock_state() {
+    // Sixteen spacings along the unit tube, the gas starting at x = 0, and
+    // six across the periodic y and z
+    let (dir, path) = common::config("piston_shock", "piston", &[("nx", "16"), ("wd", "1.5"), ("x0", "-0.25"), ("lg", "0.375"), ("hg", "0.375"), ("tf", "0.2"), ("dt_sav", "0.2"), ("output", "csv")]);
+    common::rusph("setup", &path);
+    common::rusph("run", &path);
+    let particles: Vec<Particle> = common::final_particles(&dir);
+    fs::remove_dir_all(&dir).ok();
+
+    // Shock speed and density behind it, with cs0^2 = gamma p0/rho0
+    let (gamma, rho0, p0, v_p, t): (f64, f64, f64, f64, f64) = (1.667, 1.0, 1.0, 1.0, 0.2);
+    let a: f64 = 0.25*(gamma + 1.0)*v_p;
+    let v_s: f64 = a + (a*a + gamma*p0/rho0).sqrt();
+    let rho1: f64 = rho0*v_s/(v_s - v_p);
+    // Middle half of the gas between the piston and the shock
+    let (low, up): (f64, f64) = (v_p*t + 0.25*(v_s - v_p)*t, v_p*t + 0.75*(v_s - v_p)*t);
+    let gas: Vec<&Particle> = particles.iter().filter(|p| p.ptype == 0 && p.x > low && p.x < up).collect();
+    assert!(!gas.is_empty());
+    let n: f64 = gas.len() as f64;
+    let rho: f64 = gas.iter().map(|p| sphfunctions::density_from_h(p.m, p.h, 1.2)).sum::<f64>()/n;
+    let vx: f64 = gas.iter().map(|p| p.vx).sum::<f64>()/n;
+    assert!((rho - rho1).abs() < 0.1*rho1, "post-shock density {} instead of {}", rho, rho1);
+    assert!((vx - v_p).abs() < 0.1*v_p, "post-shock velocity {} instead of {}", vx, v_p);
+}
@@ -0,0 +1,31 @@
+// ------------------------------------------------------------------------- //
+// Rayleigh-Taylor: the walls at the y ends hold the hydrostatic fluids.     //
+// ------------------------------------------------------------------------- //
+
+use std::fs;
+
+use structures::Particle;
+
+mod common;
+
+#[test]
+fn walls_hold_the_fluids() {
+    // The cubic kernel keeps the support of eight particles across within
+    // the periodic z extent
+    let (dir, path) = common::config("rayleigh_taylor", "rayleigh-taylor", &[("nx", "8"), ("hg", "0.5"), ("kernel", "cubic"), ("tf", "0.1"), ("dt_sav", "0.1"), ("output", "csv")]);
+    common::rusph("setup", &path);
+    let mut initial: Vec<Particle> = Vec::new();
+    datafunctions::read_data(&datafunctions::output_file(&dir, "Ini_00.csv"), &mut initial).unwrap();
+    common::rusph("run", &path);
+    let particles: Vec<Particle> = common::final_particles(&dir);
+    fs::remove_dir_all(&dir).ok();
+
+    // No ghost is written and no particle is lost
+    assert_eq!(particles.len(), initial.len());
+    assert!(particles.iter().all(|p| p.ptype == 0));
+    // y0 = 0, lg = 1.5
+    assert!(particles.iter().all(|p| p.y >= 0.0 && p.y <= 1.5));
+    // With open ends the speeds reach ~0.9 by then
+    let v_max: f64 = particles.iter().map(|p| (p.vx*p.vx + p.vy*p.vy + p.vz*p.vz).sqrt()).fold(0.0, f64::max);
+    assert!(v_max.is_finite() && v_max < 0.3, "largest speed {}", v_max);
+}
@@ -0,0 +1,33 @@
+// ------------------------------------------------------------------------- //
+// Shearing box: the background flow vy = -q*omega*(x - xc) is stationary    //
+// when the images across x follow the shear, y_shear = q*omega*wd*t.        //
+// ------------------------------------------------------------------------- //
+
+use std::fs;
+
+use structures::Particle;
+
+mod common;
+
+// Deviations from the background flow after t = 0.3, with a box tall
+// enough for the kernel support of eight particles across
+fn deviations(name: &str) -> Vec<f64> {
+    let (dir, path) = common::config("shearing_box", name, &[("nx", "8"), ("hg", "1.0"), ("tf", "0.3"), ("dt_sav", "0.3"), ("output", "csv")]);
+    common::rusph("setup", &path);
+    common::rusph("run", &path);
+    let particles: Vec<Particle> = common::final_particles(&dir);
+    fs::remove_dir_all(&dir).ok();
+    // q = 1.5, omega = 1, x0 = 0, wd = 1
+    return particles.iter().map(|p| (p.vx*p.vx + (p.vy + 1.5*(p.x - 0.5)).powi(2)).sqrt()).collect();
+}
+
+#[test]
+fn background_flow_is_stationary() {
+    let dev: Vec<f64> = deviations("shearing-box");
+    assert!(dev.iter().all(|d| d.is_finite()), "non-finite velocities");
+    // Plain periodic images would see a jump of q*omega*wd = 1.5 across x
+    let max: f64 = dev.iter().cloned().fold(0.0, f64::max);
+    let mean: f64 = dev.iter().sum::<f64>()/dev.len() as f64;
+    assert!(max < 0.1, "largest deviation {}", max);
+    assert!(mean < 0.03, "mean deviation {}", mean);
+}
@@ -0,0 +1,35 @@
+// ------------------------------------------------------------------------- //
+// Stop: a run past its wall-clock limit checkpoints and says it stopped,    //
+// and its resumption reaches the final time.                                //
+// ------------------------------------------------------------------------- //
+
+use std::{
+    fs,
+    process::Command,
+};
+
+mod common;
+
+fn stdout(command: &str, path: &str) -> String {
+    let out = Command::new(env!("CARGO_BIN_EXE_rusph")).args([command, path]).output().unwrap();
+    let text: String = String::from_utf8_lossy(&out.stdout).into_owned();
+    assert!(out.status.success(), "rusph {} failed:\n{}", command, text);
+    return text;
+}
+
+#[test]
+fn stopped_run_is_not_reported_as_finished() {
+    // The limit is reached at the end of the first step
+    let keys: [(&str, &str); 5] = [("nxl", "16"), ("nxr", "8"), ("tf", "0.02"), ("dt_sav", "0.02"), ("output", "csv")];
+    let (dir, path) = common::config("sod_shock_tube", "stop", &[&keys[..], &[("max_walltime", "1e-9")]].concat());
+    common::rusph("setup", &path);
+    let text: String = stdout("run", &path);
+    assert!(text.contains("Simulation stopped at"), "{}", text);
+    assert!(!text.contains("successfully"), "{}", text);
+    assert!(fs::metadata(datafunctions::output_file(&dir, "Checkpoint")).is_ok());
+
+    common::config("sod_shock_tube", "stop", &[&keys[..], &[("max_walltime", "0.0")]].concat());
+    let text: String = stdout("resume", &path);
+    assert!(text.contains("Simulation run successfully"), "{}", text);
+    fs::remove_dir_all(&dir).ok();
+}
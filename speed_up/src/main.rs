@@ -15,7 +15,6 @@ use structures::{
     Particle,
     Node,
     Pointer,
-    Star,
 };
 
 use std::f64::consts::PI;
@@ -68,22 +67,22 @@ fn main() -> Result<(), Box<dyn Error>> {
     //---------------------------------------------------------------------------------------------
     
     for ii in 0..n {
-        particles[ii].rho = sphfunctions::density_from_h(dm, particles[ii].h, eta);
+        particles[ii].m   = dm;
+        particles[ii].rho = sphfunctions::density_from_h(particles[ii].m, particles[ii].h, eta);
     }
     
     let mut tree:Node= <Node as BuildTree>::new(n as i32, x0, y0, z0, wd, lg, hg);
 
     //------------------------------------ Main Loop ----------------------------------------------
     let start       = Instant::now();   // Runing time
-    let star : Star = Star{..Default::default()};
     while it < it_tot  {
-        sphfunctions::predictor_kdk_integrator(&mut particles, dt, dm, eos_t, sphfunctions::eos_polytropic, sphfunctions::sound_speed_polytropic, gamma, k,
+        sphfunctions::predictor_kdk_integrator(&mut particles, dt, eos_t, sphfunctions::eos_polytropic, sphfunctions::sound_speed_polytropic, gamma, k,
                                        sphfunctions::dwdh, sphfunctions::f_cubic_kernel, sphfunctions::dfdq_cubic_kernel, sigma, rkern,
                                        eta, &mut tree, s_, alpha_, beta_, n, particles_ptr,
                                        sphfunctions::mon97_art_vis,
-                                       sphfunctions::body_forces_null, &star, false,
-                                       sphfunctions::periodic_boundary, xper, yper, zper, wd, lg, hg, x0, y0, z0);
-        dt = sphfunctions::time_step_mon(&particles, n, gamma, k, rkern, wd, lg, hg, x0, y0, z0, &mut tree, s_, sphfunctions::sound_speed_polytropic, xper, yper, zper);
+                                       &[],
+                                       sphfunctions::periodic_boundary, xper, yper, zper, 0.0, 0.0, wd, lg, hg, x0, y0, z0);
+        dt = sphfunctions::time_step_mon(&particles, n, gamma, k, rkern, wd, lg, hg, x0, y0, z0, &mut tree, s_, sphfunctions::sound_speed_polytropic, xper, yper, zper, 0.0, 0.0);
         tree.restart(n);
         it += 1;
     }
@@ -0,0 +1,40 @@
+// ------------------------------------------------------------------------- //
+// Converts a positional input file into a `key = value` configuration file. //
+//      cargo run -p datafunctions --bin convert_input -- <input> [<output>] //
+// Without an output file the configuration is printed.                      //
+// ------------------------------------------------------------------------- //
+use std::{
+    env,
+    fs,
+    process,
+};
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 || args.len() > 3 {
+        println!("Usage: convert_input <input> [<output>]");
+        process::exit(1);
+    }
+    let text: String = match fs::read_to_string(&args[1]) {
+        Ok(text) => text,
+        Err(err) => {
+            println!("Cannot open {}: {}", args[1], err);
+            process::exit(1);
+        }
+    };
+    let config: String = match datafunctions::convert_input(&text) {
+        Ok(config) => config,
+        Err(err) => {
+            println!("{}: {}", args[1], err);
+            process::exit(1);
+        }
+    };
+    if args.len() == 3 {
+        if let Err(err) = fs::write(&args[2], config) {
+            println!("Cannot write {}: {}", args[2], err);
+            process::exit(1);
+        }
+    } else {
+        print!("{}", config);
+    }
+}
@@ -1,21 +1,37 @@
 use std::{
-    io::{BufRead, BufReader, prelude::*},
-    fs::File,
+    collections::HashMap,
+    io::{BufRead, BufReader, BufWriter, prelude::*},
+    fs::{self, File, OpenOptions},
+    env,
     error::Error,
+    path::Path,
+    time::Instant,
+    sync::{Arc, atomic::{AtomicBool, Ordering}},
     f64,
 };
 
 use csv::Writer;
+use signal_hook::{
+    consts::{SIGTERM, SIGUSR1},
+    flag,
+};
 
-use structures::Particle;
+use structures::{
+    Particle,
+    Star,
+};
 
 // -------- Write data --------
 
+// The directory of path is created if needed.
 pub fn save_data(path: &str, particles: & Vec<Particle>)-> Result<(), Box<dyn Error>>{
+    if let Some(dir) = Path::new(path).parent() {
+        fs::create_dir_all(dir)?;
+    }
     let mut wtr = Writer::from_path(path)?;
-    wtr.write_record(&["ptype", "x", "y", "z", "vx", "vy", "vz", "h", "u"])?;
+    wtr.write_record(&["ptype", "id", "m", "x", "y", "z", "vx", "vy", "vz", "h", "u"])?;
     for particle in particles {
-        wtr.write_record(&[particle.ptype.to_string(), particle.x.to_string(), particle.y.to_string(), particle.z.to_string(),
+        wtr.write_record(&[particle.ptype.to_string(), particle.id.to_string(), particle.m.to_string(), particle.x.to_string(), particle.y.to_string(), particle.z.to_string(),
                            particle.vx.to_string(), particle.vy.to_string(), particle.vz.to_string(),
                            particle.h.to_string(), particle.u.to_string()])?;
     }
@@ -23,9 +39,20 @@ pub fn save_data(path: &str, particles: & Vec<Particle>)-> Result<(), Box<dyn Er
     Ok(())
 }
 
+// Compact binary layout, little-endian: magic "RUSPHBIN", version (u32),
+// number of particles (u64), and per particle id (u64), m, x, y, z, vx, vy,
+// vz, h, u (f64).
+pub const BINARY_MAGIC: &[u8; 8]    = b"RUSPHBIN";
+pub const BINARY_VERSION: u32       = 1;
+
 pub fn save_data_bin(path: &str, particles: & Vec<Particle>)-> Result<(), Box<dyn Error>>{
-    let mut wtr = File::create(path)?;
+    let mut wtr = BufWriter::new(File::create(path)?);
+    wtr.write_all(BINARY_MAGIC)?;
+    wtr.write_all(&BINARY_VERSION.to_le_bytes())?;
+    wtr.write_all(&(particles.len() as u64).to_le_bytes())?;
     for particle in particles {
+        wtr.write_all(&particle.id.to_le_bytes())?;
+        wtr.write_all(&particle.m.to_le_bytes())?;
         wtr.write_all(&particle.x.to_le_bytes())?;
         wtr.write_all(&particle.y.to_le_bytes())?;
         wtr.write_all(&particle.z.to_le_bytes())?;
@@ -39,29 +66,1312 @@ pub fn save_data_bin(path: &str, particles: & Vec<Particle>)-> Result<(), Box<dy
     Ok(())
 }
 
+// -------- VTK output --------
+
+// Snapshot formats of the drivers, chosen in their input file.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum OutputFormat {
+    Binary,     // save_snapshot, with the header of the run
+    Csv,        // save_data
+    Vtu,        // save_vtu, indexed by save_pvd
+}
+
+impl OutputFormat {
+    // Saves the particles at path plus the extension of the format. Only the
+    // binary snapshots keep the header and the sinks.
+    pub fn save(&self, path: &str, header: &Header, particles: & Vec<Particle>, stars: &[&Star]) -> Result<(), Box<dyn Error>> {
+        match self {
+            OutputFormat::Binary => save_snapshot(path, header, particles, stars),
+            OutputFormat::Csv    => save_data(&format!("{}.csv", path), particles),
+            OutputFormat::Vtu    => save_vtu(&format!("{}.vtu", path), particles),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Binary => "",
+            OutputFormat::Csv    => ".csv",
+            OutputFormat::Vtu    => ".vtu",
+        }
+    }
+}
+
+// VTU unstructured grid of the particles as vertices, in ASCII, with the
+// point data rho, u, h, m, ptype, id, velocity and acceleration.
+pub fn save_vtu(path: &str, particles: & Vec<Particle>) -> Result<(), Box<dyn Error>> {
+    let n: usize = particles.len();
+    let mut wtr = BufWriter::new(File::create(path)?);
+    writeln!(wtr, "<?xml version=\"1.0\"?>")?;
+    writeln!(wtr, "<VTKFile type=\"UnstructuredGrid\" version=\"0.1\" byte_order=\"LittleEndian\">")?;
+    writeln!(wtr, "  <UnstructuredGrid>")?;
+    writeln!(wtr, "    <Piece NumberOfPoints=\"{}\" NumberOfCells=\"{}\">", n, n)?;
+
+    writeln!(wtr, "      <PointData Scalars=\"rho\" Vectors=\"velocity\">")?;
+    let scalars: [(&str, Vec<f64>); 4] = [("rho", particles.iter().map(|p| p.rho).collect()),
+                                          ("u", particles.iter().map(|p| p.u).collect()),
+                                          ("h", particles.iter().map(|p| p.h).collect()),
+                                          ("m", particles.iter().map(|p| p.m).collect())];
+    for (name, values) in scalars {
+        writeln!(wtr, "        <DataArray type=\"Float64\" Name=\"{}\" format=\"ascii\">", name)?;
+        for value in values {
+            writeln!(wtr, "{:e}", value)?;
+        }
+        writeln!(wtr, "        </DataArray>")?;
+    }
+    writeln!(wtr, "        <DataArray type=\"UInt8\" Name=\"ptype\" format=\"ascii\">")?;
+    for particle in particles {
+        writeln!(wtr, "{}", particle.ptype)?;
+    }
+    writeln!(wtr, "        </DataArray>")?;
+    writeln!(wtr, "        <DataArray type=\"UInt64\" Name=\"id\" format=\"ascii\">")?;
+    for particle in particles {
+        writeln!(wtr, "{}", particle.id)?;
+    }
+    writeln!(wtr, "        </DataArray>")?;
+    let vectors: [(&str, Vec<[f64; 3]>); 2] = [("velocity", particles.iter().map(|p| [p.vx, p.vy, p.vz]).collect()),
+                                                ("acceleration", particles.iter().map(|p| [p.ax, p.ay, p.az]).collect())];
+    for (name, values) in vectors {
+        writeln!(wtr, "        <DataArray type=\"Float64\" Name=\"{}\" NumberOfComponents=\"3\" format=\"ascii\">", name)?;
+        for [x, y, z] in values {
+            writeln!(wtr, "{:e} {:e} {:e}", x, y, z)?;
+        }
+        writeln!(wtr, "        </DataArray>")?;
+    }
+    writeln!(wtr, "      </PointData>")?;
+
+    writeln!(wtr, "      <Points>")?;
+    writeln!(wtr, "        <DataArray type=\"Float64\" NumberOfComponents=\"3\" format=\"ascii\">")?;
+    for particle in particles {
+        writeln!(wtr, "{:e} {:e} {:e}", particle.x, particle.y, particle.z)?;
+    }
+    writeln!(wtr, "        </DataArray>")?;
+    writeln!(wtr, "      </Points>")?;
+
+    // One vertex (VTK type 1) per particle
+    writeln!(wtr, "      <Cells>")?;
+    writeln!(wtr, "        <DataArray type=\"Int64\" Name=\"connectivity\" format=\"ascii\">")?;
+    for ii in 0..n {
+        writeln!(wtr, "{}", ii)?;
+    }
+    writeln!(wtr, "        </DataArray>")?;
+    writeln!(wtr, "        <DataArray type=\"Int64\" Name=\"offsets\" format=\"ascii\">")?;
+    for ii in 0..n {
+        writeln!(wtr, "{}", ii + 1)?;
+    }
+    writeln!(wtr, "        </DataArray>")?;
+    writeln!(wtr, "        <DataArray type=\"UInt8\" Name=\"types\" format=\"ascii\">")?;
+    for _ in 0..n {
+        writeln!(wtr, "1")?;
+    }
+    writeln!(wtr, "        </DataArray>")?;
+    writeln!(wtr, "      </Cells>")?;
+
+    writeln!(wtr, "    </Piece>")?;
+    writeln!(wtr, "  </UnstructuredGrid>")?;
+    writeln!(wtr, "</VTKFile>")?;
+    wtr.flush()?;
+    Ok(())
+}
+
+// Writes dir/Evolution.pvd, the ParaView time series of the VTU snapshots
+// prefix + number (zero-padded to digits), at the times listed in
+// dir/Time.txt, the last one being the final state Fin_00 if fin is set.
+// Output rewrites it after every VTU snapshot, so that a stopped or killed
+// run has it too.
+pub fn save_pvd(dir: &str, prefix: &str, digits: usize, fin: bool) -> Result<(), Box<dyn Error>> {
+    let times: Vec<f64> = read_times(&output_file(dir, "Time.txt"))?;
+    let mut wtr = BufWriter::new(File::create(output_file(dir, "Evolution.pvd"))?);
+    writeln!(wtr, "<?xml version=\"1.0\"?>")?;
+    writeln!(wtr, "<VTKFile type=\"Collection\" version=\"0.1\" byte_order=\"LittleEndian\">")?;
+    writeln!(wtr, "  <Collection>")?;
+    for (ii, t) in times.iter().enumerate() {
+        let name: String = if fin && ii + 1 == times.len() {
+            String::from("Fin_00")
+        } else {
+            format!("{}{:0width$}", prefix, ii, width = digits)
+        };
+        writeln!(wtr, "    <DataSet timestep=\"{:e}\" group=\"\" part=\"0\" file=\"{}.vtu\"/>", t, name)?;
+    }
+    writeln!(wtr, "  </Collection>")?;
+    writeln!(wtr, "</VTKFile>")?;
+    wtr.flush()?;
+    Ok(())
+}
+
+// -------- Snapshot format --------
+
+// Self-describing binary snapshot, little-endian:
+//      magic "RUSPHSNP" and version (u32)
+//      header: time, n (u64), dm, x0, y0, z0, wd, lg, hg, periodicity and
+//              EoS flags (u8), gamma, coeff
+//      sinks: number (u64) and the 16 fields of every Star (f64)
+//      blocks: number (u32) and, for every block, the length of its name
+//              (u8), the name, the type (b'f': f64, b'u': u64, b'i': u32,
+//              b'b': u8) and the n values of the field.
+// Readers skip the blocks they do not know and keep the default value of
+// the fields missing in the file.
+pub const SNAPSHOT_MAGIC: &[u8; 8]  = b"RUSPHSNP";
+pub const SNAPSHOT_VERSION: u32     = 1;
+
+#[derive(Clone, Copy)]
+pub struct Header {
+    pub time: f64,
+    pub dm: f64,
+    pub x0: f64,
+    pub y0: f64,
+    pub z0: f64,
+    pub wd: f64,
+    pub lg: f64,
+    pub hg: f64,
+    pub xper: bool,
+    pub yper: bool,
+    pub zper: bool,
+    pub eos_t: bool,
+    pub gamma: f64,
+    pub coeff: f64,
+}
+
+impl Default for Header {
+    fn default() -> Header {
+        Header {
+            time: 0.0,
+            dm: 0.0,
+            x0: 0.0,
+            y0: 0.0,
+            z0: 0.0,
+            wd: 1.0,
+            lg: 1.0,
+            hg: 1.0,
+            xper: false,
+            yper: false,
+            zper: false,
+            eos_t: true,
+            gamma: 5./3.,
+            coeff: 0.0,
+        }
+    }
+}
+
+// Per-particle fields stored as f64 blocks, in the order of the file.
+const F64_BLOCKS: [&str; 19] = ["m", "rho", "x", "y", "z", "h", "vx", "vy", "vz", "vx_star", "vy_star", "vz_star",
+                                "divv", "ax", "ay", "az", "u", "u_star", "du"];
+
+fn f64_fields(particle: & Particle) -> [f64; 19] {
+    [particle.m, particle.rho, particle.x, particle.y, particle.z, particle.h, particle.vx, particle.vy, particle.vz,
+     particle.vx_star, particle.vy_star, particle.vz_star, particle.divv, particle.ax, particle.ay, particle.az,
+     particle.u, particle.u_star, particle.du]
+}
+
+fn f64_fields_mut(particle: &mut Particle) -> [&mut f64; 19] {
+    [&mut particle.m, &mut particle.rho, &mut particle.x, &mut particle.y, &mut particle.z, &mut particle.h,
+     &mut particle.vx, &mut particle.vy, &mut particle.vz, &mut particle.vx_star, &mut particle.vy_star, &mut particle.vz_star,
+     &mut particle.divv, &mut particle.ax, &mut particle.ay, &mut particle.az, &mut particle.u, &mut particle.u_star, &mut particle.du]
+}
+
+fn star_fields(star: & Star) -> [f64; 16] {
+    [star.m, star.x, star.y, star.z, star.hacc, star.facc, star.vx, star.vy, star.vz,
+     star.ax, star.ay, star.az, star.sx, star.sy, star.sz, star.c]
+}
+
+fn star_fields_mut(star: &mut Star) -> [&mut f64; 16] {
+    [&mut star.m, &mut star.x, &mut star.y, &mut star.z, &mut star.hacc, &mut star.facc,
+     &mut star.vx, &mut star.vy, &mut star.vz, &mut star.ax, &mut star.ay, &mut star.az,
+     &mut star.sx, &mut star.sy, &mut star.sz, &mut star.c]
+}
+
+fn write_block<W: Write>(wtr: &mut W, name: &str, kind: u8) -> Result<(), Box<dyn Error>> {
+    wtr.write_all(&[name.len() as u8])?;
+    wtr.write_all(name.as_bytes())?;
+    wtr.write_all(&[kind])?;
+    Ok(())
+}
+
+pub fn save_snapshot(path: &str, header: &Header, particles: & Vec<Particle>, stars: &[&Star]) -> Result<(), Box<dyn Error>> {
+    let mut wtr = BufWriter::new(File::create(path)?);
+    write_snapshot(&mut wtr, header, particles, stars)?;
+    wtr.flush()?;
+    Ok(())
+}
+
+fn write_snapshot<W: Write>(wtr: &mut W, header: &Header, particles: & Vec<Particle>, stars: &[&Star]) -> Result<(), Box<dyn Error>> {
+    wtr.write_all(SNAPSHOT_MAGIC)?;
+    wtr.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+
+    // Header
+    wtr.write_all(&header.time.to_le_bytes())?;
+    wtr.write_all(&(particles.len() as u64).to_le_bytes())?;
+    for value in [header.dm, header.x0, header.y0, header.z0, header.wd, header.lg, header.hg] {
+        wtr.write_all(&value.to_le_bytes())?;
+    }
+    wtr.write_all(&[header.xper as u8, header.yper as u8, header.zper as u8, header.eos_t as u8])?;
+    wtr.write_all(&header.gamma.to_le_bytes())?;
+    wtr.write_all(&header.coeff.to_le_bytes())?;
+
+    // Sinks
+    wtr.write_all(&(stars.len() as u64).to_le_bytes())?;
+    for star in stars {
+        for value in star_fields(star) {
+            wtr.write_all(&value.to_le_bytes())?;
+        }
+    }
+
+    // Blocks
+    wtr.write_all(&((F64_BLOCKS.len() + 4) as u32).to_le_bytes())?;
+    write_block(wtr, "ptype", b'b')?;
+    for particle in particles {
+        wtr.write_all(&[particle.ptype])?;
+    }
+    write_block(wtr, "id", b'u')?;
+    for particle in particles {
+        wtr.write_all(&particle.id.to_le_bytes())?;
+    }
+    write_block(wtr, "ibin", b'i')?;
+    for particle in particles {
+        wtr.write_all(&particle.ibin.to_le_bytes())?;
+    }
+    write_block(wtr, "active", b'b')?;
+    for particle in particles {
+        wtr.write_all(&[particle.active as u8])?;
+    }
+    for (k, name) in F64_BLOCKS.iter().enumerate() {
+        write_block(wtr, name, b'f')?;
+        for particle in particles {
+            wtr.write_all(&f64_fields(particle)[k].to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+// Cursor over the bytes of a snapshot.
+struct Bytes<'a> {
+    buffer: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Bytes<'a> {
+    fn take(&mut self, size: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        if self.pos + size > self.buffer.len() {
+            return Err("Truncated snapshot".into());
+        }
+        let chunk: &[u8] = &self.buffer[self.pos..self.pos+size];
+        self.pos += size;
+        Ok(chunk)
+    }
+
+    fn u8(&mut self) -> Result<u8, Box<dyn Error>> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, Box<dyn Error>> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, Box<dyn Error>> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn f64(&mut self) -> Result<f64, Box<dyn Error>> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+}
+
+// True if the file starts with the magic number of the snapshot format.
+pub fn is_snapshot(path: &str) -> bool {
+    let mut magic: [u8; 8] = [0; 8];
+    match File::open(path) {
+        Ok(mut file) => file.read_exact(&mut magic).is_ok() && &magic == SNAPSHOT_MAGIC,
+        Err(_) => false,
+    }
+}
+
+// Reads the files written by save_snapshot. The particles and sinks are
+// appended to the vectors, and the header is returned.
+pub fn read_snapshot(path: &str, particles: &mut Vec<Particle>, stars: &mut Vec<Star>) -> Result<Header, Box<dyn Error>> {
+    let mut buffer: Vec<u8> = Vec::new();
+    File::open(path)?.read_to_end(&mut buffer)?;
+    let mut bytes: Bytes = Bytes{buffer: &buffer, pos: 0};
+    parse_snapshot(&mut bytes, path, particles, stars)
+}
+
+fn parse_snapshot(bytes: &mut Bytes, path: &str, particles: &mut Vec<Particle>, stars: &mut Vec<Star>) -> Result<Header, Box<dyn Error>> {
+    if bytes.take(8)? != SNAPSHOT_MAGIC {
+        return Err(format!("{} is not a snapshot", path).into());
+    }
+    let version: u32 = bytes.u32()?;
+    if version > SNAPSHOT_VERSION {
+        return Err(format!("Snapshot {} has version {}, newer than {}", path, version, SNAPSHOT_VERSION).into());
+    }
+
+    // Header
+    let time: f64   = bytes.f64()?;
+    let n: usize    = bytes.u64()? as usize;
+    let header: Header = Header{time, dm: bytes.f64()?,
+                                x0: bytes.f64()?, y0: bytes.f64()?, z0: bytes.f64()?,
+                                wd: bytes.f64()?, lg: bytes.f64()?, hg: bytes.f64()?,
+                                xper: bytes.u8()? != 0, yper: bytes.u8()? != 0, zper: bytes.u8()? != 0,
+                                eos_t: bytes.u8()? != 0, gamma: bytes.f64()?, coeff: bytes.f64()?};
+
+    // Sinks
+    let n_stars: usize = bytes.u64()? as usize;
+    for _ in 0..n_stars {
+        let mut star: Star = Star::default();
+        for field in star_fields_mut(&mut star) {
+            *field = bytes.f64()?;
+        }
+        stars.push(star);
+    }
+
+    // Blocks
+    let first: usize = particles.len();
+    particles.resize_with(first + n, Default::default);
+    let new = &mut particles[first..];
+    let n_blocks: u32 = bytes.u32()?;
+    for _ in 0..n_blocks {
+        let length: usize   = bytes.u8()? as usize;
+        let name: String    = String::from_utf8(bytes.take(length)?.to_vec())?;
+        let kind: u8        = bytes.u8()?;
+        let k: Option<usize> = F64_BLOCKS.iter().position(|block| *block == name);
+        match (name.as_str(), kind) {
+            ("ptype", b'b')     => for particle in new.iter_mut() { particle.ptype = bytes.u8()?; },
+            ("active", b'b')    => for particle in new.iter_mut() { particle.active = bytes.u8()? != 0; },
+            ("id", b'u')        => for particle in new.iter_mut() { particle.id = bytes.u64()?; },
+            ("ibin", b'i')      => for particle in new.iter_mut() { particle.ibin = bytes.u32()?; },
+            (_, b'f') if k.is_some() => for particle in new.iter_mut() { *f64_fields_mut(particle)[k.unwrap()] = bytes.f64()?; },
+            (_, b'f') | (_, b'u') => { bytes.take(8*n)?; },
+            (_, b'i')           => { bytes.take(4*n)?; },
+            (_, b'b')           => { bytes.take(n)?; },
+            _                   => return Err(format!("Unknown type of block '{}' in {}", name, path).into()),
+        }
+    }
+    Ok(header)
+}
+
+// -------- Checkpoints --------
+
+// State of the main loop that is not stored in the particles.
+pub struct RunState {
+    pub t: f64,             // Time
+    pub t_boundary: f64,    // Time of the moving boundaries (t if there are none)
+    pub dt: f64,            // Time step
+    pub it: u32,            // Time iterations
+    pub it_sav: u32,        // Save data iteration
+}
+
+pub const CHECKPOINT_MAGIC: &[u8; 8] = b"RUSPHCHK";
+
+// A checkpoint is the run state followed by a snapshot holding every field of
+// the particles and sinks. It is written to a temporary file and renamed, so
+// a job killed while writing keeps the previous checkpoint.
+pub fn save_checkpoint(path: &str, state: &RunState, header: &Header, particles: & Vec<Particle>, stars: &[&Star]) -> Result<(), Box<dyn Error>> {
+    let path_tmp: String = String::from(path) + ".tmp";
+    let mut wtr = BufWriter::new(File::create(&path_tmp)?);
+    wtr.write_all(CHECKPOINT_MAGIC)?;
+    wtr.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+    for value in [state.t, state.t_boundary, state.dt] {
+        wtr.write_all(&value.to_le_bytes())?;
+    }
+    wtr.write_all(&state.it.to_le_bytes())?;
+    wtr.write_all(&state.it_sav.to_le_bytes())?;
+    write_snapshot(&mut wtr, &Header{time: state.t, ..*header}, particles, stars)?;
+    wtr.flush()?;
+    drop(wtr);
+    fs::rename(&path_tmp, path)?;
+    Ok(())
+}
+
+pub fn read_checkpoint(path: &str, particles: &mut Vec<Particle>, stars: &mut Vec<Star>) -> Result<RunState, Box<dyn Error>> {
+    let mut buffer: Vec<u8> = Vec::new();
+    File::open(path)?.read_to_end(&mut buffer)?;
+    let mut bytes: Bytes = Bytes{buffer: &buffer, pos: 0};
+    if bytes.take(8)? != CHECKPOINT_MAGIC {
+        return Err(format!("{} is not a checkpoint", path).into());
+    }
+    bytes.u32()?;
+    let state: RunState = RunState{t: bytes.f64()?, t_boundary: bytes.f64()?, dt: bytes.f64()?, it: bytes.u32()?, it_sav: bytes.u32()?};
+    parse_snapshot(&mut bytes, path, particles, stars)?;
+    Ok(state)
+}
+
+// With "--resume" among the arguments of the program, the particles and
+// sinks are read from the checkpoint and its run state is returned.
+pub fn resume(path: &str, particles: &mut Vec<Particle>, stars: &mut Vec<Star>) -> Result<Option<RunState>, Box<dyn Error>> {
+    if !env::args().any(|arg| arg == "--resume") {
+        return Ok(None);
+    }
+    let state: RunState = read_checkpoint(path, particles, stars)?;
+    println!("Resuming from {} at t = {} (iteration {}).", path, state.t, state.it);
+    Ok(Some(state))
+}
+
+// Checkpoints every `steps` iterations or `minutes` of wall-clock time,
+// whichever comes first. Zero switches off either criterion.
+pub struct Checkpoint {
+    pub path: String,
+    pub steps: u32,
+    pub minutes: f64,
+    last: Instant,
+}
+
+impl Checkpoint {
+    pub fn new(path: &str, steps: u32, minutes: f64) -> Checkpoint {
+        Checkpoint{path: String::from(path), steps, minutes, last: Instant::now()}
+    }
+
+    pub fn due(&self, it: u32) -> bool {
+        (self.steps > 0 && it.is_multiple_of(self.steps)) ||
+        (self.minutes > 0.0 && self.last.elapsed().as_secs_f64() >= 60.0*self.minutes)
+    }
+
+    pub fn save(&mut self, state: &RunState, header: &Header, particles: & Vec<Particle>, stars: &[&Star]) -> Result<(), Box<dyn Error>> {
+        save_checkpoint(&self.path, state, header, particles, stars)?;
+        self.last = Instant::now();
+        Ok(())
+    }
+}
+
+// Stop requests. Batch schedulers send SIGTERM or SIGUSR1 ahead of the
+// wall-time limit, and the run can also stop itself after max_walltime
+// hours (0: no limit). The main loop finishes the current step, writes a
+// checkpoint and the final output, and exits. A second signal terminates
+// the program at once.
+pub struct Stop {
+    signal: Arc<AtomicBool>,
+    max_walltime: f64,
+    start: Instant,
+}
+
+impl Stop {
+    pub fn new(max_walltime: f64) -> Result<Stop, Box<dyn Error>> {
+        let signal: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+        for sig in [SIGTERM, SIGUSR1] {
+            flag::register_conditional_shutdown(sig, 1, Arc::clone(&signal))?;
+            flag::register(sig, Arc::clone(&signal))?;
+        }
+        Ok(Stop{signal, max_walltime, start: Instant::now()})
+    }
+
+    pub fn requested(&self) -> bool {
+        self.signal.load(Ordering::Relaxed) ||
+        (self.max_walltime > 0.0 && self.start.elapsed().as_secs_f64() >= 3600.0*self.max_walltime)
+    }
+}
+
+// Opens Time.txt for appending after its first `lines` times. A new run
+// starts with no times, and a resumed one keeps those of the snapshots
+// saved before the checkpoint.
+pub fn open_times(path: &str, lines: usize) -> Result<File, Box<dyn Error>> {
+    let mut kept: String = String::new();
+    if let Ok(file) = File::open(path) {
+        for line in BufReader::new(file).lines().take(lines) {
+            kept += &(line? + "\n");
+        }
+    }
+    let mut file: File = OpenOptions::new().create(true).write(true).truncate(true).open(path)?;
+    file.write_all(kept.as_bytes())?;
+    Ok(file)
+}
+
+// -------- Output streams --------
+
+// The files of a run go to one directory, created if needed. Snapshots are
+// named prefix + number, the number zero-padded to `digits` digits (0: no
+// padding), plus the extension of the format, and their times are kept in
+// Time.txt. Diagnostics.csv gets a line of global quantities of the gas
+// every `diag_steps` steps and every `diag_dt` of time (0: off), cheap
+// enough to follow the run between snapshots. Every file written is listed
+// in Manifest.csv, one line `stream,index,t,file` per write, with the file
+// relative to the directory.
+pub const MANIFEST_HEADER: &str     = "stream,index,t,file";
+pub const DIAGNOSTICS_HEADER: &str  = "t,it,dt,n,mass,px,py,pz,ekin,eth";
+
+// Path of a file of the run's directory dir.
+pub fn output_file(dir: &str, name: &str) -> String {
+    Path::new(dir).join(name).to_string_lossy().into_owned()
+}
+
+pub struct Output {
+    pub dir: String,
+    pub prefix: String,
+    pub digits: usize,
+    pub format: OutputFormat,
+    pub diag_steps: u32,
+    pub diag_dt: f64,
+    pub header: Header,         // Written with the binary snapshots, at their time
+    times: File,
+    manifest: File,
+    diagnostics: Option<File>,
+    diagnostics_listed: bool,
+    t_diag: f64,                // Time of the next line every diag_dt
+}
+
+impl Output {
+    // A resumed run (state read from its checkpoint) keeps what was written
+    // up to the checkpoint, and a new one starts the files afresh.
+    pub fn new(dir: &str, prefix: &str, digits: u32, format: OutputFormat, diag_steps: u32, diag_dt: f64, state: Option<&RunState>) -> Result<Output, Box<dyn Error>> {
+        fs::create_dir_all(dir).map_err(|err| format!("Cannot create {}: {}", dir, err))?;
+        let it_sav: u32 = state.map_or(1, |state| state.it_sav);
+        let t: Option<f64> = state.map(|state| state.t);
+        let times: File = open_times(&output_file(dir, "Time.txt"), (it_sav - 1) as usize)?;
+        let (manifest, kept): (File, String) = open_until(&output_file(dir, "Manifest.csv"), MANIFEST_HEADER, 2, t)?;
+        let diagnostics: Option<File> = if diag_steps > 0 || diag_dt > 0.0 {
+            Some(open_until(&output_file(dir, "Diagnostics.csv"), DIAGNOSTICS_HEADER, 0, t)?.0)
+        } else {
+            None
+        };
+        let t_diag: f64 = if diag_dt > 0.0 { ((t.unwrap_or(0.0)/diag_dt).floor() + 1.0)*diag_dt } else { 0.0 };
+        Ok(Output{dir: String::from(dir), prefix: String::from(prefix), digits: digits as usize, format, diag_steps, diag_dt,
+                  header: Header::default(), times, manifest, diagnostics, diagnostics_listed: kept.lines().any(|line| line.starts_with("diagnostics,")), t_diag})
+    }
+
+    pub fn file(&self, name: &str) -> String {
+        output_file(&self.dir, name)
+    }
+
+    // Appends a written file to the manifest.
+    pub fn record(&mut self, stream: &str, index: u32, t: f64, name: &str) -> Result<(), Box<dyn Error>> {
+        writeln!(self.manifest, "{},{},{},{}", stream, index, t, name)?;
+        Ok(())
+    }
+
+    // Saves the index-th snapshot, at time t, with the sinks of the run.
+    pub fn snapshot(&mut self, index: u32, t: f64, particles: & Vec<Particle>, stars: &[&Star]) -> Result<(), Box<dyn Error>> {
+        let name: String = format!("{}{:0width$}", self.prefix, index, width = self.digits);
+        self.save("snapshot", index, t, &name, particles, stars)
+    }
+
+    // Saves the final state as Fin_00.
+    pub fn finish(&mut self, t: f64, particles: & Vec<Particle>, stars: &[&Star]) -> Result<(), Box<dyn Error>> {
+        self.save("final", 0, t, "Fin_00", particles, stars)
+    }
+
+    fn save(&mut self, stream: &str, index: u32, t: f64, name: &str, particles: & Vec<Particle>, stars: &[&Star]) -> Result<(), Box<dyn Error>> {
+        self.format.save(&self.file(name), &Header{time: t, ..self.header}, particles, stars)?;
+        writeln!(self.times, "{}", t)?;
+        if self.format == OutputFormat::Vtu {
+            save_pvd(&self.dir, &self.prefix, self.digits, stream == "final")?;
+        }
+        self.record(stream, index, t, &(String::from(name) + self.format.extension()))
+    }
+
+    // Called after every step: writes the diagnostics when due, every
+    // diag_steps steps or once t reaches the next multiple of diag_dt.
+    pub fn step(&mut self, it: u32, t: f64, dt: f64, particles: & Vec<Particle>) -> Result<(), Box<dyn Error>> {
+        let by_steps: bool = self.diag_steps > 0 && it.is_multiple_of(self.diag_steps);
+        let by_time: bool = self.diag_dt > 0.0 && t >= self.t_diag;
+        if !by_steps && !by_time {
+            return Ok(());
+        }
+        if by_time {
+            self.t_diag = ((t/self.diag_dt).floor() + 1.0)*self.diag_dt;
+        }
+        if let Some(file) = &mut self.diagnostics {
+            writeln!(file, "{},{},{},{}", t, it, dt, diagnostics(particles).values().join(","))?;
+        }
+        if !self.diagnostics_listed {
+            self.diagnostics_listed = true;
+            self.record("diagnostics", it, t, "Diagnostics.csv")?;
+        }
+        Ok(())
+    }
+}
+
+// Global quantities of the gas (ptype 0): number of particles, mass,
+// momentum, and kinetic and thermal energy.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Diagnostics {
+    pub n: usize,
+    pub mass: f64,
+    pub px: f64,
+    pub py: f64,
+    pub pz: f64,
+    pub ekin: f64,
+    pub eth: f64,
+}
+
+impl Diagnostics {
+    // The values in the order of the columns n,mass,px,py,pz,ekin,eth.
+    pub fn values(&self) -> Vec<String> {
+        vec![self.n.to_string(), self.mass.to_string(), self.px.to_string(), self.py.to_string(), self.pz.to_string(), self.ekin.to_string(), self.eth.to_string()]
+    }
+}
+
+pub fn diagnostics(particles: &[Particle]) -> Diagnostics {
+    let mut diag: Diagnostics = Diagnostics::default();
+    for particle in particles.iter().filter(|particle| particle.ptype == 0) {
+        diag.n    += 1;
+        diag.mass += particle.m;
+        diag.px   += particle.m*particle.vx;
+        diag.py   += particle.m*particle.vy;
+        diag.pz   += particle.m*particle.vz;
+        diag.ekin += 0.5*particle.m*(particle.vx*particle.vx + particle.vy*particle.vy + particle.vz*particle.vz);
+        diag.eth  += particle.m*particle.u;
+    }
+    return diag;
+}
+
+// Opens a CSV file of a run for appending. Resuming from time t keeps the
+// lines whose time (column col) is not after t, otherwise only the header.
+// The final state of the interrupted run is dropped: Fin_00 is rewritten.
+// Returns the file and the lines kept.
+fn open_until(path: &str, header: &str, col: usize, t: Option<f64>) -> Result<(File, String), Box<dyn Error>> {
+    let mut kept: String = String::new();
+    if let (Some(t), Ok(file)) = (t, File::open(path)) {
+        for line in BufReader::new(file).lines().skip(1) {
+            let line: String = line?;
+            if !line.starts_with("final,") && line.split(',').nth(col).and_then(|value| value.parse::<f64>().ok()).is_some_and(|time| time <= t) {
+                kept += &(line + "\n");
+            }
+        }
+    }
+    let mut file: File = OpenOptions::new().create(true).write(true).truncate(true).open(path)?;
+    file.write_all(format!("{}\n{}", header, kept).as_bytes())?;
+    Ok((file, kept))
+}
+
+// A line of Manifest.csv.
+pub struct ManifestEntry {
+    pub stream: String,
+    pub index: u32,
+    pub t: f64,
+    pub file: String,
+}
+
+// Files listed in dir/Manifest.csv, in the order they were written.
+pub fn read_manifest(dir: &str) -> Result<Vec<ManifestEntry>, Box<dyn Error>> {
+    let path: String = output_file(dir, "Manifest.csv");
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .from_path(&path)
+        .map_err(|err| format!("Cannot open {}: {}", path, err))?;
+    let mut entries: Vec<ManifestEntry> = Vec::new();
+    for result in rdr.records() {
+        let record = result?;
+        entries.push(ManifestEntry{stream: String::from(&record[0]), index: record[1].parse::<u32>()?,
+                                   t: record[2].parse::<f64>()?, file: String::from(&record[3])});
+    }
+    Ok(entries)
+}
+
+// -------- Phantom dumps --------
+
+// Phantom full dumps are Fortran unformatted files: every record is framed by
+// its length in bytes (i32). After two identification records, the header
+// holds tagged values of 8 types (int, int*1, int*2, int*4, int*8, real,
+// real*4, real*8) and the body holds tagged arrays, grouped by length: the
+// particles first and the sink particles second.
+// Phantom's gas (1) and boundary (2) types are ptype 0 and 1, accreted
+// particles (h < 0) are skipped, and the sinks are read into Star. Phantom
+// stores one mass per type, and the sinks' 'h' is their accretion radius.
+const PHANTOM_INT1: i32         = 60769;
+const PHANTOM_INT2: i32         = 60878;
+const PHANTOM_INT3: i32         = 690706;
+const PHANTOM_TAG: usize        = 16;
+// Size in bytes of every type. The size of the default real (0 here) is
+// found from the first record.
+const PHANTOM_SIZES: [usize; 8] = [4, 1, 2, 4, 8, 0, 4, 8];
+// Tag of an array and its value for every particle or sink
+type PhantomArray<T> = (&'static str, fn(&T) -> f64);
+
+impl<'a> Bytes<'a> {
+    fn record(&mut self) -> Result<&'a [u8], Box<dyn Error>> {
+        let length: usize = self.u32()? as usize;
+        let data: &[u8] = self.take(length)?;
+        if self.u32()? as usize != length {
+            return Err("Corrupted Fortran record".into());
+        }
+        Ok(data)
+    }
+}
+
+fn write_record<W: Write>(wtr: &mut W, data: &[u8]) -> Result<(), Box<dyn Error>> {
+    let length: [u8; 4] = (data.len() as i32).to_le_bytes();
+    wtr.write_all(&length)?;
+    wtr.write_all(data)?;
+    wtr.write_all(&length)?;
+    Ok(())
+}
+
+fn phantom_tags(tags: &[&str]) -> Vec<u8> {
+    tags.iter().flat_map(|tag| format!("{:<16.16}", tag).into_bytes()).collect()
+}
+
+fn phantom_value(chunk: &[u8], kind: usize) -> f64 {
+    match (kind, chunk.len()) {
+        (1, _)  => chunk[0] as i8 as f64,
+        (2, _)  => i16::from_le_bytes(chunk.try_into().unwrap()) as f64,
+        (4, _)  => i64::from_le_bytes(chunk.try_into().unwrap()) as f64,
+        (0, _) | (3, _) => i32::from_le_bytes(chunk.try_into().unwrap()) as f64,
+        (_, 4)  => f32::from_le_bytes(chunk.try_into().unwrap()) as f64,
+        _       => f64::from_le_bytes(chunk.try_into().unwrap()),
+    }
+}
+
+// Writes a Phantom full dump with the living particles (ptype 0, 1 and 4) and
+// the sinks. eta is Phantom's hfact, the EoS constant (coeff) is written as
+// polyk, and the code units are those of Phantom with G = 1, given the units
+// of distance and mass in cgs. Phantom has no per-particle masses, so the
+// gas and the boundary particles must each have a single mass.
+pub fn save_phantom(path: &str, header: &Header, eta: f64, udist: f64, umass: f64, particles: & Vec<Particle>, stars: &[&Star]) -> Result<(), Box<dyn Error>> {
+    let gas: Vec<&Particle> = particles.iter().filter(|particle| particle.ptype != 2 && particle.ptype != 3).collect();
+    let npart: usize = gas.len();
+    let n_gas: usize = gas.iter().filter(|particle| particle.ptype != 1).count();
+    let m_gas: f64   = gas.iter().find(|particle| particle.ptype != 1).map_or(0.0, |particle| particle.m);
+    let m_bound: f64 = gas.iter().find(|particle| particle.ptype == 1).map_or(0.0, |particle| particle.m);
+    if let Some(particle) = gas.iter().find(|particle| particle.m != if particle.ptype == 1 {m_bound} else {m_gas}) {
+        return Err(format!("Phantom dumps have one mass per type, but particle {} has mass {} and others of its type {}",
+                           particle.id, particle.m, if particle.ptype == 1 {m_bound} else {m_gas}).into());
+    }
+    let utime: f64   = (udist.powi(3)/(6.67430e-8*umass)).sqrt();
+
+    let mut wtr = BufWriter::new(File::create(path)?);
+    let mut first: Vec<u8> = PHANTOM_INT1.to_le_bytes().to_vec();
+    first.extend((PHANTOM_INT2 as f64).to_le_bytes());
+    first.extend(PHANTOM_INT2.to_le_bytes());
+    first.extend(1_i32.to_le_bytes());
+    first.extend(PHANTOM_INT3.to_le_bytes());
+    write_record(&mut wtr, &first)?;
+    write_record(&mut wtr, format!("{:<100}", "FT:Phantom:rusph").as_bytes())?;
+
+    // Header, by type
+    let ints: [(&str, i32); 5]  = [("nblocks", 1), ("nptmass", stars.len() as i32), ("ndustsmall", 0), ("ndustlarge", 0), ("ntypes", 2)];
+    let int8s: [(&str, i64); 3] = [("nparttot", npart as i64), ("npartoftype", n_gas as i64), ("npartoftype", (npart - n_gas) as i64)];
+    let reals: [(&str, f64); 9] = [("time", header.time), ("gamma", header.gamma), ("hfact", eta), ("polyk", header.coeff),
+                                   ("massoftype", m_gas), ("massoftype", m_bound), ("udist", udist), ("umass", umass), ("utime", utime)];
+    for kind in 0..8 {
+        let (tags, values): (Vec<&str>, Vec<u8>) = match kind {
+            0 => (ints.iter().map(|pair| pair.0).collect(), ints.iter().flat_map(|pair| pair.1.to_le_bytes()).collect()),
+            4 => (int8s.iter().map(|pair| pair.0).collect(), int8s.iter().flat_map(|pair| pair.1.to_le_bytes()).collect()),
+            5 => (reals.iter().map(|pair| pair.0).collect(), reals.iter().flat_map(|pair| pair.1.to_le_bytes()).collect()),
+            _ => (Vec::new(), Vec::new()),
+        };
+        write_record(&mut wtr, &(tags.len() as i32).to_le_bytes())?;
+        if !tags.is_empty() {
+            write_record(&mut wtr, &phantom_tags(&tags))?;
+            write_record(&mut wtr, &values)?;
+        }
+    }
+
+    // Body: the lengths of the two groups of arrays, and the number of arrays
+    // of every type in each of them
+    let part_reals: Vec<PhantomArray<Particle>> = {
+        let mut fields: Vec<PhantomArray<Particle>> = vec![("x", |p| p.x), ("y", |p| p.y), ("z", |p| p.z),
+                                                                 ("vx", |p| p.vx), ("vy", |p| p.vy), ("vz", |p| p.vz)];
+        if header.eos_t {
+            fields.push(("u", |p| p.u));
+        }
+        fields
+    };
+    let sink_reals: [PhantomArray<Star>; 12] = [("x", |s| s.x), ("y", |s| s.y), ("z", |s| s.z), ("m", |s| s.m), ("h", |s| s.hacc), ("hsoft", |_| 0.0),
+                                                      ("spinx", |s| s.sx), ("spiny", |s| s.sy), ("spinz", |s| s.sz), ("vx", |s| s.vx), ("vy", |s| s.vy), ("vz", |s| s.vz)];
+    write_record(&mut wtr, &2_i32.to_le_bytes())?;
+    let mut block: Vec<u8> = (npart as i64).to_le_bytes().to_vec();
+    for count in [0, 1, 0, 0, 1, part_reals.len() as i32, 1, 0] {
+        block.extend(count.to_le_bytes());
+    }
+    write_record(&mut wtr, &block)?;
+    let mut block: Vec<u8> = (stars.len() as i64).to_le_bytes().to_vec();
+    for count in [0, 0, 0, 0, 0, sink_reals.len() as i32, 0, 0] {
+        block.extend(count.to_le_bytes());
+    }
+    write_record(&mut wtr, &block)?;
+
+    // Particles' arrays, in the order of the types
+    write_record(&mut wtr, &phantom_tags(&["itype"]))?;
+    write_record(&mut wtr, &gas.iter().map(|particle| if particle.ptype == 1 {2_u8} else {1_u8}).collect::<Vec<u8>>())?;
+    write_record(&mut wtr, &phantom_tags(&["iorig"]))?;
+    write_record(&mut wtr, &gas.iter().flat_map(|particle| (particle.id as i64).to_le_bytes()).collect::<Vec<u8>>())?;
+    for (tag, field) in &part_reals {
+        write_record(&mut wtr, &phantom_tags(&[tag]))?;
+        write_record(&mut wtr, &gas.iter().flat_map(|particle| field(particle).to_le_bytes()).collect::<Vec<u8>>())?;
+    }
+    write_record(&mut wtr, &phantom_tags(&["h"]))?;
+    write_record(&mut wtr, &gas.iter().flat_map(|particle| (particle.h as f32).to_le_bytes()).collect::<Vec<u8>>())?;
+
+    // Sinks' arrays
+    for (tag, field) in &sink_reals {
+        write_record(&mut wtr, &phantom_tags(&[tag]))?;
+        write_record(&mut wtr, &stars.iter().flat_map(|star| field(star).to_le_bytes()).collect::<Vec<u8>>())?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+// Reads a Phantom full dump in code units. The particles and sinks are
+// appended to the vectors.
+pub fn read_phantom(path: &str, particles: &mut Vec<Particle>, stars: &mut Vec<Star>) -> Result<Header, Box<dyn Error>> {
+    let mut buffer: Vec<u8> = Vec::new();
+    File::open(path)?.read_to_end(&mut buffer)?;
+    let mut bytes: Bytes = Bytes{buffer: &buffer, pos: 0};
+
+    // Identification: the size of the default real follows from the length
+    // of the first record.
+    let first: &[u8] = bytes.record()?;
+    let real_size: usize = match first.len() {
+        24 => 8,
+        20 => 4,
+        _  => return Err(format!("{} is not a Phantom dump", path).into()),
+    };
+    if i32::from_le_bytes(first[0..4].try_into()?) != PHANTOM_INT1 {
+        return Err(format!("{} is not a Phantom dump or has another endianness", path).into());
+    }
+    let fileid: &[u8] = bytes.record()?;
+    if fileid.len() < 2 || fileid[0] != b'F' || fileid[1] != b'T' {
+        return Err(format!("{} is not a tagged full dump", path).into());
+    }
+    let size = |kind: usize| -> usize { if kind == 5 {real_size} else {PHANTOM_SIZES[kind]} };
+
+    // Header. Some tags repeat, one per particle type.
+    let mut values: Vec<(String, f64)> = Vec::new();
+    for kind in 0..8 {
+        let nvars: usize = i32::from_le_bytes(bytes.record()?.try_into()?) as usize;
+        if nvars > 0 {
+            let tags: &[u8] = bytes.record()?;
+            let data: &[u8] = bytes.record()?;
+            for ii in 0..nvars {
+                let tag: String = String::from_utf8_lossy(&tags[PHANTOM_TAG*ii..PHANTOM_TAG*(ii+1)]).trim().to_string();
+                values.push((tag, phantom_value(&data[size(kind)*ii..size(kind)*(ii+1)], kind)));
+            }
+        }
+    }
+    let get = |tag: &str| -> Option<f64> { values.iter().find(|value| value.0 == tag).map(|value| value.1) };
+    let massoftype: Vec<f64> = values.iter().filter(|value| value.0 == "massoftype").map(|value| value.1).collect();
+    let hfact: f64 = get("hfact").ok_or("Missing hfact in the Phantom dump")?;
+
+    // Body: the arrays of every block are joined by group and tag
+    let nblocks: usize = get("nblocks").unwrap_or(1.0) as usize;
+    let number: usize = i32::from_le_bytes(bytes.record()?.try_into()?) as usize;
+    let narraylengths: usize = number/nblocks.max(1);
+    let mut arrays: HashMap<(usize, String), Vec<f64>> = HashMap::new();
+    for _ in 0..nblocks {
+        let mut lengths: Vec<usize> = Vec::new();
+        let mut nums: Vec<[usize; 8]> = Vec::new();
+        for _ in 0..narraylengths {
+            let record: &[u8] = bytes.record()?;
+            lengths.push(i64::from_le_bytes(record[0..8].try_into()?) as usize);
+            let mut num: [usize; 8] = [0; 8];
+            for (kind, count) in num.iter_mut().enumerate() {
+                *count = i32::from_le_bytes(record[8+4*kind..12+4*kind].try_into()?) as usize;
+            }
+            nums.push(num);
+        }
+        for jj in 0..narraylengths {
+            for kind in 0..8 {
+                for _ in 0..nums[jj][kind] {
+                    let tag: String = String::from_utf8_lossy(bytes.record()?).trim().to_string();
+                    let data: &[u8] = bytes.record()?;
+                    if data.len() != lengths[jj]*size(kind) {
+                        return Err(format!("Array '{}' of {} has a wrong length", tag, path).into());
+                    }
+                    arrays.entry((jj, tag)).or_default().extend(data.chunks_exact(size(kind)).map(|chunk| phantom_value(chunk, kind)));
+                }
+            }
+        }
+    }
+    let array = |group: usize, tag: &str| -> Option<&Vec<f64>> { arrays.get(&(group, String::from(tag))) };
+    let column = |group: usize, tag: &str| -> Result<&Vec<f64>, Box<dyn Error>> {
+        array(group, tag).ok_or(format!("Missing array '{}' in {}", tag, path).into())
+    };
+
+    // Particles
+    let (x, y, z, h) = (column(0, "x")?, column(0, "y")?, column(0, "z")?, column(0, "h")?);
+    let (vx, vy, vz) = (column(0, "vx")?, column(0, "vy")?, column(0, "vz")?);
+    for ii in 0..x.len() {
+        if h[ii] <= 0.0 {
+            continue;
+        }
+        let itype: usize = array(0, "itype").map_or(1, |itype| itype[ii].abs() as usize);
+        let ptype: u8 = match itype {
+            1 => 0,
+            2 => 1,
+            _ => return Err(format!("Unsupported Phantom particle type {} in {}", itype, path).into()),
+        };
+        let m: f64 = *massoftype.get(itype-1).ok_or("Missing massoftype in the Phantom dump")?;
+        particles.push(Particle{ptype, id: array(0, "iorig").map_or(ii as u64, |iorig| iorig[ii] as u64), m,
+                                rho: m*(hfact/h[ii]).powi(3), x: x[ii], y: y[ii], z: z[ii], h: h[ii],
+                                vx: vx[ii], vy: vy[ii], vz: vz[ii],
+                                u: array(0, "u").map_or(Particle::default().u, |u| u[ii]),
+                                ..Default::default()});
+    }
+
+    // Sinks
+    if let Some(m) = array(1, "m") {
+        let value = |tag: &str, ii: usize| -> f64 { array(1, tag).map_or(0.0, |values| values[ii]) };
+        for ii in 0..m.len() {
+            stars.push(Star{m: m[ii], x: value("x", ii), y: value("y", ii), z: value("z", ii), hacc: value("h", ii),
+                            vx: value("vx", ii), vy: value("vy", ii), vz: value("vz", ii),
+                            sx: value("spinx", ii), sy: value("spiny", ii), sz: value("spinz", ii),
+                            ..Default::default()});
+        }
+    }
+
+    Ok(Header{time: get("time").unwrap_or(0.0), dm: massoftype.first().copied().unwrap_or(0.0),
+              eos_t: array(0, "u").is_some(), gamma: get("gamma").unwrap_or(Header::default().gamma),
+              coeff: get("polyk").unwrap_or(0.0), ..Default::default()})
+}
+
+// -------- GADGET snapshots --------
+
+// GADGET snapshots are Fortran unformatted files as well. Format 1 has its
+// blocks in a fixed order (HEAD, POS, VEL, ID, MASS, U, RHO, HSML), and format 2
+// precedes every block with a record holding its label and size. The header
+// gives the number of particles and the mass of each of the 6 types: MASS only
+// holds the particles whose type has no fixed mass, and U, RHO and HSML only
+// the gas. Type 0 (gas) is ptype 0 and type 5 are the sinks. Types 1-4 are
+// collisionless (dark matter, stars): they are kept as ptype 5, which the
+// SPH sums leave out. The reals are single precision unless the blocks have
+// the length of doubles, and so are the ids unless ID has 8 bytes a value
+// (GADGET's LONGIDS). A snapshot split in files is read from path.0,
+// path.1, ...
+const GADGET_TYPES: usize         = 6;
+const GADGET_COLLISIONLESS: usize = 1;
+const GADGET_SINK: usize          = 5;
+const GADGET_HEAD: usize          = 256;
+const GADGET_BLOCKS: [&str; 7]    = ["POS", "VEL", "ID", "MASS", "U", "RHO", "HSML"];
+
+// Data of a block and the size of its values
+type GadgetBlock<'a> = (&'a [u8], usize);
+
+#[derive(Default)]
+struct GadgetHeader {
+    npart: [usize; GADGET_TYPES],
+    massarr: [f64; GADGET_TYPES],
+    time: f64,
+    num_files: usize,
+    box_size: f64,
+}
+
+fn write_gadget_block<W: Write>(wtr: &mut W, format: u8, label: &str, data: &[u8]) -> Result<(), Box<dyn Error>> {
+    if format == 2 {
+        let mut record: Vec<u8> = format!("{:<4.4}", label).into_bytes();
+        record.extend((data.len() as i32 + 8).to_le_bytes());
+        write_record(wtr, &record)?;
+    }
+    write_record(wtr, data)
+}
+
+// Writes a GADGET snapshot of format 1 or 2 in single precision: the gas
+// (ptype 0 and 4) as type 0, the collisionless particles (ptype 5) as type 1
+// and the sinks as type 5, with ids following the largest one of the
+// particles. The ids are 32-bit unless one of them needs 64 bits. GADGET has
+// no boundary particles, so they are rejected. The box is only written for a
+// periodic domain, with its side along x.
+pub fn save_gadget(path: &str, header: &Header, format: u8, particles: & Vec<Particle>, stars: &[&Star]) -> Result<(), Box<dyn Error>> {
+    if format != 1 && format != 2 {
+        return Err(format!("Unknown GADGET format {}", format).into());
+    }
+    if let Some(particle) = particles.iter().find(|particle| particle.ptype == 1) {
+        return Err(format!("GADGET snapshots have no boundary particles, as particle {}", particle.id).into());
+    }
+    let gas: Vec<&Particle> = particles.iter().filter(|particle| particle.ptype == 0 || particle.ptype == 4).collect();
+    let collisionless: Vec<&Particle> = particles.iter().filter(|particle| particle.ptype == 5).collect();
+
+    // Every record lists the gas, the collisionless particles and the sinks
+    let fields = |particle: fn(&Particle) -> f64, star: fn(&Star) -> f64| -> Vec<f64> {
+        gas.iter().chain(collisionless.iter()).map(|p| particle(p)).chain(stars.iter().map(|s| star(s))).collect()
+    };
+    let masses: [Vec<f64>; 3] = [gas.iter().map(|p| p.m).collect(), collisionless.iter().map(|p| p.m).collect(), stars.iter().map(|s| s.m).collect()];
+    let mut npart: [u32; GADGET_TYPES] = [0; GADGET_TYPES];
+    let mut massarr: [f64; GADGET_TYPES] = [0.0; GADGET_TYPES];
+    for (kind, mass) in [0, GADGET_COLLISIONLESS, GADGET_SINK].into_iter().zip(masses.iter()) {
+        npart[kind] = mass.len() as u32;
+        if !mass.is_empty() && mass.iter().all(|m| *m == mass[0]) {
+            massarr[kind] = mass[0];
+        }
+    }
+    let box_size: f64 = if header.xper && header.yper && header.zper {header.wd} else {0.0};
+
+    let mut head: Vec<u8> = Vec::with_capacity(GADGET_HEAD);
+    head.extend(npart.iter().flat_map(|n| n.to_le_bytes()));
+    head.extend(massarr.iter().flat_map(|m| m.to_le_bytes()));
+    head.extend(header.time.to_le_bytes());
+    head.extend(0.0_f64.to_le_bytes());                     // redshift
+    head.extend([0_i32; 2].iter().flat_map(|flag| flag.to_le_bytes()));
+    head.extend(npart.iter().flat_map(|n| n.to_le_bytes())); // npartTotal
+    head.extend(0_i32.to_le_bytes());                       // flag_cooling
+    head.extend(1_i32.to_le_bytes());                       // num_files
+    head.extend(box_size.to_le_bytes());
+    head.resize(GADGET_HEAD, 0);
+
+    let reals = |values: Vec<f64>| -> Vec<u8> { values.iter().flat_map(|value| (*value as f32).to_le_bytes()).collect() };
+    let vectors = |x: Vec<f64>, y: Vec<f64>, z: Vec<f64>| -> Vec<u8> {
+        reals(x.iter().zip(y.iter()).zip(z.iter()).flat_map(|((x, y), z)| [*x, *y, *z]).collect())
+    };
+
+    let mut wtr = BufWriter::new(File::create(path)?);
+    write_gadget_block(&mut wtr, format, "HEAD", &head)?;
+    write_gadget_block(&mut wtr, format, "POS", &vectors(fields(|p| p.x, |s| s.x), fields(|p| p.y, |s| s.y), fields(|p| p.z, |s| s.z)))?;
+    write_gadget_block(&mut wtr, format, "VEL", &vectors(fields(|p| p.vx, |s| s.vx), fields(|p| p.vy, |s| s.vy), fields(|p| p.vz, |s| s.vz)))?;
+    let id_sink: u64 = gas.iter().chain(collisionless.iter()).map(|p| p.id + 1).max().unwrap_or(0);
+    let ids: Vec<u64> = gas.iter().chain(collisionless.iter()).map(|p| p.id)
+                           .chain((0..stars.len() as u64).map(|ii| id_sink + ii)).collect();
+    let ids: Vec<u8> = if ids.iter().all(|id| *id <= u32::MAX as u64) {
+        ids.iter().flat_map(|id| (*id as u32).to_le_bytes()).collect()
+    } else {
+        ids.iter().flat_map(|id| id.to_le_bytes()).collect()
+    };
+    write_gadget_block(&mut wtr, format, "ID", &ids)?;
+    let varying: Vec<f64> = [0, GADGET_COLLISIONLESS, GADGET_SINK].into_iter().zip(masses)
+                                               .filter(|(kind, _)| massarr[*kind] == 0.0)
+                                               .flat_map(|(_, mass)| mass).collect();
+    if !varying.is_empty() {
+        write_gadget_block(&mut wtr, format, "MASS", &reals(varying))?;
+    }
+    if !gas.is_empty() {
+        write_gadget_block(&mut wtr, format, "U", &reals(gas.iter().map(|p| p.u).collect()))?;
+        write_gadget_block(&mut wtr, format, "RHO", &reals(gas.iter().map(|p| p.rho).collect()))?;
+        write_gadget_block(&mut wtr, format, "HSML", &reals(gas.iter().map(|p| p.h).collect()))?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+// Reads a GADGET snapshot of format 1 or 2 in its units. The particles and
+// sinks are appended to the vectors. The domain is the periodic box, from the
+// origin, or else the bounding box of the particles and sinks read.
+pub fn read_gadget(path: &str, particles: &mut Vec<Particle>, stars: &mut Vec<Star>) -> Result<Header, Box<dyn Error>> {
+    let (n0, s0): (usize, usize) = (particles.len(), stars.len());
+    let first: String = if fs::metadata(path).is_ok() {String::from(path)} else {format!("{}.0", path)};
+    let head: GadgetHeader = read_gadget_file(&first, particles, stars)?;
+    for ii in 1..head.num_files {
+        read_gadget_file(&format!("{}.{}", path, ii), particles, stars)?;
+    }
+
+    let periodic: bool = head.box_size > 0.0;
+    let (mut x0, mut y0, mut z0): (f64, f64, f64) = (0.0, 0.0, 0.0);
+    let (mut wd, mut lg, mut hg): (f64, f64, f64) = (head.box_size, head.box_size, head.box_size);
+    let points: Vec<(f64, f64, f64)> = particles[n0..].iter().map(|p| (p.x, p.y, p.z)).chain(stars[s0..].iter().map(|s| (s.x, s.y, s.z))).collect();
+    if !periodic && !points.is_empty() {
+        let lower = |axis: fn(&(f64, f64, f64)) -> f64| -> f64 { points.iter().map(axis).fold(f64::INFINITY, f64::min) };
+        let upper = |axis: fn(&(f64, f64, f64)) -> f64| -> f64 { points.iter().map(axis).fold(f64::NEG_INFINITY, f64::max) };
+        (x0, y0, z0) = (lower(|p| p.0), lower(|p| p.1), lower(|p| p.2));
+        (wd, lg, hg) = (upper(|p| p.0) - x0, upper(|p| p.1) - y0, upper(|p| p.2) - z0);
+    }
+    Ok(Header{time: head.time, dm: particles[n0..].iter().find(|particle| particle.ptype == 0).map_or(head.massarr[0], |particle| particle.m),
+              x0, y0, z0, wd, lg, hg, xper: periodic, yper: periodic, zper: periodic,
+              eos_t: true, ..Default::default()})
+}
+
+fn read_gadget_file(path: &str, particles: &mut Vec<Particle>, stars: &mut Vec<Star>) -> Result<GadgetHeader, Box<dyn Error>> {
+    let mut buffer: Vec<u8> = Vec::new();
+    File::open(path)?.read_to_end(&mut buffer)?;
+    let mut bytes: Bytes = Bytes{buffer: &buffer, pos: 0};
+
+    // Format 2 starts with the label of the header
+    let format2: bool = buffer.len() >= 4 && u32::from_le_bytes(buffer[0..4].try_into()?) == 8;
+    if format2 {
+        bytes.record()?;
+    }
+    let data: &[u8] = bytes.record()?;
+    if data.len() != GADGET_HEAD {
+        return Err(format!("{} is not a GADGET snapshot", path).into());
+    }
+    let mut record: Bytes = Bytes{buffer: data, pos: 0};
+    let mut head: GadgetHeader = GadgetHeader::default();
+    for kind in 0..GADGET_TYPES {
+        head.npart[kind] = record.u32()? as usize;
+    }
+    for kind in 0..GADGET_TYPES {
+        head.massarr[kind] = record.f64()?;
+    }
+    head.time = record.f64()?;
+    record.take(8 + 8 + 4*GADGET_TYPES + 4)?;   // redshift, flags, npartTotal, flag_cooling
+    head.num_files = record.u32()? as usize;
+    head.box_size = record.f64()?;
+
+    // The blocks, by label. Format 1 has no MASS unless a type needs it.
+    let with_mass: bool = (0..GADGET_TYPES).any(|kind| head.npart[kind] > 0 && head.massarr[kind] == 0.0);
+    let mut order = GADGET_BLOCKS.iter().filter(|label| with_mass || **label != "MASS");
+    let mut blocks: HashMap<String, &[u8]> = HashMap::new();
+    while bytes.pos < buffer.len() {
+        let label: String = if format2 {
+            String::from_utf8_lossy(&bytes.record()?[0..4]).trim().to_string()
+        } else {
+            order.next().map_or(String::new(), |label| label.to_string())
+        };
+        let data: &[u8] = bytes.record()?;
+        blocks.insert(label, data);
+    }
+
+    let ntot: usize = head.npart.iter().sum();
+    let ngas: usize = head.npart[0];
+    if ntot == 0 {
+        return Ok(head);
+    }
+    let block = |label: &str, count: usize| -> Result<Option<GadgetBlock>, Box<dyn Error>> {
+        match blocks.get(label) {
+            Some(data) if data.len() == 4*count || data.len() == 8*count => Ok(Some((data, data.len()/count))),
+            Some(_) => Err(format!("Block {} of {} has a wrong length", label, path).into()),
+            None => Ok(None),
+        }
+    };
+    let values = |data: Option<GadgetBlock>| -> Vec<f64> {
+        data.map_or(Vec::new(), |(data, size)| data.chunks_exact(size).map(|chunk| {
+            if size == 4 {f32::from_le_bytes(chunk.try_into().unwrap()) as f64} else {f64::from_le_bytes(chunk.try_into().unwrap())}
+        }).collect())
+    };
+    let pos: Vec<f64> = values(Some(block("POS", 3*ntot)?.ok_or(format!("Missing POS in {}", path))?));
+    let vel: Vec<f64> = values(Some(block("VEL", 3*ntot)?.ok_or(format!("Missing VEL in {}", path))?));
+    let ids: Vec<u64> = block("ID", ntot)?.map_or((0..ntot as u64).collect(), |(data, size)| data.chunks_exact(size).map(|chunk| {
+        if size == 4 {u32::from_le_bytes(chunk.try_into().unwrap()) as u64} else {u64::from_le_bytes(chunk.try_into().unwrap())}
+    }).collect());
+    let mass: Vec<f64> = {
+        let count: usize = (0..GADGET_TYPES).filter(|kind| head.massarr[*kind] == 0.0).map(|kind| head.npart[kind]).sum();
+        if count > 0 {values(Some(block("MASS", count)?.ok_or(format!("Missing MASS in {}", path))?))} else {Vec::new()}
+    };
+    let u: Vec<f64>    = if ngas > 0 {values(block("U", ngas)?)} else {Vec::new()};
+    let rho: Vec<f64>  = if ngas > 0 {values(block("RHO", ngas)?)} else {Vec::new()};
+    let hsml: Vec<f64> = if ngas > 0 {values(block("HSML", ngas)?)} else {Vec::new()};
+
+    let mut ii: usize = 0;
+    let mut im: usize = 0;
+    for kind in 0..GADGET_TYPES {
+        for _ in 0..head.npart[kind] {
+            let m: f64 = if head.massarr[kind] > 0.0 {head.massarr[kind]} else {im += 1; mass[im-1]};
+            if kind == GADGET_SINK {
+                stars.push(Star{m, x: pos[3*ii], y: pos[3*ii+1], z: pos[3*ii+2], vx: vel[3*ii], vy: vel[3*ii+1], vz: vel[3*ii+2], ..Default::default()});
+            } else {
+                let default: Particle = Particle::default();
+                particles.push(Particle{ptype: if kind == 0 {0} else {5}, id: ids[ii], m,
+                                        x: pos[3*ii], y: pos[3*ii+1], z: pos[3*ii+2], vx: vel[3*ii], vy: vel[3*ii+1], vz: vel[3*ii+2],
+                                        u: *u.get(ii).unwrap_or(&default.u), rho: *rho.get(ii).unwrap_or(&default.rho),
+                                        h: *hsml.get(ii).unwrap_or(&default.h), ..default});
+            }
+            ii += 1;
+        }
+    }
+    Ok(head)
+}
+
 // -------- Read data --------
 
+// Index of a named column. Files written before a column existed return None.
+fn column(headers: &csv::StringRecord, name: &str) -> Option<usize> {
+    headers.iter().position(|header| header == name)
+}
+
 pub fn read_data(path: &str, particles: &mut Vec<Particle>) -> Result<(), Box<dyn Error>> {
     let mut rdr = csv::ReaderBuilder::new()
         .has_headers(true)
         .from_path(path)?;
-    for result in rdr.records() {
+    let headers = rdr.headers()?.clone();
+    let mut cols: Vec<usize> = Vec::new();
+    for name in ["ptype", "x", "y", "z", "vx", "vy", "vz", "h", "u"] {
+        cols.push(column(&headers, name).ok_or(format!("Missing column '{}' in {}", name, path))?);
+    }
+    // Files written before the mass was stored on the particles can't be
+    // used: every density would be zero.
+    let m_col: usize = column(&headers, "m").ok_or(format!("Missing column 'm' in {}: it was written by an older version", path))?;
+    let id_col: Option<usize> = column(&headers, "id");
+    for (ii, result) in rdr.records().enumerate() {
         let record = result?;
-        particles.push(Particle{ptype:(&record[0]).parse::<u8>().unwrap(), x:(&record[1]).parse::<f64>().unwrap(), y:(&record[2]).parse::<f64>().unwrap(), z:(&record[3]).parse::<f64>().unwrap(),
-                                vx:(&record[4]).parse::<f64>().unwrap(), vy:(&record[5]).parse::<f64>().unwrap(), vz:(&record[6]).parse::<f64>().unwrap(),
-                                h:(&record[7]).parse::<f64>().unwrap(), u:(&record[8]).parse::<f64>().unwrap(),
+        let id: u64 = match id_col {
+            Some(col) => (&record[col]).parse::<u64>()?,
+            None => ii as u64,
+        };
+        let m: f64 = (&record[m_col]).parse::<f64>()?;
+        particles.push(Particle{ptype:(&record[cols[0]]).parse::<u8>()?, id:id, m:m,
+                                x:(&record[cols[1]]).parse::<f64>()?, y:(&record[cols[2]]).parse::<f64>()?, z:(&record[cols[3]]).parse::<f64>()?,
+                                vx:(&record[cols[4]]).parse::<f64>()?, vy:(&record[cols[5]]).parse::<f64>()?, vz:(&record[cols[6]]).parse::<f64>()?,
+                                h:(&record[cols[7]]).parse::<f64>()?, u:(&record[cols[8]]).parse::<f64>()?,
                                 ..Default::default()});
     }
     Ok(())
 }
 
-pub fn read_data_iso(path: &str, particles: &mut Vec<Particle>) -> Result<(), Box<dyn Error>> {
+// Reads a snapshot written by Output, whatever its format: csv and vtu files
+// by their extension and binary ones by their magic number, runs of former
+// versions having written them with save_data_bin.
+pub fn read_output(path: &str, particles: &mut Vec<Particle>) -> Result<(), Box<dyn Error>> {
+    if path.ends_with(".csv") {
+        read_data(path, particles)
+    } else if path.ends_with(".vtu") {
+        read_vtu(path, particles)
+    } else if is_snapshot(path) {
+        read_snapshot(path, particles, &mut Vec::new()).map(|_| ())
+    } else {
+        read_data_bin(path, particles)
+    }
+}
+
+// Reads the files written by save_vtu. Those of former versions, without
+// the id and m arrays, are rejected.
+pub fn read_vtu(path: &str, particles: &mut Vec<Particle>) -> Result<(), Box<dyn Error>> {
+    // The values of every DataArray by name, the points having none
+    let mut arrays: HashMap<String, Vec<String>> = HashMap::new();
+    let mut current: Option<(String, Vec<String>)> = None;
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line: String = line?;
+        let line: &str = line.trim();
+        if line.starts_with("<DataArray") {
+            let name: &str = line.split("Name=\"").nth(1).and_then(|rest| rest.split('"').next()).unwrap_or("points");
+            current = Some((String::from(name), Vec::new()));
+        } else if line.starts_with("</DataArray") {
+            if let Some((name, values)) = current.take() {
+                arrays.insert(name, values);
+            }
+        } else if let Some((_, values)) = &mut current {
+            values.extend(line.split_whitespace().map(String::from));
+        }
+    }
+
+    let n: usize = arrays.get("points").ok_or(format!("{} has no points", path))?.len()/3;
+    let array = |name: &str, width: usize| -> Result<&Vec<String>, Box<dyn Error>> {
+        match arrays.get(name) {
+            Some(values) if values.len() == width*n => Ok(values),
+            Some(_) => Err(format!("{}: {} has not {} values per point", path, name, width).into()),
+            None => Err(format!("{} has no {} array: it was written by an older version", path, name).into()),
+        }
+    };
+    let (points, velocity): (&Vec<String>, &Vec<String>) = (array("points", 3)?, array("velocity", 3)?);
+    let (rho, u, h, m): (&Vec<String>, &Vec<String>, &Vec<String>, &Vec<String>) = (array("rho", 1)?, array("u", 1)?, array("h", 1)?, array("m", 1)?);
+    let (ptype, id): (&Vec<String>, &Vec<String>) = (array("ptype", 1)?, array("id", 1)?);
+    particles.reserve(n);
+    for ii in 0..n {
+        particles.push(Particle{ptype:ptype[ii].parse::<u8>()?, id:id[ii].parse::<u64>()?, m:m[ii].parse::<f64>()?, rho:rho[ii].parse::<f64>()?,
+                                x:points[3*ii].parse::<f64>()?, y:points[3*ii+1].parse::<f64>()?, z:points[3*ii+2].parse::<f64>()?,
+                                vx:velocity[3*ii].parse::<f64>()?, vy:velocity[3*ii+1].parse::<f64>()?, vz:velocity[3*ii+2].parse::<f64>()?,
+                                h:h[ii].parse::<f64>()?, u:u[ii].parse::<f64>()?,
+                                ..Default::default()});
+    }
+    Ok(())
+}
+
+// Reads the files written by save_data_bin. Files without the header (the
+// former layout of 8 f64 per particle, with no id or mass) are rejected.
+pub fn read_data_bin(path: &str, particles: &mut Vec<Particle>) -> Result<(), Box<dyn Error>> {
+    let mut buffer: Vec<u8> = Vec::new();
+    File::open(path)?.read_to_end(&mut buffer)?;
+    let mut bytes: Bytes = Bytes{buffer: &buffer, pos: 0};
+    if buffer.len() < 8 || &buffer[0..8] != BINARY_MAGIC {
+        return Err(format!("{} has no binary header: it was written by an older version", path).into());
+    }
+    bytes.take(8)?;
+    let version: u32 = bytes.u32()?;
+    if version > BINARY_VERSION {
+        return Err(format!("Binary file {} has version {}, newer than {}", path, version, BINARY_VERSION).into());
+    }
+    let n: usize = bytes.u64()? as usize;
+    if buffer.len() - bytes.pos != 10*8*n {
+        return Err(format!("Corrupted binary file {}: {} bytes for {} particles", path, buffer.len(), n).into());
+    }
+    for _ in 0..n {
+        particles.push(Particle{id:bytes.u64()?, m:bytes.f64()?,
+                                x:bytes.f64()?, y:bytes.f64()?, z:bytes.f64()?,
+                                vx:bytes.f64()?, vy:bytes.f64()?, vz:bytes.f64()?,
+                                h:bytes.f64()?, u:bytes.f64()?,
+                                ..Default::default()});
+    }
+    Ok(())
+}
+
+// Isothermal files carry no mass: every particle gets the mass m.
+pub fn read_data_iso(path: &str, m: f64, particles: &mut Vec<Particle>) -> Result<(), Box<dyn Error>> {
     let mut rdr = csv::ReaderBuilder::new()
         .has_headers(true)
         .from_path(path)?;
-    for result in rdr.records() {
+    for (ii, result) in rdr.records().enumerate() {
         let record = result?;
-        particles.push(Particle{x:(&record[0]).parse::<f64>().unwrap(), y:(&record[1]).parse::<f64>().unwrap(), z:(&record[2]).parse::<f64>().unwrap(),
+        particles.push(Particle{id:ii as u64, m:m, x:(&record[0]).parse::<f64>().unwrap(), y:(&record[1]).parse::<f64>().unwrap(), z:(&record[2]).parse::<f64>().unwrap(),
                                 vx:(&record[3]).parse::<f64>().unwrap(), vy:(&record[4]).parse::<f64>().unwrap(), vz:(&record[5]).parse::<f64>().unwrap(),
                                 h:(&record[6]).parse::<f64>().unwrap(),
                                 ..Default::default()});
@@ -69,19 +1379,311 @@ pub fn read_data_iso(path: &str, particles: &mut Vec<Particle>) -> Result<(), Bo
     Ok(())
 }
 
-pub fn read_input(path: &str) -> Vec<f64> {
-    let reader = BufReader::new(File::open(path).expect("Cannot open input file"));
-    let mut input = Vec::new();
+// Times of the saved snapshots, one per line (Time.txt).
+pub fn read_times(path: &str) -> Result<Vec<f64>, Box<dyn Error>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut times = Vec::new();
     for line in reader.lines() {
-        for word in line.unwrap().split_whitespace() {
-            if word == "#" {
-                break;
-            } else {
-                input.push(word.parse().unwrap());
+        let line = line?;
+        if !line.trim().is_empty() {
+            times.push(line.trim().parse::<f64>()?);
+        }
+    }
+    Ok(times)
+}
+
+// -------- Configuration --------
+
+// A configuration file holds one `key = value` per line, and '#' starts a
+// comment at the beginning of a line or after a blank, so that values such
+// as paths may hold it. The keys of a run are declared with config! as a
+// struct, and the file is checked as a whole: unknown, repeated and missing
+// keys, values that don't parse and values out of range are all reported
+// together. A file may be split in sections by `[name]` lines, each read
+// into its own struct; the keys before the first section form the unnamed
+// section "".
+//
+//     datafunctions::config! {
+//         pub struct Input {
+//             eta: f64 = 1.2, "", "Dimensionless constant specifying the smoothing length", min 0.0;
+//             nx: u32,        "", "Particle resolution in the x direction", min 1;
+//             tf: f64,        "T", "Final time";
+//         }
+//     }
+//
+// Each key has its type, an optional default, its unit ("" if dimensionless)
+// and a description, optionally followed by its bounds.
+//
+// A value may be followed by its unit, `tf = 2.0 T`. The code units (L, T, M
+// and their products) have no scale, so the unit must be the one of the key.
+// Wall-clock times are converted between s, min and h: `max_walltime = 30
+// min` is 0.5 for a key in hours.
+
+// Types of the configuration values.
+pub trait Value: Sized {
+    fn parse(text: &str) -> Result<Self, String>;
+
+    fn check(text: &str) -> Result<(), String> {
+        Self::parse(text).map(|_| ())
+    }
+}
+
+impl Value for f64 {
+    fn parse(text: &str) -> Result<f64, String> {
+        text.parse().map_err(|_| format!("'{}' is not a number", text))
+    }
+}
+
+macro_rules! integer_value {
+    ($($ty:ty),*) => {
+        $(impl Value for $ty {
+            fn parse(text: &str) -> Result<$ty, String> {
+                text.parse().map_err(|_| format!("'{}' is not an integer in [{}, {}]", text, <$ty>::MIN, <$ty>::MAX))
             }
+        })*
+    };
+}
+integer_value!(i32, u32, u64, usize);
+
+// Booleans are true/false, yes/no, on/off or 1/0.
+impl Value for bool {
+    fn parse(text: &str) -> Result<bool, String> {
+        match text {
+            "true" | "yes" | "on" | "1"  => Ok(true),
+            "false" | "no" | "off" | "0" => Ok(false),
+            _ => Err(format!("'{}' is not a boolean (true or false)", text)),
+        }
+    }
+}
+
+impl Value for String {
+    fn parse(text: &str) -> Result<String, String> {
+        Ok(String::from(text))
+    }
+}
+
+// Formats are named, or given by their former codes (0=binary; 1=csv; 2=vtu).
+impl Value for OutputFormat {
+    fn parse(text: &str) -> Result<OutputFormat, String> {
+        match text {
+            "binary" | "0" => Ok(OutputFormat::Binary),
+            "csv" | "1"    => Ok(OutputFormat::Csv),
+            "vtu" | "2"    => Ok(OutputFormat::Vtu),
+            _ => Err(format!("'{}' is not an output format (binary, csv or vtu)", text)),
+        }
+    }
+}
+
+// Declaration of a key.
+pub struct Param {
+    pub key: &'static str,
+    pub default: Option<&'static str>,
+    pub unit: &'static str,
+    pub help: &'static str,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub check: fn(&str) -> Result<(), String>,
+}
+
+// Seconds in a wall-clock unit
+fn wall_clock(unit: &str) -> Option<f64> {
+    match unit {
+        "s"   => Some(1.0),
+        "min" => Some(60.0),
+        "h"   => Some(3600.0),
+        _     => None,
+    }
+}
+
+impl Param {
+    // The value in the unit of the key, without the unit
+    fn convert(&self, text: &str) -> Result<String, String> {
+        let Some((number, unit)) = text.split_once(char::is_whitespace).filter(|_| !self.unit.is_empty()) else {
+            return Ok(String::from(text));
+        };
+        let unit: String = unit.split_whitespace().collect();
+        if unit == self.unit.split_whitespace().collect::<String>() {
+            return Ok(String::from(number));
+        }
+        match (wall_clock(&unit), wall_clock(self.unit), number.parse::<f64>()) {
+            (Some(from), Some(to), Ok(value)) => Ok((value*from/to).to_string()),
+            _ => Err(format!("key '{}': '{}' is not in {}", self.key, text, self.unit)),
+        }
+    }
+
+    fn validate(&self, text: &str) -> Result<(), String> {
+        (self.check)(text).map_err(|err| format!("key '{}': {}", self.key, err))?;
+        if let Ok(value) = text.parse::<f64>() {
+            let unit: String = if self.unit.is_empty() {String::new()} else {format!(" {}", self.unit)};
+            if self.min.is_some_and(|min| value < min) {
+                return Err(format!("key '{}': {} is below the minimum {}{}", self.key, text, self.min.unwrap(), unit));
+            }
+            if self.max.is_some_and(|max| value > max) {
+                return Err(format!("key '{}': {} is above the maximum {}{}", self.key, text, self.max.unwrap(), unit));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Text before the comment of a line
+fn strip_comment(line: &str) -> &str {
+    let start: Option<usize> = line.char_indices()
+                                   .find(|(ii, c)| *c == '#' && (*ii == 0 || line[..*ii].ends_with(char::is_whitespace)))
+                                   .map(|(ii, _)| ii);
+    &line[..start.unwrap_or(line.len())]
+}
+
+// Quotes around a value are optional.
+fn unquote(text: &str) -> &str {
+    let text: &str = text.trim();
+    if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') {&text[1..text.len()-1]} else {text}
+}
+
+// Values of a checked configuration file, defaults included.
+pub struct Config {
+    path: String,
+    values: HashMap<String, String>,
+}
+
+impl Config {
+    // Reads the keys of a section. The names of the sections allowed in the
+    // file are given in sections.
+    pub fn read(path: &str, section: &str, params: &[Param], sections: &[&str]) -> Result<Config, Box<dyn Error>> {
+        let text: String = fs::read_to_string(path).map_err(|err| format!("Cannot open {}: {}", path, err))?;
+        Config::parse(path, &text, section, params, sections)
+    }
+
+    pub fn parse(path: &str, text: &str, section: &str, params: &[Param], sections: &[&str]) -> Result<Config, Box<dyn Error>> {
+        let mut values: HashMap<String, String> = HashMap::new();
+        let mut errors: Vec<String> = Vec::new();
+        let mut current: &str = "";
+        for (ii, line) in text.lines().enumerate() {
+            let line: &str = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                current = line[1..line.len()-1].trim();
+                if !sections.contains(&current) {
+                    errors.push(format!("line {}: unknown section '[{}]'", ii+1, current));
+                }
+                continue;
+            }
+            if current != section {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                errors.push(format!("line {}: expected `key = value`, found '{}'", ii+1, line));
+                continue;
+            };
+            let (key, value): (&str, &str) = (key.trim(), unquote(value));
+            match params.iter().find(|param| param.key == key) {
+                None => errors.push(format!("line {}: unknown key '{}'", ii+1, key)),
+                Some(_) if values.contains_key(key) => errors.push(format!("line {}: key '{}' is repeated", ii+1, key)),
+                Some(param) => {
+                    match param.convert(value) {
+                        Ok(value) => {
+                            if let Err(err) = param.validate(&value) {
+                                errors.push(format!("line {}: {}", ii+1, err));
+                            }
+                            values.insert(String::from(key), value);
+                        },
+                        Err(err) => {
+                            errors.push(format!("line {}: {}", ii+1, err));
+                            values.insert(String::from(key), String::from(value));
+                        },
+                    }
+                }
+            }
+        }
+        for param in params {
+            if values.contains_key(param.key) {
+                continue;
+            }
+            match param.default {
+                Some(default) => {
+                    values.insert(String::from(param.key), String::from(unquote(default)));
+                },
+                None => {
+                    let unit: String = if param.unit.is_empty() {String::new()} else {format!(" [{}]", param.unit)};
+                    errors.push(format!("missing key '{}': {}{}", param.key, param.help, unit));
+                },
+            }
+        }
+        if !errors.is_empty() {
+            let name: String = if section.is_empty() {String::new()} else {format!(" [{}]", section)};
+            return Err(format!("Invalid configuration {}{}:\n  {}", path, name, errors.join("\n  ")).into());
+        }
+        Ok(Config{path: String::from(path), values})
+    }
+
+    pub fn get<T: Value>(&self, key: &str) -> Result<T, Box<dyn Error>> {
+        let text: &String = self.values.get(key).ok_or(format!("{}: undeclared key '{}'", self.path, key))?;
+        T::parse(text).map_err(|err| format!("{}: key '{}': {}", self.path, key, err).into())
+    }
+}
+
+// Declares a struct of configuration keys with PARAMS, its declarations,
+// read(path), which checks a file without sections and fills the struct, and
+// read_section(path, section, sections).
+#[macro_export]
+macro_rules! config {
+    ($(#[$meta:meta])* $vis:vis struct $name:ident {
+        $($key:ident: $ty:ty $(= $default:expr)?, $unit:literal, $help:literal $(, min $min:literal)? $(, max $max:literal)?;)*
+    }) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $(pub $key: $ty,)*
+        }
+
+        impl $name {
+            pub const PARAMS: &'static [$crate::Param] = &[$($crate::Param{
+                key: stringify!($key),
+                default: $crate::config!(@option $(stringify!($default))?),
+                unit: $unit,
+                help: $help,
+                min: $crate::config!(@option $($min as f64)?),
+                max: $crate::config!(@option $($max as f64)?),
+                check: <$ty as $crate::Value>::check,
+            },)*];
+
+            pub fn read(path: &str) -> Result<$name, Box<dyn std::error::Error>> {
+                Self::read_section(path, "", &[])
+            }
+
+            pub fn read_section(path: &str, section: &str, sections: &[&str]) -> Result<$name, Box<dyn std::error::Error>> {
+                let config: $crate::Config = $crate::Config::read(path, section, Self::PARAMS, sections)?;
+                Ok($name{$($key: config.get(stringify!($key))?,)*})
+            }
+        }
+    };
+    (@option) => {None};
+    (@option $value:expr) => {Some($value)};
+}
+
+// Converts a positional input file (`value # index key: description`) into
+// a configuration file. Comments and blank lines are kept.
+pub fn convert_input(text: &str) -> Result<String, Box<dyn Error>> {
+    let mut lines: Vec<String> = Vec::new();
+    for (ii, line) in text.lines().enumerate() {
+        let trimmed: &str = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            lines.push(String::from(line.trim_end()));
+            continue;
+        }
+        let (value, comment): (&str, &str) = trimmed.split_once('#').unwrap_or((trimmed, ""));
+        let comment: &str = comment.trim().trim_start_matches(|c: char| c.is_ascii_digit()).trim();
+        let Some((key, help)) = comment.split_once(':') else {
+            return Err(format!("line {}: expected `value # index key: description`, found '{}'", ii+1, trimmed).into());
+        };
+        let key: String = key.trim().to_lowercase();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(format!("line {}: '{}' is not a key", ii+1, key).into());
         }
+        lines.push(format!("{:<14}= {:<10}# {}", key, value.trim(), help.trim()).trim_end().to_string());
     }
-    return input;
+    Ok(lines.join("\n") + "\n")
 }
 
 // -------- Time step --------
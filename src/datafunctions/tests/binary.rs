@@ -0,0 +1,100 @@
+// ------------------------------------------------------------------------- //
+// Binary files: the compact layout (save_data_bin / read_data_bin) and the  //
+// binary snapshots of Output, read back with read_output.                   //
+// ------------------------------------------------------------------------- //
+
+use std::{
+    env,
+    fs,
+    process,
+};
+
+use structures::{Particle, Star};
+
+fn scratch(name: &str) -> String {
+    let dir = env::temp_dir().join(format!("rusph-{}-{}", name, process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir.to_string_lossy().into_owned()
+}
+
+fn particles() -> Vec<Particle> {
+    (0..5).map(|ii| {
+        let a: f64 = ii as f64;
+        Particle{id: 100 + 7*ii, m: 0.5 + a, x: a, y: -a, z: 2.*a, vx: 0.1*a, vy: 0.2*a, vz: 0.3*a, h: 0.05 + a, u: 1.5*a,
+                 ..Default::default()}
+    }).collect()
+}
+
+#[test]
+fn round_trip() {
+    let path: String = datafunctions::output_file(&scratch("bin-round-trip"), "Ev_0");
+    let written: Vec<Particle> = particles();
+    datafunctions::save_data_bin(&path, &written).unwrap();
+
+    let mut read: Vec<Particle> = Vec::new();
+    datafunctions::read_data_bin(&path, &mut read).unwrap();
+    assert_eq!(read.len(), written.len());
+    for (a, b) in written.iter().zip(read.iter()) {
+        assert_eq!((a.id, a.m, a.x, a.y, a.z), (b.id, b.m, b.x, b.y, b.z));
+        assert_eq!((a.vx, a.vy, a.vz, a.h, a.u), (b.vx, b.vy, b.vz, b.h, b.u));
+    }
+}
+
+#[test]
+fn rejects_headerless_files() {
+    // The former layout: 8 f64 per particle and no header
+    let path: String = datafunctions::output_file(&scratch("bin-headerless"), "Ev_0");
+    fs::write(&path, vec![0_u8; 8*8*10]).unwrap();
+    let mut read: Vec<Particle> = Vec::new();
+    let err: String = datafunctions::read_data_bin(&path, &mut read).unwrap_err().to_string();
+    assert!(err.contains("no binary header"), "{}", err);
+}
+
+#[test]
+fn rejects_truncated_files() {
+    let path: String = datafunctions::output_file(&scratch("bin-truncated"), "Ev_0");
+    datafunctions::save_data_bin(&path, &particles()).unwrap();
+    let bytes: Vec<u8> = fs::read(&path).unwrap();
+    fs::write(&path, &bytes[..bytes.len() - 8]).unwrap();
+    let mut read: Vec<Particle> = Vec::new();
+    assert!(datafunctions::read_data_bin(&path, &mut read).is_err());
+}
+
+#[test]
+fn output_round_trip() {
+    // Binary snapshots written by Output keep the ptype, the density, the
+    // sinks and the header of the run, stamped with the time of the snapshot
+    let dir: String = scratch("bin-output");
+    let mut written: Vec<Particle> = particles();
+    for (ii, particle) in written.iter_mut().enumerate() {
+        particle.ptype = (ii%3) as u8;
+        particle.rho = 1.0 + ii as f64;
+    }
+    let mut output: datafunctions::Output = datafunctions::Output::new(&dir, "Ev_", 3, datafunctions::OutputFormat::Binary, 0, 0.0, None).unwrap();
+    output.header = datafunctions::Header{wd: 2.0, xper: true, gamma: 1.4, ..Default::default()};
+    let star: Star = Star{m: 1.0, x: 0.5, vy: 2.0, hacc: 0.1, ..Default::default()};
+    output.snapshot(0, 0.25, &written, &[&star]).unwrap();
+    output.finish(0.5, &written, &[&star]).unwrap();
+
+    let entries: Vec<datafunctions::ManifestEntry> = datafunctions::read_manifest(&dir).unwrap();
+    assert_eq!(entries.iter().map(|entry| entry.file.as_str()).collect::<Vec<&str>>(), vec!["Ev_000", "Fin_00"]);
+    let mut read: Vec<Particle> = Vec::new();
+    datafunctions::read_output(&datafunctions::output_file(&dir, "Ev_000"), &mut read).unwrap();
+    assert_eq!(read.len(), written.len());
+    for (a, b) in written.iter().zip(read.iter()) {
+        assert_eq!((a.ptype, a.id, a.m, a.rho, a.x, a.y, a.z), (b.ptype, b.id, b.m, b.rho, b.x, b.y, b.z));
+        assert_eq!((a.vx, a.vy, a.vz, a.h, a.u), (b.vx, b.vy, b.vz, b.h, b.u));
+    }
+    let mut stars: Vec<Star> = Vec::new();
+    let header: datafunctions::Header = datafunctions::read_snapshot(&datafunctions::output_file(&dir, "Fin_00"), &mut Vec::new(), &mut stars).unwrap();
+    assert_eq!((header.time, header.wd, header.xper, header.gamma), (0.5, 2.0, true, 1.4));
+    assert_eq!(stars.len(), 1);
+    assert_eq!((stars[0].m, stars[0].x, stars[0].vy, stars[0].hacc), (star.m, star.x, star.vy, star.hacc));
+
+    // Files of former runs are still read
+    let path: String = datafunctions::output_file(&dir, "Ev_old");
+    datafunctions::save_data_bin(&path, &written).unwrap();
+    read.clear();
+    datafunctions::read_output(&path, &mut read).unwrap();
+    assert_eq!(read.iter().map(|particle| particle.id).collect::<Vec<u64>>(), written.iter().map(|particle| particle.id).collect::<Vec<u64>>());
+}
@@ -0,0 +1,77 @@
+// ------------------------------------------------------------------------- //
+// Configuration files: typed keys with defaults, the errors of a file,      //
+// comments and units.                                                       //
+// ------------------------------------------------------------------------- //
+
+use datafunctions::{Config, OutputFormat};
+
+datafunctions::config! {
+    pub struct Input {
+        eta: f64 = 1.2,                  "",    "Dimensionless constant specifying the smoothing length", min 0.0;
+        nx: u32,                         "",    "Particle resolution in the x direction", min 1;
+        tf: f64,                         "T",   "Final time";
+        eos_type: bool,                  "",    "EoS (0=isoth[No u]; 1=adiab[u])";
+        max_walltime: f64 = 0.0,         "h",   "Hours after which the run checkpoints and stops (0=off)", min 0.0;
+        output: OutputFormat = "binary", "",    "Snapshot format (binary, csv or vtu)";
+        output_dir: String,              "",    "Directory of the run's files (created if needed)";
+    }
+}
+
+fn parse(text: &str) -> Result<Config, String> {
+    Config::parse("run.conf", text, "", Input::PARAMS, &[]).map_err(|err| err.to_string())
+}
+
+#[test]
+fn typed_values_and_defaults() {
+    let config: Config = parse("# Run\nnx = 32 # resolution\ntf = 0.5\neos_type = yes\n\noutput_dir = \"Run 1\"\n").unwrap();
+    assert_eq!(config.get::<u32>("nx").unwrap(), 32);
+    assert_eq!(config.get::<f64>("tf").unwrap(), 0.5);
+    assert!(config.get::<bool>("eos_type").unwrap());
+    assert_eq!(config.get::<String>("output_dir").unwrap(), "Run 1");
+    assert_eq!(config.get::<f64>("eta").unwrap(), 1.2);
+    assert_eq!(config.get::<OutputFormat>("output").unwrap(), OutputFormat::Binary);
+}
+
+#[test]
+fn reports_every_error() {
+    let err: String = parse("nx = 0\ntf = soon\neos_type = maybe\nnx = 4\nsteps = 10\noutput_dir\n").err().unwrap();
+    for expected in ["line 1: key 'nx': 0 is below the minimum 1",
+                     "line 2: key 'tf': 'soon' is not a number",
+                     "line 3: key 'eos_type': 'maybe' is not a boolean",
+                     "line 4: key 'nx' is repeated",
+                     "line 5: unknown key 'steps'",
+                     "line 6: expected `key = value`, found 'output_dir'",
+                     "missing key 'output_dir'"] {
+        assert!(err.contains(expected), "'{}' not in:\n{}", expected, err);
+    }
+}
+
+#[test]
+fn missing_keys_name_their_unit() {
+    let err: String = parse("nx = 8\neos_type = 0\noutput_dir = Run\n").err().unwrap();
+    assert!(err.contains("missing key 'tf': Final time [T]"), "{}", err);
+    assert!(!err.contains("'eta'"), "{}", err);
+}
+
+#[test]
+fn comments_start_a_line_or_follow_a_blank() {
+    let config: Config = parse("#nx = 4\nnx = 8\ntf = 1.0\neos_type = 0\noutput_dir = Runs/#1 # runs\n").unwrap();
+    assert_eq!(config.get::<u32>("nx").unwrap(), 8);
+    assert_eq!(config.get::<String>("output_dir").unwrap(), "Runs/#1");
+
+    let err: String = parse("nx = 8# no blank\ntf = 1.0\neos_type = 0\noutput_dir = Run\n").err().unwrap();
+    assert!(err.contains("line 1: key 'nx': '8# no blank' is not an integer"), "{}", err);
+}
+
+#[test]
+fn units() {
+    // The code units must match, wall-clock times are converted
+    let config: Config = parse("nx = 8\ntf = 2.0 T\neos_type = 0\noutput_dir = Run\nmax_walltime = 30 min\n").unwrap();
+    assert_eq!(config.get::<f64>("tf").unwrap(), 2.0);
+    assert_eq!(config.get::<f64>("max_walltime").unwrap(), 0.5);
+
+    let err: String = parse("nx = 8\ntf = 2.0 L\neos_type = 0\noutput_dir = Run\nmax_walltime = 2 days\n").err().unwrap();
+    assert!(err.contains("line 2: key 'tf': '2.0 L' is not in T"), "{}", err);
+    assert!(err.contains("line 5: key 'max_walltime': '2 days' is not in h"), "{}", err);
+    assert!(!err.contains("missing"), "{}", err);
+}
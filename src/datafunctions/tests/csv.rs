@@ -0,0 +1,42 @@
+// ------------------------------------------------------------------------- //
+// CSV snapshots: save_data / read_data.                                     //
+// ------------------------------------------------------------------------- //
+
+use std::{
+    env,
+    fs,
+    process,
+};
+
+use structures::Particle;
+
+fn scratch(name: &str) -> String {
+    let dir = env::temp_dir().join(format!("rusph-{}-{}", name, process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir.to_string_lossy().into_owned()
+}
+
+#[test]
+fn round_trip() {
+    let path: String = datafunctions::output_file(&scratch("csv-round-trip"), "Ev_0.csv");
+    let written: Vec<Particle> = (0..4).map(|ii| Particle{ptype: (ii%2) as u8, id: 3*ii, m: 0.25*(ii + 1) as f64, x: ii as f64, u: 2.0,
+                                                          ..Default::default()}).collect();
+    datafunctions::save_data(&path, &written).unwrap();
+
+    let mut read: Vec<Particle> = Vec::new();
+    datafunctions::read_data(&path, &mut read).unwrap();
+    assert_eq!(read.len(), written.len());
+    for (a, b) in written.iter().zip(read.iter()) {
+        assert_eq!((a.ptype, a.id, a.m, a.x, a.u), (b.ptype, b.id, b.m, b.x, b.u));
+    }
+}
+
+#[test]
+fn missing_mass_is_an_error() {
+    let path: String = datafunctions::output_file(&scratch("csv-no-mass"), "Ini_00.csv");
+    fs::write(&path, "ptype,x,y,z,vx,vy,vz,h,u\n0,0,0,0,0,0,0,0.1,1\n").unwrap();
+    let mut read: Vec<Particle> = Vec::new();
+    let err: String = datafunctions::read_data(&path, &mut read).unwrap_err().to_string();
+    assert!(err.contains("Missing column 'm'"), "{}", err);
+    assert!(read.is_empty());
+}
@@ -0,0 +1,145 @@
+// ------------------------------------------------------------------------- //
+// GADGET snapshots: save_gadget / read_gadget round trips in both formats,  //
+// the domain of the header, the particle types and the width of the ids.    //
+// ------------------------------------------------------------------------- //
+
+use std::{
+    env,
+    fs,
+    process,
+};
+
+use datafunctions::Header;
+use structures::{Particle, Star};
+
+fn scratch(name: &str) -> String {
+    let dir = env::temp_dir().join(format!("rusph-{}-{}", name, process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir.to_string_lossy().into_owned()
+}
+
+// Gas of one mass, in single precision values, and two sinks of different
+// masses, which need the MASS block
+fn particles() -> Vec<Particle> {
+    (0..5).map(|ii| {
+        let a: f64 = ii as f64;
+        Particle{id: 20 + 3*ii, m: 0.25, x: 0.5*a, y: 1.0 - a, z: 0.25*a, vx: 0.125*a, vy: -0.5*a, vz: 2.0,
+                 h: 0.5 + 0.25*a, u: 1.5 + a, rho: 2.0 + 0.5*a, ..Default::default()}
+    }).collect()
+}
+
+fn stars() -> Vec<Star> {
+    vec![Star{m: 1.0, x: -1.0, y: 0.5, z: 3.0, vx: 0.25, ..Default::default()},
+         Star{m: 0.5, x: 2.0, y: -2.5, z: 0.0, vy: -0.75, ..Default::default()}]
+}
+
+fn round_trip(format: u8) {
+    let path: String = datafunctions::output_file(&scratch(&format!("gadget-{}", format)), "snap_000");
+    let written: Vec<Particle> = particles();
+    let sinks: Vec<Star> = stars();
+    datafunctions::save_gadget(&path, &Header{time: 0.75, ..Default::default()}, format, &written, &sinks.iter().collect::<Vec<&Star>>()).unwrap();
+
+    let mut read: Vec<Particle> = Vec::new();
+    let mut read_sinks: Vec<Star> = Vec::new();
+    let head: Header = datafunctions::read_gadget(&path, &mut read, &mut read_sinks).unwrap();
+    assert_eq!(read.len(), written.len());
+    for (a, b) in written.iter().zip(read.iter()) {
+        assert_eq!((b.ptype, a.id, a.m, a.x, a.y, a.z), (0, b.id, b.m, b.x, b.y, b.z));
+        assert_eq!((a.vx, a.vy, a.vz, a.h, a.u, a.rho), (b.vx, b.vy, b.vz, b.h, b.u, b.rho));
+    }
+    assert_eq!(read_sinks.len(), sinks.len());
+    for (a, b) in sinks.iter().zip(read_sinks.iter()) {
+        assert_eq!((a.m, a.x, a.y, a.z, a.vx, a.vy, a.vz), (b.m, b.x, b.y, b.z, b.vx, b.vy, b.vz));
+    }
+
+    // Not periodic: the bounding box of the gas and the sinks
+    assert_eq!((head.time, head.dm, head.xper), (0.75, 0.25, false));
+    assert_eq!((head.x0, head.y0, head.z0), (-1.0, -3.0, 0.0));
+    assert_eq!((head.wd, head.lg, head.hg), (3.0, 4.0, 3.0));
+}
+
+#[test]
+fn round_trip_format_1() {
+    round_trip(1);
+}
+
+#[test]
+fn round_trip_format_2() {
+    round_trip(2);
+}
+
+#[test]
+fn periodic_box_from_the_origin() {
+    let path: String = datafunctions::output_file(&scratch("gadget-box"), "snap_000");
+    let header: Header = Header{wd: 4.0, xper: true, yper: true, zper: true, ..Default::default()};
+    datafunctions::save_gadget(&path, &header, 2, &particles(), &[]).unwrap();
+    let head: Header = datafunctions::read_gadget(&path, &mut Vec::new(), &mut Vec::new()).unwrap();
+    assert_eq!((head.x0, head.y0, head.z0, head.wd, head.lg, head.hg), (0.0, 0.0, 0.0, 4.0, 4.0, 4.0));
+    assert!(head.xper && head.yper && head.zper);
+}
+
+#[test]
+fn rejects_boundary_particles() {
+    let path: String = datafunctions::output_file(&scratch("gadget-boundary"), "snap_000");
+    let mut written: Vec<Particle> = particles();
+    written[1].ptype = 1;
+    let err: String = datafunctions::save_gadget(&path, &Header::default(), 1, &written, &[]).unwrap_err().to_string();
+    assert!(err.contains("particle 23"), "{}", err);
+}
+
+#[test]
+fn keeps_collisionless_particles() {
+    // The gas of a format 1 file relabelled as type 1 (dark matter): the
+    // header follows the 4 bytes of its record length
+    let path: String = datafunctions::output_file(&scratch("gadget-collisionless"), "snap_000");
+    let written: Vec<Particle> = particles();
+    datafunctions::save_gadget(&path, &Header::default(), 1, &written, &[]).unwrap();
+    let mut bytes: Vec<u8> = fs::read(&path).unwrap();
+    bytes.copy_within(4..8, 8);
+    bytes[4..8].copy_from_slice(&[0; 4]);
+    bytes.copy_within(28..36, 36);
+    bytes[28..36].copy_from_slice(&[0; 8]);
+    fs::write(&path, bytes).unwrap();
+    let mut read: Vec<Particle> = Vec::new();
+    datafunctions::read_gadget(&path, &mut read, &mut Vec::new()).unwrap();
+    assert_eq!(read.len(), written.len());
+    for (a, b) in written.iter().zip(read.iter()) {
+        assert_eq!((b.ptype, a.id, a.m, a.x, a.y, a.z), (5, b.id, b.m, b.x, b.y, b.z));
+    }
+
+    // and written back as type 1, after the gas
+    let mut mixed: Vec<Particle> = particles();
+    mixed.extend(read);
+    for (ii, particle) in mixed.iter_mut().enumerate().skip(5) {
+        particle.id = 100 + ii as u64;
+    }
+    datafunctions::save_gadget(&path, &Header::default(), 2, &mixed, &[]).unwrap();
+    let mut read: Vec<Particle> = Vec::new();
+    datafunctions::read_gadget(&path, &mut read, &mut Vec::new()).unwrap();
+    assert_eq!(read.iter().map(|p| (p.ptype, p.id)).collect::<Vec<(u8, u64)>>(), mixed.iter().map(|p| (p.ptype, p.id)).collect::<Vec<(u8, u64)>>());
+}
+
+#[test]
+fn long_ids() {
+    // An id beyond 32 bits makes the ID block 64-bit
+    let path: String = datafunctions::output_file(&scratch("gadget-long-ids"), "snap_000");
+    let mut written: Vec<Particle> = particles();
+    written[2].id = u32::MAX as u64 + 7;
+    datafunctions::save_gadget(&path, &Header::default(), 2, &written, &stars().iter().collect::<Vec<&Star>>()).unwrap();
+    let mut read: Vec<Particle> = Vec::new();
+    datafunctions::read_gadget(&path, &mut read, &mut Vec::new()).unwrap();
+    assert_eq!(read.iter().map(|p| p.id).collect::<Vec<u64>>(), written.iter().map(|p| p.id).collect::<Vec<u64>>());
+}
+
+#[test]
+fn sink_ids_follow_the_gas() {
+    // Format 1: the ID block comes after the records of HEAD, POS and VEL,
+    // each framed by 8 bytes, and holds the 5 gas particles first
+    let path: String = datafunctions::output_file(&scratch("gadget-ids"), "snap_000");
+    datafunctions::save_gadget(&path, &Header::default(), 1, &particles(), &stars().iter().collect::<Vec<&Star>>()).unwrap();
+    let bytes: Vec<u8> = fs::read(&path).unwrap();
+    let start: usize = (8 + 256) + 2*(8 + 7*3*4) + 4 + 5*4;
+    let ids: Vec<u32> = bytes[start..start+8].chunks_exact(4).map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap())).collect();
+    // The largest gas id is 32
+    assert_eq!(ids, vec![33, 34]);
+}
@@ -0,0 +1,102 @@
+// ------------------------------------------------------------------------- //
+// Output streams: the VTU snapshots read back with read_output and indexed  //
+// by Evolution.pvd, and the lines of Diagnostics.csv by steps and by time.  //
+// ------------------------------------------------------------------------- //
+
+use std::{
+    env,
+    fs,
+    process,
+};
+
+use structures::Particle;
+
+fn scratch(name: &str) -> String {
+    let dir = env::temp_dir().join(format!("rusph-{}-{}", name, process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir.to_string_lossy().into_owned()
+}
+
+fn particles() -> Vec<Particle> {
+    (0..5).map(|ii| {
+        let a: f64 = ii as f64;
+        Particle{ptype: (ii%2) as u8, id: 100 + 7*ii, m: 0.5 + a, rho: 1.0/3.0 + a, x: a, y: -a, z: 2.*a, vx: 0.1*a, vy: 0.2*a, vz: 0.3*a, h: 0.05 + a, u: 1.5*a,
+                 ..Default::default()}
+    }).collect()
+}
+
+#[test]
+fn vtu_round_trip() {
+    let dir: String = scratch("vtu-output");
+    let written: Vec<Particle> = particles();
+    let mut output: datafunctions::Output = datafunctions::Output::new(&dir, "Ev_", 0, datafunctions::OutputFormat::Vtu, 0, 0.0, None).unwrap();
+    output.snapshot(0, 0.25, &written, &[]).unwrap();
+    output.finish(0.5, &written, &[]).unwrap();
+
+    let mut read: Vec<Particle> = Vec::new();
+    datafunctions::read_output(&datafunctions::output_file(&dir, "Ev_0.vtu"), &mut read).unwrap();
+    fs::remove_dir_all(&dir).ok();
+    assert_eq!(read.len(), written.len());
+    for (a, b) in written.iter().zip(read.iter()) {
+        assert_eq!((a.ptype, a.id, a.m, a.rho, a.x, a.y, a.z), (b.ptype, b.id, b.m, b.rho, b.x, b.y, b.z));
+        assert_eq!((a.vx, a.vy, a.vz, a.h, a.u), (b.vx, b.vy, b.vz, b.h, b.u));
+    }
+}
+
+#[test]
+fn pvd_follows_the_snapshots() {
+    let dir: String = scratch("vtu-pvd");
+    let particles: Vec<Particle> = particles();
+    let files = |dir: &str| -> Vec<(f64, String)> {
+        let text: String = fs::read_to_string(datafunctions::output_file(dir, "Evolution.pvd")).unwrap();
+        text.lines().filter(|line| line.contains("<DataSet")).map(|line| {
+            let field = |key: &str| -> String {line.split(&format!("{}=\"", key)).nth(1).unwrap().split('"').next().unwrap().to_string()};
+            (field("timestep").parse::<f64>().unwrap(), field("file"))
+        }).collect()
+    };
+    let mut output: datafunctions::Output = datafunctions::Output::new(&dir, "Ev_", 2, datafunctions::OutputFormat::Vtu, 0, 0.0, None).unwrap();
+    // Indexed from the first snapshot on, as a run may stop at any point
+    output.snapshot(0, 0.25, &particles, &[]).unwrap();
+    assert_eq!(files(&dir), vec![(0.25, String::from("Ev_00.vtu"))]);
+    output.snapshot(1, 0.5, &particles, &[]).unwrap();
+    output.finish(0.6, &particles, &[]).unwrap();
+    let listed: Vec<(f64, String)> = files(&dir);
+    fs::remove_dir_all(&dir).ok();
+    assert_eq!(listed, vec![(0.25, String::from("Ev_00.vtu")), (0.5, String::from("Ev_01.vtu")), (0.6, String::from("Fin_00.vtu"))]);
+}
+
+#[test]
+fn vtu_rejects_files_without_ids() {
+    // The former layout, without the id and m arrays
+    let dir: String = scratch("vtu-old");
+    let path: String = datafunctions::output_file(&dir, "Ev_0.vtu");
+    datafunctions::save_vtu(&path, &particles()).unwrap();
+    let text: String = fs::read_to_string(&path).unwrap().replace("Name=\"id\"", "Name=\"other\"");
+    fs::write(&path, text).unwrap();
+    let mut read: Vec<Particle> = Vec::new();
+    let err: String = datafunctions::read_output(&path, &mut read).unwrap_err().to_string();
+    fs::remove_dir_all(&dir).ok();
+    assert!(err.contains("no id array"), "{}", err);
+}
+
+#[test]
+fn diagnostics_by_steps_and_time() {
+    let dir: String = scratch("diagnostics");
+    let particles: Vec<Particle> = particles();
+    // Every 4 steps and every 0.25 of time, with dt = 0.1
+    let mut output: datafunctions::Output = datafunctions::Output::new(&dir, "Ev_", 0, datafunctions::OutputFormat::Csv, 4, 0.25, None).unwrap();
+    for it in 1..=10 {
+        output.step(it, 0.1*it as f64, 0.1, &particles).unwrap();
+    }
+    drop(output);
+    let text: String = fs::read_to_string(datafunctions::output_file(&dir, "Diagnostics.csv")).unwrap();
+    fs::remove_dir_all(&dir).ok();
+
+    let its: Vec<u32> = text.lines().skip(1).map(|line| line.split(',').nth(1).unwrap().parse::<u32>().unwrap()).collect();
+    // t reaches 0.25, 0.5, 0.75 and 1 at the steps 3, 5, 8 and 10
+    assert_eq!(its, vec![3, 4, 5, 8, 10]);
+    // The gas is the particles of ptype 0: ids 100, 114 and 128
+    let last: Vec<&str> = text.lines().last().unwrap().split(',').collect();
+    assert_eq!(last[3], "3");
+    assert_eq!(last[4].parse::<f64>().unwrap(), 0.5 + 2.5 + 4.5);
+}
@@ -0,0 +1,72 @@
+// ------------------------------------------------------------------------- //
+// Phantom full dumps: save_phantom / read_phantom round trip, and the one   //
+// mass per type of the format.                                              //
+// ------------------------------------------------------------------------- //
+
+use std::{
+    env,
+    fs,
+    process,
+};
+
+use datafunctions::Header;
+use structures::{Particle, Star};
+
+fn scratch(name: &str) -> String {
+    let dir = env::temp_dir().join(format!("rusph-{}-{}", name, process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir.to_string_lossy().into_owned()
+}
+
+// Four gas particles, one of them in the open buffer, and two boundary ones
+fn particles() -> Vec<Particle> {
+    (0..6).map(|ii| {
+        let a: f64 = ii as f64;
+        let ptype: u8 = match ii {3 => 4, 4 | 5 => 1, _ => 0};
+        Particle{ptype, id: 10 + ii, m: if ptype == 1 {2.0} else {0.25}, x: a, y: -a, z: 0.5*a,
+                 vx: 0.1*a, vy: 0.2*a, vz: 0.3*a, h: 0.5 + 0.125*a, u: 1.5 + a, ..Default::default()}
+    }).collect()
+}
+
+fn stars() -> Vec<Star> {
+    vec![Star{m: 1.0, x: 0.1, y: 0.2, z: 0.3, hacc: 0.5, vx: -0.1, vy: 0.4, vz: 0.0, sx: 0.0, sy: 0.0, sz: 0.7, ..Default::default()},
+         Star{m: 0.01, x: 5.0, y: 0.0, z: 0.0, hacc: 0.1, vy: 0.45, ..Default::default()}]
+}
+
+#[test]
+fn round_trip() {
+    let path: String = datafunctions::output_file(&scratch("phantom-round-trip"), "Fin_00.phantom");
+    let header: Header = Header{time: 12.5, eos_t: true, gamma: 1.4, coeff: 0.0025, ..Default::default()};
+    let written: Vec<Particle> = particles();
+    let sinks: Vec<Star> = stars();
+    datafunctions::save_phantom(&path, &header, 1.2, 1.496e13, 1.989e33, &written, &sinks.iter().collect::<Vec<&Star>>()).unwrap();
+
+    let mut read: Vec<Particle> = Vec::new();
+    let mut read_sinks: Vec<Star> = Vec::new();
+    let dump: Header = datafunctions::read_phantom(&path, &mut read, &mut read_sinks).unwrap();
+    assert_eq!((dump.time, dump.gamma, dump.coeff, dump.eos_t), (12.5, 1.4, 0.0025, true));
+
+    // The open buffer is gas for Phantom, and h is single precision
+    assert_eq!(read.len(), written.len());
+    for (a, b) in written.iter().zip(read.iter()) {
+        assert_eq!(b.ptype, if a.ptype == 1 {1} else {0});
+        assert_eq!((a.id, a.m, a.x, a.y, a.z), (b.id, b.m, b.x, b.y, b.z));
+        assert_eq!((a.vx, a.vy, a.vz, a.u), (b.vx, b.vy, b.vz, b.u));
+        assert_eq!(b.h, a.h as f32 as f64);
+        assert!((b.rho - a.m*(1.2/a.h).powi(3)).abs() < 1e-6*b.rho);
+    }
+    assert_eq!(read_sinks.len(), sinks.len());
+    for (a, b) in sinks.iter().zip(read_sinks.iter()) {
+        assert_eq!((a.m, a.x, a.y, a.z, a.hacc), (b.m, b.x, b.y, b.z, b.hacc));
+        assert_eq!((a.vx, a.vy, a.vz, a.sx, a.sy, a.sz), (b.vx, b.vy, b.vz, b.sx, b.sy, b.sz));
+    }
+}
+
+#[test]
+fn rejects_several_masses_per_type() {
+    let path: String = datafunctions::output_file(&scratch("phantom-masses"), "Fin_00.phantom");
+    let mut written: Vec<Particle> = particles();
+    written[2].m = 0.5;
+    let err: String = datafunctions::save_phantom(&path, &Header::default(), 1.2, 1.496e13, 1.989e33, &written, &[]).unwrap_err().to_string();
+    assert!(err.contains("particle 12"), "{}", err);
+}
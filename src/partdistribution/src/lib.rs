@@ -4,9 +4,23 @@ use rand::Rng;
 
 const G: f64    = 1.0;
 
-use structures::Particle;
-
-use sphfunctions::h_from_density;
+use structures::{
+    Particle,
+    Node,
+    Pointer,
+    Star,
+};
+
+use tree_algorithm::{
+    FindNeighbors,
+    BuildTree,
+};
+
+use sphfunctions::{
+    h_from_density,
+    periodic_rel_vector,
+    remove_dead_particles,
+};
 
 use std::f64::consts::PI;
 const PI2:f64 = PI/2.0;
@@ -29,7 +43,7 @@ pub fn init_dist_random(
         xp = x0 + wd*rng.gen::<f64>();
         yp = y0 + lg*rng.gen::<f64>();
         zp = z0 + hg*rng.gen::<f64>();
-        particles.push(Particle{x:xp, y:yp, z:zp, h:hp,
+        particles.push(Particle{id:particles.len() as u64, m:dm, x:xp, y:yp, z:zp, h:hp,
             ..Default::default()});
     }
 }
@@ -60,7 +74,7 @@ pub fn init_dist_cubic(
             yp = ystart + dx*jj as f64;
             for ii in 0..nx {
                 xp = xstart + dx*ii as f64;
-                particles.push(Particle{x:xp, y:yp, z:zp, h:hp,
+                particles.push(Particle{id:particles.len() as u64, m:dm, x:xp, y:yp, z:zp, h:hp,
                                         ..Default::default()});
             }
         }
@@ -118,7 +132,7 @@ pub fn init_dist_hcp(
             yp = ystart + dy*jj as f64;
             for ii in 0..nxnew {
                 xp = xstart + dx*ii as f64;
-                particles.push(Particle{x:xp, y:yp, z:zp, h:hp,
+                particles.push(Particle{id:particles.len() as u64, m:dmp, x:xp, y:yp, z:zp, h:hp,
                                         ..Default::default()});
             }
         }
@@ -239,9 +253,9 @@ pub fn init_dist_disc1(
         yp = r*(phi.sin()+(phi-PI2).cos())/2.0;
         zp = z;
         hp  = h_from_density(dm, rho, eta);
-        particles.push(Particle{x:xp, y:yp, z:zp, h:hp,
+        particles.push(Particle{id:particles.len() as u64, m:dm, x:xp, y:yp, z:zp, h:hp,
             ..Default::default()});
-        particles.push(Particle{x:-xp, y:-yp, z:-zp, h:hp,
+        particles.push(Particle{id:particles.len() as u64, m:dm, x:-xp, y:-yp, z:-zp, h:hp,
             ..Default::default()});
         }
 }
@@ -279,7 +293,7 @@ pub fn init_dist_disc_velocities(
 }
 
 pub fn com_frame(
-    particles: &mut Vec<Particle>, n: u32, dm: f64,
+    particles: &mut Vec<Particle>, n: u32,
     x_c: f64, y_c:f64, z_c:f64, vx0: f64, vy0: f64, vz0: f64
 ) {
     let mut xcm     : f64 = 0.0;
@@ -292,13 +306,14 @@ pub fn com_frame(
     let invtmass: f64;
 
     for ii in 0..n as usize {
-        totmass += dm;
-        xcm += particles[ii].x * dm;
-        ycm += particles[ii].y * dm;
-        zcm += particles[ii].z * dm;
-        vxcm += particles[ii].vx * dm;
-        vycm += particles[ii].vy * dm;
-        vzcm += particles[ii].vz * dm;
+        let m: f64 = particles[ii].m;
+        totmass += m;
+        xcm += particles[ii].x * m;
+        ycm += particles[ii].y * m;
+        zcm += particles[ii].z * m;
+        vxcm += particles[ii].vx * m;
+        vycm += particles[ii].vy * m;
+        vzcm += particles[ii].vz * m;
     }
 
     invtmass= 1./totmass;
@@ -317,4 +332,244 @@ pub fn com_frame(
         particles[ii].vy += vy0 - vycm;  
         particles[ii].vz += vz0 - vzcm;  
     }
-}
\ No newline at end of file
+}
+
+// -------- Adaptive resolution --------
+
+// ------------------------------------------------------------------------- //
+// Offsets of the 13 particles in an hcp cluster of spacing dx: the central  //
+// particle, its 6 neighbours in the same plane and 3 in each of the planes  //
+// above and below. The geometry is the same as in init_dist_hcp, and the    //
+// offsets add up to zero so the centre of mass is kept.                     //
+// ------------------------------------------------------------------------- //
+pub fn hcp_cluster(
+    dx: f64
+) -> Vec<(f64, f64, f64)> {
+    let dy: f64 = (0.75_f64).sqrt()*dx;
+    let dz: f64 = (2./3.0_f64).sqrt()*dx;
+    let dxp: f64= 0.5*dx;
+    let dyp: f64= dy/3.;
+
+    let mut offsets: Vec<(f64, f64, f64)> = vec![(0.0, 0.0, 0.0)];
+    // Same plane
+    offsets.push(( dx, 0.0, 0.0));
+    offsets.push((-dx, 0.0, 0.0));
+    offsets.push(( dxp,  dy, 0.0));
+    offsets.push((-dxp,  dy, 0.0));
+    offsets.push(( dxp, -dy, 0.0));
+    offsets.push((-dxp, -dy, 0.0));
+    // Planes above and below
+    for zp in [dz, -dz] {
+        offsets.push(( dxp, dyp, zp));
+        offsets.push((-dxp, dyp, zp));
+        offsets.push((0.0, -2.0*dyp, zp));
+    }
+    return offsets;
+}
+
+// ------------------------------------------------------------------------- //
+// Jeans length of a gas particle with density rho and sound speed cs:       //
+//      lambda_J = sqrt(pi*cs^2/(G*rho))                                     //
+// ------------------------------------------------------------------------- //
+pub fn jeans_length(
+    rho: f64, cs: f64
+) -> f64 {
+    return (PI*cs*cs/(G*rho)).sqrt();
+}
+
+// ------------------------------------------------------------------------- //
+// Refinement criteria for split_particles and merge_particles               //
+// jeans_criterion: the Jeans length is resolved by fewer than nj smoothing  //
+// lengths, lambda_J < nj*h. cs gives the sound speed of a particle.         //
+// sink_criterion: the particle is closer than r to any of the sinks.        //
+// ------------------------------------------------------------------------- //
+pub fn jeans_criterion(
+    nj: f64, cs: impl Fn(&Particle) -> f64
+) -> impl Fn(&Particle) -> bool {
+    return move |particle: &Particle| jeans_length(particle.rho, cs(particle)) < nj*particle.h;
+}
+
+pub fn sink_criterion<'a>(
+    stars: &'a [&'a Star], r: f64
+) -> impl Fn(&Particle) -> bool + 'a {
+    return move |particle: &Particle| stars.iter().any(|star| {
+        let dx: f64 = particle.x - star.x;
+        let dy: f64 = particle.y - star.y;
+        let dz: f64 = particle.z - star.z;
+        dx*dx + dy*dy + dz*dz < r*r
+    });
+}
+
+// ------------------------------------------------------------------------- //
+// Particle splitting                                                        //
+// Every gas particle satisfying the criterion is replaced by 13 children    //
+// placed in an hcp cluster that fills the volume of the parent, m/rho.      //
+// Each child gets m/13 and h/13^(1/3), and keeps the velocity and internal  //
+// energy of the parent, so mass, momentum and energy are conserved.         //
+// Children leaving the domain are wrapped on periodic axes (taking the      //
+// shearing box offsets y_shear and vy_shear across x, as periodic_boundary) //
+// and clipped to the walls otherwise.                                       //
+// The parent becomes the central child and keeps its id; the rest are      //
+// appended with new ids. The number of particles and the pointer are        //
+// updated, the tree is restarted and the number of split particles is      //
+// returned.                                                                 //
+// ------------------------------------------------------------------------- //
+pub fn split_particles(
+    particles: &mut Vec<Particle>, n: &mut usize, ptr: &mut Pointer, tree: &mut Node,
+    wd: f64, lg: f64, hg: f64, x0:f64, y0: f64, z0: f64, xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64, vy_shear: f64,
+    criterion: impl Fn(&Particle) -> bool
+) -> usize {
+    let nchild: f64 = 13.0;
+    let mut next_id: u64 = particles.iter().map(|particle| particle.id + 1).max().unwrap_or(0);
+    let mut nsplit: usize = 0;
+
+    for ii in 0..*n {
+        if particles[ii].ptype != 0 || !criterion(&particles[ii]) {
+            continue;
+        }
+        let m: f64 = particles[ii].m/nchild;
+        // Spacing of an hcp lattice with volume m/rho per particle
+        let dx: f64 = (2.0_f64.sqrt()*m/particles[ii].rho).cbrt();
+        let offsets = hcp_cluster(dx);
+
+        particles[ii].m = m;
+        particles[ii].h = particles[ii].h/nchild.cbrt();
+        for (dxc, dyc, dzc) in offsets.into_iter().skip(1) {
+            let mut child: Particle = particles[ii].clone();
+            child.id = next_id;
+            child.x += dxc;
+            child.y += dyc;
+            child.z += dzc;
+            keep_in_domain(&mut child, wd, lg, hg, x0, y0, z0, xperiodic, yperiodic, zperiodic, y_shear, vy_shear);
+            particles.push(child);
+            next_id += 1;
+        }
+        nsplit += 1;
+    }
+
+    *n = particles.len();
+    *ptr = Pointer(particles.as_mut_ptr());
+    tree.restart(*n);
+    return nsplit;
+}
+
+fn keep_in_domain(
+    particle: &mut Particle, wd: f64, lg: f64, hg: f64, x0:f64, y0: f64, z0: f64,
+    xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64, vy_shear: f64
+) {
+    if xperiodic {
+        if particle.x >= x0 + wd {
+            particle.x -= wd;
+            particle.y -= y_shear;
+            particle.vy -= vy_shear;
+            particle.vy_star -= vy_shear;
+        } else if particle.x < x0 {
+            particle.x += wd;
+            particle.y += y_shear;
+            particle.vy += vy_shear;
+            particle.vy_star += vy_shear;
+        }
+    }
+    particle.x = wrap_or_clip(particle.x, x0, wd, xperiodic);
+    particle.y = wrap_or_clip(particle.y, y0, lg, yperiodic);
+    particle.z = wrap_or_clip(particle.z, z0, hg, zperiodic);
+}
+
+fn wrap_or_clip(
+    x: f64, x0: f64, wd: f64, periodic: bool
+) -> f64 {
+    if periodic {
+        return x0 + (x - x0).rem_euclid(wd);
+    }
+    return x.clamp(x0, x0 + wd);
+}
+
+// ------------------------------------------------------------------------- //
+// Particle merging                                                          //
+// Every gas particle satisfying the criterion is merged with its closest    //
+// neighbour (within rkern*h) that also satisfies it. The merged particle    //
+// sits at the centre of mass with the centre of mass velocity. The kinetic  //
+// energy lost in the relative motion goes into the internal energy, so      //
+// mass, momentum and energy are conserved. The volume, h^3, is added up.    //
+// Pairs across the periodic boundaries use the image of the partner (with   //
+// vy_shear across x in a shearing box), and the merged particle is wrapped  //
+// back into the domain.                                                     //
+// The tree must have been built for the current positions. The absorbed    //
+// particles are removed, the tree is restarted and the number of merges is  //
+// returned.                                                                 //
+// ------------------------------------------------------------------------- //
+pub fn merge_particles(
+    particles: &mut Vec<Particle>, n: &mut usize, ptr: &mut Pointer, tree: &mut Node, s_: i32, rkern: f64,
+    wd: f64, lg: f64, hg: f64, x0:f64, y0: f64, z0: f64, xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64, vy_shear: f64,
+    criterion: impl Fn(&Particle) -> bool
+) -> usize {
+    let mut merged: Vec<bool> = vec![false; *n];
+    let mut nmerge: usize = 0;
+
+    for ii in 0..*n {
+        if particles[ii].ptype != 0 || merged[ii] || !criterion(&particles[ii]) {
+            continue;
+        }
+        let hrkern: f64 = rkern*particles[ii].h;
+        let mut neighbors: Vec<usize> = Vec::new();
+        tree.find_neighbors(ii, s_, particles, &mut neighbors, wd, lg, hg, x0, y0, z0, hrkern, xperiodic, yperiodic, zperiodic, y_shear);
+
+        // Closest available partner
+        let mut partner: Option<usize> = None;
+        let mut r2_min: f64 = f64::MAX;
+        for jj in neighbors {
+            if jj == ii || particles[jj].ptype != 0 || merged[jj] || !criterion(&particles[jj]) {
+                continue;
+            }
+            let (dx, dy, dz) = periodic_rel_vector(&particles[jj], &particles[ii], wd, lg, hg, hrkern, xperiodic, yperiodic, zperiodic, y_shear);
+            let r2: f64 = dx*dx + dy*dy + dz*dz;
+            if r2 < r2_min {
+                r2_min = r2;
+                partner = Some(jj);
+            }
+        }
+        let jj: usize = match partner {
+            Some(jj) => jj,
+            None => continue,
+        };
+
+        let (dx, dy, dz) = periodic_rel_vector(&particles[jj], &particles[ii], wd, lg, hg, hrkern, xperiodic, yperiodic, zperiodic, y_shear);
+        let mi: f64 = particles[ii].m;
+        let mj: f64 = particles[jj].m;
+        let m: f64  = mi + mj;
+        let fj: f64 = mj/m;
+        // Number of times the partner's image is shifted by wd in x
+        let kx: f64 = if xperiodic {((particles[jj].x - particles[ii].x - dx)/wd).round()} else {0.0};
+        let dvx: f64 = particles[jj].vx - particles[ii].vx;
+        let dvy: f64 = particles[jj].vy - kx*vy_shear - particles[ii].vy;
+        let dvz: f64 = particles[jj].vz - particles[ii].vz;
+        let de: f64  = 0.5*mi*mj/m*(dvx*dvx + dvy*dvy + dvz*dvz);
+
+        let pj: Particle = particles[jj].clone();
+        let pi: &mut Particle = &mut particles[ii];
+        pi.x  += fj*dx;
+        pi.y  += fj*dy;
+        pi.z  += fj*dz;
+        pi.vx += fj*dvx;
+        pi.vy += fj*dvy;
+        pi.vz += fj*dvz;
+        pi.ax  = (mi*pi.ax + mj*pj.ax)/m;
+        pi.ay  = (mi*pi.ay + mj*pj.ay)/m;
+        pi.az  = (mi*pi.az + mj*pj.az)/m;
+        pi.u   = (mi*pi.u + mj*pj.u + de)/m;
+        pi.du  = (mi*pi.du + mj*pj.du)/m;
+        pi.rho = (mi*pi.rho + mj*pj.rho)/m;
+        pi.h   = (pi.h.powi(3) + pj.h.powi(3)).cbrt();
+        pi.m   = m;
+        keep_in_domain(pi, wd, lg, hg, x0, y0, z0, xperiodic, yperiodic, zperiodic, y_shear, vy_shear);
+
+        particles[jj].ptype = 2;
+        merged[ii] = true;
+        merged[jj] = true;
+        nmerge += 1;
+    }
+
+    remove_dead_particles(particles, n, ptr);
+    tree.restart(*n);
+    return nmerge;
+}
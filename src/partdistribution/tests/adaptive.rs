@@ -0,0 +1,124 @@
+// ------------------------------------------------------------------------- //
+// Adaptive resolution: split_particles / merge_particles.                   //
+// ------------------------------------------------------------------------- //
+
+use std::{
+    collections::HashSet,
+    f64::consts::PI,
+};
+
+use structures::{
+    Particle,
+    Node,
+    Pointer,
+    Star,
+};
+
+use tree_algorithm::BuildTree;
+
+const X0: f64 = -0.5;
+const WD: f64 = 1.0;
+
+// Mass, momentum and total (kinetic + internal) energy
+fn totals(particles: &[Particle]) -> [f64; 5] {
+    let mut sum: [f64; 5] = [0.0; 5];
+    for p in particles.iter().filter(|p| p.ptype == 0) {
+        sum[0] += p.m;
+        sum[1] += p.m*p.vx;
+        sum[2] += p.m*p.vy;
+        sum[3] += p.m*p.vz;
+        sum[4] += p.m*(0.5*(p.vx*p.vx + p.vy*p.vy + p.vz*p.vz) + p.u);
+    }
+    return sum;
+}
+
+fn assert_conserved(before: [f64; 5], after: [f64; 5]) {
+    for (a, b) in before.iter().zip(after.iter()) {
+        assert!((a - b).abs() < 1e-12*(1.0 + a.abs()), "{:?} -> {:?}", before, after);
+    }
+}
+
+fn shearing_gas() -> Vec<Particle> {
+    let mut particles: Vec<Particle> = Vec::new();
+    partdistribution::init_dist_hcp(&mut particles, 6, 1.0, 1.2, WD, WD, WD, X0, X0, X0);
+    for p in particles.iter_mut() {
+        p.vx = (2.0*PI*p.y).sin();
+        p.vy = (2.0*PI*p.z).cos();
+        p.vz = 0.5*p.x;
+        p.u  = 1.0 + p.x;
+    }
+    return particles;
+}
+
+fn build(particles: &Vec<Particle>, n: usize) -> Node {
+    let mut tree: Node = <Node as BuildTree>::new(n as i32, X0, X0, X0, WD, WD, WD);
+    tree.build_tree(4, 0.5, 0.5, particles, 1e-02);
+    return tree;
+}
+
+fn inside(p: &Particle) -> bool {
+    return [p.x, p.y, p.z].iter().all(|&x| x >= X0 && x <= X0 + WD);
+}
+
+#[test]
+fn split_and_merge_conserve_mass_momentum_and_energy() {
+    let mut particles: Vec<Particle> = shearing_gas();
+    let mut n: usize = particles.len();
+    let n0: usize = n;
+    let m0: f64 = particles[0].m;
+    let mut ptr: Pointer = Pointer(particles.as_mut_ptr());
+    let mut tree: Node = build(&particles, n);
+    let before: [f64; 5] = totals(&particles);
+
+    let nsplit: usize = partdistribution::split_particles(&mut particles, &mut n, &mut ptr, &mut tree, WD, WD, WD, X0, X0, X0, true, true, true, 0.0, 0.0,
+                                                          |p| p.x < 0.0);
+    assert!(nsplit > 0);
+    assert_eq!(n, n0 + 12*nsplit);
+    assert_eq!(n, particles.len());
+    assert!(particles.iter().all(inside));
+    let ids: HashSet<u64> = particles.iter().map(|p| p.id).collect();
+    assert_eq!(ids.len(), n);
+    assert_conserved(before, totals(&particles));
+
+    // Merge the children back with their own neighbours
+    tree = build(&particles, n);
+    let nmerge: usize = partdistribution::merge_particles(&mut particles, &mut n, &mut ptr, &mut tree, 4, 2.0, WD, WD, WD, X0, X0, X0, true, true, true, 0.0, 0.0,
+                                                          |p| p.m < 0.5*m0);
+    assert!(nmerge > 0);
+    assert_eq!(n, particles.len());
+    assert!(particles.iter().all(|p| p.ptype == 0));
+    assert!(particles.iter().all(inside));
+    assert_conserved(before, totals(&particles));
+}
+
+#[test]
+fn children_stay_in_the_domain() {
+    for periodic in [true, false] {
+        let mut particles: Vec<Particle> = vec![Particle{m: 1e-3, rho: 1.0, h: 0.12, x: X0 + 1e-4, y: X0 + WD - 1e-4, z: X0, vy: 1.0,
+                                                         ..Default::default()}];
+        let mut n: usize = 1;
+        let mut ptr: Pointer = Pointer(particles.as_mut_ptr());
+        let mut tree: Node = build(&particles, n);
+        let before: [f64; 5] = totals(&particles);
+        partdistribution::split_particles(&mut particles, &mut n, &mut ptr, &mut tree, WD, WD, WD, X0, X0, X0, periodic, periodic, periodic, 0.0, 0.0,
+                                          |_| true);
+        assert_eq!(n, 13);
+        assert!(particles.iter().all(inside), "periodic: {}", periodic);
+        assert_conserved(before, totals(&particles));
+    }
+}
+
+#[test]
+fn criteria() {
+    let dense: Particle = Particle{rho: 1e3, h: 0.1, x: 0.05, ..Default::default()};
+    let diffuse: Particle = Particle{rho: 1e-3, h: 0.1, x: 2.0, ..Default::default()};
+    let jeans = partdistribution::jeans_criterion(4.0, |_| 0.1);
+    assert!(jeans(&dense));
+    assert!(!jeans(&diffuse));
+
+    let star: Star = Star{m: 1.0, ..Default::default()};
+    let sinks: [&Star; 1] = [&star];
+    let near = partdistribution::sink_criterion(&sinks, 1.0);
+    assert!(near(&dense));
+    assert!(!near(&diffuse));
+}
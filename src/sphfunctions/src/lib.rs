@@ -31,32 +31,40 @@ use structures::{
 // Returns                                                                   //
 //      r(p1)-r(p2) = r_12 = (x_12, y_12, z_12),                             //
 // the relative distance between particles p1 and p2.                        //
-// Periodic boundary conditions are included using booleans rperiodic.       //
+// Periodic boundary conditions are included using booleans rperiodic. In a  //
+// shearing box, the image at x + wd is shifted by y_shear in y. The closest //
+// image is taken once the separation exceeds half the box, so boxes thinner //
+// than 4*eps are handled too.                                               //
 // ------------------------------------------------------------------------- //
 pub fn periodic_rel_vector(
     p1: &Particle, p2: &Particle, wd: f64, lg: f64, hg: f64, eps: f64,
-    xperiodic: bool, yperiodic:bool, zperiodic:bool
+    xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64
 ) -> (f64, f64, f64) {
     
     let mut x_temp: f64 = p1.x - p2.x;
     let mut y_temp: f64 = p1.y - p2.y;
     let mut z_temp: f64 = p1.z - p2.z;
     let twoeps: f64 = 2.*eps;
-    if xperiodic && x_temp.abs() > wd-twoeps {
+    if xperiodic && x_temp.abs() > (wd-twoeps).max(0.5*wd) {
         if x_temp > 0. {
             x_temp -= wd;
+            y_temp -= y_shear;
         } else {
             x_temp += wd;
+            y_temp += y_shear;
+        }
+        if yperiodic && y_shear != 0. {
+            y_temp -= lg*(y_temp/lg).round();
         }
     }
-    if yperiodic && y_temp.abs() > lg-twoeps {
+    if yperiodic && y_temp.abs() > (lg-twoeps).max(0.5*lg) {
         if y_temp > 0. {
             y_temp -= lg;
         } else {
             y_temp += lg;
         }
     }
-    if zperiodic && z_temp.abs() > hg-twoeps {
+    if zperiodic && z_temp.abs() > (hg-twoeps).max(0.5*hg) {
         if z_temp > 0. {
             z_temp -= hg;
         } else {
@@ -66,6 +74,28 @@ pub fn periodic_rel_vector(
     return (x_temp, y_temp, z_temp);
 }
 
+// ------------------------------------------------------------------------- //
+// Periodic relative velocity:                                               //
+// Returns                                                                   //
+//      v(p1)-v(p2) = v_12 = (vx_12, vy_12, vz_12),                          //
+// the relative velocity between particles p1 and p2. In a shearing box, the //
+// image at x + wd moves with vy_shear in y.                                 //
+// ------------------------------------------------------------------------- //
+pub fn periodic_rel_velocity(
+    p1: &Particle, p2: &Particle, wd: f64, eps: f64, xperiodic: bool, vy_shear: f64
+) -> (f64, f64, f64) {
+    let mut vy_temp: f64 = p1.vy - p2.vy;
+    let x_temp: f64 = p1.x - p2.x;
+    if xperiodic && x_temp.abs() > (wd-2.*eps).max(0.5*wd) {
+        if x_temp > 0. {
+            vy_temp -= vy_shear;
+        } else {
+            vy_temp += vy_shear;
+        }
+    }
+    return (p1.vx - p2.vx, vy_temp, p1.vz - p2.vz);
+}
+
 // ------------------------------------------------------------------------- //
 // Norm of periodic distance:                                                //
 // Returns                                                                   //
@@ -75,9 +105,9 @@ pub fn periodic_rel_vector(
 // ------------------------------------------------------------------------- //
 pub fn periodic_norm(
     p1: &Particle, p2: &Particle, wd: f64, lg: f64, hg: f64, eps: f64,
-    xperiodic: bool, yperiodic:bool, zperiodic:bool
+    xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64
 ) -> f64 { 
-    let (x_temp, y_temp, z_temp) = periodic_rel_vector(p1, p2, wd, lg, hg, eps, xperiodic, yperiodic, zperiodic);
+    let (x_temp, y_temp, z_temp) = periodic_rel_vector(p1, p2, wd, lg, hg, eps, xperiodic, yperiodic, zperiodic, y_shear);
     return (x_temp*x_temp + y_temp*y_temp + z_temp*z_temp).sqrt();
 }
 
@@ -97,6 +127,18 @@ pub fn distance_star(
 }
 
 
+// ------------------------------------------------------------------------- //
+// Cross product:                                                            //
+// Returns                                                                   //
+//      a x b                                                                //
+// ------------------------------------------------------------------------- //
+pub fn cross(
+    ax: f64, ay: f64, az: f64, bx: f64, by: f64, bz: f64
+) -> (f64, f64, f64) {
+    return (ay*bz - az*by, az*bx - ax*bz, ax*by - ay*bx);
+}
+
+
 // !!!------------------------- Kernel Functions ------------------------!!! //
 
 // ***------------------------- B-Spline Kernels ------------------------*** //
@@ -277,10 +319,10 @@ pub fn dwdh(
 // the density calculated from the smoothing length.                         //
 // ------------------------------------------------------------------------- //
 pub fn density_from_h(
-    dm:f64, h:f64, eta:f64
+    m:f64, h:f64, eta:f64
 ) -> f64{
     let vol: f64 = eta/h;
-    dm*vol*vol*vol
+    m*vol*vol*vol
 }
 
 // ------------------------------------------------------------------------- //
@@ -289,9 +331,9 @@ pub fn density_from_h(
 // the smoothing length calculated from the density number.                  //
 // ------------------------------------------------------------------------- //
 pub fn h_from_density(
-    dm:f64, rho:f64, eta:f64
+    m:f64, rho:f64, eta:f64
 ) -> f64{
-    eta*(dm/rho).cbrt()
+    eta*(m/rho).cbrt()
 }
 
 
@@ -303,15 +345,15 @@ pub fn h_from_density(
 // the usual SPH density sum.                                                //
 // ------------------------------------------------------------------------- //
 pub fn density_kernel(
-    particles: & Vec<Particle>, ii:usize, neigh_particles: & Vec<usize>, dm:f64, h: f64,
-    sigma:f64, rkern: f64, f: fn(f64)->f64, wd: f64, lg: f64, hg: f64, xperiodic: bool, yperiodic:bool, zperiodic:bool
+    particles: & Vec<Particle>, ii:usize, neigh_particles: & Vec<usize>, h: f64,
+    sigma:f64, rkern: f64, f: fn(f64)->f64, wd: f64, lg: f64, hg: f64, xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64
 ) -> f64 {
     let mut rho :f64 = 0.0;
     for jj in neigh_particles{
-        let r: f64 = periodic_norm(&particles[ii], &particles[*jj], wd, lg, hg, rkern*particles[ii].h, xperiodic, yperiodic, zperiodic);
-        rho += f(r/h);
+        let r: f64 = periodic_norm(&particles[ii], &particles[*jj], wd, lg, hg, rkern*particles[ii].h, xperiodic, yperiodic, zperiodic, y_shear);
+        rho += particles[*jj].m * f(r/h);
     }
-    rho * dm * sigma / (h*h*h)
+    rho * sigma / (h*h*h)
 }
 
 // ------------------------------------------------------------------------- //
@@ -320,16 +362,16 @@ pub fn density_kernel(
 // Monaghan (2002)                                                           //
 // ------------------------------------------------------------------------- //
 pub fn omega(
-    particles: & Vec<Particle>, ii:usize, neigh_particles: & Vec<usize>, dm:f64, h: f64, rho: f64,
+    particles: & Vec<Particle>, ii:usize, neigh_particles: & Vec<usize>, h: f64, rho: f64,
     dwdh_: fn(f64, fn(f64) -> f64, fn(f64) -> f64) -> f64, f: fn(f64) -> f64, dfdq: fn(f64) -> f64, sigma: f64, rkern: f64,
-    wd: f64, lg: f64, hg: f64, xperiodic: bool, yperiodic:bool, zperiodic:bool
+    wd: f64, lg: f64, hg: f64, xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64
 ) -> f64{
     let mut omeg :f64 = 0.0;
     for jj in neigh_particles {
-        let q: f64 = periodic_norm(&particles[ii], &particles[*jj], wd, lg, hg, rkern*h, xperiodic, yperiodic, zperiodic)/h;
-        omeg -= dwdh_(q, f, dfdq);
+        let q: f64 = periodic_norm(&particles[ii], &particles[*jj], wd, lg, hg, rkern*h, xperiodic, yperiodic, zperiodic, y_shear)/h;
+        omeg -= particles[*jj].m * dwdh_(q, f, dfdq);
     }
-    omeg *= dm*sigma/(3.*h*h*h*rho);
+    omeg *= sigma/(3.*h*h*h*rho);
     if omeg <= -1.0 {
         omeg = 0.0;
     }
@@ -348,14 +390,14 @@ pub fn omega(
 // the function to find the root and its derivative.                         //
 // ------------------------------------------------------------------------- //
 pub fn f_iter(
-    particles: & Vec<Particle>, ii:usize, neigh_particles: & Vec<usize>, dm:f64, h: f64, eta:f64,
+    particles: & Vec<Particle>, ii:usize, neigh_particles: & Vec<usize>, h: f64, eta:f64,
     f: fn(f64) -> f64, dfdq: fn(f64) -> f64, sigma:f64, rkern: f64,
-    wd: f64, lg: f64, hg: f64, xperiodic: bool, yperiodic:bool, zperiodic:bool
+    wd: f64, lg: f64, hg: f64, xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64
 ) -> (f64 , f64) {
-    let rho_kernel: f64 = density_kernel(particles, ii, neigh_particles, dm, h, sigma, rkern, f, wd, lg, hg, xperiodic, yperiodic, zperiodic);
-    let rho_h: f64  = density_from_h(dm, h, eta);
+    let rho_kernel: f64 = density_kernel(particles, ii, neigh_particles, h, sigma, rkern, f, wd, lg, hg, xperiodic, yperiodic, zperiodic, y_shear);
+    let rho_h: f64  = density_from_h(particles[ii].m, h, eta);
     let f_h: f64    = rho_h - rho_kernel;
-    let omeg: f64   = omega(particles, ii, neigh_particles, dm, h, rho_h, dwdh, f, dfdq, sigma, rkern, wd, lg, hg, xperiodic, yperiodic, zperiodic);
+    let omeg: f64   = omega(particles, ii, neigh_particles, h, rho_h, dwdh, f, dfdq, sigma, rkern, wd, lg, hg, xperiodic, yperiodic, zperiodic, y_shear);
     let df: f64     = -3.*rho_h*omeg/ h;
     (f_h, df)
 }
@@ -367,11 +409,11 @@ pub fn f_iter(
 // the new value of h after one iteration.                                   //
 // ------------------------------------------------------------------------- //
 fn nr_iter(
-    particles: & Vec<Particle>, ii:usize, neigh_particles: & Vec<usize>, dm:f64, h_old: f64, eta:f64,
+    particles: & Vec<Particle>, ii:usize, neigh_particles: & Vec<usize>, h_old: f64, eta:f64,
     f: fn(f64) -> f64, dfdq: fn(f64) -> f64, sigma:f64, rkern: f64,
-    wd: f64, lg: f64, hg: f64, xperiodic: bool, yperiodic:bool, zperiodic:bool
+    wd: f64, lg: f64, hg: f64, xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64
 ) -> f64 {
-    let (f_i, df_i) = f_iter(particles, ii, neigh_particles, dm, h_old, eta, f, dfdq, sigma, rkern, wd, lg, hg, xperiodic, yperiodic, zperiodic);
+    let (f_i, df_i) = f_iter(particles, ii, neigh_particles, h_old, eta, f, dfdq, sigma, rkern, wd, lg, hg, xperiodic, yperiodic, zperiodic, y_shear);
     h_old - f_i / df_i
 }
 
@@ -383,9 +425,9 @@ fn nr_iter(
 //      f(h) = rho(h) - rho_sph(h)                                           //
 // ------------------------------------------------------------------------- //
 pub fn newton_raphson(
-    ii: usize, particles: & Vec<Particle>, dm:f64, h_guess: f64, eta:f64,
+    ii: usize, particles: & Vec<Particle>, h_guess: f64, eta:f64,
     f: fn(f64) -> f64, dfdq: fn(f64) -> f64, sigma:f64, rkern: f64, tol: f64, it: u32, tree: &Node, s_: i32,
-    wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64, xperiodic: bool, yperiodic:bool, zperiodic:bool
+    wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64, xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64
 ) -> (f64, Vec<usize>) {
     let mut h_new :f64 = 0.0;
     let mut h_old :f64 = h_guess;
@@ -394,9 +436,9 @@ pub fn newton_raphson(
     while i <= it {
         // Searching neighbouring particles
         neighbors.clear();
-        tree.find_neighbors(ii, s_, particles, &mut neighbors, wd, lg, hg, x0, y0, z0, h_old*rkern, xperiodic, yperiodic, zperiodic);
+        tree.find_neighbors(ii, s_, particles, &mut neighbors, wd, lg, hg, x0, y0, z0, h_old*rkern, xperiodic, yperiodic, zperiodic, y_shear);
         // Obtain h_new
-        h_new = nr_iter(particles, ii, &neighbors, dm, h_old, eta, f, dfdq, sigma, rkern, wd, lg, hg, xperiodic, yperiodic, zperiodic);
+        h_new = nr_iter(particles, ii, &neighbors, h_old, eta, f, dfdq, sigma, rkern, wd, lg, hg, xperiodic, yperiodic, zperiodic, y_shear);
         
         // Restrict result to [0.8h_old, 1.2h_old]
         if h_new > 1.2*particles[ii].h {
@@ -435,9 +477,9 @@ pub fn newton_raphson(
 //      f(h) = rho(h) - rho_sph(h)                                           //
 // ------------------------------------------------------------------------- //
 pub fn bisection(
-    ii: usize, particles: & Vec<Particle>, dm:f64, h_guess: f64, eta:f64,
+    ii: usize, particles: & Vec<Particle>, h_guess: f64, eta:f64,
     f: fn(f64) -> f64, sigma:f64, rkern: f64, tol: f64, it: u32, tree: &Node, s_: i32,
-    wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64, xperiodic: bool, yperiodic:bool, zperiodic:bool
+    wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64, xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64
 ) -> (f64, Vec<usize>) {
     let mut h_left :f64 = 0.6*h_guess;
     let mut h_right :f64= 1.667*h_guess;
@@ -447,14 +489,14 @@ pub fn bisection(
     // f_left
     let mut neighbors_left: Vec<usize> = Vec::new();
     neighbors_left.clear();
-    tree.find_neighbors(ii, s_, particles, &mut neighbors_left, wd, lg, hg, x0, y0, z0, h_left*rkern, xperiodic, yperiodic, zperiodic);
-    let mut f_left: f64 = density_from_h(dm, h_left, eta) - density_kernel(particles, ii, &neighbors_left, dm, h_left, sigma, rkern, f, wd, lg, hg, xperiodic, yperiodic, zperiodic);
+    tree.find_neighbors(ii, s_, particles, &mut neighbors_left, wd, lg, hg, x0, y0, z0, h_left*rkern, xperiodic, yperiodic, zperiodic, y_shear);
+    let mut f_left: f64 = density_from_h(particles[ii].m, h_left, eta) - density_kernel(particles, ii, &neighbors_left, h_left, sigma, rkern, f, wd, lg, hg, xperiodic, yperiodic, zperiodic, y_shear);
 
     // f_right
     let mut neighbors_mid: Vec<usize> = Vec::new();
     neighbors_mid.clear();
-    tree.find_neighbors(ii, s_, particles, &mut neighbors_mid, wd, lg, hg, x0, y0, z0, h_right*rkern, xperiodic, yperiodic, zperiodic);
-    let mut f_mid: f64  = density_from_h(dm, h_right, eta) - density_kernel(particles, ii, &neighbors_mid, dm, h_right, sigma, rkern, f, wd, lg, hg, xperiodic, yperiodic, zperiodic);
+    tree.find_neighbors(ii, s_, particles, &mut neighbors_mid, wd, lg, hg, x0, y0, z0, h_right*rkern, xperiodic, yperiodic, zperiodic, y_shear);
+    let mut f_mid: f64  = density_from_h(particles[ii].m, h_right, eta) - density_kernel(particles, ii, &neighbors_mid, h_right, sigma, rkern, f, wd, lg, hg, xperiodic, yperiodic, zperiodic, y_shear);
     
     if f_mid*f_left > 0.0 {
         i = it + 1;
@@ -465,8 +507,8 @@ pub fn bisection(
 
         // f_middle
         neighbors_mid.clear();
-        tree.find_neighbors(ii, s_, particles, &mut neighbors_mid, wd, lg, hg, x0, y0, z0, h_mid*rkern, xperiodic, yperiodic, zperiodic);
-        f_mid  = density_from_h(dm, h_mid, eta) - density_kernel(particles, ii, &neighbors_mid, dm, h_mid, sigma, rkern, f, wd, lg, hg, xperiodic, yperiodic, zperiodic);
+        tree.find_neighbors(ii, s_, particles, &mut neighbors_mid, wd, lg, hg, x0, y0, z0, h_mid*rkern, xperiodic, yperiodic, zperiodic, y_shear);
+        f_mid  = density_from_h(particles[ii].m, h_mid, eta) - density_kernel(particles, ii, &neighbors_mid, h_mid, sigma, rkern, f, wd, lg, hg, xperiodic, yperiodic, zperiodic, y_shear);
 
         if ((h_right - h_left)/h_mid).abs() <=  tol  {
             i = it + 2;
@@ -497,28 +539,28 @@ pub fn bisection(
 // uses bisection solver. Finally, if not root found, it keeps h constant    //
 // ------------------------------------------------------------------------- //
 pub fn smoothing_length(
-    particles: &mut Vec<Particle>, dm:f64, eta:f64, f: fn(f64) -> f64, dfdq: fn(f64) -> f64, sigma:f64, rkern: f64,
+    particles: &mut Vec<Particle>, eta:f64, f: fn(f64) -> f64, dfdq: fn(f64) -> f64, sigma:f64, rkern: f64,
     tol: f64, it: u32, dt:f64, tree: &Node, s_: i32, n: usize, ptr : Pointer,
-    wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64, xperiodic: bool, yperiodic:bool, zperiodic:bool
+    wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64, xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64
 ){
     (0..n).into_par_iter().for_each(|ii| {
-        if particles[ii].ptype==0 {
+        if particles[ii].ptype==0 && particles[ii].active {
             let h_guess: f64 = particles[ii].h*(1.+dt*particles[ii].divv/3.);
-            let (mut h_new, mut neighbors) = newton_raphson(ii, particles, dm, h_guess, eta, f, dfdq, sigma, rkern, tol, it, tree, s_, wd, lg, hg, x0, y0, z0, xperiodic, yperiodic, zperiodic);
+            let (mut h_new, mut neighbors) = newton_raphson(ii, particles, h_guess, eta, f, dfdq, sigma, rkern, tol, it, tree, s_, wd, lg, hg, x0, y0, z0, xperiodic, yperiodic, zperiodic, y_shear);
             let particle = unsafe { &mut *{ptr}.0.add(ii)};
             if h_new != 0.0 {
                 particle.h = h_new;
             } else {
-                (h_new, neighbors) = bisection(ii, particles, dm, h_guess, eta, f, sigma, rkern, tol, it, tree, s_, wd, lg, hg, x0, y0, z0, xperiodic, yperiodic, zperiodic);
+                (h_new, neighbors) = bisection(ii, particles, h_guess, eta, f, sigma, rkern, tol, it, tree, s_, wd, lg, hg, x0, y0, z0, xperiodic, yperiodic, zperiodic, y_shear);
                 if h_new != 0.0 {
                     particle.h = h_new;
                 } else {
                     // h is not found, then keep it constant in time.
                     neighbors.clear();
-                    tree.find_neighbors(ii, s_, particles, &mut neighbors, wd, lg, hg, x0, y0, z0, particle.h*rkern, xperiodic, yperiodic, zperiodic);
+                    tree.find_neighbors(ii, s_, particles, &mut neighbors, wd, lg, hg, x0, y0, z0, particle.h*rkern, xperiodic, yperiodic, zperiodic, y_shear);
                 }
             }
-         particle.rho = density_kernel(particles, ii, &neighbors, dm, particle.h, sigma, rkern, f, wd, lg, hg, xperiodic, yperiodic, zperiodic);
+         particle.rho = density_kernel(particles, ii, &neighbors, particle.h, sigma, rkern, f, wd, lg, hg, xperiodic, yperiodic, zperiodic, y_shear);
         }
     });
 }
@@ -752,49 +794,271 @@ pub fn acceleration_ab(
 // ***------------------------- External Forces -------------------------*** //
 
 // ------------------------------------------------------------------------- //
-// No external forces are applied.                                           //
-// Nothing is returned                                                       //
+// External force (per unit of mass) acting on the gas particles.            //
+// Every force keeps its own parameters, and several of them can be applied  //
+// in the same run by passing a list, &[&dyn ExternalForce]; an empty list   //
+// means no external forces. Potentials are centred at the origin unless     //
+// they have a position. G = 1.                                              //
 // ------------------------------------------------------------------------- //
-pub fn body_forces_null(
-    _particles: &mut Particle, _m_star: & Star
-) {
+pub trait ExternalForce: Sync {
+    fn acceleration(&self, particle: &Particle) -> (f64, f64, f64);
+}
+
+// ------------------------------------------------------------------------- //
+// Softened point mass at (x, y, z):                                         //
+//      a = -G*M/(r^2 + eps^2)^(3/2) r                                       //
+// ------------------------------------------------------------------------- //
+pub struct PointMass {
+    pub m: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub eps: f64,
+}
+
+impl ExternalForce for PointMass {
+    fn acceleration(&self, particle: &Particle) -> (f64, f64, f64) {
+        let x_r: f64 = particle.x - self.x;
+        let y_r: f64 = particle.y - self.y;
+        let z_r: f64 = particle.z - self.z;
+        let f_grav: f64 = self.m * (x_r*x_r + y_r*y_r + z_r*z_r + self.eps*self.eps).powf(-1.5);
+        (-f_grav*x_r, -f_grav*y_r, -f_grav*z_r)
+    }
+}
+
+// Softening of the gravity of sink particles, e^2 with e = 0.025
+pub const SINK_EPS2: f64 = 0.000625;
+
+// ------------------------------------------------------------------------- //
+// Gravitational force due to a sink particle (star):                        //
+//      a_i = -G*M/(r^2 + e^2)^(3/2) r                                       //
+// where r is the position relative to the star and e^2 = SINK_EPS2.         //
+// If c > 0, the post-Newtonian terms of Nelson & Papaloizou (2000) are      //
+// added: the Einstein precession from the potential -3(GM)^2/(c^2 r^2),     //
+//      a_E = -6 (GM)^2/(c^2 r^4) r,                                         //
+// and the Lense-Thirring (gravitomagnetic) term of the star's spin S,       //
+//      a_LT = v x W,   W = 2G/(c^2 r^3) (S - 3 (S.r/|r|) r/|r|),            //
+// with v the velocity relative to the star. Both are softened like the      //
+// Newtonian term, with r^2 + e^2 in place of r^2.                           //
+// ------------------------------------------------------------------------- //
+impl ExternalForce for Star {
+    fn acceleration(&self, particle: &Particle) -> (f64, f64, f64) {
+        let x_r: f64 = particle.x - self.x;
+        let y_r: f64 = particle.y - self.y;
+        let z_r: f64 = particle.z - self.z;
+        let r2: f64 = x_r*x_r + y_r*y_r + z_r*z_r;
+        let f_grav: f64 = self.m * (r2+SINK_EPS2).powf(-1.5);
+        let (mut ax, mut ay, mut az) = (-f_grav*x_r, -f_grav*y_r, -f_grav*z_r);
+        if self.c > 0.0 {
+            let c2: f64 = self.c*self.c;
+            let r2_s: f64 = r2 + SINK_EPS2;
+            let r_s: f64 = r2_s.sqrt();
+            // Einstein precession
+            let f_e: f64 = 6.0*self.m*self.m/(c2*r2_s*r2_s);
+            ax -= f_e*x_r;
+            ay -= f_e*y_r;
+            az -= f_e*z_r;
+            // Lense-Thirring precession
+            let s_r: f64 = 3.0*(self.sx*x_r + self.sy*y_r + self.sz*z_r)/r2_s;
+            let f_lt: f64 = 2.0/(c2*r2_s*r_s);
+            let (wx, wy, wz) = (f_lt*(self.sx - s_r*x_r), f_lt*(self.sy - s_r*y_r), f_lt*(self.sz - s_r*z_r));
+            let (lx, ly, lz) = cross(particle.vx - self.vx, particle.vy - self.vy, particle.vz - self.vz, wx, wy, wz);
+            ax += lx;
+            ay += ly;
+            az += lz;
+        }
+        (ax, ay, az)
+    }
 }
 
 // ------------------------------------------------------------------------- //
 // Toy Star model, Monaghan & Price (2004):                                  //
-// Computes external forces:                                                 //
 //      a_i = -nu * v_i - lambda * x_i                                       //
 // where nu is a damping coefficient for the equilibrium state, and the      //
-// lambda term is the simpliﬁed gravitational term.                          //
-// For this case, we use some features of star to save the nu and lmbda      //
-// parameters. star.hacc = nu; star.facc = lmbda.                            //
-// This is a temporal implementation. To be fixed in future versions.        //
+// lambda term is the simplified gravitational term.                         //
 // ------------------------------------------------------------------------- //
-pub fn body_forces_toy_star(
-    particle: &mut Particle, star: &Star
-) { 
-    particle.ax -= star.hacc * particle.vx + star.facc*particle.x;
-    particle.ay -= star.hacc * particle.vy + star.facc*particle.y; 
-    particle.az -= star.hacc * particle.vz + star.facc*particle.z; 
+pub struct ToyStar {
+    pub nu: f64,
+    pub lambda: f64,
+}
+
+impl ExternalForce for ToyStar {
+    fn acceleration(&self, particle: &Particle) -> (f64, f64, f64) {
+        (-self.nu * particle.vx - self.lambda * particle.x,
+         -self.nu * particle.vy - self.lambda * particle.y,
+         -self.nu * particle.vz - self.lambda * particle.z)
+    }
 }
 
 // ------------------------------------------------------------------------- //
-// Newtonian gravitational force due to a sink particle (star):              //
-// Computes external forces:                                                 //
-//      a_i = -G*M/(r^2 + e^2) \hat{r}                                       //
-// where G is the gravitational constant, M is the star mass, r the distance //
-// to the star and e is the softening term.                                  //
+// Plummer sphere of mass M and scale length b:                              //
+//      phi = -G*M/sqrt(r^2 + b^2)                                           //
 // ------------------------------------------------------------------------- //
-pub fn body_forces_gravitation(
-    particle: &mut Particle, m_star: & Star
-) {
-    let x_r: f64 = particle.x - m_star.x;
-    let y_r: f64 = particle.y - m_star.y;
-    let z_r: f64 = particle.z - m_star.z;
-    let f_grav: f64 = m_star.m * (x_r*x_r + y_r*y_r + z_r*z_r+0.000625).powf(-1.5);
-    particle.ax += -f_grav*x_r;
-    particle.ay += -f_grav*y_r;
-    particle.az += -f_grav*z_r;
+pub struct Plummer {
+    pub m: f64,
+    pub b: f64,
+}
+
+impl ExternalForce for Plummer {
+    fn acceleration(&self, particle: &Particle) -> (f64, f64, f64) {
+        let r2: f64 = particle.x*particle.x + particle.y*particle.y + particle.z*particle.z;
+        let f: f64 = self.m * (r2 + self.b*self.b).powf(-1.5);
+        (-f*particle.x, -f*particle.y, -f*particle.z)
+    }
+}
+
+// ------------------------------------------------------------------------- //
+// Hernquist (1990) profile of mass M and scale length a:                    //
+//      phi = -G*M/(r + a)                                                   //
+// ------------------------------------------------------------------------- //
+pub struct Hernquist {
+    pub m: f64,
+    pub a: f64,
+}
+
+impl ExternalForce for Hernquist {
+    fn acceleration(&self, particle: &Particle) -> (f64, f64, f64) {
+        let r: f64 = (particle.x*particle.x + particle.y*particle.y + particle.z*particle.z).sqrt();
+        if r == 0.0 {
+            return (0.0, 0.0, 0.0);
+        }
+        let f: f64 = self.m / (r*(r + self.a)*(r + self.a));
+        (-f*particle.x, -f*particle.y, -f*particle.z)
+    }
+}
+
+// ------------------------------------------------------------------------- //
+// Navarro, Frenk & White (1997) halo with scale radius rs and               //
+// characteristic mass m_s = 4*pi*rho_0*rs^3:                                //
+//      phi = -G*m_s*ln(1 + r/rs)/r                                          //
+//      a = -G*M(<r)/r^3 r,  M(<r) = m_s*(ln(1 + r/rs) - r/(r + rs))         //
+// ------------------------------------------------------------------------- //
+pub struct NFW {
+    pub m_s: f64,
+    pub rs: f64,
+}
+
+impl ExternalForce for NFW {
+    fn acceleration(&self, particle: &Particle) -> (f64, f64, f64) {
+        let r: f64 = (particle.x*particle.x + particle.y*particle.y + particle.z*particle.z).sqrt();
+        if r == 0.0 {
+            return (0.0, 0.0, 0.0);
+        }
+        let m_r: f64 = self.m_s * ((1.0 + r/self.rs).ln() - r/(r + self.rs));
+        let f: f64 = m_r / (r*r*r);
+        (-f*particle.x, -f*particle.y, -f*particle.z)
+    }
+}
+
+// ------------------------------------------------------------------------- //
+// Miyamoto & Nagai (1975) disc of mass M, scale length a and height b:      //
+//      phi = -G*M/sqrt(R^2 + (a + sqrt(z^2 + b^2))^2)                       //
+// ------------------------------------------------------------------------- //
+pub struct MiyamotoNagai {
+    pub m: f64,
+    pub a: f64,
+    pub b: f64,
+}
+
+impl ExternalForce for MiyamotoNagai {
+    fn acceleration(&self, particle: &Particle) -> (f64, f64, f64) {
+        let zeta: f64 = (particle.z*particle.z + self.b*self.b).sqrt();
+        let az: f64 = self.a + zeta;
+        let f: f64 = self.m * (particle.x*particle.x + particle.y*particle.y + az*az).powf(-1.5);
+        (-f*particle.x, -f*particle.y, -f*particle.z*az/zeta)
+    }
+}
+
+// ------------------------------------------------------------------------- //
+// Logarithmic halo with circular velocity v0, core radius rc and            //
+// flattening q:                                                             //
+//      phi = 0.5*v0^2*ln(rc^2 + x^2 + y^2 + z^2/q^2)                        //
+// ------------------------------------------------------------------------- //
+pub struct LogarithmicHalo {
+    pub v0: f64,
+    pub rc: f64,
+    pub q: f64,
+}
+
+impl ExternalForce for LogarithmicHalo {
+    fn acceleration(&self, particle: &Particle) -> (f64, f64, f64) {
+        let q2: f64 = self.q*self.q;
+        let f: f64 = self.v0*self.v0 / (self.rc*self.rc + particle.x*particle.x + particle.y*particle.y + particle.z*particle.z/q2);
+        (-f*particle.x, -f*particle.y, -f*particle.z/q2)
+    }
+}
+
+// ------------------------------------------------------------------------- //
+// Constant gravitational field:                                             //
+//      a = (gx, gy, gz)                                                     //
+// ------------------------------------------------------------------------- //
+pub struct ConstantGravity {
+    pub gx: f64,
+    pub gy: f64,
+    pub gz: f64,
+}
+
+impl ExternalForce for ConstantGravity {
+    fn acceleration(&self, _particle: &Particle) -> (f64, f64, f64) {
+        (self.gx, self.gy, self.gz)
+    }
+}
+
+// ------------------------------------------------------------------------- //
+// Fictitious forces in a frame rotating with angular velocity w about the   //
+// origin:                                                                   //
+//      a = -w x (w x r) - 2 w x v                                           //
+// the centrifugal and Coriolis accelerations. The Coriolis term can be left //
+// out (coriolis = false) when the integrator handles it, see                //
+// CoriolisLeapfrog.                                                         //
+// ------------------------------------------------------------------------- //
+pub struct RotatingFrame {
+    pub wx: f64,
+    pub wy: f64,
+    pub wz: f64,
+    pub coriolis: bool,
+}
+
+impl ExternalForce for RotatingFrame {
+    fn acceleration(&self, particle: &Particle) -> (f64, f64, f64) {
+        // w x r
+        let (cx, cy, cz) = cross(self.wx, self.wy, self.wz, particle.x, particle.y, particle.z);
+        // -w x (w x r)
+        let (mut ax, mut ay, mut az) = cross(self.wx, self.wy, self.wz, cx, cy, cz);
+        ax = -ax;
+        ay = -ay;
+        az = -az;
+        if self.coriolis {
+            let (vx, vy, vz) = cross(self.wx, self.wy, self.wz, particle.vx, particle.vy, particle.vz);
+            ax -= 2.0*vx;
+            ay -= 2.0*vy;
+            az -= 2.0*vz;
+        }
+        (ax, ay, az)
+    }
+}
+
+// ------------------------------------------------------------------------- //
+// Tidal and Coriolis forces of an (unstratified) shearing box, the local    //
+// frame of a disc rotating with angular velocity omega about z (Hill's      //
+// equations), with shear parameter q (1.5 for a Keplerian disc):            //
+//      a_x = 2 omega vy + 2 q omega^2 (x - xc)                              //
+//      a_y = -2 omega vx                                                    //
+// The background flow vy = -q*omega*(x - xc) is an equilibrium. Used with   //
+// periodic_boundary and the offsets of shear_offsets.                       //
+// ------------------------------------------------------------------------- //
+pub struct ShearingBox {
+    pub q: f64,
+    pub omega: f64,
+    pub xc: f64,
+}
+
+impl ExternalForce for ShearingBox {
+    fn acceleration(&self, particle: &Particle) -> (f64, f64, f64) {
+        (2.0*self.omega*particle.vy + 2.0*self.q*self.omega*self.omega*(particle.x - self.xc),
+         -2.0*self.omega*particle.vx,
+         0.0)
+    }
 }
 
 
@@ -808,21 +1072,21 @@ pub fn body_forces_gravitation(
 //      Delta u (du)                                                         //
 // ------------------------------------------------------------------------- //
 pub fn accelerations(
-    particles: &mut Vec<Particle>, dm:f64, eos_type: bool, eos: fn(f64, f64, f64, f64, f64, f64, f64)->f64, cs: fn(f64, f64, f64, f64, f64, f64, f64)->f64, gamma: f64, coeff: f64,
+    particles: &mut Vec<Particle>, eos_type: bool, eos: fn(f64, f64, f64, f64, f64, f64, f64)->f64, cs: fn(f64, f64, f64, f64, f64, f64, f64)->f64, gamma: f64, coeff: f64,
     dwdh_: fn(f64, fn(f64) -> f64, fn(f64) -> f64) -> f64, f: fn(f64) -> f64, dfdq: fn(f64) -> f64, sigma: f64, rkern: f64,
     tree: &Node, s_: i32, n: usize, ptr : Pointer, wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64,
     artificial_viscosity: fn(f64, f64, f64, f64, f64, f64, f64) -> (f64, f64),
-    body_forces: fn(&mut Particle, &Star), star: &Star, bf: bool, xperiodic: bool, yperiodic:bool, zperiodic:bool
+    forces: &[&dyn ExternalForce], xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64, vy_shear: f64
 ) {
     // Find every neighbour of every particle.
     let neighbors: Vec<Vec<usize>> = (0..n).into_par_iter().map(|ii: usize| {
         let mut neighbors: Vec<usize> = Vec::new();
-        tree.find_neighbors(ii, s_, particles, &mut neighbors, wd, lg, hg, x0, y0, z0, particles[ii].h*rkern, xperiodic, yperiodic, zperiodic);
+        tree.find_neighbors(ii, s_, particles, &mut neighbors, wd, lg, hg, x0, y0, z0, particles[ii].h*rkern, xperiodic, yperiodic, zperiodic, y_shear);
         return neighbors;
     }).collect();
     // Update particles state
     (0..n).into_par_iter().for_each(move |ii| {
-        if particles[ii].ptype==0 {
+        if particles[ii].ptype==0 && particles[ii].active {
 
             // Pointer to iith-particle
             let particle_i = unsafe { &mut *{ptr}.0.add(ii)};
@@ -836,11 +1100,11 @@ pub fn accelerations(
 
             let p_i: f64    = eos(particles[ii].rho, particles[ii].u, gamma, particles[ii].x, particles[ii].y, particles[ii].z, coeff);
             let cs_i: f64   = cs(particles[ii].rho, particles[ii].u, gamma, particles[ii].x, particles[ii].y, particles[ii].z, coeff);
-            let omeg_i: f64 = omega(particles, ii, &neighbors[ii], dm, particles[ii].h, particles[ii].rho, dwdh_, f, dfdq, sigma, rkern, wd, lg, hg, xperiodic, yperiodic, zperiodic);
+            let omeg_i: f64 = omega(particles, ii, &neighbors[ii], particles[ii].h, particles[ii].rho, dwdh_, f, dfdq, sigma, rkern, wd, lg, hg, xperiodic, yperiodic, zperiodic, y_shear);
             
             for jj in 0..n {
-                if ii != jj && particles[jj].ptype != 2 {
-                    let (x_rel, y_rel, z_rel) = periodic_rel_vector(&particles[ii], &particles[jj], wd, lg, hg, rkern*particles[ii].h, xperiodic, yperiodic, zperiodic);
+                if ii != jj && particles[jj].ptype != 2 && particles[jj].ptype != 5 {
+                    let (x_rel, y_rel, z_rel) = periodic_rel_vector(&particles[ii], &particles[jj], wd, lg, hg, rkern*particles[ii].h, xperiodic, yperiodic, zperiodic, y_shear);
                     let r_ij: f64 = (x_rel*x_rel + y_rel*y_rel+ z_rel*z_rel).sqrt();
 
                     let mut grad_hi: f64 = 0.0;
@@ -856,12 +1120,11 @@ pub fn accelerations(
                     if grad_hi != 0. || grad_hj != 0.0 {
                         let p_j: f64    = eos(particles[jj].rho, particles[jj].u, gamma, particles[jj].x, particles[jj].y, particles[jj].z, coeff);
                         let cs_j: f64   = cs(particles[jj].rho, particles[jj].u, gamma, particles[jj].x, particles[jj].y, particles[jj].z, coeff);
-                        let omeg_j: f64 = omega(particles, jj, &neighbors[jj], dm, particles[jj].h, particles[jj].rho, dwdh_, f, dfdq, sigma, rkern, wd, lg, hg, xperiodic, yperiodic, zperiodic);
+                        let omeg_j: f64 = omega(particles, jj, &neighbors[jj], particles[jj].h, particles[jj].rho, dwdh_, f, dfdq, sigma, rkern, wd, lg, hg, xperiodic, yperiodic, zperiodic, y_shear);
 
                         // Velocity dot position
-                        let dot_r_v = (particles[ii].vx-particles[jj].vx)*x_rel
-                                          +(particles[ii].vy-particles[jj].vy)*y_rel
-                                          +(particles[ii].vz-particles[jj].vz)*z_rel;
+                        let (vx_rel, vy_rel, vz_rel) = periodic_rel_velocity(&particles[ii], &particles[jj], wd, rkern*particles[ii].h, xperiodic, vy_shear);
+                        let dot_r_v = vx_rel*x_rel + vy_rel*y_rel + vz_rel*z_rel;
 
                         // Mean density
                         let rho_mean: f64 = 0.5 * (particles[ii].rho + particles[jj].rho);
@@ -871,19 +1134,19 @@ pub fn accelerations(
 
                         // Acceleration
                         let (f_ij_x, f_ij_y, f_ij_z) = acceleration_ab(&particles[ii], &particles[jj], x_rel, y_rel, z_rel, p_i, p_j, omeg_i, omeg_j, grad_hi, grad_hj, art_visc_mom);
-                        particle_i.ax += dm * f_ij_x;
-                        particle_i.ay += dm * f_ij_y;
-                        particle_i.az += dm * f_ij_z;
+                        particle_i.ax += particles[jj].m * f_ij_x;
+                        particle_i.ay += particles[jj].m * f_ij_y;
+                        particle_i.az += particles[jj].m * f_ij_z;
                         
                         // Divergence of v per unit of mass
                         let div_vel :f64 = grad_hi*dot_r_v / (omeg_i*particles[ii].rho);
-                        particle_i.divv -= dm*div_vel;
+                        particle_i.divv -= particles[jj].m*div_vel;
                         
                         // Internal energy change
                         if eos_type {
                             // Artificial thermal conductivity
                             let art_therm_cond: f64 = price08_therm_cond(p_i, p_j, rho_mean, particles[ii].u, particles[jj].u);
-                            particle_i.du += dm * (0.5*(art_visc_ene + art_therm_cond*r_ij)*(grad_hi/omeg_i+grad_hj/omeg_j));
+                            particle_i.du += particles[jj].m * (0.5*(art_visc_ene + art_therm_cond*r_ij)*(grad_hi/omeg_i+grad_hj/omeg_j));
                         }
                     }
                 }
@@ -891,9 +1154,12 @@ pub fn accelerations(
             if eos_type {
                 particle_i.du -= (p_i/particles[ii].rho)*particle_i.divv;
             }
-            // Body forces
-            if bf {
-                body_forces(particle_i, star);
+            // External forces
+            for force in forces {
+                let (ax, ay, az) = force.acceleration(particle_i);
+                particle_i.ax += ax;
+                particle_i.ay += ay;
+                particle_i.az += az;
             }
         }
     });
@@ -925,6 +1191,108 @@ pub fn star_integrator(
     star.vz += 0.5 * dt * star.az;
 }
 
+// ------------------------------------------------------------------------- //
+// Newtonian gravity between sink particles (stars):                         //
+//      a_i = -SUM_j G*M_j/(r_ij^2 + e^2)^(3/2) r_ij                         //
+// with e^2 = SINK_EPS2, as in the star's force on the gas.                  //
+// Returns the acceleration of every star.                                   //
+// ------------------------------------------------------------------------- //
+pub fn sink_accelerations(
+    stars: & [Star]
+) -> Vec<(f64, f64, f64)> {
+    let mut acc: Vec<(f64, f64, f64)> = vec![(0.0, 0.0, 0.0); stars.len()];
+    for ii in 0..stars.len() {
+        for jj in ii+1..stars.len() {
+            let x_r: f64 = stars[jj].x - stars[ii].x;
+            let y_r: f64 = stars[jj].y - stars[ii].y;
+            let z_r: f64 = stars[jj].z - stars[ii].z;
+            let f_grav: f64 = (x_r*x_r + y_r*y_r + z_r*z_r + SINK_EPS2).powf(-1.5);
+            acc[ii].0 += stars[jj].m*f_grav*x_r;
+            acc[ii].1 += stars[jj].m*f_grav*y_r;
+            acc[ii].2 += stars[jj].m*f_grav*z_r;
+            acc[jj].0 -= stars[ii].m*f_grav*x_r;
+            acc[jj].1 -= stars[ii].m*f_grav*y_r;
+            acc[jj].2 -= stars[ii].m*f_grav*z_r;
+        }
+    }
+    return acc;
+}
+
+// ------------------------------------------------------------------------- //
+// Orbital accuracy criterion.                                               //
+// Returns the number of substeps needed to integrate the stars over dt,     //
+// with a substep of at most                                                 //
+//      dt_sub = eta_orb * min_ij sqrt(r_ij^3/(G*(M_i+M_j))),                //
+// i.e. about 2*pi/eta_orb substeps per orbit of the closest pair.           //
+// ------------------------------------------------------------------------- //
+pub fn sink_substeps(
+    stars: & [Star], dt: f64, eta_orb: f64
+) -> u32 {
+    let mut t_orb: f64 = f64::INFINITY;
+    for ii in 0..stars.len() {
+        for jj in ii+1..stars.len() {
+            let x_r: f64 = stars[jj].x - stars[ii].x;
+            let y_r: f64 = stars[jj].y - stars[ii].y;
+            let z_r: f64 = stars[jj].z - stars[ii].z;
+            let r: f64 = (x_r*x_r + y_r*y_r + z_r*z_r).sqrt();
+            t_orb = t_orb.min((r*r*r/(stars[ii].m + stars[jj].m)).sqrt());
+        }
+    }
+    if !t_orb.is_finite() {
+        return 1;
+    }
+    return ((dt/(eta_orb*t_orb)).ceil() as u32).max(1);
+}
+
+// ------------------------------------------------------------------------- //
+// Substepped (RESPA) integrator for several stars.                          //
+// The acceleration due to the gas (star.ax, ay, az) is held fixed over dt   //
+// and applied as two half kicks around the substeps, while the sink-sink    //
+// gravity is integrated with a KDK over each substep:                       //
+//      gas kick (dt/2),                                                     //
+//      nsub x [sink kick (dt_sub/2), drift (dt_sub), sink kick (dt_sub/2)], //
+//      gas kick (dt/2)                                                      //
+// The number of substeps comes from sink_substeps and is returned.          //
+// With a single star it reduces to star_integrator.                         //
+// ------------------------------------------------------------------------- //
+pub fn sinks_integrator(
+    stars: &mut [Star], dt: f64, eta_orb: f64
+) -> u32 {
+    for star in stars.iter_mut() {
+        star.vx += 0.5 * dt * star.ax;
+        star.vy += 0.5 * dt * star.ay;
+        star.vz += 0.5 * dt * star.az;
+    }
+
+    let nsub: u32 = sink_substeps(stars, dt, eta_orb);
+    let dt_sub: f64 = dt/nsub as f64;
+    let mut acc: Vec<(f64, f64, f64)> = sink_accelerations(stars);
+    for _ in 0..nsub {
+        for (star, (ax, ay, az)) in stars.iter_mut().zip(acc.iter()) {
+            star.vx += 0.5 * dt_sub * ax;
+            star.vy += 0.5 * dt_sub * ay;
+            star.vz += 0.5 * dt_sub * az;
+
+            star.x  += dt_sub * star.vx;
+            star.y  += dt_sub * star.vy;
+            star.z  += dt_sub * star.vz;
+        }
+        acc = sink_accelerations(stars);
+        for (star, (ax, ay, az)) in stars.iter_mut().zip(acc.iter()) {
+            star.vx += 0.5 * dt_sub * ax;
+            star.vy += 0.5 * dt_sub * ay;
+            star.vz += 0.5 * dt_sub * az;
+        }
+    }
+
+    for star in stars.iter_mut() {
+        star.vx += 0.5 * dt * star.ax;
+        star.vy += 0.5 * dt * star.ay;
+        star.vz += 0.5 * dt * star.az;
+    }
+    return nsub;
+}
+
 
 // !!!------------------------- Time Integrator -------------------------!!! //
 
@@ -934,16 +1302,16 @@ pub fn star_integrator(
 //      f(t + dt) = f(t) + dt * f'(t)                                        //
 // ------------------------------------------------------------------------- //
 pub fn euler_integrator(
-    particles: &mut Vec<Particle>, dt:f64, dm:f64, eos_type: bool, eos: fn(f64, f64, f64, f64, f64, f64, f64)->f64, cs: fn(f64, f64, f64, f64, f64, f64, f64)->f64, gamma:f64, coeff: f64,
+    particles: &mut Vec<Particle>, dt:f64, eos_type: bool, eos: fn(f64, f64, f64, f64, f64, f64, f64)->f64, cs: fn(f64, f64, f64, f64, f64, f64, f64)->f64, gamma:f64, coeff: f64,
     dwdh_: fn(f64, fn(f64) -> f64, fn(f64) -> f64) -> f64, f: fn(f64) -> f64, dfdq: fn(f64) -> f64, sigma: f64, rkern: f64,
     eta: f64, tree: &mut Node, s_: i32, alpha_: f64, beta_:f64, n: usize, ptr : Pointer,
     artificial_viscosity: fn(f64, f64, f64, f64, f64, f64, f64) -> (f64, f64),
-    body_forces: fn(&mut Particle, &Star), star: &Star, bf: bool,
-    boundary: fn(&mut Vec<Particle>, f64, f64,f64, f64, f64, f64), xperiodic: bool, yperiodic:bool, zperiodic:bool, wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64
+    forces: &[&dyn ExternalForce],
+    boundary: fn(&mut Vec<Particle>, f64, f64, f64, f64, f64, f64, f64, f64), xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64, vy_shear: f64, wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64
 ) {
     tree.build_tree(s_, alpha_, beta_, particles, 1.0e-02);
-    smoothing_length(particles, dm, eta, f, dfdq, sigma, rkern, 1e-03, 30, dt, tree, s_, n, ptr, wd, lg, hg, x0, y0, z0, xperiodic, yperiodic, zperiodic);
-    accelerations(particles, dm, eos_type, eos, cs, gamma, coeff, dwdh_, f, dfdq, sigma, rkern, tree, s_, n, ptr, wd, lg, hg, x0, y0, z0, artificial_viscosity, body_forces, star, bf, xperiodic, yperiodic, zperiodic);
+    smoothing_length(particles, eta, f, dfdq, sigma, rkern, 1e-03, 30, dt, tree, s_, n, ptr, wd, lg, hg, x0, y0, z0, xperiodic, yperiodic, zperiodic, y_shear);
+    accelerations(particles, eos_type, eos, cs, gamma, coeff, dwdh_, f, dfdq, sigma, rkern, tree, s_, n, ptr, wd, lg, hg, x0, y0, z0, artificial_viscosity, forces, xperiodic, yperiodic, zperiodic, y_shear, vy_shear);
     particles.par_iter_mut().for_each(|particle|{
         if particle.ptype==0 {
             particle.x  += dt * particle.vx;
@@ -955,7 +1323,7 @@ pub fn euler_integrator(
             particle.u  += dt * particle.du;
         }
     });
-    boundary(particles, wd, lg, hg, x0, y0, z0);
+    boundary(particles, wd, lg, hg, x0, y0, z0, y_shear, vy_shear);
 }
 
 // ------------------------------------------------------------------------- //
@@ -964,40 +1332,18 @@ pub fn euler_integrator(
 // Verlet (1967)                                                             //
 // ------------------------------------------------------------------------- //
 pub fn velocity_verlet_integrator(
-    particles: &mut Vec<Particle>, dt:f64, dm:f64, eos_type: bool, eos: fn(f64, f64, f64, f64, f64, f64, f64)->f64, cs: fn(f64, f64, f64, f64, f64, f64, f64)->f64, gamma:f64, coeff: f64,
+    particles: &mut Vec<Particle>, dt:f64, eos_type: bool, eos: fn(f64, f64, f64, f64, f64, f64, f64)->f64, cs: fn(f64, f64, f64, f64, f64, f64, f64)->f64, gamma:f64, coeff: f64,
     dwdh_: fn(f64, fn(f64) -> f64, fn(f64) -> f64) -> f64, f: fn(f64) -> f64, dfdq: fn(f64) -> f64, sigma: f64, rkern: f64,
     eta: f64, tree: &mut Node, s_: i32, alpha_: f64, beta_:f64, n: usize, ptr : Pointer,
     artificial_viscosity: fn(f64, f64, f64, f64, f64, f64, f64) -> (f64, f64),
-    body_forces: fn(&mut Particle, &Star), star: &Star, bf: bool,
-    boundary: fn(&mut Vec<Particle>, f64, f64, f64, f64, f64, f64), xperiodic: bool, yperiodic:bool, zperiodic:bool, wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64
-) {    
-    particles.par_iter_mut().for_each(|particle|{
-        if particle.ptype==0 {
-            particle.vx += 0.5 * dt * particle.ax;
-            particle.vy += 0.5 * dt * particle.ay;
-            particle.vz += 0.5 * dt * particle.az;
-
-            particle.u  += 0.5 * dt * particle.du;
-
-            particle.x  += dt * particle.vx;
-            particle.y  += dt * particle.vy;
-            particle.z  += dt * particle.vz;
-        }
-    });
-    boundary(particles, wd, lg, hg, x0, y0, z0);
-    
-    tree.build_tree(s_, alpha_, beta_, particles, 1.0e-02);
-    smoothing_length(particles, dm, eta, f, dfdq, sigma, rkern, 1e-03, 30, dt, tree, s_, n, ptr, wd, lg, hg, x0, y0, z0, xperiodic, yperiodic, zperiodic);
-
-    accelerations(particles, dm, eos_type, eos, cs, gamma, coeff, dwdh_, f, dfdq, sigma, rkern, tree, s_, n, ptr, wd, lg, hg, x0, y0, z0, artificial_viscosity, body_forces, star, bf, xperiodic, yperiodic, zperiodic);
-    particles.par_iter_mut().for_each(|particle|{
-        if particle.ptype==0 {
-            particle.vx += 0.5 * dt * particle.ax;
-            particle.vy += 0.5 * dt * particle.ay;
-            particle.vz += 0.5 * dt * particle.az;
-            particle.u  += 0.5 * dt * particle.du;
-        }
-    });
+    forces: &[&dyn ExternalForce],
+    boundary: fn(&mut Vec<Particle>, f64, f64, f64, f64, f64, f64, f64, f64), xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64, vy_shear: f64, wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64
+) {
+    let mut derivatives = |particles: &mut Vec<Particle>, dt: f64| {
+        sph_derivatives(particles, dt, eos_type, eos, cs, gamma, coeff, dwdh_, f, dfdq, sigma, rkern, eta, tree, s_, alpha_, beta_, n, ptr,
+                        artificial_viscosity, forces, boundary, xperiodic, yperiodic, zperiodic, y_shear, vy_shear, wd, lg, hg, x0, y0, z0);
+    };
+    VelocityVerlet.step(particles, dt, &mut derivatives);
 }
 
 // ------------------------------------------------------------------------- //
@@ -1008,52 +1354,460 @@ pub fn velocity_verlet_integrator(
 //      velocities (half a step)                                             //
 // ------------------------------------------------------------------------- //
 pub fn predictor_kdk_integrator(
-    particles: &mut Vec<Particle>, dt:f64, dm:f64, eos_type: bool, eos: fn(f64, f64, f64, f64, f64, f64, f64)->f64, cs: fn(f64, f64, f64, f64, f64, f64, f64)->f64, gamma:f64, coeff: f64,
+    particles: &mut Vec<Particle>, dt:f64, eos_type: bool, eos: fn(f64, f64, f64, f64, f64, f64, f64)->f64, cs: fn(f64, f64, f64, f64, f64, f64, f64)->f64, gamma:f64, coeff: f64,
     dwdh_: fn(f64, fn(f64) -> f64, fn(f64) -> f64) -> f64, f: fn(f64) -> f64, dfdq: fn(f64) -> f64, sigma: f64, rkern: f64,
     eta: f64, tree: &mut Node, s_: i32, alpha_: f64, beta_:f64, n: usize, ptr : Pointer,
     artificial_viscosity: fn(f64, f64, f64, f64, f64, f64, f64) -> (f64, f64),
-    body_forces: fn(&mut Particle, &Star), star: &Star, bf: bool,
-    boundary: fn(&mut Vec<Particle>, f64, f64, f64, f64, f64, f64), xperiodic: bool, yperiodic:bool, zperiodic:bool, wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64
+    forces: &[&dyn ExternalForce],
+    boundary: fn(&mut Vec<Particle>, f64, f64, f64, f64, f64, f64, f64, f64), xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64, vy_shear: f64, wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64
 ) {
-    particles.par_iter_mut().for_each(|particle|{
-        if particle.ptype==0 {
-            particle.vx += 0.5 * dt * particle.ax;
-            particle.vy += 0.5 * dt * particle.ay;
-            particle.vz += 0.5 * dt * particle.az;
-            
-            particle.u  += 0.5 * dt * particle.du;
-            
-            particle.x  += dt * particle.vx;
-            particle.y  += dt * particle.vy;
-            particle.z  += dt * particle.vz;
-            
-            // Predictor 
-            particle.vx_star = particle.vx;
-            particle.vy_star = particle.vy;
-            particle.vz_star = particle.vz;
-            particle.u_star = particle.u;
-            
-            particle.vx += 0.5 * dt * particle.ax;
-            particle.vy += 0.5 * dt * particle.ay;
-            particle.vz += 0.5 * dt * particle.az;
-            
-            particle.u  += 0.5 * dt * particle.du;
+    let mut derivatives = |particles: &mut Vec<Particle>, dt: f64| {
+        sph_derivatives(particles, dt, eos_type, eos, cs, gamma, coeff, dwdh_, f, dfdq, sigma, rkern, eta, tree, s_, alpha_, beta_, n, ptr,
+                        artificial_viscosity, forces, boundary, xperiodic, yperiodic, zperiodic, y_shear, vy_shear, wd, lg, hg, x0, y0, z0);
+    };
+    Leapfrog.step(particles, dt, &mut derivatives);
+}
+
+
+// ------------------------------------------------------------------------- //
+// Block (hierarchical) Kick-Drift-Kick integrator                           //
+// Advances the system by dt_block, split in 2^nbins substeps of dt_min.     //
+// Each particle has its own step dt_block/2^ibin (the ibins must have been  //
+// set, e.g. with assign_bins, and every particle must be synchronised).     //
+// On each substep:                                                          //
+//      particles starting a step are kicked by half of their step,          //
+//      every gas particle drifts with its half step velocity,               //
+//      the velocities and energies are predicted to the end of the substep, //
+//      forces are computed only for particles ending their step (active),   //
+//      and these are kicked by the second half of their step.               //
+// After their kick, active particles may move to a smaller step, or to a    //
+// step twice as large when both bins are synchronised.                      //
+// With the limiter, the inactive neighbours that need a shorter step end    //
+// theirs on this substep too (see time_step_limiter).                       //
+// Every particle is synchronised (and active) at the end of dt_block.       //
+// The stars are advanced with sinks_integrator on every substep, so the gas //
+// feels them (together with forces) where they are at the time.             //
+// ------------------------------------------------------------------------- //
+pub fn block_kdk_integrator(
+    particles: &mut Vec<Particle>, dt_block:f64, nbins: u32, limiter: bool, eos_type: bool, eos: fn(f64, f64, f64, f64, f64, f64, f64)->f64, cs: fn(f64, f64, f64, f64, f64, f64, f64)->f64, gamma:f64, coeff: f64,
+    dwdh_: fn(f64, fn(f64) -> f64, fn(f64) -> f64) -> f64, f: fn(f64) -> f64, dfdq: fn(f64) -> f64, sigma: f64, rkern: f64,
+    eta: f64, tree: &mut Node, s_: i32, alpha_: f64, beta_:f64, n: usize, ptr : Pointer,
+    artificial_viscosity: fn(f64, f64, f64, f64, f64, f64, f64) -> (f64, f64),
+    forces: &[&dyn ExternalForce], stars: &mut [Star], eta_orb: f64,
+    boundary: fn(&mut Vec<Particle>, f64, f64, f64, f64, f64, f64, f64, f64), xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64, vy_shear: f64, wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64
+) {
+    let nsub: u64   = 1 << nbins;
+    let dt_min: f64 = dt_block/nsub as f64;
+
+    for tick in 0..nsub {
+        let tick_end: u64 = tick + 1;
+        particles.par_iter_mut().for_each(|particle|{
+            if particle.ptype==0 {
+                let len: u64    = 1 << (nbins - particle.ibin);
+                let dt_i: f64   = dt_min*len as f64;
+                // Opening kick
+                if tick.is_multiple_of(len) {
+                    particle.vx_star = particle.vx + 0.5 * dt_i * particle.ax;
+                    particle.vy_star = particle.vy + 0.5 * dt_i * particle.ay;
+                    particle.vz_star = particle.vz + 0.5 * dt_i * particle.az;
+                    particle.u_star  = particle.u  + 0.5 * dt_i * particle.du;
+                }
+                // Drift
+                particle.x  += dt_min * particle.vx_star;
+                particle.y  += dt_min * particle.vy_star;
+                particle.z  += dt_min * particle.vz_star;
+
+                // Predictor
+                let elapsed: f64 = (tick_end - (tick - tick%len)) as f64 * dt_min - 0.5 * dt_i;
+                particle.vx = particle.vx_star + elapsed * particle.ax;
+                particle.vy = particle.vy_star + elapsed * particle.ay;
+                particle.vz = particle.vz_star + elapsed * particle.az;
+                particle.u  = particle.u_star  + elapsed * particle.du;
+
+                particle.active = tick_end.is_multiple_of(len);
+            }
+        });
+        boundary(particles, wd, lg, hg, x0, y0, z0, y_shear, vy_shear);
+        sinks_integrator(stars, dt_min, eta_orb);
+        if !particles.iter().any(|particle| particle.ptype==0 && particle.active) {
+            continue;
         }
+        let mut all_forces: Vec<&dyn ExternalForce> = forces.to_vec();
+        all_forces.extend(stars.iter().map(|star| star as &dyn ExternalForce));
+        tree.restart(n);
+        tree.build_tree(s_, alpha_, beta_, particles, 1.0e-02);
+        smoothing_length(particles, eta, f, dfdq, sigma, rkern, 1e-03, 30, dt_min, tree, s_, n, ptr, wd, lg, hg, x0, y0, z0, xperiodic, yperiodic, zperiodic, y_shear);
+        accelerations(particles, eos_type, eos, cs, gamma, coeff, dwdh_, f, dfdq, sigma, rkern, tree, s_, n, ptr, wd, lg, hg, x0, y0, z0, artificial_viscosity, &all_forces, xperiodic, yperiodic, zperiodic, y_shear, vy_shear);
+        // Closing kick
+        particles.par_iter_mut().for_each(|particle|{
+            if particle.ptype==0 && particle.active {
+                let dt_i: f64 = dt_min*(1_u64 << (nbins - particle.ibin)) as f64;
+                particle.vx = particle.vx_star + 0.5 * dt_i * particle.ax;
+                particle.vy = particle.vy_star + 0.5 * dt_i * particle.ay;
+                particle.vz = particle.vz_star + 0.5 * dt_i * particle.az;
+                particle.u  = particle.u_star  + 0.5 * dt_i * particle.du;
+            }
+        });
+        // New bins for the particles starting a step
+        if tick_end < nsub {
+            let dts: Vec<f64> = time_step_mon_particles(particles, n, gamma, coeff, rkern, wd, lg, hg, x0, y0, z0, tree, s_, cs, xperiodic, yperiodic, zperiodic, y_shear, vy_shear);
+            particles.par_iter_mut().zip(dts.par_iter()).for_each(|(particle, dt)|{
+                if particle.ptype==0 && particle.active {
+                    let ibin: u32 = bin_from_dt(dt_block, *dt, nbins);
+                    let len: u64  = 1 << (nbins - particle.ibin);
+                    if ibin > particle.ibin {
+                        particle.ibin = ibin;
+                    } else if ibin < particle.ibin && tick_end.is_multiple_of(2*len) {
+                        particle.ibin -= 1;
+                    }
+                }
+            });
+
+            // Timestep limiter: the woken particles end their step now
+            if limiter {
+                let woken: Vec<(usize, u32)> = time_step_limiter(particles, dt_block, nbins, n, gamma, coeff, rkern, wd, lg, hg, x0, y0, z0, tree, s_, cs, xperiodic, yperiodic, zperiodic, y_shear, vy_shear);
+                if !woken.is_empty() {
+                    let mut dts: Vec<f64> = vec![0.0; n];
+                    particles.par_iter_mut().for_each(|particle|{
+                        particle.active = false;
+                    });
+                    for (jj, ibin) in woken {
+                        let particle: &mut Particle = &mut particles[jj];
+                        let len: u64    = 1 << (nbins - particle.ibin);
+                        let dt_old: f64 = dt_min*len as f64;
+                        let dt_new: f64 = (tick_end - (tick - tick%len)) as f64 * dt_min;
+                        // Undo the part of the opening kick beyond the new step
+                        particle.vx_star -= 0.5 * (dt_old - dt_new) * particle.ax;
+                        particle.vy_star -= 0.5 * (dt_old - dt_new) * particle.ay;
+                        particle.vz_star -= 0.5 * (dt_old - dt_new) * particle.az;
+                        particle.u_star  -= 0.5 * (dt_old - dt_new) * particle.du;
+                        // The next step must start at a multiple of its length
+                        particle.ibin = ibin;
+                        while !tick_end.is_multiple_of(1 << (nbins - particle.ibin)) {
+                            particle.ibin += 1;
+                        }
+                        particle.active = true;
+                        dts[jj] = dt_new;
+                    }
+                    tree.restart(n);
+                    tree.build_tree(s_, alpha_, beta_, particles, 1.0e-02);
+                    smoothing_length(particles, eta, f, dfdq, sigma, rkern, 1e-03, 30, dt_min, tree, s_, n, ptr, wd, lg, hg, x0, y0, z0, xperiodic, yperiodic, zperiodic, y_shear);
+                    accelerations(particles, eos_type, eos, cs, gamma, coeff, dwdh_, f, dfdq, sigma, rkern, tree, s_, n, ptr, wd, lg, hg, x0, y0, z0, artificial_viscosity, &all_forces, xperiodic, yperiodic, zperiodic, y_shear, vy_shear);
+                    particles.par_iter_mut().zip(dts.par_iter()).for_each(|(particle, dt_i)|{
+                        if particle.ptype==0 && particle.active {
+                            particle.vx = particle.vx_star + 0.5 * dt_i * particle.ax;
+                            particle.vy = particle.vy_star + 0.5 * dt_i * particle.ay;
+                            particle.vz = particle.vz_star + 0.5 * dt_i * particle.az;
+                            particle.u  = particle.u_star  + 0.5 * dt_i * particle.du;
+                        }
+                    });
+                }
+            }
+        }
+    }
+    particles.par_iter_mut().for_each(|particle|{
+        particle.active = true;
     });
-    boundary(particles, wd, lg, hg, x0, y0, z0);
+}
+
+// ***------------------------ Integrator trait -------------------------*** //
+
+// ------------------------------------------------------------------------- //
+// SPH derivatives of the current state.                                     //
+// Applies the boundary conditions, builds the tree and updates the          //
+// smoothing lengths, densities, accelerations and du of the particles.      //
+// dt is the time since the previous evaluation (used to guess h).           //
+// ------------------------------------------------------------------------- //
+pub fn sph_derivatives(
+    particles: &mut Vec<Particle>, dt:f64, eos_type: bool, eos: fn(f64, f64, f64, f64, f64, f64, f64)->f64, cs: fn(f64, f64, f64, f64, f64, f64, f64)->f64, gamma:f64, coeff: f64,
+    dwdh_: fn(f64, fn(f64) -> f64, fn(f64) -> f64) -> f64, f: fn(f64) -> f64, dfdq: fn(f64) -> f64, sigma: f64, rkern: f64,
+    eta: f64, tree: &mut Node, s_: i32, alpha_: f64, beta_:f64, n: usize, ptr : Pointer,
+    artificial_viscosity: fn(f64, f64, f64, f64, f64, f64, f64) -> (f64, f64),
+    forces: &[&dyn ExternalForce],
+    boundary: fn(&mut Vec<Particle>, f64, f64, f64, f64, f64, f64, f64, f64), xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64, vy_shear: f64, wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64
+) {
+    boundary(particles, wd, lg, hg, x0, y0, z0, y_shear, vy_shear);
+    tree.restart(n);
     tree.build_tree(s_, alpha_, beta_, particles, 1.0e-02);
-    smoothing_length(particles, dm, eta, f, dfdq, sigma, rkern, 1e-03, 30, dt, tree, s_, n, ptr, wd, lg, hg, x0, y0, z0, xperiodic, yperiodic, zperiodic);
-    accelerations(particles, dm, eos_type, eos, cs, gamma, coeff, dwdh_, f, dfdq, sigma, rkern, tree, s_, n, ptr, wd, lg, hg, x0, y0, z0, artificial_viscosity, body_forces, star, bf, xperiodic, yperiodic, zperiodic);
-    particles.par_iter_mut().for_each(|particle|{
-        if particle.ptype==0 {
-            particle.vx = particle.vx_star + 0.5 * dt * particle.ax;
-            particle.vy = particle.vy_star + 0.5 * dt * particle.ay;
-            particle.vz = particle.vz_star + 0.5 * dt * particle.az;
-            particle.u = particle.u_star + 0.5 * dt * particle.du;
+    smoothing_length(particles, eta, f, dfdq, sigma, rkern, 1e-03, 30, dt, tree, s_, n, ptr, wd, lg, hg, x0, y0, z0, xperiodic, yperiodic, zperiodic, y_shear);
+    accelerations(particles, eos_type, eos, cs, gamma, coeff, dwdh_, f, dfdq, sigma, rkern, tree, s_, n, ptr, wd, lg, hg, x0, y0, z0, artificial_viscosity, forces, xperiodic, yperiodic, zperiodic, y_shear, vy_shear);
+}
+
+// ------------------------------------------------------------------------- //
+// Time integrator of the gas particles.                                     //
+// A step only needs to evaluate the derivatives (ax, ay, az, du) of the     //
+// current state, e.g. with a closure calling sph_derivatives:               //
+//      derivatives(particles, dt_since_last_evaluation)                     //
+// On entry, the derivatives must belong to the current state (as they are   //
+// after the previous step).                                                 //
+// ------------------------------------------------------------------------- //
+pub trait Integrator {
+    fn step(&self, particles: &mut Vec<Particle>, dt: f64, derivatives: &mut dyn FnMut(&mut Vec<Particle>, f64));
+}
+
+// Velocity Verlet (second order), Verlet (1967)
+pub struct VelocityVerlet;
+
+// Kick-Drift-Kick leapfrog (second order), with the velocities predicted to
+// the end of the step for the force evaluation
+pub struct Leapfrog;
+
+// Midpoint Runge-Kutta (second order)
+pub struct RungeKutta2;
+
+// Classic Runge-Kutta (fourth order)
+pub struct RungeKutta4;
+
+// Symplectic fourth order integrator: three leapfrog steps, Yoshida (1990)
+pub struct Yoshida4;
+
+impl Integrator for VelocityVerlet {
+    fn step(&self, particles: &mut Vec<Particle>, dt: f64, derivatives: &mut dyn FnMut(&mut Vec<Particle>, f64)) {
+        particles.par_iter_mut().for_each(|particle|{
+            if particle.ptype==0 {
+                particle.vx += 0.5 * dt * particle.ax;
+                particle.vy += 0.5 * dt * particle.ay;
+                particle.vz += 0.5 * dt * particle.az;
+
+                particle.u  += 0.5 * dt * particle.du;
+
+                particle.x  += dt * particle.vx;
+                particle.y  += dt * particle.vy;
+                particle.z  += dt * particle.vz;
+            }
+        });
+        derivatives(particles, dt);
+        particles.par_iter_mut().for_each(|particle|{
+            if particle.ptype==0 {
+                particle.vx += 0.5 * dt * particle.ax;
+                particle.vy += 0.5 * dt * particle.ay;
+                particle.vz += 0.5 * dt * particle.az;
+                particle.u  += 0.5 * dt * particle.du;
+            }
+        });
+    }
+}
+
+impl Integrator for Leapfrog {
+    fn step(&self, particles: &mut Vec<Particle>, dt: f64, derivatives: &mut dyn FnMut(&mut Vec<Particle>, f64)) {
+        particles.par_iter_mut().for_each(|particle|{
+            if particle.ptype==0 {
+                particle.vx += 0.5 * dt * particle.ax;
+                particle.vy += 0.5 * dt * particle.ay;
+                particle.vz += 0.5 * dt * particle.az;
+                
+                particle.u  += 0.5 * dt * particle.du;
+                
+                particle.x  += dt * particle.vx;
+                particle.y  += dt * particle.vy;
+                particle.z  += dt * particle.vz;
+                
+                // Predictor 
+                particle.vx_star = particle.vx;
+                particle.vy_star = particle.vy;
+                particle.vz_star = particle.vz;
+                particle.u_star = particle.u;
+                
+                particle.vx += 0.5 * dt * particle.ax;
+                particle.vy += 0.5 * dt * particle.ay;
+                particle.vz += 0.5 * dt * particle.az;
+                
+                particle.u  += 0.5 * dt * particle.du;
+            }
+        });
+        derivatives(particles, dt);
+        particles.par_iter_mut().for_each(|particle|{
+            if particle.ptype==0 {
+                particle.vx = particle.vx_star + 0.5 * dt * particle.ax;
+                particle.vy = particle.vy_star + 0.5 * dt * particle.ay;
+                particle.vz = particle.vz_star + 0.5 * dt * particle.az;
+                particle.u = particle.u_star + 0.5 * dt * particle.du;
+            }
+        });
+    }
+}
+
+impl Integrator for RungeKutta2 {
+    fn step(&self, particles: &mut Vec<Particle>, dt: f64, derivatives: &mut dyn FnMut(&mut Vec<Particle>, f64)) {
+        let y0: Vec<[f64; 7]> = rk_state(particles);
+        // k1: the derivatives on entry
+        let k1: Vec<[f64; 7]> = rk_rates(particles);
+        rk_update(particles, &y0, &k1, 0.5*dt);
+        // k2 at t + dt/2
+        derivatives(particles, 0.5*dt);
+        let k2: Vec<[f64; 7]> = rk_rates(particles);
+        rk_update(particles, &y0, &k2, dt);
+        // Derivatives of y(t + dt): not used by this step, but they are the
+        // k1 of the next one (see Integrator) and the accelerations the
+        // time step and the diagnostics are taken from
+        derivatives(particles, 0.5*dt);
+    }
+}
+
+impl Integrator for RungeKutta4 {
+    fn step(&self, particles: &mut Vec<Particle>, dt: f64, derivatives: &mut dyn FnMut(&mut Vec<Particle>, f64)) {
+        let y0: Vec<[f64; 7]> = rk_state(particles);
+        // k1: the derivatives on entry
+        let mut k: Vec<[f64; 7]> = rk_rates(particles);
+        let mut k_sum: Vec<[f64; 7]> = k.clone();
+        // k2 (t + dt/2), k3 (t + dt/2), k4 (t + dt), with the time elapsed
+        // since the previous evaluation
+        for (c, w, dt_eval) in [(0.5, 2.0, 0.5*dt), (0.5, 2.0, 0.0), (1.0, 1.0, 0.5*dt)] {
+            rk_update(particles, &y0, &k, c*dt);
+            derivatives(particles, dt_eval);
+            k = rk_rates(particles);
+            k_sum.par_iter_mut().zip(k.par_iter()).for_each(|(k_sum, k)|{
+                for ll in 0..7 {
+                    k_sum[ll] += w*k[ll];
+                }
+            });
         }
-    });
+        rk_update(particles, &y0, &k_sum, dt/6.0);
+        // Derivatives of y(t + dt), the k1 of the next step (see RungeKutta2)
+        derivatives(particles, 0.0);
+    }
+}
+
+impl Integrator for Yoshida4 {
+    fn step(&self, particles: &mut Vec<Particle>, dt: f64, derivatives: &mut dyn FnMut(&mut Vec<Particle>, f64)) {
+        let w1: f64 = 1.0/(2.0 - 2.0_f64.cbrt());
+        let w0: f64 = 1.0 - 2.0*w1;
+        for w in [w1, w0, w1] {
+            Leapfrog.step(particles, w*dt, derivatives);
+        }
+    }
+}
+
+// ------------------------------------------------------------------------- //
+// Kick-Drift-Kick leapfrog in a frame rotating with angular velocity w.     //
+// The derivatives must not include the Coriolis term (use RotatingFrame     //
+// with coriolis = false). Each kick treats it implicitly,                   //
+//      v1 = v0 + dt_k*(a - w x (v0 + v1)),                                  //
+// which keeps |v| for the Coriolis part, as it does no work.                //
+// ------------------------------------------------------------------------- //
+pub struct CoriolisLeapfrog {
+    pub wx: f64,
+    pub wy: f64,
+    pub wz: f64,
 }
 
+impl CoriolisLeapfrog {
+    // Implicit kick: solves v1 + w_k x v1 = b, with w_k = dt_k*w and
+    //      b = v0 + dt_k*a - w_k x v0,
+    // as v1 = (b + (w_k.b) w_k - w_k x b)/(1 + w_k.w_k)
+    fn kick(&self, vx: f64, vy: f64, vz: f64, ax: f64, ay: f64, az: f64, dt_k: f64) -> (f64, f64, f64) {
+        let (wx, wy, wz) = (dt_k*self.wx, dt_k*self.wy, dt_k*self.wz);
+        let (cx, cy, cz) = cross(wx, wy, wz, vx, vy, vz);
+        let (bx, by, bz) = (vx + dt_k*ax - cx, vy + dt_k*ay - cy, vz + dt_k*az - cz);
+        let wb: f64 = wx*bx + wy*by + wz*bz;
+        let (cx, cy, cz) = cross(wx, wy, wz, bx, by, bz);
+        let inv: f64 = 1.0/(1.0 + wx*wx + wy*wy + wz*wz);
+        ((bx + wb*wx - cx)*inv, (by + wb*wy - cy)*inv, (bz + wb*wz - cz)*inv)
+    }
+}
+
+impl Integrator for CoriolisLeapfrog {
+    fn step(&self, particles: &mut Vec<Particle>, dt: f64, derivatives: &mut dyn FnMut(&mut Vec<Particle>, f64)) {
+        particles.par_iter_mut().for_each(|particle|{
+            if particle.ptype==0 {
+                (particle.vx, particle.vy, particle.vz) = self.kick(particle.vx, particle.vy, particle.vz, particle.ax, particle.ay, particle.az, 0.5*dt);
+                particle.u  += 0.5 * dt * particle.du;
+
+                particle.x  += dt * particle.vx;
+                particle.y  += dt * particle.vy;
+                particle.z  += dt * particle.vz;
+
+                // Predictor
+                particle.vx_star = particle.vx;
+                particle.vy_star = particle.vy;
+                particle.vz_star = particle.vz;
+                particle.u_star = particle.u;
+
+                (particle.vx, particle.vy, particle.vz) = self.kick(particle.vx, particle.vy, particle.vz, particle.ax, particle.ay, particle.az, 0.5*dt);
+                particle.u  += 0.5 * dt * particle.du;
+            }
+        });
+        derivatives(particles, dt);
+        particles.par_iter_mut().for_each(|particle|{
+            if particle.ptype==0 {
+                (particle.vx, particle.vy, particle.vz) = self.kick(particle.vx_star, particle.vy_star, particle.vz_star, particle.ax, particle.ay, particle.az, 0.5*dt);
+                particle.u = particle.u_star + 0.5 * dt * particle.du;
+            }
+        });
+    }
+}
+
+// ------------------------------------------------------------------------- //
+// Transforms particles from a frame rotating with angular velocity w about  //
+// the origin to the inertial frame, at time t (both frames coincide at      //
+// t = 0). Positions are rotated by |w|*t about w (Rodrigues' formula), and  //
+// velocities are rotated after adding w x r.                                //
+// Returns the transformed copy of the particles.                            //
+// ------------------------------------------------------------------------- //
+pub fn inertial_frame(
+    particles: & Vec<Particle>, wx: f64, wy: f64, wz: f64, t: f64
+) -> Vec<Particle> {
+    let w: f64 = (wx*wx + wy*wy + wz*wz).sqrt();
+    let (ex, ey, ez) = if w > 0.0 {(wx/w, wy/w, wz/w)} else {(0.0, 0.0, 1.0)};
+    let (sin, cos) = (w*t).sin_cos();
+    // v_rot = cos*v + sin*(e x v) + (1 - cos)*(e.v)*e
+    let rotate = |vx: f64, vy: f64, vz: f64| -> (f64, f64, f64) {
+        let (cx, cy, cz) = cross(ex, ey, ez, vx, vy, vz);
+        let ev: f64 = (ex*vx + ey*vy + ez*vz)*(1.0 - cos);
+        (cos*vx + sin*cx + ev*ex, cos*vy + sin*cy + ev*ey, cos*vz + sin*cz + ev*ez)
+    };
+    particles.par_iter().map(|particle| {
+        let mut inertial: Particle = particle.clone();
+        let (cx, cy, cz) = cross(wx, wy, wz, particle.x, particle.y, particle.z);
+        (inertial.x, inertial.y, inertial.z) = rotate(particle.x, particle.y, particle.z);
+        (inertial.vx, inertial.vy, inertial.vz) = rotate(particle.vx + cx, particle.vy + cy, particle.vz + cz);
+        return inertial;
+    }).collect()
+}
+
+// ------------------------------------------------------------------------- //
+// Runge-Kutta helpers. The state of a gas particle is                       //
+//      y = (x, y, z, vx, vy, vz, u),                                        //
+// and its rates dy/dt = (vx, vy, vz, ax, ay, az, du). Other particles are   //
+// left as they are.                                                         //
+// The stages are stored by particle index, so the derivatives must not add  //
+// or remove particles (e.g. accrete them) within a step.                    //
+// ------------------------------------------------------------------------- //
+fn rk_state(
+    particles: & Vec<Particle>
+) -> Vec<[f64; 7]> {
+    particles.par_iter().map(|p| [p.x, p.y, p.z, p.vx, p.vy, p.vz, p.u]).collect()
+}
+
+fn rk_rates(
+    particles: & Vec<Particle>
+) -> Vec<[f64; 7]> {
+    particles.par_iter().map(|p| {
+        if p.ptype==0 {
+            return [p.vx, p.vy, p.vz, p.ax, p.ay, p.az, p.du];
+        }
+        return [0.0; 7];
+    }).collect()
+}
+
+// y = y0 + c*k
+fn rk_update(
+    particles: &mut Vec<Particle>, y0: & Vec<[f64; 7]>, k: & Vec<[f64; 7]>, c: f64
+) {
+    assert_eq!(particles.len(), y0.len(), "the particle count changed within a Runge-Kutta step");
+    particles.par_iter_mut().zip(y0.par_iter().zip(k.par_iter())).for_each(|(p, (y0, k))|{
+        if p.ptype!=0 {
+            return;
+        }
+        p.x  = y0[0] + c*k[0];
+        p.y  = y0[1] + c*k[1];
+        p.z  = y0[2] + c*k[2];
+        p.vx = y0[3] + c*k[3];
+        p.vy = y0[4] + c*k[4];
+        p.vz = y0[5] + c*k[5];
+        p.u  = y0[6] + c*k[6];
+    });
+}
 
 // ***----------------------- Boundary conditions -----------------------*** //
 
@@ -1061,23 +1815,32 @@ pub fn predictor_kdk_integrator(
 // None Boundary Condition                                                   //
 // ------------------------------------------------------------------------- //
 pub fn none_boundary(
-    _particles: &mut Vec<Particle>, _wd: f64, _lg: f64, _hg: f64, _x0:f64, _y0: f64, _z0: f64
+    _particles: &mut Vec<Particle>, _wd: f64, _lg: f64, _hg: f64, _x0:f64, _y0: f64, _z0: f64, _y_shear: f64, _vy_shear: f64
 ){
 }
 
 // ------------------------------------------------------------------------- //
 // Periodic Boundary Conditions                                              //
+// For a shearing box, the image of the domain at x + wd is shifted by       //
+// y_shear in y (0 <= y_shear < lg) and moves with vy_shear in y, see        //
+// shear_offsets. Particles crossing the x-boundaries take these shifts.     //
 // ------------------------------------------------------------------------- //
 pub fn periodic_boundary(
-    particles: &mut Vec<Particle>, wd: f64, lg: f64, hg: f64, x0:f64, y0: f64, z0: f64
+    particles: &mut Vec<Particle>, wd: f64, lg: f64, hg: f64, x0:f64, y0: f64, z0: f64, y_shear: f64, vy_shear: f64
 ){
     // We assume that the system's domain is a rectangular box.
     particles.par_iter_mut().for_each(|particle|{
         if particle.ptype==0 {
             if particle.x >= (wd+x0) {
                 particle.x -= wd;
+                particle.y -= y_shear;
+                particle.vy -= vy_shear;
+                particle.vy_star -= vy_shear;
             } else if particle.x < x0 {
                 particle.x += wd;
+                particle.y += y_shear;
+                particle.vy += vy_shear;
+                particle.vy_star += vy_shear;
             }
             if particle.y >= (lg + y0) {
                 particle.y -= lg;
@@ -1093,6 +1856,20 @@ pub fn periodic_boundary(
     });
 }
 
+// ------------------------------------------------------------------------- //
+// Shearing box offsets at time t, for a local frame rotating with angular   //
+// velocity omega and background shear flow vy = -q*omega*(x - xc):          //
+//      vy_shear = -q*omega*wd                                               //
+//      y_shear  = vy_shear*t  (mod lg)                                      //
+// Returns (y_shear, vy_shear).                                              //
+// ------------------------------------------------------------------------- //
+pub fn shear_offsets(
+    q: f64, omega: f64, wd: f64, lg: f64, t: f64
+) -> (f64, f64) {
+    let vy_shear: f64 = -q*omega*wd;
+    return ((vy_shear*t).rem_euclid(lg), vy_shear);
+}
+
 // ------------------------------------------------------------------------- //
 // Open Boundary Conditions                                              //
 // ------------------------------------------------------------------------- //
@@ -1118,13 +1895,13 @@ pub fn open_boundary(
 // Non-Periodic Boundary Conditions                                              //
 // ------------------------------------------------------------------------- //
 pub fn box_boundary(
-    particles: &mut Vec<Particle>, wd: f64, lg: f64, hg: f64, x0:f64, y0: f64, z0: f64
+    particles: &mut Vec<Particle>, wd: f64, lg: f64, hg: f64, x0:f64, y0: f64, z0: f64, _y_shear: f64, _vy_shear: f64
 ){
     // We assume that the system's domain is a rectangular box.
     particles.par_iter_mut().for_each(|particle|{
         if particle.ptype==0 {
             if (particle.x >= (wd+x0)) || (particle.x < x0) || (particle.y >= (lg + y0)) || (particle.y < y0)
-                || (particle.z >= (hg + z0)) || (particle.y < z0){
+                || (particle.z >= (hg + z0)) || (particle.z < z0){
                 particle.ptype = 2;
             }
         }
@@ -1132,25 +1909,487 @@ pub fn box_boundary(
 }
 
 // ------------------------------------------------------------------------- //
-// Accretion onto sink particles                                             //
+// Reflective walls at the faces of the domain on the axes rwall.            //
+// Particles that crossed a wall are mirrored back into the domain and their //
+// normal velocity is reversed.                                              //
 // ------------------------------------------------------------------------- //
-pub fn accretion_boundary(
-    star: &mut Star, particles: &mut Vec<Particle>, dm:f64, n: &mut usize, tree: & Node, s_: i32, wd: f64, lg: f64, hg: f64, x0:f64, y0: f64, z0: f64, xperiodic: bool, yperiodic:bool, zperiodic:bool
+pub fn reflective_boundary(
+    particles: &mut Vec<Particle>, wd: f64, lg: f64, hg: f64, x0:f64, y0: f64, z0: f64,
+    xwall: bool, ywall: bool, zwall: bool
 ){
-    for ii in 0..*n {
-        if particles[ii].ptype == 2 {
-            particles.remove(ii);
-            *n -= 1;
+    particles.par_iter_mut().for_each(|particle|{
+        if particle.ptype==0 {
+            if xwall && (particle.x < x0 || particle.x > x0 + wd) {
+                particle.x = if particle.x < x0 {2.0*x0 - particle.x} else {2.0*(x0 + wd) - particle.x};
+                particle.vx = -particle.vx;
+                particle.vx_star = -particle.vx_star;
+            }
+            if ywall && (particle.y < y0 || particle.y > y0 + lg) {
+                particle.y = if particle.y < y0 {2.0*y0 - particle.y} else {2.0*(y0 + lg) - particle.y};
+                particle.vy = -particle.vy;
+                particle.vy_star = -particle.vy_star;
+            }
+            if zwall && (particle.z < z0 || particle.z > z0 + hg) {
+                particle.z = if particle.z < z0 {2.0*z0 - particle.z} else {2.0*(z0 + hg) - particle.z};
+                particle.vz = -particle.vz;
+                particle.vz_star = -particle.vz_star;
+            }
+        }
+    });
+}
+
+// ------------------------------------------------------------------------- //
+// Mirror (ghost) particles for reflective walls.                            //
+// Every gas particle closer than rkern*h to a wall gets a ghost (ptype = 3) //
+// mirrored through it, with reversed normal velocity. Near edges and        //
+// corners the combined mirrors are created too. Ghosts are appended at the  //
+// end of the array, so they enter density and force sums but are never      //
+// integrated. The number of particles and the pointer are updated.          //
+// Returns the index of the source particle of each ghost.                   //
+// ------------------------------------------------------------------------- //
+pub fn mirror_ghosts(
+    particles: &mut Vec<Particle>, n: &mut usize, ptr: &mut Pointer, rkern: f64,
+    wd: f64, lg: f64, hg: f64, x0:f64, y0: f64, z0: f64, xwall: bool, ywall: bool, zwall: bool
+) -> Vec<usize> {
+    let mut sources: Vec<usize> = Vec::new();
+    let mut ghosts: Vec<Particle> = Vec::new();
+    for ii in 0..particles.len() {
+        if particles[ii].ptype != 0 {
+            continue;
         }
+        let radius: f64 = rkern*particles[ii].h;
+        // Mirror planes of each axis (None: not reflected)
+        let walls = |wall: bool, r: f64, l0: f64, l: f64| -> Vec<Option<f64>> {
+            let mut planes: Vec<Option<f64>> = vec![None];
+            if wall && r - l0 < radius {
+                planes.push(Some(l0));
+            }
+            if wall && l0 + l - r < radius {
+                planes.push(Some(l0 + l));
+            }
+            planes
+        };
+        let x_planes: Vec<Option<f64>> = walls(xwall, particles[ii].x, x0, wd);
+        let y_planes: Vec<Option<f64>> = walls(ywall, particles[ii].y, y0, lg);
+        let z_planes: Vec<Option<f64>> = walls(zwall, particles[ii].z, z0, hg);
+        for x_plane in &x_planes {
+            for y_plane in &y_planes {
+                for z_plane in &z_planes {
+                    if x_plane.is_none() && y_plane.is_none() && z_plane.is_none() {
+                        continue;
+                    }
+                    let mut ghost: Particle = particles[ii].clone();
+                    ghost.ptype = 3;
+                    if let Some(plane) = x_plane {
+                        ghost.x = 2.0*plane - ghost.x;
+                        ghost.vx = -ghost.vx;
+                        ghost.vx_star = -ghost.vx_star;
+                    }
+                    if let Some(plane) = y_plane {
+                        ghost.y = 2.0*plane - ghost.y;
+                        ghost.vy = -ghost.vy;
+                        ghost.vy_star = -ghost.vy_star;
+                    }
+                    if let Some(plane) = z_plane {
+                        ghost.z = 2.0*plane - ghost.z;
+                        ghost.vz = -ghost.vz;
+                        ghost.vz_star = -ghost.vz_star;
+                    }
+                    ghosts.push(ghost);
+                    sources.push(ii);
+                }
+            }
+        }
+    }
+    particles.append(&mut ghosts);
+    *n = particles.len();
+    *ptr = Pointer(particles.as_mut_ptr());
+    sources
+}
+
+// ------------------------------------------------------------------------- //
+// Copies the smoothing length, density and internal energy of the sources   //
+// to their ghosts (at the end of the array, see mirror_ghosts).             //
+// ------------------------------------------------------------------------- //
+pub fn update_ghosts(
+    particles: &mut Vec<Particle>, sources: &[usize]
+){
+    let n0: usize = particles.len() - sources.len();
+    for (kk, ii) in sources.iter().enumerate() {
+        particles[n0 + kk].h   = particles[*ii].h;
+        particles[n0 + kk].rho = particles[*ii].rho;
+        particles[n0 + kk].u   = particles[*ii].u;
+    }
+}
+
+// ------------------------------------------------------------------------- //
+// Removes the ghost particles (ptype = 3).                                  //
+// The number of particles and the pointer to the array are updated.         //
+// ------------------------------------------------------------------------- //
+pub fn remove_ghosts(
+    particles: &mut Vec<Particle>, n: &mut usize, ptr: &mut Pointer
+){
+    particles.retain(|particle| particle.ptype != 3);
+    *n = particles.len();
+    *ptr = Pointer(particles.as_mut_ptr());
+}
+
+// ------------------------------------------------------------------------- //
+// Prescribed motion of a rigid body of boundary particles (ptype = 1).      //
+// The displacement is measured from the position of the body at t = 0, and  //
+// the velocity and acceleration are its time derivatives.                   //
+// ------------------------------------------------------------------------- //
+pub trait PrescribedMotion: Sync {
+    fn displacement(&self, t: f64) -> (f64, f64, f64);
+    fn velocity(&self, t: f64) -> (f64, f64, f64);
+    fn acceleration(&self, t: f64) -> (f64, f64, f64);
+}
+
+// ------------------------------------------------------------------------- //
+// Piston moving with constant velocity:                                     //
+//      x(t) = x(0) + v*t                                                    //
+// ------------------------------------------------------------------------- //
+pub struct Piston {
+    pub vx: f64,
+    pub vy: f64,
+    pub vz: f64,
+}
+
+impl PrescribedMotion for Piston {
+    fn displacement(&self, t: f64) -> (f64, f64, f64) {
+        (self.vx*t, self.vy*t, self.vz*t)
+    }
+    fn velocity(&self, _t: f64) -> (f64, f64, f64) {
+        (self.vx, self.vy, self.vz)
+    }
+    fn acceleration(&self, _t: f64) -> (f64, f64, f64) {
+        (0.0, 0.0, 0.0)
+    }
+}
+
+// ------------------------------------------------------------------------- //
+// Wall sliding along itself with constant velocity (e.g. the lid of a       //
+// cavity). The particles stay in place, as for an infinitely long wall,     //
+// but the fluid feels the wall velocity v.                                  //
+// ------------------------------------------------------------------------- //
+pub struct SlidingWall {
+    pub vx: f64,
+    pub vy: f64,
+    pub vz: f64,
+}
+
+impl PrescribedMotion for SlidingWall {
+    fn displacement(&self, _t: f64) -> (f64, f64, f64) {
+        (0.0, 0.0, 0.0)
+    }
+    fn velocity(&self, _t: f64) -> (f64, f64, f64) {
+        (self.vx, self.vy, self.vz)
+    }
+    fn acceleration(&self, _t: f64) -> (f64, f64, f64) {
+        (0.0, 0.0, 0.0)
+    }
+}
+
+// ------------------------------------------------------------------------- //
+// Harmonic oscillation with amplitude A and angular frequency omega:        //
+//      x(t) = x(0) + A*sin(omega*t)                                         //
+// ------------------------------------------------------------------------- //
+pub struct Oscillation {
+    pub ax: f64,
+    pub ay: f64,
+    pub az: f64,
+    pub omega: f64,
+}
+
+impl PrescribedMotion for Oscillation {
+    fn displacement(&self, t: f64) -> (f64, f64, f64) {
+        let s: f64 = (self.omega*t).sin();
+        (self.ax*s, self.ay*s, self.az*s)
+    }
+    fn velocity(&self, t: f64) -> (f64, f64, f64) {
+        let c: f64 = self.omega*(self.omega*t).cos();
+        (self.ax*c, self.ay*c, self.az*c)
+    }
+    fn acceleration(&self, t: f64) -> (f64, f64, f64) {
+        let s: f64 = -self.omega*self.omega*(self.omega*t).sin();
+        (self.ax*s, self.ay*s, self.az*s)
+    }
+}
+
+// ------------------------------------------------------------------------- //
+// Body of boundary particles: the particles with ptype = 1 and id in ids    //
+// follow the prescribed motion. Boundary particles that belong to no body   //
+// are fixed.                                                                //
+// ------------------------------------------------------------------------- //
+pub struct BoundaryBody<'a> {
+    pub ids: std::ops::Range<u64>,
+    pub motion: &'a dyn PrescribedMotion,
+}
+
+// ------------------------------------------------------------------------- //
+// Moves the boundary bodies from time t_old to t, and sets the velocity of  //
+// their particles to the velocity of the body.                              //
+// ------------------------------------------------------------------------- //
+pub fn move_boundaries(
+    particles: &mut Vec<Particle>, bodies: &[BoundaryBody], t_old: f64, t: f64
+){
+    for body in bodies {
+        let (dx_old, dy_old, dz_old) = body.motion.displacement(t_old);
+        let (dx, dy, dz) = body.motion.displacement(t);
+        let (vx, vy, vz) = body.motion.velocity(t);
+        particles.par_iter_mut().for_each(|particle|{
+            if particle.ptype==1 && body.ids.contains(&particle.id) {
+                particle.x += dx - dx_old;
+                particle.y += dy - dy_old;
+                particle.z += dz - dz_old;
+                particle.vx = vx;
+                particle.vy = vy;
+                particle.vz = vz;
+            }
+        });
     }
-    star.ax = 0.0;
-    star.ay = 0.0;
-    star.az = 0.0;
+}
 
+// ------------------------------------------------------------------------- //
+// State of the boundary particles (ptype = 1) extrapolated from their gas   //
+// neighbours, Adami, Hu & Adams (2012):                                     //
+//      p_w = [SUM_f p_f W_wf + (g - a_w).SUM_f rho_f r_wf W_wf]/SUM_f W_wf  //
+//      rho_w = SUM_f rho_f W_wf / SUM_f W_wf                                //
+//      v_w' = 2 v_w - SUM_f v_f W_wf / SUM_f W_wf                           //
+// where g is the external acceleration at the wall and v_w, a_w are the     //
+// velocity and acceleration of its body. The no-slip velocity v_w' is       //
+// stored in (vx, vy, vz) and enters the artificial viscosity. The pressure  //
+// is imposed through the ideal gas EoS, u_w = p_w/((gamma-1)*rho_w), and    //
+// clamped at zero. Boundary particles keep their smoothing length (they     //
+// are not part of the smoothing length iteration) and so the volume         //
+// (h/eta)^3 of their lattice site: their mass follows the extrapolated      //
+// density, m_w = rho_w*(h/eta)^3, as the gas next to them is compressed.    //
+// Those without gas neighbours keep their state.                            //
+// ------------------------------------------------------------------------- //
+pub fn boundary_particles(
+    particles: &mut Vec<Particle>, bodies: &[BoundaryBody], t: f64,
+    eos: fn(f64, f64, f64, f64, f64, f64, f64)->f64, gamma: f64, coeff: f64, eta: f64, f: fn(f64) -> f64, rkern: f64,
+    forces: &[&dyn ExternalForce], tree: &Node, s_: i32, n: usize, ptr : Pointer,
+    wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64, xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64
+){
+    (0..n).into_par_iter().for_each(|ii| {
+        if particles[ii].ptype==1 {
+            // Motion of the wall
+            let ((vx_w, vy_w, vz_w), (ax_w, ay_w, az_w)) = match bodies.iter().find(|body| body.ids.contains(&particles[ii].id)) {
+                Some(body) => (body.motion.velocity(t), body.motion.acceleration(t)),
+                None => ((0.0, 0.0, 0.0), (0.0, 0.0, 0.0)),
+            };
+            let (mut gx, mut gy, mut gz) = (0.0, 0.0, 0.0);
+            for force in forces {
+                let (ax, ay, az) = force.acceleration(&particles[ii]);
+                gx += ax;
+                gy += ay;
+                gz += az;
+            }
+
+            let mut neighbors: Vec<usize> = Vec::new();
+            tree.find_neighbors(ii, s_, particles, &mut neighbors, wd, lg, hg, x0, y0, z0, particles[ii].h*rkern, xperiodic, yperiodic, zperiodic, y_shear);
+
+            let mut sum_w: f64 = 0.0;
+            let mut sum_p: f64 = 0.0;
+            let mut sum_rho: f64 = 0.0;
+            let (mut sum_rx, mut sum_ry, mut sum_rz) = (0.0, 0.0, 0.0);
+            let (mut sum_vx, mut sum_vy, mut sum_vz) = (0.0, 0.0, 0.0);
+            for jj in &neighbors {
+                let particle_f: &Particle = &particles[*jj];
+                if particle_f.ptype != 0 {
+                    continue;
+                }
+                let (x_rel, y_rel, z_rel) = periodic_rel_vector(&particles[ii], particle_f, wd, lg, hg, rkern*particles[ii].h, xperiodic, yperiodic, zperiodic, y_shear);
+                let r: f64 = (x_rel*x_rel + y_rel*y_rel + z_rel*z_rel).sqrt();
+                // The normalisation of the kernel cancels out
+                let w: f64 = f(r/particles[ii].h);
+                let p_f: f64 = eos(particle_f.rho, particle_f.u, gamma, particle_f.x, particle_f.y, particle_f.z, coeff);
+                sum_w   += w;
+                sum_p   += p_f*w;
+                sum_rho += particle_f.rho*w;
+                sum_rx  += particle_f.rho*x_rel*w;
+                sum_ry  += particle_f.rho*y_rel*w;
+                sum_rz  += particle_f.rho*z_rel*w;
+                sum_vx  += particle_f.vx*w;
+                sum_vy  += particle_f.vy*w;
+                sum_vz  += particle_f.vz*w;
+            }
+            if sum_w > 0.0 {
+                let p_w: f64 = (sum_p + (gx - ax_w)*sum_rx + (gy - ay_w)*sum_ry + (gz - az_w)*sum_rz)/sum_w;
+                let particle = unsafe { &mut *{ptr}.0.add(ii)};
+                particle.rho = sum_rho/sum_w;
+                particle.m   = particle.rho*(particle.h/eta).powi(3);
+                particle.u   = p_w.max(0.0)/((gamma - 1.0)*particle.rho);
+                particle.vx  = 2.0*vx_w - sum_vx/sum_w;
+                particle.vy  = 2.0*vy_w - sum_vy/sum_w;
+                particle.vz  = 2.0*vz_w - sum_vz/sum_w;
+            }
+        }
+    });
+}
+
+// ------------------------------------------------------------------------- //
+// Open boundaries (inflow and outflow) through the faces of the domain on   //
+// axis (0: x, 1: y, 2: z). Next to each face, a buffer of width `buffer`    //
+// holds buffer particles (ptype = 4), which give kernel support to the gas  //
+// near the face. They enter density and force sums with a frozen state and  //
+// are not integrated: they just move along the axis with their velocity.    //
+// Open boundaries change the number of particles, so they are applied       //
+// between time steps rather than as the boundary of sph_derivatives. The    //
+// number of particles and the pointer are updated, and the tree (which must //
+// cover the buffers) has to be restarted afterwards.                        //
+// ------------------------------------------------------------------------- //
+
+// ------------------------------------------------------------------------- //
+// Inflow through the lower face with density rho, velocity v (along the     //
+// axis, v > 0) and internal energy u. The buffer in front of the face is a  //
+// cubic lattice of spacing dx; its particles become gas once they cross the //
+// face, and new layers are created at the back of the buffer.               //
+//  back:       position (on the axis) of the last layer created. Start with //
+//              the first layer of gas, e.g. face + 0.5*dx, so that the      //
+//              first call (dt = 0) fills the buffer.                        //
+//  next_id:    id of the next particle created.                             //
+// ------------------------------------------------------------------------- //
+pub struct Inflow {
+    pub axis: usize,
+    pub rho: f64,
+    pub v: f64,
+    pub u: f64,
+    pub dx: f64,
+    pub eta: f64,
+    pub buffer: f64,
+    pub back: f64,
+    pub next_id: u64,
+}
+
+// ------------------------------------------------------------------------- //
+// Advances the inflow buffer by dt, see Inflow. The particle array grows.   //
+// ------------------------------------------------------------------------- //
+pub fn inflow_boundary(
+    particles: &mut Vec<Particle>, n: &mut usize, ptr: &mut Pointer, inflow: &mut Inflow, dt: f64,
+    wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64
+){
+    // Face and cross section (l1 x l2 from (o1, o2)) of the inflow
+    let (face, l1, l2, o1, o2) = match inflow.axis {
+        0 => (x0, lg, hg, y0, z0),
+        1 => (y0, wd, hg, x0, z0),
+        _ => (z0, wd, lg, x0, y0),
+    };
+    let axis: usize = inflow.axis;
+
+    // Buffer particles in front of the face join the gas once they cross it
+    particles.par_iter_mut().for_each(|particle|{
+        if particle.ptype==4 {
+            let (coord, v) = match axis {0 => (&mut particle.x, particle.vx), 1 => (&mut particle.y, particle.vy), _ => (&mut particle.z, particle.vz)};
+            if *coord < face {
+                *coord += v*dt;
+                if *coord >= face {
+                    particle.ptype = 0;
+                }
+            }
+        }
+    });
+
+    // New layers at the back of the buffer
+    inflow.back += inflow.v*dt;
+    let dx: f64 = inflow.dx;
+    let m: f64  = inflow.rho*dx*dx*dx;
+    let h: f64  = h_from_density(m, inflow.rho, inflow.eta);
+    let n1: usize = (l1/dx).round() as usize;
+    let n2: usize = (l2/dx).round() as usize;
+    let (vx, vy, vz) = match axis {0 => (inflow.v, 0.0, 0.0), 1 => (0.0, inflow.v, 0.0), _ => (0.0, 0.0, inflow.v)};
+    while inflow.back - dx >= face - inflow.buffer {
+        inflow.back -= dx;
+        for jj in 0..n1 {
+            for kk in 0..n2 {
+                let a: f64 = o1 + (jj as f64 + 0.5)*dx;
+                let b: f64 = o2 + (kk as f64 + 0.5)*dx;
+                let (x, y, z) = match axis {0 => (inflow.back, a, b), 1 => (a, inflow.back, b), _ => (a, b, inflow.back)};
+                particles.push(Particle{ptype: 4, id: inflow.next_id, m, rho: inflow.rho, x, y, z, h,
+                                        vx, vy, vz, vx_star: vx, vy_star: vy, vz_star: vz,
+                                        u: inflow.u, u_star: inflow.u, ..Default::default()});
+                inflow.next_id += 1;
+            }
+        }
+    }
+    *n = particles.len();
+    *ptr = Pointer(particles.as_mut_ptr());
+}
+
+// ------------------------------------------------------------------------- //
+// Outflow through the upper face. Gas particles that cross the face become  //
+// buffer particles with the state they had, and are removed once they leave //
+// the buffer. The particle array shrinks. Start with the gas filling the    //
+// buffer (the first call, dt = 0, turns it into buffer particles), or the   //
+// gas near the face expands into the empty buffer.                          //
+// Returns the map of old to new indices, see remove_dead_particles.         //
+// ------------------------------------------------------------------------- //
+pub fn outflow_boundary(
+    particles: &mut Vec<Particle>, n: &mut usize, ptr: &mut Pointer, axis: usize, buffer: f64, dt: f64,
+    wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64
+) -> Vec<usize> {
+    let face: f64 = match axis {0 => x0 + wd, 1 => y0 + lg, _ => z0 + hg};
+    particles.par_iter_mut().for_each(|particle|{
+        let (coord, v) = match axis {0 => (&mut particle.x, particle.vx), 1 => (&mut particle.y, particle.vy), _ => (&mut particle.z, particle.vz)};
+        if particle.ptype==4 && *coord >= face {
+            *coord += v*dt;
+        }
+        if (particle.ptype==0 || particle.ptype==4) && *coord >= face {
+            particle.ptype = if *coord >= face + buffer {2} else {4};
+        }
+    });
+    remove_dead_particles(particles, n, ptr)
+}
+
+// ------------------------------------------------------------------------- //
+// Removes dead particles (ptype = 2) in a single pass.                      //
+// The relative order of the surviving particles is kept, and the returned   //
+// vector maps every old index to its new one (usize::MAX if removed).       //
+// The number of particles and the pointer to the array are updated.         //
+// ------------------------------------------------------------------------- //
+pub fn remove_dead_particles(
+    particles: &mut Vec<Particle>, n: &mut usize, ptr: &mut Pointer
+) -> Vec<usize> {
+    let mut remap: Vec<usize> = vec![usize::MAX; particles.len()];
+    let mut jj: usize = 0;
+    for ii in 0..particles.len() {
+        if particles[ii].ptype != 2 {
+            remap[ii] = jj;
+            jj += 1;
+        }
+    }
+    particles.retain(|particle| particle.ptype != 2);
+    *n = particles.len();
+    *ptr = Pointer(particles.as_mut_ptr());
+    remap
+}
+
+// ------------------------------------------------------------------------- //
+// Accretion onto sink particles                                             //
+// Particles satisfying the accretion criteria are first marked as dead,     //
+// while the tree still matches the particles' indices. Their mass and       //
+// momentum are added to the star, and finally every dead particle is        //
+// removed at once. The tree is restarted with the new number of particles.  //
+// ------------------------------------------------------------------------- //
+pub fn accretion_boundary(
+    star: &mut Star, particles: &mut Vec<Particle>, n: &mut usize, ptr: &mut Pointer, tree: &mut Node, s_: i32,
+    wd: f64, lg: f64, hg: f64, x0:f64, y0: f64, z0: f64, xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64
+){
     let mut neighbors: Vec<usize> = Vec::new();
-    tree.find_neighbors_star(&star, s_, particles, &mut neighbors, wd, lg, hg, x0, y0, z0, star.hacc, xperiodic, yperiodic, zperiodic);
-    
+    tree.find_neighbors_star(&star, s_, particles, &mut neighbors, wd, lg, hg, x0, y0, z0, star.hacc, xperiodic, yperiodic, zperiodic, y_shear);
+
+    // Accreted mass, and its first moments
+    let mut m_acc: f64 = 0.0;
+    let (mut mx, mut my, mut mz): (f64, f64, f64) = (0.0, 0.0, 0.0);
+    let (mut mvx, mut mvy, mut mvz): (f64, f64, f64) = (0.0, 0.0, 0.0);
+    let (mut max, mut may, mut maz): (f64, f64, f64) = (0.0, 0.0, 0.0);
+
+    let rds: f64 = star.facc*star.hacc;
     for ii in neighbors {
+        if particles[ii].ptype != 0 {
+            continue;
+        }
         let dx: f64 = star.x - particles[ii].x;
         let dy: f64 = star.y - particles[ii].y;
         let dz: f64 = star.z - particles[ii].z;
@@ -1159,7 +2398,6 @@ pub fn accretion_boundary(
         let dvy: f64= star.vy - particles[ii].vy;
         let dvz: f64= star.vz - particles[ii].vz;
         let v2: f64 = dvx*dvx + dvy*dvy + dvz*dvz;
-        let rds: f64= star.facc*star.hacc;
         let mut acc: bool = false;
         if r2 < rds*rds {
             acc = true;
@@ -1177,22 +2415,38 @@ pub fn accretion_boundary(
             }
         }
         if acc {
-            star.m += dm;
-            let inv_mtot: f64 = 1.0/star.m;
-            star.x = (particles[ii].x*dm + star.x*star.m)*inv_mtot;
-            star.y = (particles[ii].y*dm + star.y*star.m)*inv_mtot;
-            star.z = (particles[ii].z*dm + star.z*star.m)*inv_mtot;
-            star.vx = (particles[ii].vx*dm + star.vx*star.m)*inv_mtot;
-            star.vy = (particles[ii].vy*dm + star.vy*star.m)*inv_mtot;
-            star.vz = (particles[ii].vz*dm + star.vz*star.m)*inv_mtot;
-            star.ax = (particles[ii].ax*dm + star.ax*star.m)*inv_mtot;
-            star.ay = (particles[ii].ay*dm + star.ay*star.m)*inv_mtot;
-            star.az = (particles[ii].az*dm + star.az*star.m)*inv_mtot;
+            let m: f64 = particles[ii].m;
+            m_acc += m;
+            mx  += m*particles[ii].x;
+            my  += m*particles[ii].y;
+            mz  += m*particles[ii].z;
+            mvx += m*particles[ii].vx;
+            mvy += m*particles[ii].vy;
+            mvz += m*particles[ii].vz;
+            max += m*particles[ii].ax;
+            may += m*particles[ii].ay;
+            maz += m*particles[ii].az;
             particles[ii].ptype = 2;
-            particles.remove(ii);
-            *n -= 1;
         }
     }
+
+    // Centre of mass and momentum conservation
+    let m_old: f64      = star.m;
+    star.m += m_acc;
+    let inv_mtot: f64   = 1.0/star.m;
+    star.x  = (mx  + star.x*m_old)*inv_mtot;
+    star.y  = (my  + star.y*m_old)*inv_mtot;
+    star.z  = (mz  + star.z*m_old)*inv_mtot;
+    star.vx = (mvx + star.vx*m_old)*inv_mtot;
+    star.vy = (mvy + star.vy*m_old)*inv_mtot;
+    star.vz = (mvz + star.vz*m_old)*inv_mtot;
+    star.ax = max*inv_mtot;
+    star.ay = may*inv_mtot;
+    star.az = maz*inv_mtot;
+
+    // Compact the particles' array (also drops particles killed elsewhere)
+    remove_dead_particles(particles, n, ptr);
+    tree.restart(*n);
 }
 
 
@@ -1249,43 +2503,67 @@ pub fn time_step_bale(
     dts.iter().fold(f64::INFINITY, |a, &b| a.min(b))
 }
 
+// ------------------------------------------------------------------------- //
+// Signal velocity between particles a and b, Monaghan (1997):               //
+//      v_sig = alpha*(cs_a + cs_b - beta*v_ab.r_ab/|r_ab|),                 //
+// for approaching particles (v_ab.r_ab < 0), and zero otherwise.            //
+// ------------------------------------------------------------------------- //
+pub fn signal_velocity(
+    particle_a: &Particle, particle_b: &Particle, cs_a: f64, cs_b: f64, rkern: f64,
+    wd: f64, lg: f64, hg: f64, xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64, vy_shear: f64
+) -> f64 {
+    let alpha: f64  = 1.;
+    let beta: f64   = 2.;
+
+    // Velocity dot position
+    let (x_rel, y_rel, z_rel) = periodic_rel_vector(particle_a, particle_b, wd, lg, hg, rkern*particle_a.h, xperiodic, yperiodic, zperiodic, y_shear);
+    let r_ab: f64 = (x_rel*x_rel + y_rel*y_rel + z_rel*z_rel).sqrt();
+    let (vx_rel, vy_rel, vz_rel) = periodic_rel_velocity(particle_a, particle_b, wd, rkern*particle_a.h, xperiodic, vy_shear);
+    let dot_r_v: f64 = vx_rel*x_rel + vy_rel*y_rel + vz_rel*z_rel;
+
+    if dot_r_v < 0. {
+        return alpha*(cs_a+cs_b - beta*(dot_r_v/r_ab));
+    }
+    return 0.0;
+}
+
 // ------------------------------------------------------------------------- //
 // Timestepping Criteria by Monaghan (1997)                                  //
 // Returns the minimum time step between the CFL and the force conditions.   //
 // ------------------------------------------------------------------------- //
 pub fn time_step_mon(
     particles: & Vec<Particle>, n: usize, gamma: f64, coeff: f64, rkern: f64, wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64,
-    tree: &mut Node, s_: i32, cs: fn(f64, f64, f64, f64, f64, f64, f64) -> f64, xperiodic: bool, yperiodic:bool, zperiodic:bool
+    tree: &mut Node, s_: i32, cs: fn(f64, f64, f64, f64, f64, f64, f64) -> f64, xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64, vy_shear: f64
 ) -> f64 {
+    let dts: Vec<f64> = time_step_mon_particles(particles, n, gamma, coeff, rkern, wd, lg, hg, x0, y0, z0, tree, s_, cs, xperiodic, yperiodic, zperiodic, y_shear, vy_shear);
+    dts.iter().fold(f64::INFINITY, |a, &b| a.min(b))
+}
+
+// ------------------------------------------------------------------------- //
+// Timestepping Criteria by Monaghan (1997) for every particle.              //
+// Returns the time step of each active gas particle (infinity for the rest) //
+// ------------------------------------------------------------------------- //
+pub fn time_step_mon_particles(
+    particles: & Vec<Particle>, n: usize, gamma: f64, coeff: f64, rkern: f64, wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64,
+    tree: &mut Node, s_: i32, cs: fn(f64, f64, f64, f64, f64, f64, f64) -> f64, xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64, vy_shear: f64
+) -> Vec<f64> {
     // Find every neighbor of every particle.
     let neighbors: Vec<Vec<usize>> = (0..n).into_par_iter().map(|ii: usize| {
         let mut neighbors: Vec<usize> = Vec::new();
-        if particles[ii].ptype == 0 {
-            tree.find_neighbors(ii, s_, particles, &mut neighbors, wd, lg, hg, x0, y0, z0, particles[ii].h*rkern, xperiodic, yperiodic, zperiodic);
+        if particles[ii].ptype == 0 && particles[ii].active {
+            tree.find_neighbors(ii, s_, particles, &mut neighbors, wd, lg, hg, x0, y0, z0, particles[ii].h*rkern, xperiodic, yperiodic, zperiodic, y_shear);
         }
         return neighbors;
     }).collect();
-    let dts :Vec<f64> = (0..n).into_par_iter().map(|ii| -> f64 {
-        if particles[ii].ptype == 0 {
-        let alpha: f64  = 1.;
-        let beta: f64   = 2.;
+    (0..n).into_par_iter().map(|ii| -> f64 {
+        if particles[ii].ptype == 0 && particles[ii].active {
         let mut v_sig:f64 = 0.0;
         let cs_i: f64 = cs(particles[ii].rho, particles[ii].u, gamma, particles[ii].x, particles[ii].y, particles[ii].z, coeff);
         for jj in &neighbors[ii] {
             let cs_j: f64 = cs(particles[*jj].rho, particles[*jj].u, gamma, particles[*jj].x, particles[*jj].y, particles[*jj].z, coeff);
-
-            // Velocity dot position
-            let (x_rel, y_rel, z_rel) = periodic_rel_vector(&particles[ii], &particles[*jj], wd, lg, hg, rkern*particles[ii].h, xperiodic, yperiodic, zperiodic);
-            let r_ij: f64 = (x_rel*x_rel + y_rel*y_rel + z_rel*z_rel).sqrt();
-            let dot_r_v: f64 = (particles[ii].vx-particles[*jj].vx)*x_rel
-                              +(particles[ii].vy-particles[*jj].vy)*y_rel
-                              +(particles[ii].vz-particles[*jj].vz)*z_rel;
-            
-            if dot_r_v < 0. {
-                let v_sig_ij: f64 = alpha*(cs_i+cs_j - beta*(dot_r_v/r_ij));
-                if v_sig_ij > v_sig {
-                    v_sig = v_sig_ij;
-                }
+            let v_sig_ij: f64 = signal_velocity(&particles[ii], &particles[*jj], cs_i, cs_j, rkern, wd, lg, hg, xperiodic, yperiodic, zperiodic, y_shear, vy_shear);
+            if v_sig_ij > v_sig {
+                v_sig = v_sig_ij;
             }
         }
         let a_norm: f64 = (particles[ii].ax*particles[ii].ax + particles[ii].ay*particles[ii].ay + particles[ii].az*particles[ii].az).sqrt();
@@ -1295,6 +2573,95 @@ pub fn time_step_mon(
         } else {
             return f64::INFINITY;
         }
+    }).collect()
+}
+
+// ***------------------------- Block timesteps -------------------------*** //
+
+// ------------------------------------------------------------------------- //
+// Returns the smallest power-of-two bin whose step,                         //
+//      dt_block/2^ibin <= dt,                                               //
+// satisfies the particle's time step (at most nbins).                       //
+// ------------------------------------------------------------------------- //
+pub fn bin_from_dt(
+    dt_block: f64, dt: f64, nbins: u32
+) -> u32 {
+    if dt >= dt_block {
+        return 0;
+    }
+    let ibin: f64 = (dt_block/dt).log2().ceil();
+    if ibin >= nbins as f64 {
+        return nbins;
+    }
+    return ibin as u32;
+}
+
+// ------------------------------------------------------------------------- //
+// Returns the block time step: the largest step (at most dt_max) that       //
+// fits the smallest particle's time step within nbins levels,               //
+//      dt_block = min(dt_max, 2^nbins * min(dt_a)).                         //
+// ------------------------------------------------------------------------- //
+pub fn block_time_step(
+    dts: & Vec<f64>, dt_max: f64, nbins: u32
+) -> f64 {
+    let dt_min: f64 = dts.iter().fold(f64::INFINITY, |a, &b| a.min(b));
+    return dt_max.min(dt_min*(1_u64 << nbins) as f64);
+}
+
+// ------------------------------------------------------------------------- //
+// Sets the timestep bin of every gas particle for a block of dt_block,      //
+// where dts are the particles' time steps.                                  //
+// ------------------------------------------------------------------------- //
+pub fn assign_bins(
+    particles: &mut Vec<Particle>, dts: & Vec<f64>, dt_block: f64, nbins: u32
+) {
+    particles.par_iter_mut().zip(dts.par_iter()).for_each(|(particle, dt)|{
+        if particle.ptype==0 {
+            particle.ibin = bin_from_dt(dt_block, *dt, nbins);
+        }
+    });
+}
+
+// ------------------------------------------------------------------------- //
+// Saitoh & Makino (2009) timestep limiter                                   //
+// Looks at the neighbours of the active particles (after their new bins     //
+// are set). An inactive neighbour b of an active particle a is woken up if  //
+//      dt_b > 4 dt_a,                                                       //
+// or if its step is longer than the CFL step given by the pair's signal     //
+// velocity, 0.3*h_b/v_sig_ab.                                               //
+// Returns the woken particles and the smallest bin each one requires.       //
+// ------------------------------------------------------------------------- //
+pub fn time_step_limiter(
+    particles: & Vec<Particle>, dt_block: f64, nbins: u32, n: usize, gamma: f64, coeff: f64, rkern: f64,
+    wd: f64, lg: f64, hg: f64, x0: f64, y0: f64, z0: f64,
+    tree: & Node, s_: i32, cs: fn(f64, f64, f64, f64, f64, f64, f64) -> f64, xperiodic: bool, yperiodic:bool, zperiodic:bool, y_shear: f64, vy_shear: f64
+) -> Vec<(usize, u32)> {
+    let requests: Vec<Vec<(usize, u32)>> = (0..n).into_par_iter().map(|ii| {
+        let mut requests: Vec<(usize, u32)> = Vec::new();
+        if particles[ii].ptype != 0 || !particles[ii].active {
+            return requests;
+        }
+        let mut neighbors: Vec<usize> = Vec::new();
+        tree.find_neighbors(ii, s_, particles, &mut neighbors, wd, lg, hg, x0, y0, z0, particles[ii].h*rkern, xperiodic, yperiodic, zperiodic, y_shear);
+        let cs_i: f64 = cs(particles[ii].rho, particles[ii].u, gamma, particles[ii].x, particles[ii].y, particles[ii].z, coeff);
+        for jj in neighbors {
+            if particles[jj].ptype != 0 || particles[jj].active {
+                continue;
+            }
+            let cs_j: f64 = cs(particles[jj].rho, particles[jj].u, gamma, particles[jj].x, particles[jj].y, particles[jj].z, coeff);
+            let v_sig: f64 = signal_velocity(&particles[ii], &particles[jj], cs_i, cs_j, rkern, wd, lg, hg, xperiodic, yperiodic, zperiodic, y_shear, vy_shear);
+            let ibin: u32 = particles[ii].ibin.saturating_sub(2).max(bin_from_dt(dt_block, 0.3*particles[jj].h/v_sig, nbins));
+            if ibin > particles[jj].ibin {
+                requests.push((jj, ibin));
+            }
+        }
+        return requests;
     }).collect();
-    dts.iter().fold(f64::INFINITY, |a, &b| a.min(b))
-}
\ No newline at end of file
+
+    // Keep the smallest step requested for every particle
+    let mut ibins: Vec<u32> = vec![0; n];
+    for (jj, ibin) in requests.into_iter().flatten() {
+        ibins[jj] = ibins[jj].max(ibin);
+    }
+    (0..n).filter(|jj| ibins[*jj] > 0).map(|jj| (jj, ibins[jj])).collect()
+}
@@ -0,0 +1,80 @@
+// ------------------------------------------------------------------------- //
+// Time integrators on a harmonic oscillator, a = -x.                        //
+// ------------------------------------------------------------------------- //
+
+use structures::Particle;
+
+use sphfunctions::{
+    Integrator,
+    VelocityVerlet,
+    Leapfrog,
+    RungeKutta2,
+    RungeKutta4,
+    Yoshida4,
+};
+
+// Sets the derivatives of the oscillator and records the elapsed times
+fn oscillator(particles: &mut Vec<Particle>, elapsed: &mut Vec<f64>, dt: f64) {
+    for p in particles.iter_mut() {
+        p.ax = -p.x;
+        p.ay = -p.y;
+        p.az = -p.z;
+        p.du = 0.0;
+    }
+    elapsed.push(dt);
+}
+
+// Error in the position at t = 1 of x(t) = cos(t), y(t) = sin(t)
+fn error(integrator: &dyn Integrator, nsteps: u32) -> f64 {
+    let mut particles: Vec<Particle> = vec![Particle{x: 1.0, vy: 1.0, ..Default::default()}];
+    let mut elapsed: Vec<f64> = Vec::new();
+    let dt: f64 = 1.0/nsteps as f64;
+    oscillator(&mut particles, &mut elapsed, 0.0);
+    for _ in 0..nsteps {
+        elapsed.clear();
+        integrator.step(&mut particles, dt, &mut |particles: &mut Vec<Particle>, dt: f64| oscillator(particles, &mut elapsed, dt));
+        // The evaluations cover the step and end at its state
+        assert!((elapsed.iter().sum::<f64>() - dt).abs() < 1e-14);
+        assert_eq!(particles[0].ax, -particles[0].x);
+    }
+    let p: &Particle = &particles[0];
+    return ((p.x - 1.0_f64.cos()).powi(2) + (p.y - 1.0_f64.sin()).powi(2)).sqrt();
+}
+
+fn order(integrator: &dyn Integrator) -> f64 {
+    return (error(integrator, 20)/error(integrator, 40)).log2();
+}
+
+#[test]
+fn second_order() {
+    for (name, integrator) in [("VelocityVerlet", &VelocityVerlet as &dyn Integrator), ("Leapfrog", &Leapfrog), ("RungeKutta2", &RungeKutta2)] {
+        let p: f64 = order(integrator);
+        assert!((p - 2.0).abs() < 0.1, "{}: order {}", name, p);
+    }
+}
+
+#[test]
+fn fourth_order() {
+    for (name, integrator) in [("RungeKutta4", &RungeKutta4 as &dyn Integrator), ("Yoshida4", &Yoshida4)] {
+        let p: f64 = order(integrator);
+        assert!((p - 4.0).abs() < 0.1, "{}: order {}", name, p);
+    }
+}
+
+#[test]
+fn non_gas_particles_are_left_alone() {
+    // A wall particle moved by the derivatives (as a moving boundary is) with
+    // the time elapsed since the previous evaluation
+    for (name, integrator) in [("VelocityVerlet", &VelocityVerlet as &dyn Integrator), ("Leapfrog", &Leapfrog), ("RungeKutta2", &RungeKutta2), ("RungeKutta4", &RungeKutta4), ("Yoshida4", &Yoshida4)] {
+        let mut particles: Vec<Particle> = vec![Particle{x: 1.0, vy: 1.0, ..Default::default()}, Particle{ptype: 1, vx: 1.0, ax: 1.0, ..Default::default()}];
+        for _ in 0..4 {
+            integrator.step(&mut particles, 0.25, &mut |particles: &mut Vec<Particle>, dt: f64| {
+                particles[1].x += particles[1].vx*dt;
+                particles[0].ax = -particles[0].x;
+                particles[0].ay = -particles[0].y;
+            });
+        }
+        assert!((particles[1].x - 1.0).abs() < 1e-14, "{}: x = {}", name, particles[1].x);
+        assert_eq!(particles[1].vx, 1.0, "{}", name);
+    }
+}
@@ -0,0 +1,66 @@
+// ------------------------------------------------------------------------- //
+// Open boundaries: particles injected by the inflow and removed by the      //
+// outflow, in a unit box with a lattice of spacing 0.25.                    //
+// ------------------------------------------------------------------------- //
+
+use structures::{
+    Particle,
+    Pointer,
+};
+
+use sphfunctions::Inflow;
+
+#[test]
+fn inflow_injects_layers() {
+    let mut particles: Vec<Particle> = Vec::new();
+    let mut n: usize = 0;
+    let mut ptr: Pointer = Pointer(particles.as_mut_ptr());
+    let mut inflow: Inflow = Inflow{axis: 0, rho: 1.0, v: 1.0, u: 1.0, dx: 0.25, eta: 1.2, buffer: 0.5, back: 0.125, next_id: 0};
+    let count = |particles: &Vec<Particle>, ptype: u8| particles.iter().filter(|p| p.ptype == ptype).count();
+
+    // The first call fills the buffer with two layers of 4x4 particles
+    sphfunctions::inflow_boundary(&mut particles, &mut n, &mut ptr, &mut inflow, 0.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0);
+    assert_eq!((n, count(&particles, 4), count(&particles, 0)), (32, 32, 0));
+
+    // A layer crosses the face for every dx/v, and a new one is created
+    sphfunctions::inflow_boundary(&mut particles, &mut n, &mut ptr, &mut inflow, 0.25, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0);
+    assert_eq!((n, count(&particles, 4), count(&particles, 0)), (48, 32, 16));
+    for _ in 0..4 {
+        sphfunctions::inflow_boundary(&mut particles, &mut n, &mut ptr, &mut inflow, 0.125, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0);
+    }
+    assert_eq!((n, count(&particles, 4), count(&particles, 0)), (80, 32, 48));
+
+    // The gas is inside the box and the buffer in front of its lower face
+    assert!(particles.iter().all(|p| if p.ptype == 0 {p.x >= 0.0} else {p.x < 0.0 && p.x >= -0.5}));
+    assert_eq!(particles.iter().map(|p| p.id).collect::<Vec<u64>>(), (0..80).collect::<Vec<u64>>());
+    assert_eq!(inflow.next_id, 80);
+    assert!(particles.iter().all(|p| (p.m - 0.25f64.powi(3)).abs() < 1e-15 && p.vx == 1.0));
+}
+
+#[test]
+fn outflow_removes_the_buffer() {
+    // Six layers moving with vx = 1, the last two beyond the upper face
+    let mut particles: Vec<Particle> = Vec::new();
+    for ii in 0..6 {
+        for jj in 0..4 {
+            for kk in 0..4 {
+                particles.push(Particle{id: particles.len() as u64, x: 0.125 + 0.25*ii as f64, y: 0.125 + 0.25*jj as f64, z: 0.125 + 0.25*kk as f64, vx: 1.0, ..Default::default()});
+            }
+        }
+    }
+    let mut n: usize = particles.len();
+    let mut ptr: Pointer = Pointer(particles.as_mut_ptr());
+    let count = |particles: &Vec<Particle>, ptype: u8| particles.iter().filter(|p| p.ptype == ptype).count();
+
+    // The first call turns the gas beyond the face into buffer particles
+    let remap: Vec<usize> = sphfunctions::outflow_boundary(&mut particles, &mut n, &mut ptr, 0, 0.5, 0.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0);
+    assert_eq!((n, count(&particles, 4), count(&particles, 0)), (96, 32, 64));
+    assert_eq!(remap, (0..96).collect::<Vec<usize>>());
+
+    // The outer layer leaves the buffer and is removed
+    let remap: Vec<usize> = sphfunctions::outflow_boundary(&mut particles, &mut n, &mut ptr, 0, 0.5, 0.25, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0);
+    assert_eq!((n, count(&particles, 4), count(&particles, 0)), (80, 16, 64));
+    assert!(remap[80..].iter().all(|&ii| ii == usize::MAX));
+    assert_eq!(remap[..80], (0..80).collect::<Vec<usize>>()[..]);
+    assert!(particles.iter().all(|p| p.id < 80 && p.x < 1.5));
+}
@@ -0,0 +1,75 @@
+// ------------------------------------------------------------------------- //
+// Sink integration: a circular binary advanced with sinks_integrator.       //
+// ------------------------------------------------------------------------- //
+
+use std::f64::consts::PI;
+
+use structures::Star;
+
+use sphfunctions::SINK_EPS2;
+
+// Two equal stars on a circular orbit of separation 1 (G = 1)
+fn binary() -> Vec<Star> {
+    let m: f64 = 0.5;
+    let r: f64 = 1.0;
+    // Circular speed of the softened orbit about the centre of mass
+    let v: f64 = 0.5*(m*r*r*(r*r + SINK_EPS2).powf(-1.5)*2.0).sqrt();
+    return vec![Star{m, x:  0.5*r, vy:  v, ..Default::default()},
+                Star{m, x: -0.5*r, vy: -v, ..Default::default()}];
+}
+
+// Kinetic plus softened potential energy
+fn energy(stars: &[Star]) -> f64 {
+    let (a, b) = (&stars[0], &stars[1]);
+    let r2: f64 = (a.x - b.x).powi(2) + (a.y - b.y).powi(2) + (a.z - b.z).powi(2);
+    let ek: f64 = stars.iter().map(|s| 0.5*s.m*(s.vx*s.vx + s.vy*s.vy + s.vz*s.vz)).sum();
+    return ek - a.m*b.m/(r2 + SINK_EPS2).sqrt();
+}
+
+// Relative energy drift over 10 orbits, with 4 hydro steps per orbit, and
+// the number of substeps of each hydro step
+fn drift(eta_orb: f64) -> (f64, u32) {
+    let mut stars: Vec<Star> = binary();
+    let e0: f64 = energy(&stars);
+    let period: f64 = 2.0*PI/(1.0 + SINK_EPS2).powf(-0.75);
+    let dt: f64 = 0.25*period;
+    let mut nsub: u32 = 0;
+    for _ in 0..40 {
+        nsub = sphfunctions::sinks_integrator(&mut stars, dt, eta_orb);
+    }
+    return (((energy(&stars) - e0)/e0).abs(), nsub);
+}
+
+#[test]
+fn energy_drift_falls_with_the_substeps() {
+    let (drift_coarse, nsub_coarse) = drift(0.5);
+    let (drift_fine, nsub_fine) = drift(0.05);
+    assert!(nsub_fine >= 8*nsub_coarse, "{} vs {} substeps", nsub_fine, nsub_coarse);
+    // Second order in the substep
+    assert!(drift_fine < 1e-2*drift_coarse, "drift {:e} vs {:e}", drift_fine, drift_coarse);
+    assert!(drift_fine < 1e-5, "drift {:e}", drift_fine);
+}
+
+#[test]
+fn single_star_is_a_kdk_step() {
+    let mut star: Star = Star{m: 1.0, vx: 1.0, ax: 2.0, ..Default::default()};
+    let nsub: u32 = sphfunctions::sinks_integrator(std::slice::from_mut(&mut star), 0.5, 0.1);
+    assert_eq!(nsub, 1);
+    assert!((star.x - 0.75).abs() < 1e-15);
+    assert!((star.vx - 2.0).abs() < 1e-15);
+}
+
+#[test]
+fn post_newtonian_terms_are_softened() {
+    use sphfunctions::ExternalForce;
+    use structures::Particle;
+    let star: Star = Star{m: 1.0, c: 10.0, sz: 0.1, ..Default::default()};
+    // At the star and next to it the acceleration stays bounded: the
+    // Lense-Thirring term is at most 2|S|v/(c^2 e^3) = 128 here
+    let a: Vec<f64> = [0.0, 1e-6].iter().map(|&r| {
+        let (ax, ay, az) = star.acceleration(&Particle{x: r, vy: 1.0, ..Default::default()});
+        (ax*ax + ay*ay + az*az).sqrt()
+    }).collect();
+    assert!(a.iter().all(|a| a.is_finite() && *a <= 128.0 + 1e-9), "{:?}", a);
+    assert!((a[0] - a[1]).abs() < 1e-2*a[0], "{:?}", a);
+}
@@ -5,7 +5,11 @@
 
 // ------------------------------------------------------------------------- //
 // Particle: SPH particle                                                    //
-//  ptype:      u8  - 0: gas; 1: boundary; 2: dead                           //
+//  ptype:      u8  - 0: gas; 1: boundary; 2: dead; 3: ghost (mirror)        //
+//                    4: buffer of open boundaries                           //
+//                    5: collisionless (GADGET types 1-4), left out of SPH   //
+//  id:         u64 - persistent identifier (kept through removal/sorting)   //
+//  m:          f64 - mass                                                   //
 //  rho:        f64 - density                                                //
 //  x, y, z:    f64 - position                                               //
 //  vx, vy, vz: f64 - velocity                                               //
@@ -15,10 +19,14 @@
 //  u:          f64 - internal energy                                        //
 //  u_star:     f64 - auxiliar internal energy for time integrator           //
 //  du:         f64 - internal energy change                                 //
+//  ibin:       u32 - timestep bin (dt = dt_block/2^ibin)                    //
+//  active:     bool - the particle ends its timestep in this substep        //
 // ------------------------------------------------------------------------- //
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Particle {
     pub ptype: u8,
+    pub id: u64,
+    pub m: f64,
     pub rho: f64,
 	pub x: f64,
     pub y: f64,
@@ -37,12 +45,16 @@ pub struct Particle {
     pub u: f64,
     pub u_star: f64,
     pub du: f64,
+    pub ibin: u32,
+    pub active: bool,
 }
 
 impl Default for Particle {
     fn default() -> Particle {
         Particle {
             ptype: 0,
+            id: 0,
+            m: 0.0,
             rho: 1.0,
             x: 0.,
             y: 0.,
@@ -61,6 +73,8 @@ impl Default for Particle {
             u: 1.0,
             u_star: 0.0,
             du: 0.0,
+            ibin: 0,
+            active: true,
         }
     }
 }
@@ -80,6 +94,9 @@ unsafe impl Sync for Pointer {}
 //  branches:               i32 - number of children                         //
 //  chidren:                Vec - array of children                          //
 // particles:               Vec - array of particles' id's                   //
+//  fit:                    [bool; 3] - axes where the root is fitted to the //
+//                          particles on every rebuild (root only)           //
+//  rkern:                  f64 - kernel radius for the fitted root          //
 // ------------------------------------------------------------------------- //
 pub struct Node {
     pub xmin: f64,
@@ -94,6 +111,8 @@ pub struct Node {
     pub branches: i32,
     pub children: Vec<Node>,
     pub particles: Vec<usize>,
+    pub fit: [bool; 3],
+    pub rkern: f64,
 }
 
 // ------------------------------------------------------------------------- //
@@ -104,6 +123,8 @@ pub struct Node {
 //  x, y, z:    f64 - position                                               //
 //  vx, vy, vz: f64 - velocity                                               //
 //  ax, ay, az: f64 - acceleration                                           //
+//  sx, sy, sz: f64 - spin angular momentum                                  //
+//  c:          f64 - speed of light (0: Newtonian gravity)                  //
 // ------------------------------------------------------------------------- //
 pub struct Star {
     pub m: f64,
@@ -118,6 +139,10 @@ pub struct Star {
     pub ax: f64,
     pub ay: f64,
     pub az: f64,
+    pub sx: f64,
+    pub sy: f64,
+    pub sz: f64,
+    pub c: f64,
 }
 
 impl Default for Star {
@@ -135,6 +160,10 @@ impl Default for Star {
             ax: 0.0,
             ay: 0.0,
             az: 0.0,
+            sx: 0.0,
+            sy: 0.0,
+            sz: 0.0,
+            c: 0.0,
         }
     }
 }
\ No newline at end of file
@@ -39,6 +39,10 @@ pub trait BuildTree {
     fn build_octtree(&mut self, s: i32, alpha: f64, beta: f64, particles: & Vec<Particle>, smallest_cell: f64);
 
     fn restart(&mut self, n: usize);
+
+    fn auto_root(&mut self, rkern: f64, xperiodic: bool, yperiodic: bool, zperiodic: bool);
+
+    fn fit_root(&mut self, particles: & Vec<Particle>);
 }
 
 impl BuildTree for Node {
@@ -54,7 +58,9 @@ impl BuildTree for Node {
              id: 0,
              depth: 0,
              branches: 0,
-             children: Vec::new()}
+             children: Vec::new(),
+             fit: [false; 3],
+             rkern: 0.0}
     }
     
     fn branching_factor(& self, s:f64) -> i32 {
@@ -76,6 +82,8 @@ impl BuildTree for Node {
             branches: 0,
             children: Vec::new(),
             particles: Vec::new(),
+            fit: [false; 3],
+            rkern: 0.0,
         }
     }
 
@@ -112,13 +120,14 @@ impl BuildTree for Node {
     }
 
     fn build_tree(&mut self, s: i32, alpha: f64, beta: f64, particles: & Vec<Particle>, smallest_cell: f64) {
+        self.fit_root(particles);
         let mut redistribution :bool = true;
         let mut b: i32 = self.branching_factor(s as f64);
         while redistribution {
             self.branches = b*b*b;
             self.create_sub_cells(b);
             for p in &self.particles {
-            if particles[*p].ptype != 2 {
+            if particles[*p].ptype != 2 && particles[*p].ptype != 5 {
                 let mut x_p:i32 = ((particles[*p].x - self.xmin) as f64/ self.sidex * b as f64).floor() as i32;
                 if x_p == b {
                     x_p -= 1;
@@ -168,11 +177,12 @@ impl BuildTree for Node {
     }
 
     fn build_octtree(&mut self, s: i32, alpha: f64, beta: f64, particles: & Vec<Particle>, smallest_cell: f64) {
+        self.fit_root(particles);
         let b:i32 = 2;
         self.branches = b*b*b;
         self.create_sub_cells(b);
         for p in &self.particles {
-        if particles[*p].ptype != 2 {
+        if particles[*p].ptype != 2 && particles[*p].ptype != 5 {
             let mut x_p: i32 = ((particles[*p].x - self.xmin) / self.sidex * b as f64).floor() as i32;
             if x_p == b {
                 x_p -= 1;
@@ -198,30 +208,87 @@ impl BuildTree for Node {
     }
 
     fn restart(&mut self, n: usize) {
+        self.n = n as i32;
         self.branches = 0;
         self.particles = (0..n).collect();
         self.delete_sub_cells();
     }
+
+    // The root is fitted to the particles on every rebuild on the
+    // non-periodic axes, while the periodic ones keep the box of the root.
+    fn auto_root(&mut self, rkern: f64, xperiodic: bool, yperiodic: bool, zperiodic: bool) {
+        self.fit = [!xperiodic, !yperiodic, !zperiodic];
+        self.rkern = rkern;
+    }
+
+    // Root box from the extents of the living particles plus rkern*h_max
+    fn fit_root(&mut self, particles: & Vec<Particle>) {
+        if self.depth != 0 || !self.fit.contains(&true) {
+            return;
+        }
+        let mut low: [f64; 3]   = [f64::INFINITY; 3];
+        let mut up: [f64; 3]    = [f64::NEG_INFINITY; 3];
+        let mut h_max: f64      = 0.0;
+        for p in &self.particles {
+            if particles[*p].ptype != 2 && particles[*p].ptype != 5 {
+                let r: [f64; 3] = [particles[*p].x, particles[*p].y, particles[*p].z];
+                for ii in 0..3 {
+                    low[ii] = low[ii].min(r[ii]);
+                    up[ii]  = up[ii].max(r[ii]);
+                }
+                h_max = h_max.max(particles[*p].h);
+            }
+        }
+        if h_max == 0.0 {
+            return;
+        }
+        let pad: f64 = self.rkern*h_max;
+        if self.fit[0] {
+            self.xmin  = low[0] - pad;
+            self.sidex = up[0] - low[0] + 2.0*pad;
+        }
+        if self.fit[1] {
+            self.ymin  = low[1] - pad;
+            self.sidey = up[1] - low[1] + 2.0*pad;
+        }
+        if self.fit[2] {
+            self.zmin  = low[2] - pad;
+            self.sidez = up[2] - low[2] + 2.0*pad;
+        }
+    }
 }
 
 pub trait FindNeighbors {
-    fn range_neigh(&self, x_p: f64, y_p: f64, z_p: f64, b: i32, hrkern: f64, x0: f64, y0: f64, z0: f64, wd: f64, lg: f64, hg: f64,) -> Vec<usize>;
+    fn range_neigh(&self, x_p: f64, y_p: f64, z_p: f64, b: i32, hrkern: f64, x0: f64, y0: f64, z0: f64, wd: f64, lg: f64, hg: f64, y_shear: f64) -> Vec<usize>;
 
-    fn find_neighbors(& self, p: usize, s: i32, particles: & Vec<Particle>, neighbors_of_p: &mut Vec<usize>, wd: f64, lg: f64, hg: f64, x0:f64, y0: f64, z0:f64, hrkern: f64, xperiodic:bool, yperiodic:bool, zperiodic:bool);
+    fn find_neighbors(& self, p: usize, s: i32, particles: & Vec<Particle>, neighbors_of_p: &mut Vec<usize>, wd: f64, lg: f64, hg: f64, x0:f64, y0: f64, z0:f64, hrkern: f64, xperiodic:bool, yperiodic:bool, zperiodic:bool, y_shear: f64);
     
-    fn find_neighbors_star(& self, star: & Star, s: i32, particles: & Vec<Particle>, neighbors_of_p: &mut Vec<usize>, wd: f64, lg: f64, hg: f64, x0:f64, y0: f64, z0:f64, radius: f64, xperiodic:bool, yperiodic:bool, zperiodic:bool);
+    fn find_neighbors_star(& self, star: & Star, s: i32, particles: & Vec<Particle>, neighbors_of_p: &mut Vec<usize>, wd: f64, lg: f64, hg: f64, x0:f64, y0: f64, z0:f64, radius: f64, xperiodic:bool, yperiodic:bool, zperiodic:bool, y_shear: f64);
 }
 
 impl FindNeighbors for Node {
 
-    fn range_neigh(&self, x_p: f64, y_p: f64, z_p: f64, b: i32, hrkern: f64, x0: f64, y0: f64, z0: f64, wd: f64, lg: f64, hg: f64,) -> Vec<usize>{
+    fn range_neigh(&self, x_p: f64, y_p: f64, z_p: f64, b: i32, hrkern: f64, x0: f64, y0: f64, z0: f64, wd: f64, lg: f64, hg: f64, y_shear: f64) -> Vec<usize>{
         let factorx : f64 =  b as f64 /self.sidex;
         let factory : f64 =  b as f64 /self.sidey;
         let factorz : f64 =  b as f64 /self.sidez;
         
-        let (xlow, xup) =  limits(x_p-hrkern, x_p+hrkern, x0, wd, self.xmin, self.depth);
-        let (ylow, yup) =  limits(y_p-hrkern, y_p+hrkern, y0, lg, self.ymin, self.depth);
-        let (zlow, zup) =  limits(z_p-hrkern, z_p+hrkern, z0, hg, self.zmin, self.depth);
+        // Shearing box: beyond the x-boundaries the y-window is the one of the
+        // sheared image, y_p -/+ y_shear. A cell may hold both direct and
+        // sheared neighbours, so it searches the union of the two windows.
+        let mut y_c: Vec<f64> = vec![y_p];
+        if y_shear != 0. && self.depth != 0 {
+            if x_p-hrkern < x0 && self.xmin + self.sidex >= x_p-hrkern+wd {
+                y_c.push(y0 + (y_p + y_shear - y0).rem_euclid(lg));
+            } else if x_p+hrkern > x0+wd && self.xmin <= x_p+hrkern-wd {
+                y_c.push(y0 + (y_p - y_shear - y0).rem_euclid(lg));
+            }
+        }
+
+        let (xlow, xup) =  limits(x_p-hrkern, x_p+hrkern, x0, wd, self.xmin, self.sidex, self.depth);
+        let (ylow, yup) =  y_c.iter().map(|y| limits(y-hrkern, y+hrkern, y0, lg, self.ymin, self.sidey, self.depth))
+                                     .fold((f64::INFINITY, f64::NEG_INFINITY), |(a, b), (low, up)| (a.min(low), b.max(up)));
+        let (zlow, zup) =  limits(z_p-hrkern, z_p+hrkern, z0, hg, self.zmin, self.sidez, self.depth);
 
         let mut x_min: i32 = ((xlow - self.xmin) * factorx).floor() as i32;
         let mut x_max: i32 = ((xup - self.xmin) * factorx).floor() as i32;
@@ -248,7 +315,7 @@ impl FindNeighbors for Node {
                 }
             }
             return neighbors;
-        } else {
+        } else if y_shear == 0. {
             for kk in z_min..z_max+1{
                 for jj in y_min..y_max+1{
                     for ii in x_min..x_max+1{
@@ -256,44 +323,74 @@ impl FindNeighbors for Node {
                     }
                 }
             }
+            // A window wider than the box wraps onto the same cells twice
+            neighbors.sort_unstable();
+            neighbors.dedup();
+            return neighbors;
+        } else {
+            // Shearing box: cells wrapped in x are searched around the sheared image
+            let y_min_l: i32 = ((y_p + y_shear - hrkern - self.ymin) * factory).floor() as i32;
+            let y_max_l: i32 = ((y_p + y_shear + hrkern - self.ymin) * factory).floor() as i32;
+            let y_min_r: i32 = ((y_p - y_shear - hrkern - self.ymin) * factory).floor() as i32;
+            let y_max_r: i32 = ((y_p - y_shear + hrkern - self.ymin) * factory).floor() as i32;
+            for kk in z_min..z_max+1{
+                for ii in x_min..x_max+1{
+                    let (y_low, y_up) = if ii < 0 {(y_min_l, y_max_l)} else if ii >= b {(y_min_r, y_max_r)} else {(y_min, y_max)};
+                    for jj in y_low..y_up+1{
+                        neighbors.push((ii.rem_euclid(b)+ (jj.rem_euclid(b)+kk.rem_euclid(b)*b)*b) as usize);
+                    }
+                }
+            }
+            // A window wider than the box wraps onto the same cells twice
+            neighbors.sort_unstable();
+            neighbors.dedup();
             return neighbors;
         }
     }
 
-    fn find_neighbors(& self, p: usize, s: i32, particles: & Vec<Particle>, neighbors_of_p: &mut Vec<usize>, wd: f64, lg:f64, hg:f64, x0:f64, y0:f64, z0:f64, hrkern: f64, xperiodic:bool, yperiodic:bool, zperiodic:bool) {
+    fn find_neighbors(& self, p: usize, s: i32, particles: & Vec<Particle>, neighbors_of_p: &mut Vec<usize>, wd: f64, lg:f64, hg:f64, x0:f64, y0:f64, z0:f64, hrkern: f64, xperiodic:bool, yperiodic:bool, zperiodic:bool, y_shear: f64) {
+        // On the non-periodic axes the search uses the box of the root, which
+        // may be fitted to the particles (see auto_root).
+        let (x0, wd) = if self.depth == 0 && !xperiodic {(self.xmin, self.sidex)} else {(x0, wd)};
+        let (y0, lg) = if self.depth == 0 && !yperiodic {(self.ymin, self.sidey)} else {(y0, lg)};
+        let (z0, hg) = if self.depth == 0 && !zperiodic {(self.zmin, self.sidez)} else {(z0, hg)};
         let b: i32 = (self.branches).cbrt();
-        let cell_neighbors = self.range_neigh(particles[p].x, particles[p].y, particles[p].z, b as i32, hrkern, x0, y0, z0, wd, lg, hg);
+        let cell_neighbors = self.range_neigh(particles[p].x, particles[p].y, particles[p].z, b as i32, hrkern, x0, y0, z0, wd, lg, hg, y_shear);
         for ii in cell_neighbors {
             if self.children[ii].branches == 0 {
                 for q in &self.children[ii].particles {
-                    if particles[*q].ptype != 2 {
-                    let norm: f64 = sq_periodic_norm(particles[p].x, particles[*q].x, particles[p].y, particles[*q].y, particles[p].z, particles[*q].z, wd, lg, hg, hrkern, xperiodic, yperiodic, zperiodic);
+                    if particles[*q].ptype != 2 && particles[*q].ptype != 5 {
+                    let norm: f64 = sq_periodic_norm(particles[p].x, particles[*q].x, particles[p].y, particles[*q].y, particles[p].z, particles[*q].z, wd, lg, hg, hrkern, xperiodic, yperiodic, zperiodic, y_shear);
                     if norm <= hrkern*hrkern {
                         neighbors_of_p.push(*q);
                     }
                     }
                 }
             } else {
-                self.children[ii].find_neighbors(p, s, particles, neighbors_of_p, wd, lg, hg, x0, y0, z0, hrkern, xperiodic, yperiodic, zperiodic);
+                self.children[ii].find_neighbors(p, s, particles, neighbors_of_p, wd, lg, hg, x0, y0, z0, hrkern, xperiodic, yperiodic, zperiodic, y_shear);
             }
         }
     }
 
-    fn find_neighbors_star(& self, star: & Star, s: i32, particles: & Vec<Particle>, neighbors_of_p: &mut Vec<usize>, wd: f64, lg: f64, hg: f64, x0:f64, y0: f64, z0:f64, radius: f64, xperiodic:bool, yperiodic:bool, zperiodic:bool) {
+    fn find_neighbors_star(& self, star: & Star, s: i32, particles: & Vec<Particle>, neighbors_of_p: &mut Vec<usize>, wd: f64, lg: f64, hg: f64, x0:f64, y0: f64, z0:f64, radius: f64, xperiodic:bool, yperiodic:bool, zperiodic:bool, y_shear: f64) {
+        // Same root box as find_neighbors
+        let (x0, wd) = if self.depth == 0 && !xperiodic {(self.xmin, self.sidex)} else {(x0, wd)};
+        let (y0, lg) = if self.depth == 0 && !yperiodic {(self.ymin, self.sidey)} else {(y0, lg)};
+        let (z0, hg) = if self.depth == 0 && !zperiodic {(self.zmin, self.sidez)} else {(z0, hg)};
         let b: i32 = (self.branches).cbrt();
-        let cell_neighbors = self.range_neigh(star.x, star.y, star.z, b as i32, radius, x0, y0, z0, wd, lg, hg);
+        let cell_neighbors = self.range_neigh(star.x, star.y, star.z, b as i32, radius, x0, y0, z0, wd, lg, hg, y_shear);
         for ii in cell_neighbors {
             if self.children[ii].branches == 0 {
                 for q in &self.children[ii].particles {
-                    if particles[*q].ptype != 2 {
-                    let norm: f64 = sq_periodic_norm(star.x, particles[*q].x, star.y, particles[*q].y, star.z, particles[*q].z, wd, lg, hg, radius, xperiodic, yperiodic, zperiodic);
+                    if particles[*q].ptype != 2 && particles[*q].ptype != 5 {
+                    let norm: f64 = sq_periodic_norm(star.x, particles[*q].x, star.y, particles[*q].y, star.z, particles[*q].z, wd, lg, hg, radius, xperiodic, yperiodic, zperiodic, y_shear);
                     if norm <= radius*radius {
                         neighbors_of_p.push(*q);
                     }
                     }
                 }
             } else {
-                self.children[ii].find_neighbors_star(star, s, particles, neighbors_of_p, wd, lg, hg, x0, y0, z0, radius, xperiodic, yperiodic, zperiodic);
+                self.children[ii].find_neighbors_star(star, s, particles, neighbors_of_p, wd, lg, hg, x0, y0, z0, radius, xperiodic, yperiodic, zperiodic, y_shear);
             }
         }
     }
@@ -326,7 +423,9 @@ pub fn save_neighbors(path: &str, p: usize, neighbors: & Vec<usize>){
 
 
 // Periodic Distance
-pub fn sq_periodic_norm(x1: f64, x2: f64, y1: f64, y2: f64, z1: f64, z2: f64, wd: f64, lg: f64, hg: f64, eps: f64, xperiodic:bool, yperiodic:bool, zperiodic:bool) -> f64 {
+// In a shearing box, the image at x + wd is shifted by y_shear in y. Beyond
+// half the box the closest image is taken (see periodic_rel_vector).
+pub fn sq_periodic_norm(x1: f64, x2: f64, y1: f64, y2: f64, z1: f64, z2: f64, wd: f64, lg: f64, hg: f64, eps: f64, xperiodic:bool, yperiodic:bool, zperiodic:bool, y_shear: f64) -> f64 {
     
     let mut x_temp: f64 = x1 - x2;
     let mut y_temp: f64 = y1 - y2;
@@ -334,21 +433,26 @@ pub fn sq_periodic_norm(x1: f64, x2: f64, y1: f64, y2: f64, z1: f64, z2: f64, wd
 
     let diam: f64 = 2.*eps;
 
-    if xperiodic && x_temp.abs() > wd-diam {
+    if xperiodic && x_temp.abs() > (wd-diam).max(0.5*wd) {
         if x_temp > 0. {
             x_temp -= wd;
+            y_temp -= y_shear;
         } else {
             x_temp += wd;
+            y_temp += y_shear;
+        }
+        if yperiodic && y_shear != 0. {
+            y_temp -= lg*(y_temp/lg).round();
         }
     }
-    if yperiodic && y_temp.abs() > lg-diam {
+    if yperiodic && y_temp.abs() > (lg-diam).max(0.5*lg) {
         if y_temp > 0. {
             y_temp -= lg;
         } else {
             y_temp += lg;
         }
     }
-    if zperiodic && z_temp.abs() > hg-diam {
+    if zperiodic && z_temp.abs() > (hg-diam).max(0.5*hg) {
         if z_temp > 0. {
             z_temp -= hg;
         } else {
@@ -358,26 +462,30 @@ pub fn sq_periodic_norm(x1: f64, x2: f64, y1: f64, y2: f64, z1: f64, z2: f64, wd
     return x_temp*x_temp + y_temp*y_temp + z_temp*z_temp;
 }
 
-fn limits(low_lim: f64, up_lim: f64, l0: f64, l: f64, lmin: f64, depth: i32) -> (f64, f64) {
+// Search window [low_lim, up_lim] within a cell [lmin, lmin + side] of a box
+// [l0, l0 + l]. Below the root, the parts of the window wrapped around the box
+// that overlap the cell are merged with the direct one.
+fn limits(low_lim: f64, up_lim: f64, l0: f64, l: f64, lmin: f64, side: f64, depth: i32) -> (f64, f64) {
     if depth == 0 {
         return  (low_lim, up_lim);
-    } else {
-        if low_lim < l0 {
-            if lmin < (l0 + 0.5 * l) {
-                return (l0, up_lim);
-            } else {
-                return (low_lim + l, l0 + l);
-            }
-        } else if up_lim > (l0 + l) {
-            if lmin < (l0 + 0.5 * l) {
-                return (l0, up_lim-l);
-            } else {
-                return (low_lim, l0 + l);
-            }
-        } else {
-            return (low_lim, up_lim);
+    }
+    let lmax: f64 = lmin + side;
+    let mut low: f64 = f64::INFINITY;
+    let mut up: f64  = f64::NEG_INFINITY;
+    for shift in [0.0, l, -l] {
+        let (a, b): (f64, f64) = (low_lim + shift, up_lim + shift);
+        if (shift > 0.0 && low_lim >= l0) || (shift < 0.0 && up_lim <= l0 + l) {
+            continue;
         }
+        if b >= lmin && a <= lmax {
+            low = low.min(a.max(lmin));
+            up  = up.max(b.min(lmax));
+        }
+    }
+    if low > up {
+        return (low_lim, up_lim);
     }
+    return (low, up);
 }
 
 fn set_limits(x: &mut i32, low: i32, up: i32) {
@@ -0,0 +1,125 @@
+// ------------------------------------------------------------------------- //
+// Neighbours found with the tree against a brute-force search over the      //
+// periodic images, with and without a sheared x-boundary, and in an open    //
+// box whose root is fitted to the particles.                                //
+// ------------------------------------------------------------------------- //
+
+use structures::{
+    Particle,
+    Node,
+    Star,
+};
+
+use tree_algorithm::{
+    BuildTree,
+    FindNeighbors,
+};
+
+const X0: f64 = -0.5;
+const WD: f64 = 1.0;
+
+// A jittered lattice of nx^3 particles in the unit box
+fn particles(nx: usize, h: f64) -> Vec<Particle> {
+    let dx: f64 = WD/nx as f64;
+    let mut seed: u64 = 12345;
+    let mut jitter = || {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        return ((seed >> 11) as f64/(1u64 << 53) as f64 - 0.5)*0.5*dx;
+    };
+    let mut particles: Vec<Particle> = Vec::new();
+    for kk in 0..nx {
+        for jj in 0..nx {
+            for ii in 0..nx {
+                particles.push(Particle{x: X0 + (ii as f64 + 0.5)*dx + jitter(),
+                                        y: X0 + (jj as f64 + 0.5)*dx + jitter(),
+                                        z: X0 + (kk as f64 + 0.5)*dx + jitter(),
+                                        h, ..Default::default()});
+            }
+        }
+    }
+    return particles;
+}
+
+// Squared distance to the closest image of q, the images across x being
+// shifted by y_shear in y
+fn brute_norm(p: &Particle, q: &Particle, y_shear: f64) -> f64 {
+    let mut norm: f64 = f64::INFINITY;
+    for kx in -1..=1 {
+        let dx: f64 = p.x - q.x - kx as f64*WD;
+        let dy: f64 = p.y - q.y - kx as f64*y_shear;
+        let dy: f64 = dy - WD*(dy/WD).round();
+        let dz: f64 = p.z - q.z;
+        let dz: f64 = dz - WD*(dz/WD).round();
+        norm = norm.min(dx*dx + dy*dy + dz*dz);
+    }
+    return norm;
+}
+
+fn check(y_shear: f64) {
+    let (nx, s, rkern): (usize, i32, f64) = (10, 4, 2.0);
+    let particles: Vec<Particle> = particles(nx, 0.12);
+    let n: usize = particles.len();
+    let mut root: Node = <Node as BuildTree>::new(n as i32, X0, X0, X0, WD, WD, WD);
+    root.build_tree(s, 0.5, 0.5, &particles, 0.1*particles[0].h);
+
+    for p in 0..n {
+        let hrkern: f64 = particles[p].h*rkern;
+        let mut found: Vec<usize> = Vec::new();
+        root.find_neighbors(p, s, &particles, &mut found, WD, WD, WD, X0, X0, X0, hrkern, true, true, true, y_shear);
+        found.sort();
+        found.dedup();
+        let expected: Vec<usize> = (0..n).filter(|&q| brute_norm(&particles[p], &particles[q], y_shear) <= hrkern*hrkern).collect();
+        assert_eq!(found, expected, "particle {} at ({}, {}, {}), y_shear = {}", p, particles[p].x, particles[p].y, particles[p].z, y_shear);
+    }
+}
+
+#[test]
+fn periodic_neighbors_match_brute_force() {
+    check(0.0);
+}
+
+#[test]
+fn sheared_neighbors_match_brute_force() {
+    // An offset that is not a multiple of the lattice spacing, and one that
+    // wraps around the box
+    check(0.37);
+    check(1.83);
+}
+
+
+#[test]
+fn fitted_root_neighbors_match_brute_force() {
+    // Open box: the particles spread over three times the box passed to the
+    // tree, which the root follows through auto_root
+    let (nx, s, rkern): (usize, i32, f64) = (8, 4, 2.0);
+    let mut particles: Vec<Particle> = particles(nx, 0.3);
+    for p in particles.iter_mut() {
+        p.x *= 3.0;
+        p.y *= 3.0;
+        p.z *= 3.0;
+    }
+    let n: usize = particles.len();
+    let mut root: Node = <Node as BuildTree>::new(n as i32, X0, X0, X0, WD, WD, WD);
+    root.auto_root(rkern, false, false, false);
+    root.build_tree(s, 0.5, 0.5, &particles, 0.1*particles[0].h);
+    let norm = |x: f64, y: f64, z: f64, q: &Particle| (x - q.x).powi(2) + (y - q.y).powi(2) + (z - q.z).powi(2);
+
+    for p in 0..n {
+        let hrkern: f64 = particles[p].h*rkern;
+        let mut found: Vec<usize> = Vec::new();
+        root.find_neighbors(p, s, &particles, &mut found, WD, WD, WD, X0, X0, X0, hrkern, false, false, false, 0.0);
+        found.sort();
+        let expected: Vec<usize> = (0..n).filter(|&q| norm(particles[p].x, particles[p].y, particles[p].z, &particles[q]) <= hrkern*hrkern).collect();
+        assert_eq!(found, expected, "particle {} at ({}, {}, {})", p, particles[p].x, particles[p].y, particles[p].z);
+    }
+
+    // A star near a corner of the particles, far outside the box
+    let star: Star = Star{x: 1.2, y: -1.1, z: 1.0, ..Default::default()};
+    let radius: f64 = 0.8;
+    let mut found: Vec<usize> = Vec::new();
+    root.find_neighbors_star(&star, s, &particles, &mut found, WD, WD, WD, X0, X0, X0, radius, false, false, false, 0.0);
+    found.sort();
+    let expected: Vec<usize> = (0..n).filter(|&q| norm(star.x, star.y, star.z, &particles[q]) <= radius*radius).collect();
+    assert!(!expected.is_empty());
+    assert_eq!(found, expected);
+}
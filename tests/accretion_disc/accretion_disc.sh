@@ -1,2 +1,4 @@
+if [ "$1" != "--resume" ]; then
 cargo run -p accretion_disc --bin init_dist_accretion_disc
-cargo run --release -p accretion_disc --bin accretion_disc
\ No newline at end of file
+fi
+cargo run --release -p accretion_disc --bin accretion_disc -- $1
\ No newline at end of file
@@ -20,30 +20,34 @@ const G: f64    = 1.0;
 fn main() -> Result<(), Box<dyn Error>> {
 
     // Files
-    let path: &str      = "./Accretiondisc/Ini_00.csv";
     let input_file: &str= "./tests/accretion_disc/input";
 
     // Parameters
-    let input: Vec<f64> = datafunctions::read_input(input_file);
+    let input: accretion_disc::Input = match accretion_disc::Input::read(input_file) {
+        Ok(input) => input,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
 
-    let eta: f64    = input[0];         // eta: dimensionless constant specifying the smoothing length
-    let gamm: f64   = input[1];         // gamma: Heat capacity ratio
-    let x_c: f64    = input[3];         // x_c: center (x-coordinate)
-    let y_c: f64    = input[4];         // y_c: center (y-coordinate)
-    let z_c: f64    = input[5];         // z_c: center (z-coordinate)
-    let r_in: f64   = input[6];         // inner radius of the acc. disc
-    let r_ref: f64  = input[7];         // reference radius of the acc. disc
-    let r_out: f64  = input[8];         // outer radius of the acc. disc
-    let m_dc: f64   = input[9];         // portion of the disc's mass w.r.t. the star mass
-    let m_star: f64 = input[10];        // star's mass
-    let p_index: f64= input[11];        // p index - density profile 
-    let q_index: f64= input[12];        // q index - density profile 
-    let h_r: f64    = input[13];        // H over r_ref
+    let eta: f64    = input.eta;        // eta: dimensionless constant specifying the smoothing length
+    let gamm: f64   = input.gamma;      // gamma: Heat capacity ratio
+    let x_c: f64    = input.x0;         // x_c: center (x-coordinate)
+    let y_c: f64    = input.y0;         // y_c: center (y-coordinate)
+    let z_c: f64    = input.z0;         // z_c: center (z-coordinate)
+    let r_in: f64   = input.r_in;       // inner radius of the acc. disc
+    let r_ref: f64  = input.r_ref;      // reference radius of the acc. disc
+    let r_out: f64  = input.r_out;      // outer radius of the acc. disc
+    let m_dc: f64   = input.m_disc;     // portion of the disc's mass w.r.t. the star mass
+    let m_star: f64 = input.m_star;     // star's mass
+    let p_index: f64= input.p_index;    // p index - density profile 
+    let q_index: f64= input.q_index;    // q index - density profile 
+    let h_r: f64    = input.h_r;        // H over r_ref
     
-    let n: u32      = input[17] as u32; // Particle resolution
+    let n: u32      = input.n;          // Particle resolution
     
     let m_disc: f64 = m_dc*m_star;      // Disc's mass
-    let dm: f64     = m_disc/n as f64;  // Particle's mass
     let vx0: f64    = 0.0;              // x velocity in CoM
     let vy0: f64    = 0.0;              // y velocity in CoM
     let vz0: f64    = 0.0;              // z velocity in CoM
@@ -60,9 +64,9 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     partdistribution::init_dist_disc1(&mut particles, n, m_star, r_in, r_out, m_disc, p_index, q_index, r_ref, sigma0, cs0, eta, nbins, &mut rng);
     partdistribution::init_dist_disc_velocities(&mut particles, n, m_star, r_in, p_index, q_index, cs0, gamm);
-    partdistribution::com_frame(&mut particles, n, dm, x_c, y_c, z_c, vx0, vy0, vz0);
+    partdistribution::com_frame(&mut particles, n, x_c, y_c, z_c, vx0, vy0, vz0);
 
-    if let Err(err) = datafunctions::save_data(path, &particles){
+    if let Err(err) = datafunctions::save_data(&datafunctions::output_file(&input.output_dir, "Ini_00.csv"), &particles){
         println!("{}", err);
         process::exit(1);
     }
@@ -1 +1 @@
-cargo run --release -p accretion_disc_phantom --bin accretion_disc_phantom
+cargo run --release -p accretion_disc_phantom --bin accretion_disc_phantom -- $1
@@ -0,0 +1,44 @@
+// ------------------------------------------------------------------------- //
+// Input parameters of the 3D Accretion Disc from Phantom                    //
+// Keys of its input file, written as `key = value` lines.                   //
+// ------------------------------------------------------------------------- //
+
+use datafunctions::OutputFormat;
+
+datafunctions::config! {
+    pub struct Input {
+        eta: f64 = 1.2,                  "",    "Dimensionless constant specifying the smoothing length", min 0.0;
+        gamma: f64,                      "",    "Heat capacity ratio. 1.4 is appropriate for molecular hydrogen with rotovibrational transitions", min 1.0;
+        eos_type: bool,                  "",    "EoS (0=isoth[No u]; 1=adiab[u])";
+        source: String,                  "",    "Initial particles: a CSV export or a Phantom dump";
+        x0: f64,                         "L",   "center (x-coordinate)";
+        y0: f64,                         "L",   "center (y-coordinate)";
+        z0: f64,                         "L",   "center (z-coordinate)";
+        r_in: f64,                       "L",   "inner radius of the acc. disc";
+        r_ref: f64,                      "L",   "reference radius of the acc. disc";
+        r_out: f64,                      "L",   "outer radius of the acc. disc";
+        m_disc: f64,                     "",    "portion of the disc's mass w.r.t. the star mass";
+        m_star: f64,                     "M",   "Star's mass";
+        p_index: f64,                    "",    "for density profile";
+        q_index: f64,                    "",    "for density profile";
+        h_r: f64,                        "",    "heigth scale at r_ref";
+        t0: f64 = 0.0,                   "T",   "Initial time";
+        tf: f64,                         "T",   "Final time";
+        dt_sav: f64,                     "T",   "Recording time step", min 0.0;
+        n: u32,                          "",    "Number of Particles", min 1;
+        s: i32 = 10,                     "",    "Maximum number of particles in a leaf (Bucket size)", min 1;
+        alpha: f64 = 0.5,                "",    "Fraction of the bucket size s", min 0.0, max 1.0;
+        beta: f64 = 0.5,                 "",    "Maximum ratio of cells with less than alpha*s particles", min 0.0, max 1.0;
+        c: f64 = 0.0,                    "L/T", "Speed of light in code units (0=Newtonian gravity)";
+        eta_orb: f64 = 0.1,              "",    "Sink substep over the orbital time of the closest sink pair", min 0.0;
+        chk_steps: u32 = 0,              "",    "Steps between checkpoints (0=off)";
+        chk_min: f64 = 60.0,             "min", "Wall-clock minutes between checkpoints (0=off)", min 0.0;
+        max_walltime: f64 = 0.0,         "h",   "Hours after which the run checkpoints and stops (0=off)", min 0.0;
+        output: OutputFormat = "binary", "",    "Snapshot format (binary, csv or vtu)";
+        output_dir: String,              "",    "Directory of the run's files (created if needed)";
+        prefix: String = "Ev_",          "",    "Prefix of the snapshots' names";
+        digits: u32 = 0,                 "",    "Digits of the snapshots' numbers, zero-padded (0=no padding)";
+        diag_steps: u32 = 0,             "",    "Steps between lines of Diagnostics.csv (0=off)";
+        diag_dt: f64 = 0.0,              "T",   "Time between lines of Diagnostics.csv (0=off)", min 0.0;
+    }
+}
@@ -3,8 +3,6 @@
 // ------------------------------------------------------------------------- //
 
 use std::{
-    fs::File,
-    io::Write,
     error::Error,
     time::Instant,
     process,
@@ -23,39 +21,48 @@ use sphfunctions;
 use tree_algorithm::BuildTree;
 use std::f64::consts::PI;
 const G: f64 = 1.0;
+const AU: f64 = 1.496e13;       // Unit of distance of the Phantom dumps (cm)
+const M_SUN: f64 = 1.989e33;    // Unit of mass of the Phantom dumps (g)
 
 fn main() -> Result<(), Box<dyn Error>> {
 
     // Files
-    let path_source: &str   = "./tests/accretion_disc_phantom/AD_Phantom.csv";
     let input_file: &str    = "./tests/accretion_disc_phantom/input";
 
     //---------------------------------------------------------------------------------------------
     // Parameters
-    let input: Vec<f64> = datafunctions::read_input(input_file);
-
-    let eta: f64    = input[0];         // eta: dimensionless constant specifying the smoothing length
-    let gamm: f64   = input[1];         // gamma: Heat capacity ratio
-    let eos_t: bool = input[2] != 0.0;  // EoS (0=isoth[No u]; 1=adiab[u])
-    let x_c: f64    = input[3];         // x_c: center (x-coordinate)
-    let y_c: f64    = input[4];         // y_c: center (y-coordinate)
-    let z_c: f64    = input[5];         // z_c: center (z-coordinate)
-    let r_ref: f64  = input[7];         // reference radius of the acc. disc
-    let r_out: f64  = input[8];         // outer radius of the acc. disc
-    let m_dc: f64   = input[9];         // portion of the disc's mass w.r.t. the star mass
-    let m_star: f64 = input[10];         // star's mass
-    let q_index: f64= input[12];        // q index - density profile 
-    let h_r: f64    = input[13];        // H over r_ref
+    let input: accretion_disc_phantom::Input = match accretion_disc_phantom::Input::read(input_file) {
+        Ok(input) => input,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
+
+    let eta: f64    = input.eta;        // eta: dimensionless constant specifying the smoothing length
+    let gamm: f64   = input.gamma;      // gamma: Heat capacity ratio
+    let eos_t: bool = input.eos_type;   // EoS (0=isoth[No u]; 1=adiab[u])
+    let x_c: f64    = input.x0;         // x_c: center (x-coordinate)
+    let y_c: f64    = input.y0;         // y_c: center (y-coordinate)
+    let z_c: f64    = input.z0;         // z_c: center (z-coordinate)
+    let r_ref: f64  = input.r_ref;      // reference radius of the acc. disc
+    let r_out: f64  = input.r_out;      // outer radius of the acc. disc
+    let m_dc: f64   = input.m_disc;     // portion of the disc's mass w.r.t. the star mass
+    let m_star: f64 = input.m_star;      // star's mass
+    let q_index: f64= input.q_index;    // q index - density profile 
+    let h_r: f64    = input.h_r;        // H over r_ref
     
-    let t0: f64     = input[14];        // Initial time
-    let tf: f64     = input[15];        // Final time
-    let dt_sav: f64 = input[16];        // Recording time step
-    let mut n: usize= input[17] as usize; // Particle resolution
+    let t0: f64     = input.t0;         // Initial time
+    let tf: f64     = input.tf;         // Final time
+    let dt_sav: f64 = input.dt_sav;     // Recording time step
+    let c: f64      = input.c;          // Speed of light (0=Newtonian gravity)
+    let eta_orb: f64 = input.eta_orb;   // Sink substep over the orbital time of the closest pair
+    let mut n: usize= input.n as usize;   // Particle resolution
     
     // Tree's parameters
-    let s_: i32     = input[18] as i32; // Bucket size
-    let alpha_: f64 = input[19];        // Fraction of the bucket size
-    let beta_: f64  = input[20];        // Maximum ratio of cells with less than alpha*s particles
+    let s_: i32     = input.s;          // Bucket size
+    let alpha_: f64 = input.alpha;      // Fraction of the bucket size
+    let beta_: f64  = input.beta;       // Maximum ratio of cells with less than alpha*s particles
     
     let m_disc: f64 = m_dc*m_star;      // Disc's mass
     let dm: f64     = m_disc/n as f64;  // Particle's mass
@@ -68,6 +75,16 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut dt: f64     = 0.01*dt_sav;  // Initial time step
     let mut sav: bool   = false;        // Save data
     let mut it_sav: u32 = 1;            // Save data iteration
+
+    // Checkpoints
+    let chk_steps: u32  = input.chk_steps;  // Steps between checkpoints (0=off)
+    let chk_min: f64    = input.chk_min;    // Wall-clock minutes between checkpoints (0=off)
+    let max_walltime: f64 = input.max_walltime; // Hours after which the run checkpoints and stops (0=off)
+
+    // Output
+    let path_source: &str = &input.source; // CSV export or Phantom dump
+    let output_dir: &str = &input.output_dir; // Directory of the run's files (created if needed)
+    let path_checkpoint: &str = &datafunctions::output_file(output_dir, "Checkpoint");
     
     //---------------------------------------------------------------------------------------------
     let wd: f64 = 4.0*r_out;
@@ -86,61 +103,139 @@ fn main() -> Result<(), Box<dyn Error>> {
     let cs02: f64   = cs0*cs0;
 
     let mut particles :Vec<Particle> = Vec::new();
-    let mut star: Star = Star{ m: m_star, x: x_c, y: y_c, z: z_c, hacc:hacc, facc: facc, ..Default::default()};
-    if let Err(err) = datafunctions::read_data_iso(path_source, &mut particles) {
-        println!("{}", err);
-        process::exit(1);
+    let star: Star = Star{ m: m_star, x: x_c, y: y_c, z: z_c, hacc:hacc, facc: facc, c, ..Default::default()};
+    let mut stars: Vec<Star> = Vec::new();
+    let resume: Option<datafunctions::RunState> = match datafunctions::resume(path_checkpoint, &mut particles, &mut stars) {
+        Ok(state) => state,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
+    let mut t_dump: f64 = t0;
+    if resume.is_none() && path_source.ends_with(".csv") {
+        if let Err(err) = datafunctions::read_data_iso(path_source, dm, &mut particles) {
+            println!("{}", err);
+            process::exit(1);
+        }
+    } else if resume.is_none() {
+        match datafunctions::read_phantom(path_source, &mut particles, &mut stars) {
+            Ok(dump) => {
+                t_dump = dump.time;
+                n      = particles.len();
+                // The dump has no accretion fraction nor speed of light
+                for sink in stars.iter_mut() {
+                    sink.facc = star.facc;
+                    sink.c    = star.c;
+                }
+            },
+            Err(err) => {
+                println!("{}", err);
+                process::exit(1);
+            }
+        }
     }
-    let particles_ptr: Pointer = Pointer(particles.as_mut_ptr());
+    // Without sinks in the dump, the configured star
+    if stars.is_empty() {
+        stars.push(star);
+    }
+    let mut particles_ptr: Pointer = Pointer(particles.as_mut_ptr());
 
-    let mut t: f64  = t0;               // Time
+    let mut t: f64  = t_dump;           // Time
     let mut it: u32 = 0;                // Time iterations
+    if let Some(state) = &resume {
+        t       = state.t;
+        dt      = state.dt;
+        it      = state.it;
+        it_sav  = state.it_sav;
+        n       = particles.len();
+    }
 
     // Save time evolution
-    let mut time_file = File::create("./Accretiondiscphantom/Time.txt").expect("creation failed"); // Save time steps
+    let mut output: datafunctions::Output = match datafunctions::Output::new(output_dir, &input.prefix, input.digits, input.output, input.diag_steps, input.diag_dt, resume.as_ref()) {
+        Ok(output) => output,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
     
     //------------------------------------ kernel -------------------------------------------------
     let sigma: f64  = 1./(120.*PI);     // Normalization constant of kernel
     let rkern: f64  = 3.;               // Kernel radius
     //---------------------------------------------------------------------------------------------
 
-    for ii in 0..n{
-        particles[ii].rho = sphfunctions::density_from_h(dm, particles[ii].h, eta);
+    if resume.is_none() {
+        for ii in 0..n {
+            particles[ii].rho = sphfunctions::density_from_h(particles[ii].m, particles[ii].h, eta);
+        }
     }
 
     let mut tree: Node = <Node as BuildTree>::new(n as i32, x0, y0, z0, wd, lg, hg);
+    tree.auto_root(rkern, xper, yper, zper);
+
+    let header: datafunctions::Header = datafunctions::Header{x0, y0, z0, wd, lg, hg, xper, yper, zper, eos_t, gamma: gamm, coeff: cs02, ..Default::default()};
+    output.header = header;
+    let mut checkpoint: datafunctions::Checkpoint = datafunctions::Checkpoint::new(path_checkpoint, chk_steps, chk_min);
+    let stop: datafunctions::Stop = datafunctions::Stop::new(max_walltime).expect("signal handlers failed");
 
     //------------------------------------ Main Loop ----------------------------------------------
     let start = Instant::now();   // Runing time
+    let mut stopping: bool = false; // Stopped before the final time
     while t < tf {
-        sphfunctions::predictor_kdk_integrator(&mut particles, dt, dm, eos_t, sphfunctions::eos_isothermal_disc, sphfunctions::sound_speed_isothermal_disc, gamm, cs02,
+        let sinks: Vec<&dyn sphfunctions::ExternalForce> = stars.iter().map(|star| star as &dyn sphfunctions::ExternalForce).collect();
+        sphfunctions::predictor_kdk_integrator(&mut particles, dt, eos_t, sphfunctions::eos_isothermal_disc, sphfunctions::sound_speed_isothermal_disc, gamm, cs02,
                                        sphfunctions::dwdh, sphfunctions::f_quintic_kernel, sphfunctions::dfdq_quintic_kernel, sigma, rkern, 
                                        eta, &mut tree, s_, alpha_, beta_, n, particles_ptr,
                                        sphfunctions::mon97_art_vis,
-                                       sphfunctions::body_forces_gravitation, &star, true,
-                                       sphfunctions::periodic_boundary, xper, yper, zper, wd, lg, hg,  x0, y0, z0);
-        sphfunctions::accretion_boundary(&mut star, &mut particles, dm, &mut n, & tree, s_, wd, lg, hg, x0, y0, z0, xper, yper, zper);
-        sphfunctions::star_integrator(&mut star, dt);
+                                       &sinks,
+                                       sphfunctions::periodic_boundary, xper, yper, zper, 0.0, 0.0, wd, lg, hg,  x0, y0, z0);
+        for star in stars.iter_mut() {
+            sphfunctions::accretion_boundary(star, &mut particles, &mut n, &mut particles_ptr, &mut tree, s_, wd, lg, hg, x0, y0, z0, xper, yper, zper, 0.0);
+        }
+        sphfunctions::sinks_integrator(&mut stars, dt, eta_orb);
         tree.restart(n);
         tree.build_tree(s_, alpha_, beta_, &particles, 1.0e-02);
-        dt = sphfunctions::time_step_mon(&particles, n, gamm, cs02, rkern, wd, lg, hg, x0, y0, z0, &mut tree, s_, sphfunctions::sound_speed_isothermal_disc, xper, yper, zper);
+        dt = sphfunctions::time_step_mon(&particles, n, gamm, cs02, rkern, wd, lg, hg, x0, y0, z0, &mut tree, s_, sphfunctions::sound_speed_isothermal_disc, xper, yper, zper, 0.0, 0.0);
         tree.restart(n);
         datafunctions::time_step(&mut t, &mut dt, dt_sav, &mut sav, &mut it_sav);
         println!("dt: {:.4}\tt: {:.4}\tn:{}", dt, t, n);
         if sav {
-            time_file.write((t.to_string() + &"\n").as_bytes()).expect("write failed");
-            if let Err(err) = datafunctions::save_data_bin(&(String::from("./Accretiondiscphantom/Ev_") + &(it_sav-2).to_string()), &particles){
+            if let Err(err) = output.snapshot(it_sav-2, t, &particles, &stars.iter().collect::<Vec<&Star>>()){
                 println!("{}", err);
                 process::exit(1);
             }
         } 
         it += 1;
+        if let Err(err) = output.step(it, t, dt, &particles) {
+            println!("{}", err);
+            process::exit(1);
+        }
+        stopping = stop.requested();
+        if checkpoint.due(it) || stopping {
+            if let Err(err) = checkpoint.save(&datafunctions::RunState{t, t_boundary: t, dt, it, it_sav}, &header, &particles, &stars.iter().collect::<Vec<&Star>>()).and_then(|_| output.record("checkpoint", it, t, "Checkpoint")) {
+                println!("{}", err);
+                process::exit(1);
+            }
+        }
+        if stopping {
+            println!("Stopping at t = {} (iteration {}). Continue with --resume.", t, it);
+            break;
+        }
+    }
+    if stopping {
+        println!("Simulation stopped at t = {} of {}, resumable from the checkpoint.\n Time {} s.\n Iterations: {}.", t, tf, start.elapsed().as_secs(), it);
+    } else {
+        println!("Simulation run successfully.\n Time {} s.\n Iterations: {}.", start.elapsed().as_secs(), it);
     }
-    println!("Simulation run successfully.\n Time {} s.\n Iterations: {}.", start.elapsed().as_secs(), it);
     //---------------------------------------------------------------------------------------------
 
     // Save final information
-    if let Err(err) = datafunctions::save_data_bin(&(String::from("./Accretiondiscphantom/Fin_00")), &particles){
+    if let Err(err) = output.finish(t, &particles, &stars.iter().collect::<Vec<&Star>>()){
+        println!("{}", err);
+        process::exit(1);
+    }
+    if let Err(err) = datafunctions::save_phantom(&output.file("Fin_00.phantom"), &datafunctions::Header{time: t, ..header}, eta, AU, M_SUN, &particles, &stars.iter().collect::<Vec<&Star>>()){
         println!("{}", err);
         process::exit(1);
     }
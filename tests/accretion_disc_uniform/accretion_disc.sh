@@ -1,2 +1,4 @@
+if [ "$1" != "--resume" ]; then
 cargo run -p accretion_disc_uniform --bin init_dist_accretion_disc_uniform
-cargo run --release -p accretion_disc_uniform --bin accretion_disc_uniform
\ No newline at end of file
+fi
+cargo run --release -p accretion_disc_uniform --bin accretion_disc_uniform -- $1
\ No newline at end of file
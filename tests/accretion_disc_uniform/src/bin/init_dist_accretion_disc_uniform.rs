@@ -17,19 +17,24 @@ use std::f64::consts::PI;
 fn main() -> Result<(), Box<dyn Error>> {
 
     // Files
-    let path: &str      = "./Accretiondiscuniform/Ini_00.csv";
     let input_file: &str= "./tests/accretion_disc_uniform/input";
 
     // Parameters
-    let input: Vec<f64> = datafunctions::read_input(input_file);
-
-    let eta: f64    = input[0];         // Dimensionless constant specifying the smoothing length   
-    let m_star: f64 = input[3];         // Star's mass
-    let m_disc: f64 = input[4];         // Disc's mass
-    let r_in: f64   = input[5];        // Inner radius
-    let r_out: f64  = input[6];        // Outer radius
-    let h_disc: f64 = input[7];        // Hight
-    let nx: u32     = input[11] as u32; // Particle resolution
+    let input: accretion_disc_uniform::Input = match accretion_disc_uniform::Input::read(input_file) {
+        Ok(input) => input,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
+
+    let eta: f64    = input.eta;        // Dimensionless constant specifying the smoothing length   
+    let m_star: f64 = input.m_star;     // Star's mass
+    let m_disc: f64 = input.m_disc;     // Disc's mass
+    let r_in: f64   = input.r_in;      // Inner radius
+    let r_out: f64  = input.r_out;     // Outer radius
+    let h_disc: f64 = input.h;         // Hight
+    let nx: u32     = input.nx;         // Particle resolution
     
     let r2_in: f64  = r_in*r_in;
     let r2_out: f64 = r_out*r_out;
@@ -57,6 +62,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let m_t: f64    = m_star + dm;
     for ii in 0..n {
         let omega: f64 = keplerian_velocity(&particles[ii], m_t, x_c, y_c);
+        particles[ii].m  = dm;
         particles[ii].h  = h;
         particles[ii].vx = -particles[ii].y * omega;
         particles[ii].vy = particles[ii].x * omega;
@@ -64,7 +70,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         particles[ii].u  = 0.0;
     }
 
-    if let Err(err) = datafunctions::save_data(path, &particles){
+    if let Err(err) = datafunctions::save_data(&datafunctions::output_file(&input.output_dir, "Ini_00.csv"), &particles){
         println!("{}", err);
         process::exit(1);
     }
@@ -0,0 +1,35 @@
+// ------------------------------------------------------------------------- //
+// Input parameters of the thin Accretion Disc with uniform density          //
+// Keys of its input file, written as `key = value` lines.                   //
+// ------------------------------------------------------------------------- //
+
+use datafunctions::OutputFormat;
+
+datafunctions::config! {
+    pub struct Input {
+        eta: f64 = 1.2,                  "",    "Dimensionless constant specifying the smoothing length", min 0.0;
+        gamma: f64,                      "",    "Heat capacity ratio. 1.4 is appropriate for molecular hydrogen with rotovibrational transitions", min 1.0;
+        eos_type: bool,                  "",    "EoS (0=isoth[No u]; 1=adiab[u])";
+        m_star: f64,                     "M",   "Star's mass";
+        m_disc: f64,                     "M",   "Disc's mass";
+        r_in: f64,                       "L",   "Inner radius of the acc. disc";
+        r_out: f64,                      "L",   "Outer radius of the acc. disc";
+        h: f64,                          "L",   "Height of the disc";
+        t0: f64 = 0.0,                   "T",   "Initial time";
+        tf: f64,                         "T",   "Final time";
+        dt_sav: f64,                     "T",   "Recording time step", min 0.0;
+        nx: u32,                         "",    "Particle resolution", min 1;
+        s: i32 = 10,                     "",    "Maximum number of particles in a leaf (Bucket size)", min 1;
+        alpha: f64 = 0.5,                "",    "Fraction of the bucket size s", min 0.0, max 1.0;
+        beta: f64 = 0.5,                 "",    "Maximum ratio of cells with less than alpha*s particles", min 0.0, max 1.0;
+        chk_steps: u32 = 0,              "",    "Steps between checkpoints (0=off)";
+        chk_min: f64 = 60.0,             "min", "Wall-clock minutes between checkpoints (0=off)", min 0.0;
+        max_walltime: f64 = 0.0,         "h",   "Hours after which the run checkpoints and stops (0=off)", min 0.0;
+        output: OutputFormat = "binary", "",    "Snapshot format (binary, csv or vtu)";
+        output_dir: String,              "",    "Directory of the run's files (created if needed)";
+        prefix: String = "Ev_",          "",    "Prefix of the snapshots' names";
+        digits: u32 = 0,                 "",    "Digits of the snapshots' numbers, zero-padded (0=no padding)";
+        diag_steps: u32 = 0,             "",    "Steps between lines of Diagnostics.csv (0=off)";
+        diag_dt: f64 = 0.0,              "T",   "Time between lines of Diagnostics.csv (0=off)", min 0.0;
+    }
+}
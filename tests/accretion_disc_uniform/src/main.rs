@@ -4,8 +4,6 @@
 // ------------------------------------------------------------------------- //
 
 use std::{
-    fs::File,
-    io::Write,
     error::Error,
     time::Instant,
     process,
@@ -27,29 +25,33 @@ use std::f64::consts::PI;
 fn main() -> Result<(), Box<dyn Error>> {
 
     // Files
-    let path_source: &str   = "./Accretiondiscuniform/Ini_00.csv";
     let input_file: &str    = "./tests/accretion_disc_uniform/input";
 
     //---------------------------------------------------------------------------------------------
     // Parameters
-    let input: Vec<f64> = datafunctions::read_input(input_file);
+    let input: accretion_disc_uniform::Input = match accretion_disc_uniform::Input::read(input_file) {
+        Ok(input) => input,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
     
-    let eta: f64    = input[0];         // Dimensionless constant specifying the smoothing length   
-    let gamm: f64   = input[1];         // Heat capacity ratio
-    let eos_t: bool = input[2] != 0.0;  // EoS (0=isoth[No u]; 1=adiab[u])
-    let m_star: f64 = input[3];         // Star's mass
-    let m_disc: f64 = input[4];         // Disc's mass
-    let r_in: f64   = input[5];         // Inner radius
-    let r_out: f64  = input[6];         // Outer radius
-
-    let t0: f64     = input[8];         // Initial time
-    let tf: f64     = input[9];         // Final time
-    let dt_sav: f64 = input[10];        // Recording time step
+    let eta: f64    = input.eta;        // Dimensionless constant specifying the smoothing length   
+    let gamm: f64   = input.gamma;      // Heat capacity ratio
+    let eos_t: bool = input.eos_type;   // EoS (0=isoth[No u]; 1=adiab[u])
+    let m_star: f64 = input.m_star;     // Star's mass
+    let r_in: f64   = input.r_in;       // Inner radius
+    let r_out: f64  = input.r_out;      // Outer radius
+
+    let t0: f64     = input.t0;         // Initial time
+    let tf: f64     = input.tf;         // Final time
+    let dt_sav: f64 = input.dt_sav;     // Recording time step
     
     // Tree's parameters
-    let s_: i32     = input[12] as i32; // Bucket size
-    let alpha_: f64 = input[13];        // Fraction of the bucket size
-    let beta_: f64  = input[14];        // Maximum ratio of cells with less than alpha*s particles
+    let s_: i32     = input.s;          // Bucket size
+    let alpha_: f64 = input.alpha;      // Fraction of the bucket size
+    let beta_: f64  = input.beta;       // Maximum ratio of cells with less than alpha*s particles
     
     let x_c: f64    = 0.0;
     let y_c: f64    = 0.0;
@@ -72,6 +74,16 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut sav: bool   = false;        // Save data
     let mut it_sav: u32 = 1;            // Save data iteration
 
+    // Checkpoints
+    let chk_steps: u32  = input.chk_steps;  // Steps between checkpoints (0=off)
+    let chk_min: f64    = input.chk_min;    // Wall-clock minutes between checkpoints (0=off)
+    let max_walltime: f64 = input.max_walltime; // Hours after which the run checkpoints and stops (0=off)
+
+    // Output
+    let output_dir: &str = &input.output_dir; // Directory of the run's files (created if needed)
+    let path_source: &str   = &datafunctions::output_file(output_dir, "Ini_00.csv");
+    let path_checkpoint: &str = &datafunctions::output_file(output_dir, "Checkpoint");
+
     let coeff: f64      = 0.0;          // Not used parameter
     
     //---------------------------------------------------------------------------------------------
@@ -81,62 +93,113 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let mut particles :Vec<Particle> = Vec::new();
     let mut star: Star = Star{ m: m_star, x: x_c, y: y_c, z: z_c, hacc:hacc, facc: facc, ..Default::default()};
-    if let Err(err) = datafunctions::read_data(path_source, &mut particles) {
-        println!("{}", err);
-        process::exit(1);
+    let mut stars: Vec<Star> = Vec::new();
+    let resume: Option<datafunctions::RunState> = match datafunctions::resume(path_checkpoint, &mut particles, &mut stars) {
+        Ok(state) => state,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
+    if resume.is_none() {
+        if let Err(err) = datafunctions::read_data(path_source, &mut particles) {
+            println!("{}", err);
+            process::exit(1);
+        }
+    }
+    if let Some(sink) = stars.pop() {
+        star = sink;
     }
-    let particles_ptr: Pointer = Pointer(particles.as_mut_ptr());
+    let mut particles_ptr: Pointer = Pointer(particles.as_mut_ptr());
 
 
     let mut t: f64  = t0;               // Time
     let mut n: usize= particles.len();
-    let dm:f64      = m_disc/n as f64;
     let mut it: u32 = 0;                // Time iterations
+    if let Some(state) = &resume {
+        t       = state.t;
+        dt      = state.dt;
+        it      = state.it;
+        it_sav  = state.it_sav;
+    }
 
     // Save time evolution
-    let mut time_file = File::create("./Accretiondiscuniform/Time.txt").expect("creation failed"); // Save time steps
+    let mut output: datafunctions::Output = match datafunctions::Output::new(output_dir, &input.prefix, input.digits, input.output, input.diag_steps, input.diag_dt, resume.as_ref()) {
+        Ok(output) => output,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
     
     //------------------------------------ kernel -------------------------------------------------
     let sigma: f64  = 1./(120.*PI);     // Normalization constant of kernel
     let rkern: f64  = 3.;               // Kernel radius
     //---------------------------------------------------------------------------------------------
 
-    for ii in 0..n {
-        particles[ii].rho = sphfunctions::density_from_h(dm, particles[ii].h, eta);
+    if resume.is_none() {
+        for ii in 0..n {
+            particles[ii].rho = sphfunctions::density_from_h(particles[ii].m, particles[ii].h, eta);
+        }
     }
     let mut tree: Node = <Node as BuildTree>::new(n as i32, x0, y0, z0, wd, lg, hg);
+    tree.auto_root(rkern, xper, yper, zper);
+
+    let header: datafunctions::Header = datafunctions::Header{x0, y0, z0, wd, lg, hg, xper, yper, zper, eos_t, gamma: gamm, coeff, ..Default::default()};
+    output.header = header;
+    let mut checkpoint: datafunctions::Checkpoint = datafunctions::Checkpoint::new(path_checkpoint, chk_steps, chk_min);
+    let stop: datafunctions::Stop = datafunctions::Stop::new(max_walltime).expect("signal handlers failed");
     
     //------------------------------------ Main Loop ----------------------------------------------
     let start = Instant::now();   // Runing time
+    let mut stopping: bool = false; // Stopped before the final time
     while t < tf {
-        sphfunctions::predictor_kdk_integrator(&mut particles, dt, dm, eos_t, sphfunctions::eos_ideal_gas, sphfunctions::sound_speed_ideal_gas, gamm, coeff,
+        sphfunctions::predictor_kdk_integrator(&mut particles, dt, eos_t, sphfunctions::eos_ideal_gas, sphfunctions::sound_speed_ideal_gas, gamm, coeff,
                                        sphfunctions::dwdh, sphfunctions::f_quintic_kernel, sphfunctions::dfdq_quintic_kernel, sigma, rkern, 
                                        eta, &mut tree, s_, alpha_, beta_, n, particles_ptr,
                                        sphfunctions::mon97_art_vis,
-                                       sphfunctions::body_forces_gravitation, &star, true,
-                                       sphfunctions::periodic_boundary, xper, yper, zper, wd, lg, hg,  x0, y0, z0);
-        sphfunctions::accretion_boundary(&mut star, &mut particles, dm, &mut n, & tree, s_, wd, lg, hg, x0, y0, z0, xper, yper, zper);
+                                       &[&star],
+                                       sphfunctions::periodic_boundary, xper, yper, zper, 0.0, 0.0, wd, lg, hg,  x0, y0, z0);
+        sphfunctions::accretion_boundary(&mut star, &mut particles, &mut n, &mut particles_ptr, &mut tree, s_, wd, lg, hg, x0, y0, z0, xper, yper, zper, 0.0);
         sphfunctions::star_integrator(&mut star, dt);
         tree.restart(n);
         tree.build_tree(s_, alpha_, beta_, &particles, 1.0e-02);
-        dt = sphfunctions::time_step_mon(&particles, n, gamm, coeff, rkern, wd, lg, hg, x0, y0, z0, &mut tree, s_, sphfunctions::sound_speed_ideal_gas, xper, yper, zper);
+        dt = sphfunctions::time_step_mon(&particles, n, gamm, coeff, rkern, wd, lg, hg, x0, y0, z0, &mut tree, s_, sphfunctions::sound_speed_ideal_gas, xper, yper, zper, 0.0, 0.0);
         tree.restart(n);
         datafunctions::time_step(&mut t, &mut dt, dt_sav, &mut sav, &mut it_sav);
         println!("dt: {:.4}\tt: {:.4}\tn:{}", dt, t, n);
         if sav {
-            time_file.write((t.to_string() + &"\n").as_bytes()).expect("write failed");
-            if let Err(err) = datafunctions::save_data_bin(&(String::from("./Accretiondiscuniform/Ev_") + &(it_sav-2).to_string()), &particles){
+            if let Err(err) = output.snapshot(it_sav-2, t, &particles, &[&star]){
                 println!("{}", err);
                 process::exit(1);
             }
         } 
         it += 1;
+        if let Err(err) = output.step(it, t, dt, &particles) {
+            println!("{}", err);
+            process::exit(1);
+        }
+        stopping = stop.requested();
+        if checkpoint.due(it) || stopping {
+            if let Err(err) = checkpoint.save(&datafunctions::RunState{t, t_boundary: t, dt, it, it_sav}, &header, &particles, &[&star]).and_then(|_| output.record("checkpoint", it, t, "Checkpoint")) {
+                println!("{}", err);
+                process::exit(1);
+            }
+        }
+        if stopping {
+            println!("Stopping at t = {} (iteration {}). Continue with --resume.", t, it);
+            break;
+        }
+    }
+    if stopping {
+        println!("Simulation stopped at t = {} of {}, resumable from the checkpoint.\n Time {} s.\n Iterations: {}.", t, tf, start.elapsed().as_secs(), it);
+    } else {
+        println!("Simulation run successfully.\n Time {} s.\n Iterations: {}.", start.elapsed().as_secs(), it);
     }
-    println!("Simulation run successfully.\n Time {} s.\n Iterations: {}.", start.elapsed().as_secs(), it);
     //---------------------------------------------------------------------------------------------
 
     // Save final information
-    if let Err(err) = datafunctions::save_data_bin(&(String::from("./Accretiondiscuniform/Fin_00")), &particles){
+    if let Err(err) = output.finish(t, &particles, &[&star]){
         println!("{}", err);
         process::exit(1);
     }
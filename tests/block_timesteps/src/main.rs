@@ -0,0 +1,146 @@
+// ------------------------------------------------------------------------- //
+// Block timesteps: a hot bump expands in a periodic box under block steps.  //
+// Checks that the bins respect the particles' time steps,                   //
+//      dt_block/2^ibin <= dt_i,                                             //
+// that every particle is synchronised at the end of each block and that    //
+// the total energy is conserved.                                            //
+// ------------------------------------------------------------------------- //
+
+use std::{
+    error::Error,
+    process,
+};
+
+use tree_algorithm::BuildTree;
+
+use structures::{
+    Particle,
+    Node,
+    Pointer,
+};
+
+use partdistribution;
+use sphfunctions;
+
+use std::f64::consts::PI;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let nx: u32     = 10;   // Particle resolution
+    let x0: f64     = 0.0;  // Bottom left corner  (x-coordinate)
+    let y0: f64     = 0.0;  // Bottom left corner  (y-coordinate)
+    let z0: f64     = 0.0;  // Bottom left corner  (z-coordinate)
+    let wd: f64     = 1.;   // Width (x)
+    let lg: f64     = 1.;   // Length (y)
+    let hg: f64     = 1.;   // Height (z)
+    let rho: f64    = 1.;   // Density
+    let u0: f64     = 1.;   // Background internal energy
+    let eta: f64    = 1.2;  // Dimensionless constant specifying the smoothing length
+    let gamma: f64  = 5./3.;// Heat capacity ratio
+    let coeff: f64  = 0.0;  // Not used parameter
+    let nbins: u32  = 4;    // Number of timestep bins
+    let n_blocks: u32 = 10; // Blocks to run
+    let dt_max: f64 = 0.1;  // Largest block step
+
+    let xper: bool  = true;
+    let yper: bool  = true;
+    let zper: bool  = true;
+
+    // Tree parameters
+    let s_: i32     = 10;
+    let alpha_: f64 = 0.5;
+    let beta_: f64  = 0.5;
+
+    // Cubic kernel
+    let sigma: f64  = 1./PI;
+    let rkern: f64  = 2.;
+
+    let mut particles: Vec<Particle> = Vec::new();
+    partdistribution::init_dist_hcp(&mut particles, nx, rho, eta, wd, lg, hg, x0, y0, z0);
+    let n: usize = particles.len();
+    let particles_ptr: Pointer = Pointer(particles.as_mut_ptr());
+
+    // A smooth hot bump: the sound speed, and so the bins, span a factor ~3.
+    for particle in particles.iter_mut() {
+        let r2: f64 = (particle.x - 0.5).powi(2) + (particle.y - 0.5).powi(2) + (particle.z - 0.5).powi(2);
+        particle.u   = u0*(1. + 10.*(-r2/0.02).exp());
+        particle.rho = sphfunctions::density_from_h(particle.m, particle.h, eta);
+    }
+
+    // Initial forces
+    let mut tree: Node = <Node as BuildTree>::new(n as i32, x0, y0, z0, wd, lg, hg);
+    tree.build_tree(s_, alpha_, beta_, &particles, 1.0e-02);
+    sphfunctions::smoothing_length(&mut particles, eta, sphfunctions::f_cubic_kernel, sphfunctions::dfdq_cubic_kernel, sigma, rkern, 1e-03, 30, 0.0, &tree, s_, n, particles_ptr, wd, lg, hg, x0, y0, z0, xper, yper, zper, 0.0);
+    sphfunctions::accelerations(&mut particles, true, sphfunctions::eos_ideal_gas, sphfunctions::sound_speed_ideal_gas, gamma, coeff,
+                                sphfunctions::dwdh, sphfunctions::f_cubic_kernel, sphfunctions::dfdq_cubic_kernel, sigma, rkern,
+                                &tree, s_, n, particles_ptr, wd, lg, hg, x0, y0, z0,
+                                sphfunctions::mon97_art_vis,
+                                &[], xper, yper, zper, 0.0, 0.0);
+
+    let e_ini: f64 = energy(&particles);
+    let mut failed: bool = false;
+    let mut spread: bool = false;
+    for block in 0..n_blocks {
+        let dts: Vec<f64> = sphfunctions::time_step_mon_particles(&particles, n, gamma, coeff, rkern, wd, lg, hg, x0, y0, z0, &mut tree, s_, sphfunctions::sound_speed_ideal_gas, xper, yper, zper, 0.0, 0.0);
+        let dt_block: f64 = sphfunctions::block_time_step(&dts, dt_max, nbins);
+        sphfunctions::assign_bins(&mut particles, &dts, dt_block, nbins);
+
+        // Every particle's step fits its own time step
+        for (particle, dt) in particles.iter().zip(dts.iter()) {
+            if dt_block/(1_u64 << particle.ibin) as f64 > *dt {
+                println!("FAILED: block {}: bin {} is too coarse for dt = {}.", block, particle.ibin, dt);
+                failed = true;
+                break;
+            }
+        }
+        let ibin_min: u32 = particles.iter().map(|particle| particle.ibin).min().unwrap();
+        let ibin_max: u32 = particles.iter().map(|particle| particle.ibin).max().unwrap();
+        spread = spread || ibin_max >= ibin_min + 2;
+
+        sphfunctions::block_kdk_integrator(&mut particles, dt_block, nbins, false, true, sphfunctions::eos_ideal_gas, sphfunctions::sound_speed_ideal_gas, gamma, coeff,
+                                       sphfunctions::dwdh, sphfunctions::f_cubic_kernel, sphfunctions::dfdq_cubic_kernel, sigma, rkern,
+                                       eta, &mut tree, s_, alpha_, beta_, n, particles_ptr,
+                                       sphfunctions::mon97_art_vis,
+                                       &[], &mut [], 0.1,
+                                       sphfunctions::periodic_boundary, xper, yper, zper, 0.0, 0.0, wd, lg, hg, x0, y0, z0);
+
+        // Synchronised: every particle got the closing kick of a step ending
+        // with the block
+        let dt_min: f64 = dt_block/(1_u64 << nbins) as f64;
+        let unsynced: usize = particles.iter().filter(|particle| {
+            let dt_i: f64 = dt_min*(1_u64 << (nbins - particle.ibin)) as f64;
+            !particle.active || particle.vx != particle.vx_star + 0.5 * dt_i * particle.ax || particle.u != particle.u_star + 0.5 * dt_i * particle.du
+        }).count();
+        if unsynced > 0 {
+            println!("FAILED: block {}: {} particles are not synchronised.", block, unsynced);
+            failed = true;
+        }
+        println!("Block {}: dt = {:.3e}, bins {}-{}.", block, dt_block, ibin_min, ibin_max);
+    }
+
+    if !spread {
+        println!("FAILED: the bins never spanned three levels.");
+        failed = true;
+    }
+    let e_fin: f64 = energy(&particles);
+    let tol: f64 = 2.0e-03;
+    println!("Energy: {} -> {} (relative change {:.2e}).", e_ini, e_fin, ((e_fin - e_ini)/e_ini).abs());
+    if ((e_fin - e_ini)/e_ini).abs() > tol {
+        println!("FAILED: energy is not conserved.");
+        failed = true;
+    }
+
+    if failed {
+        process::exit(1);
+    }
+    println!("Block timesteps test passed.");
+    Ok(())
+}
+
+// Total (kinetic + internal) energy of the gas
+fn energy(particles: & Vec<Particle>) -> f64 {
+    let mut e: f64 = 0.0;
+    for particle in particles {
+        e += particle.m*(particle.u + 0.5*(particle.vx*particle.vx + particle.vy*particle.vy + particle.vz*particle.vz));
+    }
+    return e;
+}
@@ -10,7 +10,8 @@ use tree_algorithm::{
     BuildTree,
     FindNeighbors,
     save_tree,
-    save_neighbors
+    save_neighbors,
+    sq_periodic_norm,
 };
 
 use structures::{
@@ -77,13 +78,33 @@ fn main() -> Result<(), Box<dyn Error>> {
     let start2 = Instant::now();
     let mut neighbors: Vec<usize> = Vec::new();
     for p in 0..n as usize {
-        root.find_neighbors(p, s, &particles, &mut neighbors, wd, lg, hg, x0, y0, z0, particles[p].h*rkern, xper, yper, zper);
+        root.find_neighbors(p, s, &particles, &mut neighbors, wd, lg, hg, x0, y0, z0, particles[p].h*rkern, xper, yper, zper, 0.0);
         neighbors = Vec::new();
     }
     let p: usize = 124;
-    root.find_neighbors(p, s, &particles, &mut neighbors, wd, lg, hg, x0, y0, z0, particles[p].h*rkern, xper, yper, zper);
+    root.find_neighbors(p, s, &particles, &mut neighbors, wd, lg, hg, x0, y0, z0, particles[p].h*rkern, xper, yper, zper, 0.0);
     
     println!("Neighbors Finder: {} s", start2.elapsed().as_secs());
     save_neighbors(path_neighbors, p, & neighbors);
+
+    // Shearing box: the images across x are shifted by y_shear in y. Every
+    // particle is checked against a brute-force search.
+    let y_shear: f64 = 0.37;
+    let mut mismatches: usize = 0;
+    for p in 0..n as usize {
+        let hrkern: f64 = particles[p].h*rkern;
+        neighbors = Vec::new();
+        root.find_neighbors(p, s, &particles, &mut neighbors, wd, lg, hg, x0, y0, z0, hrkern, xper, yper, zper, y_shear);
+        neighbors.sort();
+        neighbors.dedup();
+        let brute: Vec<usize> = (0..n as usize).filter(|&q| sq_periodic_norm(particles[p].x, particles[q].x, particles[p].y, particles[q].y, particles[p].z, particles[q].z, wd, lg, hg, hrkern, xper, yper, zper, y_shear) <= hrkern*hrkern).collect();
+        if neighbors != brute {
+            mismatches += 1;
+        }
+    }
+    println!("Sheared neighbours (y_shear = {}): {} mismatches", y_shear, mismatches);
+    if mismatches > 0 {
+        process::exit(1);
+    }
     Ok(())
 }
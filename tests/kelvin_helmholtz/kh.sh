@@ -1,2 +1,4 @@
+if [ "$1" != "--resume" ]; then
 cargo run -p kelvin_helmholtz --bin init_dist_kelvin_helmholtz
-cargo run --release -p kelvin_helmholtz --bin kelvin_helmholtz
+fi
+cargo run --release -p kelvin_helmholtz --bin kelvin_helmholtz -- $1
@@ -16,44 +16,50 @@ use std::f64::consts::PI;
 fn main() -> Result<(), Box<dyn Error>> {
 
     // Files
-    let path: &str      = "./Kelvinhelmholtz/Ini_00.csv";
     let input_file: &str= "./tests/kelvin_helmholtz/input";
 
     // Parameters
-    let input: Vec<f64> = datafunctions::read_input(input_file);
+    let input: kelvin_helmholtz::Input = match kelvin_helmholtz::Input::read(input_file) {
+        Ok(input) => input,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
 
-    let eta: f64    = input[0];         // Dimensionless constant specifying the smoothing length
-    let gamma: f64  = input[1];         // Heat capacity ratio
+    let eta: f64    = input.eta;        // Dimensionless constant specifying the smoothing length
+    let gamma: f64  = input.gamma;      // Heat capacity ratio
     
-    let x0: f64     = input[3];         // Bottom left corner  (x-coordinate)
-    let y0: f64     = input[4];         // Bottom left corner  (y-coordinate)
-    let z0: f64     = input[5];         // Bottom left corner  (z-coordinate)
-    let wd: f64     = input[6];         // Width (x)
-    let lg: f64     = input[7];         // Length (y)
-    let hg: f64     = input[8];         // Height (z)
-    let y1: f64     = input[9];         // Y-lower edge of fluid 2 
-    let y2: f64     = input[10];         // Y-upper edge of fluid 2
-    let rho1: f64   = input[11];        // Initial density fluid 1
-    let rho2: f64   = input[12];        // Initial density fluid 2
-    let vx1: f64    = input[13];        // Initial x velocity fluid 1
-    let vx2: f64    = input[14];        // Initial x velocity fluid 2
-    let p0: f64     = input[15];        // Initial pressure
-
-    let nx: u32     = input[19] as u32; // Particle resolution
+    let x0: f64     = input.x0;         // Bottom left corner  (x-coordinate)
+    let y0: f64     = input.y0;         // Bottom left corner  (y-coordinate)
+    let z0: f64     = input.z0;         // Bottom left corner  (z-coordinate)
+    let wd: f64     = input.wd;         // Width (x)
+    let lg: f64     = input.lg;         // Length (y)
+    let hg: f64     = input.hg;         // Height (z)
+    let y1: f64     = input.y1;         // Y-lower edge of fluid 2 
+    let y2: f64     = input.y2;          // Y-upper edge of fluid 2
+    let rho1: f64   = input.rho1;       // Initial density fluid 1
+    let rho2: f64   = input.rho2;       // Initial density fluid 2
+    let vx1: f64    = input.vx1;        // Initial x velocity fluid 1
+    let vx2: f64    = input.vx2;        // Initial x velocity fluid 2
+    let p0: f64     = input.p0;         // Initial pressure
+
+    let nx: u32     = input.nx;         // Particle resolution
         
     let mut particles: Vec<Particle> = Vec::new();
 
     let lg2: f64    = 0.25*lg;
 
+    // Same lattice spacing in both fluids: the density contrast is set by the particles' mass.
     partdistribution::init_dist_hcp(&mut particles, nx, rho1, eta, wd, lg2, hg, x0, y0, z0);
-    partdistribution::init_dist_hcp(&mut particles, (1.25*nx as f64) as u32, rho2, eta, wd, 0.5*lg, hg, x0, y0+lg2, z0);
+    partdistribution::init_dist_hcp(&mut particles, nx, rho2, eta, wd, 0.5*lg, hg, x0, y0+lg2, z0);
     partdistribution::init_dist_hcp(&mut particles, nx, rho1, eta, wd, lg2, hg, x0, y0+0.75*lg, z0);
 
     let n: usize = particles.len();
 
     kh_init_setup(&mut particles, n, lg2, y1, y2, rho1, rho2, vx1, vx2, p0, gamma-1.);
     
-    if let Err(err) = datafunctions::save_data(path, &particles){
+    if let Err(err) = datafunctions::save_data(&datafunctions::output_file(&input.output_dir, "Ini_00.csv"), &particles){
         println!("{}", err);
         process::exit(1);
     }
@@ -0,0 +1,4 @@
+if [ "$1" != "--resume" ]; then
+cargo run -p lid_driven_cavity --bin init_dist_lid_driven_cavity
+fi
+cargo run --release -p lid_driven_cavity --bin lid_driven_cavity -- $1
\ No newline at end of file
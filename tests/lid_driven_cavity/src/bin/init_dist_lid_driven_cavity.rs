@@ -0,0 +1,79 @@
+// ------------------------------------------------------------------------- //
+// Initial setup for the Lid-Driven Cavity in 3D                             //
+// ------------------------------------------------------------------------- //
+use std::{
+    f64,
+    error::Error,
+    process,
+};
+
+use structures::Particle;
+use datafunctions;
+
+fn main() -> Result<(), Box<dyn Error>> {
+
+    // Files
+    let input_file: &str= "./tests/lid_driven_cavity/input";
+
+    // Parameters
+    let input: lid_driven_cavity::Input = match lid_driven_cavity::Input::read(input_file) {
+        Ok(input) => input,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
+
+    let eta: f64    = input.eta;        // Dimensionless constant specifying the smoothing length
+    let gamma: f64  = input.gamma;      // Heat capacity ratio
+
+    let x0: f64     = input.x0;         // Bottom left corner  (x-coordinate)
+    let y0: f64     = input.y0;         // Bottom left corner  (y-coordinate)
+    let z0: f64     = input.z0;         // Bottom left corner  (z-coordinate)
+    let wd: f64     = input.wd;         // Width (x)
+    let lg: f64     = input.lg;         // Depth (y)
+
+    let rho0: f64   = input.rho0;       // Initial density
+    let cs0: f64    = input.cs0;        // Initial sound speed
+    let v_lid: f64  = input.v_lid;      // Velocity of the lid
+    let nb: u32     = input.nb;         // Layers of boundary particles
+
+    let nx: u32     = input.nx;         // Particle resolution in the x direction
+    let nz: u32     = input.nz;         // Layers of particles in the z direction
+
+    let dx: f64     = wd/nx as f64;     // Particle spacing
+    let hg: f64     = nz as f64*dx;     // Height (z)
+    let bw: f64     = nb as f64*dx;     // Width of the walls
+    let u0: f64     = cs0*cs0/(gamma*(gamma - 1.)); // Initial energy
+
+    let mut particles : Vec<Particle> = Vec::new();
+
+    // Fluid
+    partdistribution::init_dist_cubic(&mut particles, nx, rho0, eta, wd, lg, hg, x0, y0, z0);
+    let n: usize    = particles.len();
+
+    // Walls: bottom, left and right
+    partdistribution::init_dist_cubic(&mut particles, nx+2*nb, rho0, eta, wd+2.*bw, bw, hg, x0-bw, y0-bw, z0);
+    partdistribution::init_dist_cubic(&mut particles, nb, rho0, eta, bw, lg, hg, x0-bw, y0, z0);
+    partdistribution::init_dist_cubic(&mut particles, nb, rho0, eta, bw, lg, hg, x0+wd, y0, z0);
+    let n_lid: usize = particles.len();
+
+    // Lid
+    partdistribution::init_dist_cubic(&mut particles, nx+2*nb, rho0, eta, wd+2.*bw, bw, hg, x0-bw, y0+lg, z0);
+
+    for ii in 0..particles.len() {
+        particles[ii].u = u0;
+        if ii >= n {
+            particles[ii].ptype = 1;
+        }
+        if ii >= n_lid {
+            particles[ii].vx = v_lid;
+        }
+    }
+    if let Err(err) = datafunctions::save_data(&datafunctions::output_file(&input.output_dir, "Ini_00.csv"), &particles){
+        println!("{}", err);
+        process::exit(1);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,48 @@
+// ------------------------------------------------------------------------- //
+// Input parameters of the Lid-Driven Cavity in 3D                           //
+// Keys of its input file, written as `key = value` lines.                   //
+// ------------------------------------------------------------------------- //
+
+use datafunctions::OutputFormat;
+use structures::Particle;
+
+datafunctions::config! {
+    pub struct Input {
+        eta: f64 = 1.2,                  "",      "Dimensionless constant specifying the smoothing length", min 0.0;
+        gamma: f64,                      "",      "Heat capacity ratio", min 1.0;
+        eos_type: bool,                  "",      "EoS (0=isoth[No u]; 1=adiab[u])";
+        x0: f64,                         "L",     "Bottom left corner of the cavity (x-coordinate)";
+        y0: f64,                         "L",     "Bottom left corner of the cavity (y-coordinate)";
+        z0: f64,                         "L",     "Bottom left corner of the cavity (z-coordinate)";
+        wd: f64,                         "L",     "Width of the cavity";
+        lg: f64,                         "L",     "Depth of the cavity";
+        rho0: f64,                       "M/L^3", "Initial density";
+        cs0: f64,                        "L/T",   "Initial sound speed";
+        v_lid: f64,                      "L/T",   "Velocity of the lid (x-direction)";
+        nb: u32,                         "",      "Layers of boundary particles in the walls", min 1;
+        t0: f64 = 0.0,                   "T",     "Initial time";
+        tf: f64,                         "T",     "Final time";
+        dt_sav: f64,                     "T",     "Recording time step", min 0.0;
+        nx: u32,                         "",      "Particle resolution in the x direction", min 1;
+        nz: u32,                         "",      "Layers of particles in the z direction (periodic)", min 1;
+        lid_drag: f64 = 0.25,            "",      "Minimum mean x-velocity of the gas under the lid, over v_lid", min 0.0;
+        s: i32 = 10,                     "",      "Maximum number of particles in a leaf (Bucket size)", min 1;
+        alpha: f64 = 0.5,                "",      "Fraction of the bucket size s", min 0.0, max 1.0;
+        beta: f64 = 0.5,                 "",      "Maximum ratio of cells with less than alpha*s particles", min 0.0, max 1.0;
+        chk_steps: u32 = 0,              "",      "Steps between checkpoints (0=off)";
+        chk_min: f64 = 60.0,             "min",   "Wall-clock minutes between checkpoints (0=off)", min 0.0;
+        max_walltime: f64 = 0.0,         "h",     "Hours after which the run checkpoints and stops (0=off)", min 0.0;
+        output: OutputFormat = "binary", "",      "Snapshot format (binary, csv or vtu)";
+        output_dir: String,              "",      "Directory of the run's files (created if needed)";
+        prefix: String = "Ev_",          "",      "Prefix of the snapshots' names";
+        digits: u32 = 0,                 "",      "Digits of the snapshots' numbers, zero-padded (0=no padding)";
+        diag_steps: u32 = 0,             "",      "Steps between lines of Diagnostics.csv (0=off)";
+        diag_dt: f64 = 0.0,              "T",     "Time between lines of Diagnostics.csv (0=off)", min 0.0;
+    }
+}
+
+// Mean x-velocity of the gas within dy of the lid, at y_lid
+pub fn under_lid(particles: &[Particle], y_lid: f64, dy: f64) -> f64 {
+    let gas: Vec<&Particle> = particles.iter().filter(|p| p.ptype == 0 && p.y > y_lid - dy).collect();
+    return gas.iter().map(|p| p.vx).sum::<f64>()/gas.len() as f64;
+}
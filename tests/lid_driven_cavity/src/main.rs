@@ -0,0 +1,231 @@
+// ------------------------------------------------------------------------- //
+// The Lid-Driven Cavity in 3D                                               //
+// Fluid in a square cavity closed by boundary particles (ptype = 1). The    //
+// top wall slides with velocity v_lid and the z-direction is periodic.      //
+// ------------------------------------------------------------------------- //
+
+use std::{
+    error::Error,
+    process,
+    time::Instant,
+};
+
+use structures::{
+    Particle,
+    Node,
+    Pointer,
+};
+
+use sphfunctions;
+use sphfunctions::Integrator;
+use datafunctions;
+
+use tree_algorithm::BuildTree;
+use std::f64::consts::PI;
+
+fn main() -> Result<(), Box<dyn Error>> {
+
+    // Files
+    let input_file: &str    = "./tests/lid_driven_cavity/input";
+
+    //---------------------------------------------------------------------------------------------
+    // Parameters
+    let input: lid_driven_cavity::Input = match lid_driven_cavity::Input::read(input_file) {
+        Ok(input) => input,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
+
+    let eta: f64        = input.eta;        // Dimensionless constant specifying the smoothing length
+    let gamma: f64      = input.gamma;      // Heat capacity ratio
+    let eos_t: bool     = input.eos_type;   // EoS (0=isoth[No u]; 1=adiab[u])
+
+    let x0: f64         = input.x0;         // Bottom left corner  (x-coordinate)
+    let y0: f64         = input.y0;         // Bottom left corner  (y-coordinate)
+    let z0: f64         = input.z0;         // Bottom left corner  (z-coordinate)
+    let wd: f64         = input.wd;         // Width (x)
+    let lg: f64         = input.lg;         // Depth (y)
+    let v_lid: f64      = input.v_lid;      // Velocity of the lid
+    let nb: u32         = input.nb;         // Layers of boundary particles
+
+    let t0: f64         = input.t0;         // Initial time
+    let tf: f64         = input.tf;         // Final time
+    let dt_sav: f64     = input.dt_sav;     // Recording time step
+    let nx: u32         = input.nx;         // Particle resolution in the x direction
+    let nz: u32         = input.nz;         // Layers of particles in the z direction
+
+    // Tree's parameters
+    let s_: i32         = input.s;          // Bucket size
+    let alpha_: f64     = input.alpha;      // Fraction of the bucket size
+    let beta_: f64      = input.beta;       // Maximum ratio of cells with less than alpha*s particles
+
+    let dx: f64         = wd/nx as f64;     // Particle spacing
+    let hg: f64         = nz as f64*dx;     // Height (z)
+    let bw: f64         = nb as f64*dx;     // Width of the walls
+
+    // Boundary conditions
+    let xper: bool      = false;
+    let yper: bool      = false;
+    let zper: bool      = true;
+
+    let mut dt: f64     = 0.01*dt_sav;  // Initial time step
+    let mut sav: bool   = false;        // Save data
+    let mut it_sav: u32 = 1;            // Save data iteration
+
+    // Checkpoints
+    let chk_steps: u32  = input.chk_steps;  // Steps between checkpoints (0=off)
+    let chk_min: f64    = input.chk_min;    // Wall-clock minutes between checkpoints (0=off)
+    let max_walltime: f64 = input.max_walltime; // Hours after which the run checkpoints and stops (0=off)
+
+    // Output
+    let output_dir: &str = &input.output_dir; // Directory of the run's files (created if needed)
+    let path_source: &str   = &datafunctions::output_file(output_dir, "Ini_00.csv");
+    let path_checkpoint: &str = &datafunctions::output_file(output_dir, "Checkpoint");
+
+    let coeff: f64      = 0.0;            // Not used parameter
+
+    //---------------------------------------------------------------------------------------------
+
+    // Create particles
+    let mut particles: Vec<Particle> = Vec::new();
+    let resume: Option<datafunctions::RunState> = match datafunctions::resume(path_checkpoint, &mut particles, &mut Vec::new()) {
+        Ok(state) => state,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
+    if resume.is_none() {
+        if let Err(err) = datafunctions::read_data(path_source, &mut particles) {
+            println!("{}", err);
+            process::exit(1);
+        }
+    }
+    let particles_ptr = Pointer(particles.as_mut_ptr());
+
+    let mut t: f64      = t0;               // Time
+    let mut t_boundary: f64 = t0;       // Time of the boundary particles
+    let n : usize       = particles.len();  // Number of particles
+    let mut it: u32     = 0;                // Time iterations
+    if let Some(state) = &resume {
+        t       = state.t;
+        dt      = state.dt;
+        t_boundary = state.t_boundary;
+        it      = state.it;
+        it_sav  = state.it_sav;
+    }
+
+    // The lid is the set of boundary particles above the cavity
+    let lid_ids = particles.iter().filter(|p| p.ptype == 1 && p.y > y0 + lg).map(|p| p.id);
+    let id_min: u64     = lid_ids.clone().min().unwrap_or(0);
+    let id_max: u64     = lid_ids.max().unwrap_or(0);
+    let lid: sphfunctions::SlidingWall = sphfunctions::SlidingWall{vx: v_lid, vy: 0.0, vz: 0.0};
+    let bodies: Vec<sphfunctions::BoundaryBody> = vec![sphfunctions::BoundaryBody{ids: id_min..id_max+1, motion: &lid}];
+
+    // Save time evolution
+    let mut output: datafunctions::Output = match datafunctions::Output::new(output_dir, &input.prefix, input.digits, input.output, input.diag_steps, input.diag_dt, resume.as_ref()) {
+        Ok(output) => output,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
+
+    //------------------------------------ kernel -------------------------------------------------
+    let sigma: f64      = 1./(120.*PI);     // Normalization constant of kernel
+    let rkern: f64      = 3.;               // Kernel radius
+    //---------------------------------------------------------------------------------------------
+
+    if resume.is_none() {
+        for ii in 0..n {
+            particles[ii].rho = sphfunctions::density_from_h(particles[ii].m, particles[ii].h, eta);
+        }
+    }
+
+    // The tree covers the walls
+    let xt0: f64        = x0 - bw;
+    let yt0: f64        = y0 - bw;
+    let wdt: f64        = wd + 2.0*bw;
+    let lgt: f64        = lg + 2.0*bw;
+
+    let mut tree: Node  = <Node as BuildTree>::new(n as i32, xt0, yt0, z0, wdt, lgt, hg);
+
+    let header: datafunctions::Header = datafunctions::Header{x0, y0, z0, wd, lg, hg, xper, yper, zper, eos_t, gamma, coeff, ..Default::default()};
+    output.header = header;
+    let mut checkpoint: datafunctions::Checkpoint = datafunctions::Checkpoint::new(path_checkpoint, chk_steps, chk_min);
+    let stop: datafunctions::Stop = datafunctions::Stop::new(max_walltime).expect("signal handlers failed");
+
+    //------------------------------------ Main Loop ----------------------------------------------
+    let start  = Instant::now();   // Runing time
+    let mut stopping: bool = false; // Stopped before the final time
+    while t < tf  {
+        let mut derivatives = |particles: &mut Vec<Particle>, dt: f64| {
+            sphfunctions::move_boundaries(particles, &bodies, t_boundary, t_boundary + dt);
+            t_boundary += dt;
+            sphfunctions::periodic_boundary(particles, wdt, lgt, hg, xt0, yt0, z0, 0.0, 0.0);
+            tree.restart(n);
+            tree.build_tree(s_, alpha_, beta_, particles, 1.0e-02);
+            sphfunctions::smoothing_length(particles, eta, sphfunctions::f_quintic_kernel, sphfunctions::dfdq_quintic_kernel, sigma, rkern, 1e-03, 30, dt, &tree, s_, n, particles_ptr,
+                                           wdt, lgt, hg, xt0, yt0, z0, xper, yper, zper, 0.0);
+            sphfunctions::boundary_particles(particles, &bodies, t_boundary, sphfunctions::eos_ideal_gas, gamma, coeff, eta, sphfunctions::f_quintic_kernel, rkern,
+                                             &[], &tree, s_, n, particles_ptr, wdt, lgt, hg, xt0, yt0, z0, xper, yper, zper, 0.0);
+            sphfunctions::accelerations(particles, eos_t, sphfunctions::eos_ideal_gas, sphfunctions::sound_speed_ideal_gas, gamma, coeff,
+                                        sphfunctions::dwdh, sphfunctions::f_quintic_kernel, sphfunctions::dfdq_quintic_kernel, sigma, rkern,
+                                        &tree, s_, n, particles_ptr, wdt, lgt, hg, xt0, yt0, z0,
+                                        sphfunctions::mon97_art_vis,
+                                        &[], xper, yper, zper, 0.0, 0.0);
+        };
+        sphfunctions::Leapfrog.step(&mut particles, dt, &mut derivatives);
+        dt = sphfunctions::time_step_mon(&particles, n, gamma, coeff, rkern, wdt, lgt, hg, xt0, yt0, z0, &mut tree, s_, sphfunctions::sound_speed_ideal_gas, xper, yper, zper, 0.0, 0.0);
+        tree.restart(n);
+        datafunctions::time_step(&mut t, &mut dt, dt_sav, &mut sav, &mut it_sav);
+        println!("dt: {:.4}\tt: {:.4}", dt, t);
+        if sav {
+            if let Err(err) = output.snapshot(it_sav-2, t, &particles, &[]){
+                println!("{}", err);
+                process::exit(1);
+            }
+        }
+        it += 1;
+        if let Err(err) = output.step(it, t, dt, &particles) {
+            println!("{}", err);
+            process::exit(1);
+        }
+        stopping = stop.requested();
+        if checkpoint.due(it) || stopping {
+            if let Err(err) = checkpoint.save(&datafunctions::RunState{t, t_boundary, dt, it, it_sav}, &header, &particles, &[]).and_then(|_| output.record("checkpoint", it, t, "Checkpoint")) {
+                println!("{}", err);
+                process::exit(1);
+            }
+        }
+        if stopping {
+            println!("Stopping at t = {} (iteration {}). Continue with --resume.", t, it);
+            break;
+        }
+    }
+    if stopping {
+        println!("Simulation stopped at t = {} of {}, resumable from the checkpoint.\n Time {} s.\n Iterations: {}.", t, tf, start.elapsed().as_secs(), it);
+    } else {
+        println!("Simulation run successfully.\n Time {} s.\n Iterations: {}.", start.elapsed().as_secs(), it);
+    }
+    //---------------------------------------------------------------------------------------------
+
+    // Save final information
+    if let Err(err) = output.finish(t, &particles, &[]){
+        println!("{}", err);
+        process::exit(1);
+    }
+
+    // The gas under the lid is dragged along with it
+    if t >= tf {
+        let vx_lid: f64 = lid_driven_cavity::under_lid(&particles, y0 + lg, dx);
+        println!("Mean x-velocity under the lid: {:.4} (lid {:.4})", vx_lid, v_lid);
+        if !(vx_lid*v_lid.signum() >= input.lid_drag*v_lid.abs()) {
+            println!("The gas under the lid moves slower than {} times the lid", input.lid_drag);
+            process::exit(1);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,34 @@
+// ------------------------------------------------------------------------- //
+// Lid-driven cavity: the gas under the lid is dragged along with it.        //
+// ------------------------------------------------------------------------- //
+
+use std::{
+    env,
+    fs,
+    path::PathBuf,
+    process::{self, Command},
+};
+
+#[test]
+fn lid_drags_the_gas() {
+    // A slower sound speed allows longer steps; the layer under the lid moves
+    // at ~0.3 v_lid by tf
+    let dir: PathBuf = env::temp_dir().join(format!("rusph-cavity-{}", process::id()));
+    fs::create_dir_all(dir.join("tests/lid_driven_cavity")).unwrap();
+    let keys: [(&str, &str); 6] = [("cs0", "3.0"), ("tf", "0.1"), ("dt_sav", "0.1"), ("nx", "12"), ("nz", "6"), ("output", "csv")];
+    let text: String = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/input")).unwrap().lines().map(|line| {
+        let key: &str = line.split('=').next().unwrap().trim();
+        match keys.iter().find(|(k, _)| *k == key) {
+            Some((k, v)) => format!("{} = {}", k, v),
+            None => line.to_string(),
+        }
+    }).collect::<Vec<String>>().join("\n");
+    fs::write(dir.join("tests/lid_driven_cavity/input"), text).unwrap();
+
+    // The driver exits with an error when the gas lags behind lid_drag*v_lid
+    for bin in [env!("CARGO_BIN_EXE_init_dist_lid_driven_cavity"), env!("CARGO_BIN_EXE_lid_driven_cavity")] {
+        let out = Command::new(bin).current_dir(&dir).output().unwrap();
+        assert!(out.status.success(), "{} failed:\n{}", bin, String::from_utf8_lossy(&out.stdout));
+    }
+    fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,4 @@
+if [ "$1" != "--resume" ]; then
+cargo run -p piston_shock --bin init_dist_piston_shock
+fi
+cargo run --release -p piston_shock --bin piston_shock -- $1
\ No newline at end of file
@@ -0,0 +1,77 @@
+// ------------------------------------------------------------------------- //
+// Initial setup for the Piston-Driven Shock in 3D                           //
+// ------------------------------------------------------------------------- //
+use std::{
+    f64,
+    error::Error,
+    process,
+};
+
+use structures::Particle;
+use datafunctions;
+
+fn main() -> Result<(), Box<dyn Error>> {
+
+    // Files
+    let input_file: &str= "./tests/piston_shock/input";
+
+    // Parameters
+    let input: piston_shock::Input = match piston_shock::Input::read(input_file) {
+        Ok(input) => input,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
+
+    let eta: f64    = input.eta;        // Dimensionless constant specifying the smoothing length
+    let gamma: f64  = input.gamma;      // Heat capacity ratio
+
+    let x0: f64     = input.x0;         // Bottom left corner  (x-coordinate)
+    let y0: f64     = input.y0;         // Bottom left corner  (y-coordinate)
+    let z0: f64     = input.z0;         // Bottom left corner  (z-coordinate)
+    let wd: f64     = input.wd;         // Length of the tube (x)
+
+    let rho0: f64   = input.rho0;       // Initial density
+    let p0: f64     = input.p0;         // Initial pressure
+    let v_p: f64    = input.v_piston;   // Velocity of the piston
+    let nb: u32     = input.nb;         // Layers of boundary particles
+
+    let nx: u32     = input.nx;         // Particle resolution in the x direction
+    let nyz: u32    = input.nyz;        // Particle resolution in the y and z directions
+
+    let dx: f64     = wd/nx as f64;     // Particle spacing
+    let lg: f64     = nyz as f64*dx;    // Length (y)
+    let hg: f64     = nyz as f64*dx;    // Height (z)
+    let bw: f64     = nb as f64*dx;     // Width of the piston and the wall
+    let u0: f64     = p0/((gamma - 1.)*rho0); // Initial energy
+
+    let mut particles : Vec<Particle> = Vec::new();
+
+    // Gas
+    partdistribution::init_dist_cubic(&mut particles, nx, rho0, eta, wd, lg, hg, x0, y0, z0);
+    let n: usize    = particles.len();
+
+    // Piston
+    partdistribution::init_dist_cubic(&mut particles, nb, rho0, eta, bw, lg, hg, x0-bw, y0, z0);
+    let n_wall: usize = particles.len();
+
+    // Wall
+    partdistribution::init_dist_cubic(&mut particles, nb, rho0, eta, bw, lg, hg, x0+wd, y0, z0);
+
+    for ii in 0..particles.len() {
+        particles[ii].u = u0;
+        if ii >= n {
+            particles[ii].ptype = 1;
+        }
+        if ii >= n && ii < n_wall {
+            particles[ii].vx = v_p;
+        }
+    }
+    if let Err(err) = datafunctions::save_data(&datafunctions::output_file(&input.output_dir, "Ini_00.csv"), &particles){
+        println!("{}", err);
+        process::exit(1);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,65 @@
+// ------------------------------------------------------------------------- //
+// Input parameters of the Piston-Driven Shock in 3D                         //
+// Keys of its input file, written as `key = value` lines.                   //
+// ------------------------------------------------------------------------- //
+
+use datafunctions::OutputFormat;
+use structures::Particle;
+
+datafunctions::config! {
+    pub struct Input {
+        eta: f64 = 1.2,                  "",          "Dimensionless constant specifying the smoothing length", min 0.0;
+        gamma: f64,                      "",          "Heat capacity ratio", min 1.0;
+        eos_type: bool,                  "",          "EoS (0=isoth[No u]; 1=adiab[u])";
+        x0: f64,                         "L",         "Bottom left corner of the tube (x-coordinate)";
+        y0: f64,                         "L",         "Bottom left corner of the tube (y-coordinate)";
+        z0: f64,                         "L",         "Bottom left corner of the tube (z-coordinate)";
+        wd: f64,                         "L",         "Length of the tube";
+        rho0: f64,                       "M/L^3",     "Initial density";
+        p0: f64,                         "M/(L T^2)", "Initial pressure";
+        v_piston: f64,                   "L/T",       "Velocity of the piston (x-direction)";
+        nb: u32,                         "",          "Layers of boundary particles in the piston and the wall", min 1;
+        t0: f64 = 0.0,                   "T",         "Initial time";
+        tf: f64,                         "T",         "Final time";
+        dt_sav: f64,                     "T",         "Recording time step", min 0.0;
+        nx: u32,                         "",          "Particle resolution in the x direction", min 1;
+        nyz: u32,                        "",          "Particle resolution in the y and z directions (periodic)", min 1;
+        tolerance: f64 = 0.1,            "",          "Relative tolerance of the post-shock density and velocity", min 0.0;
+        s: i32 = 10,                     "",          "Maximum number of particles in a leaf (Bucket size)", min 1;
+        alpha: f64 = 0.5,                "",          "Fraction of the bucket size s", min 0.0, max 1.0;
+        beta: f64 = 0.5,                 "",          "Maximum ratio of cells with less than alpha*s particles", min 0.0, max 1.0;
+        chk_steps: u32 = 0,              "",          "Steps between checkpoints (0=off)";
+        chk_min: f64 = 60.0,             "min",       "Wall-clock minutes between checkpoints (0=off)", min 0.0;
+        max_walltime: f64 = 0.0,         "h",         "Hours after which the run checkpoints and stops (0=off)", min 0.0;
+        output: OutputFormat = "binary", "",          "Snapshot format (binary, csv or vtu)";
+        output_dir: String,              "",          "Directory of the run's files (created if needed)";
+        prefix: String = "Ev_",          "",          "Prefix of the snapshots' names";
+        digits: u32 = 0,                 "",          "Digits of the snapshots' numbers, zero-padded (0=no padding)";
+        diag_steps: u32 = 0,             "",          "Steps between lines of Diagnostics.csv (0=off)";
+        diag_dt: f64 = 0.0,              "T",         "Time between lines of Diagnostics.csv (0=off)", min 0.0;
+    }
+}
+
+// Velocity of the shock driven by a piston moving with v_p into an ideal gas
+// at rest
+pub fn shock_velocity(gamma: f64, rho0: f64, p0: f64, v_p: f64) -> f64 {
+    let cs0: f64    = (gamma*p0/rho0).sqrt();
+    let a: f64      = 0.25*(gamma + 1.0)*v_p;
+    return a + (a*a + cs0*cs0).sqrt();
+}
+
+// Mean density and x-velocity of the gas in the middle half of the region
+// between the piston, at x0 + v_p*t, and the shock, at x0 + v_s*t. None if
+// no particle is there yet.
+pub fn post_shock(particles: &[Particle], x0: f64, v_p: f64, v_s: f64, t: f64) -> Option<(f64, f64)> {
+    let x_p: f64    = x0 + v_p*t;
+    let x_s: f64    = x0 + v_s*t;
+    let low: f64    = x_p + 0.25*(x_s - x_p);
+    let up: f64     = x_p + 0.75*(x_s - x_p);
+    let gas: Vec<&Particle> = particles.iter().filter(|p| p.ptype == 0 && p.x > low && p.x < up).collect();
+    if gas.is_empty() {
+        return None;
+    }
+    let n: f64      = gas.len() as f64;
+    return Some((gas.iter().map(|p| p.rho).sum::<f64>()/n, gas.iter().map(|p| p.vx).sum::<f64>()/n));
+}
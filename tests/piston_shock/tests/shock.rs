@@ -0,0 +1,82 @@
+// ------------------------------------------------------------------------- //
+// Piston: the shocked gas reaches the analytic density and velocity, and a  //
+// resumed run keeps pushing the whole piston.                               //
+// ------------------------------------------------------------------------- //
+
+use std::{
+    env,
+    fs,
+    path::PathBuf,
+    process::{self, Command},
+};
+
+use structures::Particle;
+
+// Scratch directory of a test, holding the input file read by the driver:
+// the one of the crate with some keys replaced.
+fn scratch(name: &str, keys: &[(&str, &str)]) -> PathBuf {
+    let dir: PathBuf = env::temp_dir().join(format!("rusph-{}-{}", name, process::id()));
+    fs::create_dir_all(dir.join("tests/piston_shock")).unwrap();
+    let text: String = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/input")).unwrap().lines().map(|line| {
+        let key: &str = line.split('=').next().unwrap().trim();
+        match keys.iter().find(|(k, _)| *k == key) {
+            Some((k, v)) => format!("{} = {}", k, v),
+            None => line.to_string(),
+        }
+    }).collect::<Vec<String>>().join("\n");
+    fs::write(dir.join("tests/piston_shock/input"), text).unwrap();
+    return dir;
+}
+
+fn run(dir: &PathBuf, bin: &str, args: &[&str]) {
+    let out = Command::new(bin).args(args).current_dir(dir).output().unwrap();
+    assert!(out.status.success(), "{} failed:\n{}", bin, String::from_utf8_lossy(&out.stdout));
+}
+
+#[test]
+fn post_shock_state() {
+    // A coarse tube keeps the debug run short; the shocked slab is still
+    // over three spacings wide at tf
+    let dir: PathBuf = scratch("piston", &[("tf", "0.2"), ("dt_sav", "0.2"), ("nx", "16"), ("nyz", "6"), ("output", "csv")]);
+
+    // The driver exits with an error when the state is off by more than the
+    // tolerance of the input file
+    run(&dir, env!("CARGO_BIN_EXE_init_dist_piston_shock"), &[]);
+    run(&dir, env!("CARGO_BIN_EXE_piston_shock"), &[]);
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn resumed_piston_moves() {
+    // By t = 0.05 the front layer of the piston, half a spacing behind x0,
+    // has entered the tube. The shock is not resolved yet, hence the loose
+    // tolerance.
+    let keys: [(&str, &str); 6] = [("dt_sav", "0.05"), ("nx", "16"), ("nyz", "6"), ("chk_steps", "1"), ("tolerance", "10.0"), ("output", "csv")];
+    let dir: PathBuf = scratch("piston-resume", &[&keys[..], &[("tf", "0.05")]].concat());
+    run(&dir, env!("CARGO_BIN_EXE_init_dist_piston_shock"), &[]);
+    run(&dir, env!("CARGO_BIN_EXE_piston_shock"), &[]);
+
+    // Resumed from the checkpoint of the last step, with a later final time
+    scratch("piston-resume", &[&keys[..], &[("tf", "0.07")]].concat());
+    run(&dir, env!("CARGO_BIN_EXE_piston_shock"), &["--resume"]);
+
+    // The checkpoint of the last step holds the particles along with the time
+    // of the boundaries, which lags the one of the manifest by a step
+    let mut initial: Vec<Particle> = Vec::new();
+    datafunctions::read_data(dir.join("Piston/Ini_00.csv").to_str().unwrap(), &mut initial).unwrap();
+    let mut last: Vec<Particle> = Vec::new();
+    let state: datafunctions::RunState = datafunctions::read_checkpoint(dir.join("Piston/Checkpoint").to_str().unwrap(), &mut last, &mut Vec::new()).unwrap();
+    let t: f64 = datafunctions::read_manifest(dir.join("Piston").to_str().unwrap()).unwrap().last().unwrap().t;
+    fs::remove_dir_all(&dir).ok();
+
+    // x0 = 0 and v_piston = 1. The velocity stored on boundary particles is
+    // the one of the wall model, so the piston's is taken from its motion.
+    assert!(t >= 0.07);
+    let piston: Vec<&Particle> = initial.iter().filter(|p| p.ptype == 1 && p.x < 0.0).collect();
+    assert!(!piston.is_empty());
+    for p in piston {
+        let q: &Particle = last.iter().find(|q| q.id == p.id).unwrap();
+        let v: f64 = (q.x - p.x)/state.t_boundary;
+        assert!((v - 1.0).abs() < 1e-9, "piston particle {} moved at {}, from {} to {}", p.id, v, p.x, q.x);
+    }
+}
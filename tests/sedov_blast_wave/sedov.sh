@@ -1,2 +1,4 @@
+if [ "$1" != "--resume" ]; then
 cargo run -p sedov_blast_wave --bin init_dist_sedov_blast_wave
-cargo run --release -p sedov_blast_wave --bin sedov_blast_wave
\ No newline at end of file
+fi
+cargo run --release -p sedov_blast_wave --bin sedov_blast_wave -- $1
\ No newline at end of file
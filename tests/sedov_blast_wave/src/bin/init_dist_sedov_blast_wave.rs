@@ -17,25 +17,30 @@ use partdistribution;
 fn main() -> Result<(), Box<dyn Error>> {
 
     // Files
-    let path: &str      = "./Sedov/Ini_00.csv";
     let input_file: &str= "./tests/sedov_blast_wave/input";
     
     // Parameters
-    let input: Vec<f64> = datafunctions::read_input(input_file);
+    let input: sedov_blast_wave::Input = match sedov_blast_wave::Input::read(input_file) {
+        Ok(input) => input,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
     
-    let eta: f64    = input[0];         // Dimensionless constant specifying the smoothing length
+    let eta: f64    = input.eta;        // Dimensionless constant specifying the smoothing length
     
-    let x0: f64     = input[3];         // Bottom left corner  (x-coordinate)
-    let y0: f64     = input[4];         // Bottom left corner  (y-coordinate)
-    let z0: f64     = input[5];         // Bottom left corner  (z-coordinate)
-    let wd: f64     = input[6];         // Width (x)
-    let lg: f64     = input[7];         // Length (y)
-    let hg: f64     = input[8];         // Height (z)
+    let x0: f64     = input.x0;         // Bottom left corner  (x-coordinate)
+    let y0: f64     = input.y0;         // Bottom left corner  (y-coordinate)
+    let z0: f64     = input.z0;         // Bottom left corner  (z-coordinate)
+    let wd: f64     = input.wd;         // Width (x)
+    let lg: f64     = input.lg;         // Length (y)
+    let hg: f64     = input.hg;         // Height (z)
 
-    let rho: f64    = input[9];         // Density
-    let e0: f64     = input[10];        // Initial energy
+    let rho: f64    = input.rho0;       // Density
+    let e0: f64     = input.e0;         // Initial energy
     
-    let nx: u32     = input[14] as u32; // Particle resolution
+    let nx: u32     = input.nx;         // Particle resolution
     
     let rkern: f64  = 2.;               // Cubic Kernel radius
     let vol: f64    = wd*lg*hg;         // Volumen
@@ -51,7 +56,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     init_dist_sedov(&mut particles, &mut rad_part, &mut u_norm, n, rkern, h0, x0, y0, z0, wd, lg, hg, f_cubic_kernel);
     norm_energy(&mut particles, &mut rad_part, e0, u_norm, dm);
 
-    if let Err(err) = datafunctions::save_data(path, &particles){
+    if let Err(err) = datafunctions::save_data(&datafunctions::output_file(&input.output_dir, "Ini_00.csv"), &particles){
         println!("{}", err);
         process::exit(1);
     }
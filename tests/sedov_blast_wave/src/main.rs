@@ -3,8 +3,6 @@
 // ------------------------------------------------------------------------- //
 
 use std::{
-    fs::File,
-    io::Write,
     error::Error,
     process,
     time::Instant,
@@ -14,7 +12,6 @@ use structures::{
     Particle,
     Node,
     Pointer,
-    Star,
 };
 
 use sphfunctions;
@@ -26,102 +23,178 @@ use std::f64::consts::PI;
 fn main() -> Result<(), Box<dyn Error>> {
 
     // Files
-    let path_source: &str   = "./Sedov/Ini_00.csv";
     let input_file: &str    = "./tests/sedov_blast_wave/input";
 
     //---------------------------------------------------------------------------------------------
     // Parameters
-    let input: Vec<f64> = datafunctions::read_input(input_file);
+    let input: sedov_blast_wave::Input = match sedov_blast_wave::Input::read(input_file) {
+        Ok(input) => input,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
     
-    let eta: f64    = input[0];         // Dimensionless constant specifying the smoothing length
-    let gamma: f64  = input[1];         // Heat capacity ratio
-    let eos_t: bool = input[2] != 0.0;  // EoS (0=isoth[No u]; 1=adiab[u])
+    let eta: f64    = input.eta;        // Dimensionless constant specifying the smoothing length
+    let gamma: f64  = input.gamma;      // Heat capacity ratio
+    let eos_t: bool = input.eos_type;   // EoS (0=isoth[No u]; 1=adiab[u])
     
-    let x0: f64     = input[3];         // Bottom left corner  (x-coordinate)
-    let y0: f64     = input[4];         // Bottom left corner  (y-coordinate)
-    let z0: f64     = input[5];         // Bottom left corner  (z-coordinate)
-    let wd: f64     = input[6];         // Width (x)
-    let lg: f64     = input[7];         // Length (y)
-    let hg: f64     = input[8];         // Height (z)
-    let rho0: f64   = input[9];         // Initial density
-
-    let t0: f64     = input[11];        // Initial time
-    let tf: f64     = input[12];        // Final time
-    let dt_sav: f64 = input[13];        // Recording time step
+    let x0: f64     = input.x0;         // Bottom left corner  (x-coordinate)
+    let y0: f64     = input.y0;         // Bottom left corner  (y-coordinate)
+    let z0: f64     = input.z0;         // Bottom left corner  (z-coordinate)
+    let wd: f64     = input.wd;         // Width (x)
+    let lg: f64     = input.lg;         // Length (y)
+    let hg: f64     = input.hg;         // Height (z)
+
+    let t0: f64     = input.t0;         // Initial time
+    let tf: f64     = input.tf;         // Final time
+    let dt_sav: f64 = input.dt_sav;     // Recording time step
     
     // Tree's parameters
-    let s_: i32     = input[15] as i32; // Bucket size
-    let alpha_: f64 = input[16];        // Fraction of the bucket size
-    let beta_: f64  = input[17];        // Maximum ratio of cells with less than alpha*s particles
+    let s_: i32     = input.s;          // Bucket size
+    let alpha_: f64 = input.alpha;      // Fraction of the bucket size
+    let beta_: f64  = input.beta;       // Maximum ratio of cells with less than alpha*s particles
+
+    // Block timesteps
+    let nbins: u32  = input.nbins;      // Number of timestep bins: dt_min = dt/2^nbins
+    let limiter: bool = input.limiter;    // Saitoh-Makino timestep limiter (0=off; 1=on)
 
     // Boundary conditions
     let xper: bool  = true;
     let yper: bool  = true;
     let zper: bool  = true;
 
-    let mut dt: f64     = 0.01*dt_sav;  // Initial time step
+    let mut dt: f64;                    // Block time step
     let mut sav: bool   = false;        // Save data
     let mut it_sav: u32 = 1;            // Save data iteration
 
+    // Checkpoints
+    let chk_steps: u32  = input.chk_steps;  // Steps between checkpoints (0=off)
+    let chk_min: f64    = input.chk_min;    // Wall-clock minutes between checkpoints (0=off)
+    let max_walltime: f64 = input.max_walltime; // Hours after which the run checkpoints and stops (0=off)
+
+    // Output
+    let output_dir: &str = &input.output_dir; // Directory of the run's files (created if needed)
+    let path_source: &str   = &datafunctions::output_file(output_dir, "Ini_00.csv");
+    let path_checkpoint: &str = &datafunctions::output_file(output_dir, "Checkpoint");
+
     let coeff: f64      = 0.0;          // Not used parameter
     
     //---------------------------------------------------------------------------------------------
     
     // Create particles
     let mut particles: Vec<Particle> = Vec::new();
-    let star : Star = Star{..Default::default()};
-    if let Err(err) = datafunctions::read_data(path_source, &mut particles) {
-        println!("{}", err);
-        process::exit(1);
+    let resume: Option<datafunctions::RunState> = match datafunctions::resume(path_checkpoint, &mut particles, &mut Vec::new()) {
+        Ok(state) => state,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
+    if resume.is_none() {
+        if let Err(err) = datafunctions::read_data(path_source, &mut particles) {
+            println!("{}", err);
+            process::exit(1);
+        }
     }
     let particles_ptr = Pointer(particles.as_mut_ptr());
 
     let mut t: f64  = t0;               // Time
     let n : usize   = particles.len();  // Number of particles
-    let dm : f64    = rho0*(wd*lg*hg)/n as f64; // Particles' mass
     let mut it: u32 = 0;                // Time iterations
+    if let Some(state) = &resume {
+        t       = state.t;
+        it      = state.it;
+        it_sav  = state.it_sav;
+    }
     // Save time evolution
-    let mut time_file = File::create("./Sedov/Time.txt").expect("creation failed");
+    let mut output: datafunctions::Output = match datafunctions::Output::new(output_dir, &input.prefix, input.digits, input.output, input.diag_steps, input.diag_dt, resume.as_ref()) {
+        Ok(output) => output,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
     
     //------------------------------------ kernel -------------------------------------------------
     let sigma: f64  = 1./(120.*PI);     // Normalization constant of kernel
     let rkern: f64  = 3.;               // Kernel radius
     //---------------------------------------------------------------------------------------------
     
-    for ii in 0..n {
-        particles[ii].rho = sphfunctions::density_from_h(dm, particles[ii].h, eta);
+    if resume.is_none() {
+        for ii in 0..n {
+            particles[ii].rho = sphfunctions::density_from_h(particles[ii].m, particles[ii].h, eta);
+        }
     }
     
     let mut tree: Node = <Node as BuildTree>::new(n as i32, x0, y0, z0, wd, lg, hg);
 
+    let header: datafunctions::Header = datafunctions::Header{x0, y0, z0, wd, lg, hg, xper, yper, zper, eos_t, gamma, coeff, ..Default::default()};
+    output.header = header;
+    let mut checkpoint: datafunctions::Checkpoint = datafunctions::Checkpoint::new(path_checkpoint, chk_steps, chk_min);
+    let stop: datafunctions::Stop = datafunctions::Stop::new(max_walltime).expect("signal handlers failed");
+
+    // Initial forces
+    tree.build_tree(s_, alpha_, beta_, &particles, 1.0e-02);
+    // A resumed run keeps the forces of the checkpoint
+    if resume.is_none() {
+        sphfunctions::smoothing_length(&mut particles, eta, sphfunctions::f_quintic_kernel, sphfunctions::dfdq_quintic_kernel, sigma, rkern, 1e-03, 30, 0.0, &tree, s_, n, particles_ptr, wd, lg, hg, x0, y0, z0, xper, yper, zper, 0.0);
+        sphfunctions::accelerations(&mut particles, eos_t, sphfunctions::eos_ideal_gas, sphfunctions::sound_speed_ideal_gas, gamma, coeff,
+                                    sphfunctions::dwdh, sphfunctions::f_quintic_kernel, sphfunctions::dfdq_quintic_kernel, sigma, rkern,
+                                    &tree, s_, n, particles_ptr, wd, lg, hg, x0, y0, z0,
+                                    sphfunctions::mon97_art_vis,
+                                    &[], xper, yper, zper, 0.0, 0.0);
+    }
+
     //------------------------------------ Main Loop ----------------------------------------------
     let start = Instant::now();// Runing time
+    let mut stopping: bool = false; // Stopped before the final time
     while t < tf  {
-        sphfunctions::predictor_kdk_integrator(&mut particles, dt, dm, eos_t, sphfunctions::eos_ideal_gas, sphfunctions::sound_speed_ideal_gas, gamma, coeff,
+        // Block time step and particles' bins
+        let dts: Vec<f64> = sphfunctions::time_step_mon_particles(&particles, n, gamma, coeff, rkern, wd, lg, hg, x0, y0, z0, &mut tree, s_, sphfunctions::sound_speed_ideal_gas, xper, yper, zper, 0.0, 0.0);
+        dt = sphfunctions::block_time_step(&dts, dt_sav, nbins);
+        datafunctions::time_step(&mut t, &mut dt, dt_sav, &mut sav, &mut it_sav);
+        sphfunctions::assign_bins(&mut particles, &dts, dt, nbins);
+
+        sphfunctions::block_kdk_integrator(&mut particles, dt, nbins, limiter, eos_t, sphfunctions::eos_ideal_gas, sphfunctions::sound_speed_ideal_gas, gamma, coeff,
                                        sphfunctions::dwdh, sphfunctions::f_quintic_kernel, sphfunctions::dfdq_quintic_kernel, sigma, rkern,
                                        eta, &mut tree, s_, alpha_, beta_, n, particles_ptr,
                                        sphfunctions::mon97_art_vis,
-                                       sphfunctions::body_forces_null, &star, false,
-                                       sphfunctions::periodic_boundary, xper, yper, zper, wd, lg, hg, x0, y0, z0);
-        dt = sphfunctions::time_step_mon(&particles, n, gamma, coeff, rkern, wd, lg, hg, x0, y0, z0, &mut tree, s_, sphfunctions::sound_speed_ideal_gas, xper, yper, zper);
-        tree.restart(n);
-        datafunctions::time_step(&mut t, &mut dt, dt_sav, &mut sav, &mut it_sav);
+                                       &[], &mut [], 0.1,
+                                       sphfunctions::periodic_boundary, xper, yper, zper, 0.0, 0.0, wd, lg, hg, x0, y0, z0);
         println!("dt: {:.4}\tt: {:.4}", dt, t);
         if sav {
-            time_file.write((t.to_string() + &"\n").as_bytes()).expect("write failed");
-            if let Err(err) = datafunctions::save_data_bin(&(String::from("./Sedov/Ev_") + &(it_sav-2).to_string()), &particles){
+            if let Err(err) = output.snapshot(it_sav-2, t, &particles, &[]){
                 println!("{}", err);
                 process::exit(1);
             }
         }
         it += 1;
+        if let Err(err) = output.step(it, t, dt, &particles) {
+            println!("{}", err);
+            process::exit(1);
+        }
+        stopping = stop.requested();
+        if checkpoint.due(it) || stopping {
+            if let Err(err) = checkpoint.save(&datafunctions::RunState{t, t_boundary: t, dt, it, it_sav}, &header, &particles, &[]).and_then(|_| output.record("checkpoint", it, t, "Checkpoint")) {
+                println!("{}", err);
+                process::exit(1);
+            }
+        }
+        if stopping {
+            println!("Stopping at t = {} (iteration {}). Continue with --resume.", t, it);
+            break;
+        }
+    }
+    if stopping {
+        println!("Simulation stopped at t = {} of {}, resumable from the checkpoint.\n Time {} s.\n Iterations: {}.", t, tf, start.elapsed().as_secs(), it);
+    } else {
+        println!("Simulation run successfully.\n Time {} s.\n Iterations: {}.", start.elapsed().as_secs(), it);
     }
-    println!("Simulation run successfully.\n Time {} s.\n Iterations: {}.", start.elapsed().as_secs(), it);
     //---------------------------------------------------------------------------------------------
 
     // Save final information
-    time_file.write((t.to_string() + &"\n").as_bytes()).expect("write failed");
-    if let Err(err) = datafunctions::save_data_bin(&(String::from("./Sedov/Fin_00")), &particles){
+    if let Err(err) = output.finish(t, &particles, &[]){
         println!("{}", err);
         process::exit(1);
     }
@@ -0,0 +1,141 @@
+// ------------------------------------------------------------------------- //
+// Sink accretion: many particles are accreted in a single step.             //
+// Checks that mass and momentum are conserved and that the particles'       //
+// array, the pointer and the tree stay consistent after the compaction.     //
+// ------------------------------------------------------------------------- //
+
+use std::{
+    error::Error,
+    process,
+};
+
+use tree_algorithm::{
+    BuildTree,
+    FindNeighbors,
+};
+
+use structures::{
+    Particle,
+    Node,
+    Pointer,
+    Star,
+};
+
+use partdistribution;
+use sphfunctions;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let nx: u32     = 20;   // Particle resolution
+    let x0: f64     = -1.0; // Bottom left corner  (x-coordinate)
+    let y0: f64     = -1.0; // Bottom left corner  (y-coordinate)
+    let z0: f64     = -1.0; // Bottom left corner  (z-coordinate)
+    let wd: f64     = 2.;   // Width (x)
+    let lg: f64     = 2.;   // Length (y)
+    let hg: f64     = 2.;   // Height (z)
+    let rho:f64     = 1.;   // Density
+    let eta: f64    = 1.2;  // Dimensionless constant specifying the smoothing length
+    let rkern: f64  = 2.;   // Kernel radius
+
+    let xper: bool  = false;
+    let yper: bool  = false;
+    let zper: bool  = false;
+
+    // Tree parameters
+    let s_: i32     = 10;
+    let alpha_: f64 = 0.5;
+    let beta_: f64  = 0.5;
+
+    let mut particles :Vec<Particle> = Vec::new();
+    partdistribution::init_dist_hcp(&mut particles, nx, rho, eta, wd, lg, hg, x0, y0, z0);
+    let mut n: usize = particles.len();
+
+    // Solid body rotation, so the accreted particles carry momentum.
+    for ii in 0..n {
+        particles[ii].vx = -0.3*particles[ii].y + 0.1;
+        particles[ii].vy =  0.3*particles[ii].x;
+        particles[ii].vz =  0.05;
+    }
+    // A few particles killed by other boundaries must be removed as well.
+    particles[n-1].ptype = 2;
+    particles[n-2].ptype = 2;
+
+    let mut particles_ptr: Pointer = Pointer(particles.as_mut_ptr());
+    let mut star: Star = Star{m: 1.0, hacc: 0.6, facc: 0.8, ..Default::default()};
+
+    // Initial mass and momentum (killed particles leave the system)
+    let (m_ini, px_ini, py_ini, pz_ini) = totals(&star, &particles);
+
+    let mut tree: Node = <Node as BuildTree>::new(n as i32, x0, y0, z0, wd, lg, hg);
+    tree.auto_root(rkern, xper, yper, zper);
+    tree.build_tree(s_, alpha_, beta_, &particles, 1.0e-02);
+
+    let n_ini: usize = n;
+    sphfunctions::accretion_boundary(&mut star, &mut particles, &mut n, &mut particles_ptr, &mut tree, s_, wd, lg, hg, x0, y0, z0, xper, yper, zper, 0.0);
+    let n_acc: usize = n_ini - n - 2;
+    println!("Accreted particles: {} of {}.", n_acc, n_ini);
+
+    let (m_fin, px_fin, py_fin, pz_fin) = totals(&star, &particles);
+    let tol: f64 = 1.0e-12;
+
+    let mut failed: bool = false;
+    if n_acc < 100 {
+        println!("FAILED: too few particles were accreted ({}).", n_acc);
+        failed = true;
+    }
+    if ((m_fin - m_ini)/m_ini).abs() > tol {
+        println!("FAILED: mass is not conserved ({} -> {}).", m_ini, m_fin);
+        failed = true;
+    }
+    if (px_fin - px_ini).abs() > tol || (py_fin - py_ini).abs() > tol || (pz_fin - pz_ini).abs() > tol {
+        println!("FAILED: momentum is not conserved.");
+        failed = true;
+    }
+    if n != particles.len() || particles.iter().any(|particle| particle.ptype == 2) {
+        println!("FAILED: dead particles remain in the array.");
+        failed = true;
+    }
+    if particles_ptr.0 != particles.as_mut_ptr() {
+        println!("FAILED: stale particles' pointer.");
+        failed = true;
+    }
+    if tree.n != n as i32 || tree.particles.iter().any(|p| *p >= n) {
+        println!("FAILED: the tree was not restarted.");
+        failed = true;
+    }
+
+    // Every neighbour index must be valid after the rebuild.
+    tree.build_tree(s_, alpha_, beta_, &particles, 1.0e-02);
+    let mut neighbors: Vec<usize> = Vec::new();
+    for p in 0..n {
+        neighbors.clear();
+        tree.find_neighbors(p, s_, &particles, &mut neighbors, wd, lg, hg, x0, y0, z0, particles[p].h*rkern, xper, yper, zper, 0.0);
+        if neighbors.iter().any(|q| *q >= n) {
+            println!("FAILED: invalid neighbour of particle {}.", p);
+            failed = true;
+            break;
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+    println!("Accretion test passed.");
+    Ok(())
+}
+
+// Total mass and linear momentum of gas + star
+fn totals(star: &Star, particles: & Vec<Particle>) -> (f64, f64, f64, f64) {
+    let mut m: f64  = star.m;
+    let mut px: f64 = star.m*star.vx;
+    let mut py: f64 = star.m*star.vy;
+    let mut pz: f64 = star.m*star.vz;
+    for particle in particles {
+        if particle.ptype != 2 {
+            m  += particle.m;
+            px += particle.m*particle.vx;
+            py += particle.m*particle.vy;
+            pz += particle.m*particle.vz;
+        }
+    }
+    return (m, px, py, pz);
+}
@@ -1,2 +1,4 @@
+if [ "$1" != "--resume" ]; then
 cargo run -p sod_shock_tube --bin init_dist_sod_shock_tube
-cargo run --release -p sod_shock_tube --bin sod_shock_tube
\ No newline at end of file
+fi
+cargo run --release -p sod_shock_tube --bin sod_shock_tube -- $1
\ No newline at end of file
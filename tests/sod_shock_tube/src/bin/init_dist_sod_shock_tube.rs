@@ -13,30 +13,35 @@ use datafunctions;
 fn main() -> Result<(), Box<dyn Error>> {
 
     // Files
-    let path: &str      = "./Sodtube/Ini_00.csv";
     let input_file: &str= "./tests/sod_shock_tube/input";
     
     // Parameters
-    let input: Vec<f64> = datafunctions::read_input(input_file);
+    let input: sod_shock_tube::Input = match sod_shock_tube::Input::read(input_file) {
+        Ok(input) => input,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
     
-    let eta: f64    = input[0];         // Dimensionless constant specifying the smoothing length
-    let gamma: f64  = input[1];         // Heat capacity ratio
+    let eta: f64    = input.eta;        // Dimensionless constant specifying the smoothing length
+    let gamma: f64  = input.gamma;      // Heat capacity ratio
     
-    let x0: f64     = input[3];         // Bottom left corner  (x-coordinate)
-    let y0: f64     = input[4];         // Bottom left corner  (y-coordinate)
-    let z0: f64     = input[5];         // Bottom left corner  (z-coordinate)
-    let wd: f64     = input[6];         // Width (x)
-    let lg: f64     = input[7];         // Length (y)
-    let hg: f64     = input[8];         // Height (z)
+    let x0: f64     = input.x0;         // Bottom left corner  (x-coordinate)
+    let y0: f64     = input.y0;         // Bottom left corner  (y-coordinate)
+    let z0: f64     = input.z0;         // Bottom left corner  (z-coordinate)
+    let wd: f64     = input.wd;         // Width (x)
+    let lg: f64     = input.lg;         // Length (y)
+    let hg: f64     = input.hg;         // Height (z)
 
-    let rhol: f64   = input[9];         // Left density
-    let rhor: f64   = input[10];        // Right density
-    let pl: f64     = input[11];        // Left initial pressure
-    let pr: f64     = input[12];        // Right initial pressure
-    let xm: f64     = input[13];        // Discontinuity position
+    let rhol: f64   = input.rhol;       // Left density
+    let rhor: f64   = input.rhor;       // Right density
+    let pl: f64     = input.pl;         // Left initial pressure
+    let pr: f64     = input.pr;         // Right initial pressure
+    let xm: f64     = input.xm;         // Discontinuity position
     
-    let nxl: u32    = input[17] as u32; // Particle resolution in the x direction (left)
-    let nxr: u32    = input[18] as u32; // Particle resolution in the x direction (right)
+    let nxl: u32    = input.nxl;        // Particle resolution in the x direction (left)
+    let nxr: u32    = input.nxr;        // Particle resolution in the x direction (right)
 
     let ul: f64     = pl/((gamma - 1.)*rhol);      // Left initial energy
     let ur: f64     = pr/((gamma - 1.)*rhor);      // Right initial energy
@@ -68,7 +73,7 @@ fn main() -> Result<(), Box<dyn Error>> {
             }
         }
     }
-    if let Err(err) = datafunctions::save_data(path, &particles){
+    if let Err(err) = datafunctions::save_data(&datafunctions::output_file(&input.output_dir, "Ini_00.csv"), &particles){
         println!("{}", err);
         process::exit(1);
     }
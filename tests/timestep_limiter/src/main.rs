@@ -0,0 +1,194 @@
+// ------------------------------------------------------------------------- //
+// Timestep limiter: a Sedov blast runs into cold gas under block steps.     //
+// Checks that the limiter wakes the neighbours of the finest particles so   //
+// that their bins stay within two levels (a factor 4 in the step), that the //
+// woken particles are synchronised again at the end of each block and that  //
+// the energy is conserved, which it is not without the limiter.             //
+// ------------------------------------------------------------------------- //
+
+use std::{
+    error::Error,
+    process,
+};
+
+use tree_algorithm::{
+    BuildTree,
+    FindNeighbors,
+};
+
+use structures::{
+    Particle,
+    Node,
+    Pointer,
+};
+
+use partdistribution;
+use sphfunctions;
+
+use std::f64::consts::PI;
+
+const NX: u32       = 12;   // Particle resolution
+const X0: f64       = 0.0;  // Bottom left corner  (x-coordinate)
+const Y0: f64       = 0.0;  // Bottom left corner  (y-coordinate)
+const Z0: f64       = 0.0;  // Bottom left corner  (z-coordinate)
+const WD: f64       = 1.;   // Width (x)
+const LG: f64       = 1.;   // Length (y)
+const HG: f64       = 1.;   // Height (z)
+const RHO: f64      = 1.;   // Density
+const E0: f64       = 1.;   // Energy of the blast
+const ETA: f64      = 1.2;  // Dimensionless constant specifying the smoothing length
+const GAMMA: f64    = 5./3.;// Heat capacity ratio
+const COEFF: f64    = 0.0;  // Not used parameter
+const NBINS: u32    = 5;    // Number of timestep bins
+const N_BLOCKS: u32 = 4;    // Blocks to run
+const DT_MAX: f64   = 1.;   // Largest block step
+
+const PER: bool     = true; // Periodic in every direction
+
+// Tree parameters
+const S_: i32       = 10;
+const ALPHA_: f64   = 0.5;
+const BETA_: f64    = 0.5;
+
+// Cubic kernel
+const SIGMA: f64    = 1./PI;
+const RKERN: f64    = 2.;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut failed: bool = false;
+
+    // The first substep: the finest particles end their step and the
+    // limiter looks at their neighbours.
+    let mut particles: Vec<Particle> = blast();
+    let n: usize = particles.len();
+    let mut tree: Node = <Node as BuildTree>::new(n as i32, X0, Y0, Z0, WD, LG, HG);
+    initial_forces(&mut particles, &mut tree);
+    let dts: Vec<f64> = sphfunctions::time_step_mon_particles(&particles, n, GAMMA, COEFF, RKERN, WD, LG, HG, X0, Y0, Z0, &mut tree, S_, sphfunctions::sound_speed_ideal_gas, PER, PER, PER, 0.0, 0.0);
+    let dt_block: f64 = sphfunctions::block_time_step(&dts, DT_MAX, NBINS);
+    sphfunctions::assign_bins(&mut particles, &dts, dt_block, NBINS);
+    let ibin_max: u32 = particles.iter().map(|particle| particle.ibin).max().unwrap();
+    for particle in particles.iter_mut() {
+        particle.active = particle.ibin == ibin_max;
+    }
+    let mut ibins: Vec<u32> = particles.iter().map(|particle| particle.ibin).collect();
+    let before: usize = too_coarse(&particles, &ibins, &tree);
+    let woken: Vec<(usize, u32)> = sphfunctions::time_step_limiter(&particles, dt_block, NBINS, n, GAMMA, COEFF, RKERN, WD, LG, HG, X0, Y0, Z0, &tree, S_, sphfunctions::sound_speed_ideal_gas, PER, PER, PER, 0.0, 0.0);
+    for (jj, ibin) in &woken {
+        ibins[*jj] = *ibin;
+    }
+    let after: usize = too_coarse(&particles, &ibins, &tree);
+    println!("Neighbours more than two bins coarser: {} before the limiter, {} after ({} woken).", before, after, woken.len());
+    if before == 0 {
+        println!("FAILED: the bins of the blast need no limiter.");
+        failed = true;
+    }
+    if after > 0 {
+        println!("FAILED: the limiter left neighbours more than two bins coarser.");
+        failed = true;
+    }
+
+    // Whole blocks, with and without the limiter
+    let (unsynced, error) = run(true);
+    let (_, error_off) = run(false);
+    println!("Relative energy change: {:.2e} with the limiter, {:.2e} without.", error, error_off);
+    if unsynced > 0 {
+        println!("FAILED: {} particles are not synchronised at the end of a block.", unsynced);
+        failed = true;
+    }
+    if error > 1.0e-02 || error > error_off {
+        println!("FAILED: the limiter does not conserve the energy.");
+        failed = true;
+    }
+
+    if failed {
+        process::exit(1);
+    }
+    println!("Timestep limiter test passed.");
+    Ok(())
+}
+
+// Counts the neighbours of the active particles whose bins (ibins) are
+// more than two levels coarser, i.e. whose steps are over 4 times longer.
+fn too_coarse(particles: & Vec<Particle>, ibins: & Vec<u32>, tree: & Node) -> usize {
+    let mut count: usize = 0;
+    let mut neighbors: Vec<usize> = Vec::new();
+    for ii in (0..particles.len()).filter(|ii| particles[*ii].active) {
+        neighbors.clear();
+        tree.find_neighbors(ii, S_, particles, &mut neighbors, WD, LG, HG, X0, Y0, Z0, particles[ii].h*RKERN, PER, PER, PER, 0.0);
+        count += neighbors.iter().filter(|jj| ibins[**jj] + 2 < ibins[ii]).count();
+    }
+    return count;
+}
+
+// Sedov blast: the energy E0 spread with the kernel over the central
+// particles, the rest of the gas is cold.
+fn blast() -> Vec<Particle> {
+    let mut particles: Vec<Particle> = Vec::new();
+    partdistribution::init_dist_hcp(&mut particles, NX, RHO, ETA, WD, LG, HG, X0, Y0, Z0);
+    let h0: f64     = 2.*ETA*(WD/NX as f64);
+    let mut u_norm: f64 = 0.0;
+    for particle in particles.iter_mut() {
+        let q: f64 = ((particle.x-0.5).powi(2) + (particle.y-0.5).powi(2) + (particle.z-0.5).powi(2)).sqrt()/h0;
+        particle.u   = if q < RKERN {sphfunctions::f_cubic_kernel(q)} else {0.0};
+        particle.rho = sphfunctions::density_from_h(particle.m, particle.h, ETA);
+        u_norm += particle.m*particle.u;
+    }
+    for particle in particles.iter_mut() {
+        particle.u *= E0/u_norm;
+    }
+    return particles;
+}
+
+fn initial_forces(particles: &mut Vec<Particle>, tree: &mut Node) {
+    let n: usize = particles.len();
+    let particles_ptr: Pointer = Pointer(particles.as_mut_ptr());
+    tree.build_tree(S_, ALPHA_, BETA_, particles, 1.0e-02);
+    sphfunctions::smoothing_length(particles, ETA, sphfunctions::f_cubic_kernel, sphfunctions::dfdq_cubic_kernel, SIGMA, RKERN, 1e-03, 30, 0.0, tree, S_, n, particles_ptr, WD, LG, HG, X0, Y0, Z0, PER, PER, PER, 0.0);
+    sphfunctions::accelerations(particles, true, sphfunctions::eos_ideal_gas, sphfunctions::sound_speed_ideal_gas, GAMMA, COEFF,
+                                sphfunctions::dwdh, sphfunctions::f_cubic_kernel, sphfunctions::dfdq_cubic_kernel, SIGMA, RKERN,
+                                tree, S_, n, particles_ptr, WD, LG, HG, X0, Y0, Z0,
+                                sphfunctions::mon97_art_vis,
+                                &[], PER, PER, PER, 0.0, 0.0);
+}
+
+// Runs the blast for N_BLOCKS blocks. Returns the particles that are not
+// synchronised at the end of a block and the relative energy change.
+fn run(limiter: bool) -> (usize, f64) {
+    let mut particles: Vec<Particle> = blast();
+    let n: usize = particles.len();
+    let particles_ptr: Pointer = Pointer(particles.as_mut_ptr());
+    let mut tree: Node = <Node as BuildTree>::new(n as i32, X0, Y0, Z0, WD, LG, HG);
+    initial_forces(&mut particles, &mut tree);
+
+    let e_ini: f64 = energy(&particles);
+    let mut unsynced: usize = 0;
+    for _ in 0..N_BLOCKS {
+        let dts: Vec<f64> = sphfunctions::time_step_mon_particles(&particles, n, GAMMA, COEFF, RKERN, WD, LG, HG, X0, Y0, Z0, &mut tree, S_, sphfunctions::sound_speed_ideal_gas, PER, PER, PER, 0.0, 0.0);
+        let dt_block: f64 = sphfunctions::block_time_step(&dts, DT_MAX, NBINS);
+        sphfunctions::assign_bins(&mut particles, &dts, dt_block, NBINS);
+        sphfunctions::block_kdk_integrator(&mut particles, dt_block, NBINS, limiter, true, sphfunctions::eos_ideal_gas, sphfunctions::sound_speed_ideal_gas, GAMMA, COEFF,
+                                       sphfunctions::dwdh, sphfunctions::f_cubic_kernel, sphfunctions::dfdq_cubic_kernel, SIGMA, RKERN,
+                                       ETA, &mut tree, S_, ALPHA_, BETA_, n, particles_ptr,
+                                       sphfunctions::mon97_art_vis,
+                                       &[], &mut [], 0.1,
+                                       sphfunctions::periodic_boundary, PER, PER, PER, 0.0, 0.0, WD, LG, HG, X0, Y0, Z0);
+        // Every particle, woken or not, got the closing kick of a step
+        // ending with the block
+        let dt_min: f64 = dt_block/(1_u64 << NBINS) as f64;
+        unsynced += particles.iter().filter(|particle| {
+            let dt_i: f64 = dt_min*(1_u64 << (NBINS - particle.ibin)) as f64;
+            !particle.active || particle.vx != particle.vx_star + 0.5 * dt_i * particle.ax || particle.u != particle.u_star + 0.5 * dt_i * particle.du
+        }).count();
+    }
+    let e_fin: f64 = energy(&particles);
+    return (unsynced, ((e_fin - e_ini)/e_ini).abs());
+}
+
+// Total (kinetic + internal) energy of the gas
+fn energy(particles: & Vec<Particle>) -> f64 {
+    let mut e: f64 = 0.0;
+    for particle in particles {
+        e += particle.m*(particle.u + 0.5*(particle.vx*particle.vx + particle.vy*particle.vy + particle.vz*particle.vz));
+    }
+    return e;
+}
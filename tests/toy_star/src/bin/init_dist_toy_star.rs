@@ -16,25 +16,30 @@ use std::f64::consts::PI;
 
 fn main() -> Result<(), Box<dyn Error>> {
     // Files
-    let path: &str      = "./Toystar/Ini_00.csv";
     let input_file: &str= "./tests/toy_star/input";
 
     // Parameters
-    let input: Vec<f64> = datafunctions::read_input(input_file);
+    let input: toy_star::Input = match toy_star::Input::read(input_file) {
+        Ok(input) => input,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
 
-    let eta: f64    = input[0];         // Dimensionless constant specifying the smoothing length
-    let m: f64      = input[5];         // Star's mass
-    let r: f64      = input[6];         // Star's radius
+    let eta: f64    = input.eta;        // Dimensionless constant specifying the smoothing length
+    let m: f64      = input.m;          // Star's mass
+    let r: f64      = input.r;          // Star's radius
     
-    let x0: f64     = input[7];         // Star's center (x-coordinate)
-    let y0: f64     = input[8];         // Star's center (y-coordinate)
-    let z0: f64     = input[9];         // Star's center (z-coordinate)
-    let vx0: f64    = input[10];         // Star's velocity (x-coordinate)
-    let vy0: f64    = input[11];        // Star's velocity (y-coordinate)
-    let vz0: f64    = input[12];        // Star's velocity (z-coordinate)
-    let u0: f64     = input[13];        // Initial energy
+    let x0: f64     = input.x0;         // Star's center (x-coordinate)
+    let y0: f64     = input.y0;         // Star's center (y-coordinate)
+    let z0: f64     = input.z0;         // Star's center (z-coordinate)
+    let vx0: f64    = input.vx0;         // Star's velocity (x-coordinate)
+    let vy0: f64    = input.vy0;        // Star's velocity (y-coordinate)
+    let vz0: f64    = input.vz0;        // Star's velocity (z-coordinate)
+    let u0: f64     = input.u0;         // Initial energy
     
-    let nx: u32     = input[17] as u32; // Resolution
+    let nx: u32     = input.n;          // Resolution
 
     let mut particles :Vec<Particle> = Vec::new();
     let x_i: f64    = x0-r;
@@ -51,6 +56,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let dm:f64      = m/n as f64;
     let h: f64      = h_from_density(dm, rho, eta);
     for ii in 0..n {
+        particles[ii].m  = dm;
         particles[ii].h  = h;
         particles[ii].vx = vx0;
         particles[ii].vy = vy0;
@@ -58,7 +64,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         particles[ii].u  = u0;
     }
 
-    if let Err(err) = datafunctions::save_data(path, &particles){
+    if let Err(err) = datafunctions::save_data(&datafunctions::output_file(&input.output_dir, "Ini_00.csv"), &particles){
         println!("{}", err);
         process::exit(1);
     }
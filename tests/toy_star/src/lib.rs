@@ -0,0 +1,44 @@
+// ------------------------------------------------------------------------- //
+// Input parameters of the Toy Star Problem in 3D                            //
+// Keys of its input file, written as `key = value` lines.                   //
+// ------------------------------------------------------------------------- //
+
+use datafunctions::OutputFormat;
+
+datafunctions::config! {
+    pub struct Input {
+        eta: f64 = 1.2,                  "",        "Dimensionless constant specifying the smoothing length", min 0.0;
+        gamma: f64,                      "",        "Heat capacity ratio", min 1.0;
+        k: f64,                          "",        "Constant coefficient";
+        eos_type: bool,                  "",        "EoS (0=isoth[No u]; 1=adiab[u])";
+        nu: f64,                         "",        "Viscocity parameter";
+        m: f64,                          "M",       "Star's mass";
+        r: f64,                          "L",       "Star's radius";
+        x0: f64,                         "L",       "Star's center (x-coordinate)";
+        y0: f64,                         "L",       "Star's center (y-coordinate)";
+        z0: f64,                         "L",       "Star's center (z-coordinate)";
+        vx0: f64,                        "L/T",     "Initial star's velocity (x-coordinate)";
+        vy0: f64,                        "L/T",     "Initial star's velocity (y-coordinate)";
+        vz0: f64,                        "L/T",     "Initial star's velocity (z-coordinate)";
+        u0: f64,                         "L^2/T^2", "Initial particles' energy";
+        t0: f64 = 0.0,                   "T",       "Initial time";
+        tf: f64,                         "T",       "Final time";
+        dt_sav: f64,                     "T",       "Recording time step", min 0.0;
+        n: u32,                          "",        "Total number of particles", min 1;
+        s: i32 = 10,                     "",        "Maximum number of particles in a leaf (Bucket size)", min 1;
+        alpha: f64 = 0.5,                "",        "Fraction of the bucket size s", min 0.0, max 1.0;
+        beta: f64 = 0.5,                 "",        "Maximum ratio of cells with less than alpha*s particles", min 0.0, max 1.0;
+        integrator: u32,                 "",        "0=Velocity Verlet; 1=Leapfrog (KDK); 2=RK2; 3=RK4; 4=Yoshida4; 5=Coriolis Leapfrog (KDK)", min 0, max 5;
+        omega: f64,                      "1/T",     "Angular velocity of the frame (z-axis)";
+        inertial: bool,                  "",        "Save snapshots in the inertial frame (0=no; 1=yes)";
+        chk_steps: u32 = 0,              "",        "Steps between checkpoints (0=off)";
+        chk_min: f64 = 60.0,             "min",     "Wall-clock minutes between checkpoints (0=off)", min 0.0;
+        max_walltime: f64 = 0.0,         "h",       "Hours after which the run checkpoints and stops (0=off)", min 0.0;
+        output: OutputFormat = "binary", "",        "Snapshot format (binary, csv or vtu)";
+        output_dir: String,              "",        "Directory of the run's files (created if needed)";
+        prefix: String = "Ev_",          "",        "Prefix of the snapshots' names";
+        digits: u32 = 0,                 "",        "Digits of the snapshots' numbers, zero-padded (0=no padding)";
+        diag_steps: u32 = 0,             "",        "Steps between lines of Diagnostics.csv (0=off)";
+        diag_dt: f64 = 0.0,              "T",       "Time between lines of Diagnostics.csv (0=off)", min 0.0;
+    }
+}
@@ -3,8 +3,6 @@
 // ------------------------------------------------------------------------- //
 
 use std::{
-    fs::File,
-    io::Write,
     error::Error,
     time::Instant,
     process,
@@ -14,11 +12,11 @@ use structures::{
     Particle,
     Node,
     Pointer,
-    Star,
 };
 
 use datafunctions;
 use sphfunctions;
+use sphfunctions::Integrator;
 use spfunc::gamma::*;
 
 use tree_algorithm::BuildTree;
@@ -27,41 +25,64 @@ use std::f64::consts::PI;
 fn main() -> Result<(), Box<dyn Error>> {
 
     // Files
-    let path_source: &str   = "./Toystar/Ini_00.csv";
     let input_file: &str    = "./tests/toy_star/input";
 
     //---------------------------------------------------------------------------------------------
     // Parameters
-    let input: Vec<f64> = datafunctions::read_input(input_file);
+    let input: toy_star::Input = match toy_star::Input::read(input_file) {
+        Ok(input) => input,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
     
-    let eta: f64    = input[0];         // Dimensionless constant specifying the smoothing length
-    let gamm: f64   = input[1];         // Heat capacity ratio
-    let k: f64      = input[2];         // Constant coefficient [EoS]
-    let eos_t: bool = input[3] != 0.0;  // EoS (0=isoth[No u]; 1=adiab[u])
-    let nu: f64     = input[4];         // Viscocity parameter
-    let m_star: f64 = input[5];         // Star's mass
-    let r: f64      = input[6];         // Star's radius
+    let eta: f64    = input.eta;        // Dimensionless constant specifying the smoothing length
+    let gamm: f64   = input.gamma;      // Heat capacity ratio
+    let k: f64      = input.k;          // Constant coefficient [EoS]
+    let eos_t: bool = input.eos_type;   // EoS (0=isoth[No u]; 1=adiab[u])
+    let nu: f64     = input.nu;         // Viscocity parameter
+    let m_star: f64 = input.m;          // Star's mass
+    let r: f64      = input.r;          // Star's radius
     
-    let x_c: f64    = input[7];         // Bottom left corner  (x-coordinate)
-    let y_c: f64    = input[8];         // Bottom left corner  (y-coordinate)
-    let z_c: f64    = input[9];         // Bottom left corner  (z-coordinate)
+    let x_c: f64    = input.x0;         // Bottom left corner  (x-coordinate)
+    let y_c: f64    = input.y0;         // Bottom left corner  (y-coordinate)
+    let z_c: f64    = input.z0;         // Bottom left corner  (z-coordinate)
     
-    let t0: f64     = input[14];        // Initial time
-    let tf: f64     = input[15];        // Final time
-    let dt_sav: f64 = input[16];        // Recording time step
+    let t0: f64     = input.t0;         // Initial time
+    let tf: f64     = input.tf;         // Final time
+    let dt_sav: f64 = input.dt_sav;     // Recording time step
     
     // Tree's parameters
-    let s_: i32     = input[18] as i32; // Bucket size
-    let alpha_: f64 = input[19];        // Fraction of the bucket size
-    let beta_: f64  = input[20];        // Maximum ratio of cells with less than alpha*s particles
+    let s_: i32     = input.s;          // Bucket size
+    let alpha_: f64 = input.alpha;      // Fraction of the bucket size
+    let beta_: f64  = input.beta;       // Maximum ratio of cells with less than alpha*s particles
+
+    // Time integrator
+    let integrator: Box<dyn Integrator> = match input.integrator {
+        0 => Box::new(sphfunctions::VelocityVerlet),
+        1 => Box::new(sphfunctions::Leapfrog),
+        2 => Box::new(sphfunctions::RungeKutta2),
+        3 => Box::new(sphfunctions::RungeKutta4),
+        4 => Box::new(sphfunctions::Yoshida4),
+        5 => Box::new(sphfunctions::CoriolisLeapfrog{wx: 0.0, wy: 0.0, wz: input.omega}),
+        _ => {
+            println!("Unknown integrator {}", input.integrator);
+            process::exit(1);
+        }
+    };
+
+    // Rotating frame
+    let omega: f64      = input.omega;      // Angular velocity of the frame (z-axis)
+    let inertial: bool  = input.inertial;   // Save snapshots in the inertial frame
 
-    let mut wd: f64 = 3.0*r;            // Bottom left corner  (x-coordinate)
-    let mut lg: f64 = 3.0*r;            // Bottom left corner  (y-coordinate)
-    let mut hg: f64 = 3.0*r;            // Bottom left corner  (z-coordinate)
+    let wd: f64 = 3.0*r;            // Bottom left corner  (x-coordinate)
+    let lg: f64 = 3.0*r;            // Bottom left corner  (y-coordinate)
+    let hg: f64 = 3.0*r;            // Bottom left corner  (z-coordinate)
 
-    let mut x0: f64 = x_c - 0.5*wd;
-    let mut y0: f64 = y_c - 0.5*lg;
-    let mut z0: f64 = z_c - 0.5*hg;
+    let x0: f64 = x_c - 0.5*wd;
+    let y0: f64 = y_c - 0.5*lg;
+    let z0: f64 = z_c - 0.5*hg;
 
     // Boundary conditions
     let xper: bool  = false;
@@ -71,6 +92,16 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut dt: f64     = 0.01*dt_sav;  // Initial time step
     let mut sav: bool   = false;        // Save data
     let mut it_sav: u32 = 1;            // Save data iteration
+
+    // Checkpoints
+    let chk_steps: u32  = input.chk_steps;  // Steps between checkpoints (0=off)
+    let chk_min: f64    = input.chk_min;    // Wall-clock minutes between checkpoints (0=off)
+    let max_walltime: f64 = input.max_walltime; // Hours after which the run checkpoints and stops (0=off)
+
+    // Output
+    let output_dir: &str = &input.output_dir; // Directory of the run's files (created if needed)
+    let path_source: &str   = &datafunctions::output_file(output_dir, "Ini_00.csv");
+    let path_checkpoint: &str = &datafunctions::output_file(output_dir, "Checkpoint");
     
     let lmbda: f64  = coeff_static_grav_potential(k, gamm, m_star, r);
     //---------------------------------------------------------------------------------------------
@@ -78,59 +109,113 @@ fn main() -> Result<(), Box<dyn Error>> {
     // Create Particles
     let mut particles: Vec<Particle> = Vec::new();
     
-    let star : Star = Star{m: m_star, x: x_c, y: y_c, z: z_c, hacc: nu, facc: lmbda, ..Default::default()};
-    if let Err(err) = datafunctions::read_data(path_source, &mut particles) {
-        println!("{}", err);
-        process::exit(1);
+    let toy_star: sphfunctions::ToyStar = sphfunctions::ToyStar{nu, lambda: lmbda};
+    // The Coriolis term is handled by the integrator in the Coriolis Leapfrog
+    let frame: sphfunctions::RotatingFrame = sphfunctions::RotatingFrame{wx: 0.0, wy: 0.0, wz: omega, coriolis: input.integrator != 5};
+    let forces: Vec<&dyn sphfunctions::ExternalForce> = if omega != 0.0 {vec![&toy_star, &frame]} else {vec![&toy_star]};
+    let resume: Option<datafunctions::RunState> = match datafunctions::resume(path_checkpoint, &mut particles, &mut Vec::new()) {
+        Ok(state) => state,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
+    if resume.is_none() {
+        if let Err(err) = datafunctions::read_data(path_source, &mut particles) {
+            println!("{}", err);
+            process::exit(1);
+        }
     }
     let particles_ptr: Pointer = Pointer(particles.as_mut_ptr());
 
     let mut t: f64  = t0;               // Time
     let n: usize    = particles.len();
-    let dm: f64     = star.m/n as f64;  // Particles' mass
     let mut it: u32 = 0;                // Time iterations
+    if let Some(state) = &resume {
+        t       = state.t;
+        dt      = state.dt;
+        it      = state.it;
+        it_sav  = state.it_sav;
+    }
     // Save time evolution
-    let mut time_file = File::create("./Toystar/Time.txt").expect("creation failed"); // Save time steps
+    let mut output: datafunctions::Output = match datafunctions::Output::new(output_dir, &input.prefix, input.digits, input.output, input.diag_steps, input.diag_dt, resume.as_ref()) {
+        Ok(output) => output,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
     
     //------------------------------------ kernel -------------------------------------------------
     let sigma: f64  = 1./(120.*PI);     // Normalization constant of kernel
     let rkern: f64  = 3.;               // Kernel radius
     //---------------------------------------------------------------------------------------------
 
-    for ii in 0..n {
-        particles[ii].rho = sphfunctions::density_from_h(dm, particles[ii].h, eta);
+    if resume.is_none() {
+        for ii in 0..n {
+            particles[ii].rho = sphfunctions::density_from_h(particles[ii].m, particles[ii].h, eta);
+        }
     }
 
     let mut tree: Node = <Node as BuildTree>::new(n as i32, x0, y0, z0, wd, lg, hg);
+    tree.auto_root(rkern, xper, yper, zper);
+
+    let header: datafunctions::Header = datafunctions::Header{x0, y0, z0, wd, lg, hg, xper, yper, zper, eos_t, gamma: gamm, coeff: k, ..Default::default()};
+    output.header = header;
+    let mut checkpoint: datafunctions::Checkpoint = datafunctions::Checkpoint::new(path_checkpoint, chk_steps, chk_min);
+    let stop: datafunctions::Stop = datafunctions::Stop::new(max_walltime).expect("signal handlers failed");
 
     //------------------------------------ Main Loop ----------------------------------------------
     let start = Instant::now();   // Runing time
+    let mut stopping: bool = false; // Stopped before the final time
     while t < tf {
-        sphfunctions::velocity_verlet_integrator(&mut particles, dt, dm, eos_t, sphfunctions::eos_polytropic, sphfunctions::sound_speed_polytropic, gamm, k,
-                                                 sphfunctions::dwdh, sphfunctions::f_quintic_kernel, sphfunctions::dfdq_quintic_kernel, sigma, rkern,
-                                                 eta, &mut tree, s_, alpha_, beta_, n, particles_ptr,
-                                                 sphfunctions::mon97_art_vis,
-                                                 sphfunctions::body_forces_toy_star, &star, true,
-                                                 sphfunctions::none_boundary, xper, yper, zper, wd, lg, hg, x0, y0, z0);
-        dt = sphfunctions::time_step_mon(&particles, n, gamm, k, rkern, wd, lg, hg,  x0, y0, z0, &mut tree, s_, sphfunctions::sound_speed_polytropic, xper, yper, zper);
+        let mut derivatives = |particles: &mut Vec<Particle>, dt: f64| {
+            sphfunctions::sph_derivatives(particles, dt, eos_t, sphfunctions::eos_polytropic, sphfunctions::sound_speed_polytropic, gamm, k,
+                                          sphfunctions::dwdh, sphfunctions::f_quintic_kernel, sphfunctions::dfdq_quintic_kernel, sigma, rkern,
+                                          eta, &mut tree, s_, alpha_, beta_, n, particles_ptr,
+                                          sphfunctions::mon97_art_vis,
+                                          &forces,
+                                          sphfunctions::none_boundary, xper, yper, zper, 0.0, 0.0, wd, lg, hg, x0, y0, z0);
+        };
+        integrator.step(&mut particles, dt, &mut derivatives);
+        dt = sphfunctions::time_step_mon(&particles, n, gamm, k, rkern, wd, lg, hg,  x0, y0, z0, &mut tree, s_, sphfunctions::sound_speed_polytropic, xper, yper, zper, 0.0, 0.0);
         tree.restart(n);
-        sphfunctions::open_boundary(&particles, &mut wd, &mut lg, &mut hg, &mut x0, &mut y0, &mut z0);
         datafunctions::time_step(&mut t, &mut dt, dt_sav, &mut sav, &mut it_sav);
         println!("dt: {:.4}\tt: {:.4}", dt, t);
         if sav {
-            time_file.write((t.to_string() + &"\n").as_bytes()).expect("write failed");
-            if let Err(err) = datafunctions::save_data_bin(&(String::from("./Toystar/Ev_") + &(it_sav-2).to_string()), &particles){
+            let snapshot: Vec<Particle> = if inertial {sphfunctions::inertial_frame(&particles, 0.0, 0.0, omega, t)} else {particles.clone()};
+            if let Err(err) = output.snapshot(it_sav-2, t, &snapshot, &[]){
                 println!("{}", err);
                 process::exit(1);
             }
         }
         it += 1;
+        if let Err(err) = output.step(it, t, dt, &particles) {
+            println!("{}", err);
+            process::exit(1);
+        }
+        stopping = stop.requested();
+        if checkpoint.due(it) || stopping {
+            if let Err(err) = checkpoint.save(&datafunctions::RunState{t, t_boundary: t, dt, it, it_sav}, &header, &particles, &[]).and_then(|_| output.record("checkpoint", it, t, "Checkpoint")) {
+                println!("{}", err);
+                process::exit(1);
+            }
+        }
+        if stopping {
+            println!("Stopping at t = {} (iteration {}). Continue with --resume.", t, it);
+            break;
+        }
+    }
+    if stopping {
+        println!("Simulation stopped at t = {} of {}, resumable from the checkpoint.\n Time {} s.\n Iterations: {}.", t, tf, start.elapsed().as_secs(), it);
+    } else {
+        println!("Simulation run successfully.\n Time {} s.\n Iterations: {}.", start.elapsed().as_secs(), it);
     }
-    println!("Simulation run successfully.\n Time {} s.\n Iterations: {}.", start.elapsed().as_secs(), it);
     //---------------------------------------------------------------------------------------------
 
     // Save final information
-    if let Err(err) = datafunctions::save_data_bin(&(String::from("./Toystar/Fin_00")), &particles){
+    let snapshot: Vec<Particle> = if inertial {sphfunctions::inertial_frame(&particles, 0.0, 0.0, omega, t)} else {particles};
+    if let Err(err) = output.finish(t, &snapshot, &[]){
         println!("{}", err);
         process::exit(1);
     }
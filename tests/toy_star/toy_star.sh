@@ -1,2 +1,4 @@
+if [ "$1" != "--resume" ]; then
 cargo run -p toy_star --bin init_dist_toy_star
-cargo run --release -p toy_star --bin toy_star
\ No newline at end of file
+fi
+cargo run --release -p toy_star --bin toy_star -- $1
\ No newline at end of file
@@ -0,0 +1,37 @@
+// ------------------------------------------------------------------------- //
+// Input parameters of the Turbulent Gas                                     //
+// Keys of its input file, written as `key = value` lines.                   //
+// ------------------------------------------------------------------------- //
+
+use datafunctions::OutputFormat;
+
+datafunctions::config! {
+    pub struct Input {
+        eta: f64 = 1.2,                  "",    "Dimensionless constant specifying the smoothing length", min 0.0;
+        gamma: f64,                      "",    "Heat capacity ratio", min 1.0;
+        k: f64,                          "",    "Constant coefficient";
+        eos_type: bool,                  "",    "EoS (0=isoth[No u]; 1=adiab[u])";
+        x0: f64,                         "L",   "Bottom left corner  (x-coordinate)";
+        y0: f64,                         "L",   "Bottom left corner  (y-coordinate)";
+        z0: f64,                         "L",   "Bottom left corner  (z-coordinate)";
+        wd: f64,                         "L",   "Width of the system's domain";
+        lg: f64,                         "L",   "Length of the system's domain";
+        hg: f64,                         "L",   "Height of the system's domain";
+        dm: f64,                         "M",   "particle mass (32K Particles)";
+        t0: f64 = 0.0,                   "T",   "Initial time";
+        tf: f64,                         "T",   "Final time";
+        dt_sav: f64,                     "T",   "Recording time step", min 0.0;
+        s: i32 = 10,                     "",    "Maximum number of particles in a leaf (Bucket size)", min 1;
+        alpha: f64 = 0.5,                "",    "Fraction of the bucket size s", min 0.0, max 1.0;
+        beta: f64 = 0.5,                 "",    "Maximum ratio of cells with less than alpha*s particles", min 0.0, max 1.0;
+        chk_steps: u32 = 0,              "",    "Steps between checkpoints (0=off)";
+        chk_min: f64 = 60.0,             "min", "Wall-clock minutes between checkpoints (0=off)", min 0.0;
+        max_walltime: f64 = 0.0,         "h",   "Hours after which the run checkpoints and stops (0=off)", min 0.0;
+        output: OutputFormat = "binary", "",    "Snapshot format (binary, csv or vtu)";
+        output_dir: String,              "",    "Directory of the run's files (created if needed)";
+        prefix: String = "Ev_",          "",    "Prefix of the snapshots' names";
+        digits: u32 = 0,                 "",    "Digits of the snapshots' numbers, zero-padded (0=no padding)";
+        diag_steps: u32 = 0,             "",    "Steps between lines of Diagnostics.csv (0=off)";
+        diag_dt: f64 = 0.0,              "T",   "Time between lines of Diagnostics.csv (0=off)", min 0.0;
+    }
+}
@@ -1 +1 @@
-cargo run --release -p turbulent_gas --bin turbulent_gas
\ No newline at end of file
+cargo run --release -p turbulent_gas --bin turbulent_gas -- $1
\ No newline at end of file
@@ -0,0 +1,4 @@
+if [ "$1" != "--resume" ]; then
+cargo run -p wind_blob --bin init_dist_wind_blob
+fi
+cargo run --release -p wind_blob --bin wind_blob -- $1
\ No newline at end of file
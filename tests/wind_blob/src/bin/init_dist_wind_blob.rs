@@ -0,0 +1,75 @@
+// ------------------------------------------------------------------------- //
+// Initial setup for the Blob crushed by a Wind in 3D                        //
+// ------------------------------------------------------------------------- //
+use std::{
+    error::Error,
+    process,
+};
+
+use structures::Particle;
+use datafunctions;
+
+fn main() -> Result<(), Box<dyn Error>> {
+
+    // Files
+    let input_file: &str= "./tests/wind_blob/input";
+
+    // Parameters
+    let input: wind_blob::Input = match wind_blob::Input::read(input_file) {
+        Ok(input) => input,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
+
+    let eta: f64    = input.eta;        // Dimensionless constant specifying the smoothing length
+    let gamma: f64  = input.gamma;      // Heat capacity ratio
+
+    let x0: f64     = input.x0;         // Bottom left corner  (x-coordinate)
+    let y0: f64     = input.y0;         // Bottom left corner  (y-coordinate)
+    let z0: f64     = input.z0;         // Bottom left corner  (z-coordinate)
+    let wd: f64     = input.wd;         // Length (x)
+    let lg: f64     = input.lg;         // Width (y)
+    let hg: f64     = input.hg;         // Height (z)
+
+    let rho_w: f64  = input.rho_w;      // Density of the wind
+    let v_w: f64    = input.v_w;        // Velocity of the wind
+    let p0: f64     = input.p0;         // Pressure
+    let chi: f64    = input.chi;        // Density contrast of the blob
+    let r_b: f64    = input.r_b;        // Radius of the blob
+    let x_b: f64    = input.x_b;        // Centre of the blob (x)
+    let nb: u32     = input.nb;         // Layers of buffer particles
+
+    let nx: u32     = input.nx;         // Particle resolution in the x direction
+
+    let dx: f64     = wd/nx as f64;     // Particle spacing
+    let bw: f64     = nb as f64*dx;     // Width of the buffers
+    let y_b: f64    = y0 + 0.5*lg;      // Centre of the blob (y)
+    let z_b: f64    = z0 + 0.5*hg;      // Centre of the blob (z)
+
+    let mut particles : Vec<Particle> = Vec::new();
+
+    // Wind, also filling the outflow buffer. The inflow buffer is created by
+    // the driver.
+    partdistribution::init_dist_cubic(&mut particles, nx+nb, rho_w, eta, wd+bw, lg, hg, x0, y0, z0);
+
+    // Blob at rest and in pressure equilibrium: same lattice, heavier particles
+    for particle in particles.iter_mut() {
+        let r2: f64 = (particle.x - x_b).powi(2) + (particle.y - y_b).powi(2) + (particle.z - z_b).powi(2);
+        if r2 <= r_b*r_b {
+            particle.m *= chi;
+            particle.u  = p0/((gamma - 1.)*chi*rho_w);
+        } else {
+            particle.vx = v_w;
+            particle.u  = p0/((gamma - 1.)*rho_w);
+        }
+    }
+
+    if let Err(err) = datafunctions::save_data(&datafunctions::output_file(&input.output_dir, "Ini_00.csv"), &particles){
+        println!("{}", err);
+        process::exit(1);
+    }
+
+    Ok(())
+}
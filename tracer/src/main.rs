@@ -0,0 +1,60 @@
+// ------------------------------------------------------------------------- //
+// Tracer: time history of chosen particles.                                 //
+// Reads the snapshots listed in Manifest.csv of a run directory and writes  //
+// the state of the requested particle IDs at every snapshot.                //
+// Usage:                                                                    //
+//      cargo run -p tracer -- <run_dir> <output.csv> <id> [<id> ...]        //
+// ------------------------------------------------------------------------- //
+
+use std::{
+    env,
+    error::Error,
+    process,
+};
+
+use csv::Writer;
+
+use structures::Particle;
+
+use datafunctions;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 4 {
+        println!("Usage: tracer <run_dir> <output.csv> <id> [<id> ...]");
+        process::exit(1);
+    }
+    let run_dir: &str   = &args[1];
+    let path_out: &str  = &args[2];
+    let mut ids: Vec<u64> = Vec::new();
+    for arg in &args[3..] {
+        ids.push(arg.parse::<u64>()?);
+    }
+
+    // The snapshots of the run, listed in its manifest
+    let entries: Vec<datafunctions::ManifestEntry> = datafunctions::read_manifest(run_dir)?;
+
+    let mut wtr = Writer::from_path(path_out)?;
+    wtr.write_record(&["t", "id", "x", "y", "z", "vx", "vy", "vz", "h", "u"])?;
+
+    let mut it: usize = 0;
+    let mut particles: Vec<Particle> = Vec::new();
+    for entry in entries.iter().filter(|entry| entry.stream == "snapshot") {
+        let path_snap: String = datafunctions::output_file(run_dir, &entry.file);
+        particles.clear();
+        datafunctions::read_output(&path_snap, &mut particles)?;
+        for id in &ids {
+            // Indices change between snapshots, the IDs do not.
+            if let Some(particle) = particles.iter().find(|particle| particle.id == *id) {
+                wtr.write_record(&[entry.t.to_string(), particle.id.to_string(),
+                                   particle.x.to_string(), particle.y.to_string(), particle.z.to_string(),
+                                   particle.vx.to_string(), particle.vy.to_string(), particle.vz.to_string(),
+                                   particle.h.to_string(), particle.u.to_string()])?;
+            }
+        }
+        it += 1;
+    }
+    wtr.flush()?;
+    println!("{} snapshots traced.", it);
+    Ok(())
+}
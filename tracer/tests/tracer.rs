@@ -0,0 +1,46 @@
+// ------------------------------------------------------------------------- //
+// The tracer follows particle IDs through the snapshots of a run, whatever  //
+// their order in each snapshot.                                             //
+// ------------------------------------------------------------------------- //
+
+use std::{
+    env,
+    fs,
+    process::{self, Command},
+};
+
+use structures::Particle;
+
+#[test]
+fn traces_ids_across_snapshots() {
+    let dir: String = env::temp_dir().join(format!("rusph-tracer-{}", process::id())).to_string_lossy().into_owned();
+    fs::create_dir_all(&dir).unwrap();
+
+    // Three snapshots with the particles in a different order, the second
+    // one in the compact layout of former runs and the third one in VTU
+    let mut particles: Vec<Particle> = (0..4).map(|ii| Particle{id: ii, m: 1.0, x: ii as f64, ..Default::default()}).collect();
+    datafunctions::save_snapshot(&datafunctions::output_file(&dir, "Ev_0"), &datafunctions::Header::default(), &particles, &[]).unwrap();
+    particles.reverse();
+    for particle in particles.iter_mut() {
+        particle.x += 10.0;
+    }
+    datafunctions::save_data_bin(&datafunctions::output_file(&dir, "Ev_1"), &particles).unwrap();
+    particles.swap(0, 2);
+    for particle in particles.iter_mut() {
+        particle.x += 10.0;
+    }
+    datafunctions::save_vtu(&datafunctions::output_file(&dir, "Ev_2.vtu"), &particles).unwrap();
+    fs::write(datafunctions::output_file(&dir, "Manifest.csv"),
+              format!("{}\nsnapshot,0,0,Ev_0\nsnapshot,1,0.5,Ev_1\nsnapshot,2,1,Ev_2.vtu\n", datafunctions::MANIFEST_HEADER)).unwrap();
+
+    let path_out: String = datafunctions::output_file(&dir, "Trace.csv");
+    let status = Command::new(env!("CARGO_BIN_EXE_tracer")).args([dir.as_str(), path_out.as_str(), "1", "3"]).status().unwrap();
+    assert!(status.success());
+
+    let mut rdr = csv::Reader::from_path(&path_out).unwrap();
+    let rows: Vec<(f64, u64, f64)> = rdr.records().map(|record| {
+        let record = record.unwrap();
+        (record[0].parse().unwrap(), record[1].parse().unwrap(), record[2].parse().unwrap())
+    }).collect();
+    assert_eq!(rows, vec![(0.0, 1, 1.0), (0.0, 3, 3.0), (0.5, 1, 11.0), (0.5, 3, 13.0), (1.0, 1, 21.0), (1.0, 3, 23.0)]);
+}